@@ -0,0 +1,38 @@
+// Only the socket path is used here; the listener is the event handler's
+#[allow(dead_code)]
+mod ctl;
+
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 || !["trigger", "key", "workspace"].contains(&args[0].as_str()) {
+        eprintln!("Usage: aerogel-ctl trigger <action> | key <key> | workspace <number>");
+        exit(2);
+    }
+
+    let path = match ctl::socket_path() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("aerogel-ctl: {}", e);
+            exit(1);
+        }
+    };
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!(
+                "aerogel-ctl: could not connect to '{}': {}. Is aerogel running?",
+                path.display(),
+                e
+            );
+            exit(1);
+        }
+    };
+    if let Err(e) = writeln!(stream, "{}", args.join(" ")) {
+        eprintln!("aerogel-ctl: failed to send command: {}", e);
+        exit(1);
+    }
+}
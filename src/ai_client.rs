@@ -1,4 +1,16 @@
-use crate::config::ApiConfig;
+use crate::config::{ApiConfig, ModelConfig};
+use crate::conversation::Exchange;
+use crate::documents;
+use crate::history_budget;
+use crate::http;
+use crate::key_pool::{self, KeyPool};
+use crate::ocr::{self, OcrBackend};
+use crate::overrides::Overrides;
+use crate::providers::{self, Chunk, MediaPrep, Provider, ThinkTags};
+use crate::rate_limit::RateLimiter;
+use crate::response_cache::{self, CachedResponse, ResponseCache};
+use crate::tokens::count_tokens;
+use crate::video::{self, VideoConfig};
 use anyhow::{Result, anyhow};
 use async_stream::stream;
 use base64::{Engine as _, engine::general_purpose};
@@ -10,9 +22,10 @@ use std::collections::HashMap;
 use std::io::{self, Write};
 use std::path::Path;
 use std::pin::Pin;
+use std::sync::Arc;
 use tokio::fs;
-use tokio::sync::Mutex;
-use tokio_stream::Stream;
+use tokio::sync::{Mutex, watch};
+use tokio_stream::{Stream, StreamExt};
 
 #[derive(Debug, Clone)]
 pub struct Media {
@@ -33,17 +46,32 @@ pub struct Message {
 }
 
 impl PromptData {
-    pub async fn new(text: String, file_paths: &[String]) -> Result<Self> {
+    pub async fn new(text: String, file_paths: &[String], video: &VideoConfig) -> Result<Self> {
+        let mut text = text;
         let mut media_items = Vec::new();
         for path_str in file_paths {
             let path = Path::new(path_str);
             if !path.exists() {
                 return Err(anyhow!("File not found: {}", path_str));
             }
+            if let Some(kind) = documents::kind(path) {
+                let contents = documents::extract_text(kind, &fs::read(path).await?)
+                    .map_err(|e| anyhow!("Could not read {}: {}", path_str, e))?;
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                text = format!(
+                    "Contents of {}:\n\n```\n{}\n```\n\n{}",
+                    name, contents, text
+                );
+                continue;
+            }
             let mime_type = mime_guess::from_path(path)
                 .first_or_octet_stream()
                 .to_string();
-            let file_content = fs::read(path).await?;
+            let (file_content, mime_type) = if video::is_video(&mime_type) {
+                video::prepare(video, path, &mime_type).await?
+            } else {
+                (fs::read(path).await?, mime_type)
+            };
             let base64_data = general_purpose::STANDARD.encode(&file_content);
             media_items.push(Media {
                 mime_type,
@@ -57,10 +85,39 @@ impl PromptData {
     }
 }
 
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<Chunk>> + Send>>;
+
+// An answer as it starts streaming, with the user message it was asked with
+// and the tokens the request took.
+pub struct ChatReply {
+    pub stream: ChatStream,
+    pub user_content: Value,
+    pub prompt_tokens: u64,
+    // Answered from the response cache, so nothing was billed
+    pub cached: bool,
+}
+
 pub struct AiClient {
     client: Client,
     config: ApiConfig,
+    providers: Vec<Arc<dyn Provider>>,
+    // The provider chosen with `/ask @<name>`; every provider is asked when
+    // unset
+    target: std::sync::Mutex<Option<String>>,
+    // Request limits by provider name
+    limiters: HashMap<String, RateLimiter>,
+    // API keys by provider name
+    key_pools: HashMap<String, KeyPool>,
+    // Bumped by `stop`; requests and streams end when it changes
+    stop: watch::Sender<u64>,
+    // Sent as the system prompt with every request, when set
+    system_prompt: Option<String>,
     history: Mutex<HashMap<String, Vec<Message>>>,
+    // Every round's question and answers, for /export
+    exchanges: Mutex<Vec<Exchange>>,
+    // The conversation's title, once one has been generated
+    title: std::sync::Mutex<Option<String>>,
+    cache: Option<Arc<ResponseCache>>,
 }
 
 const SUPPORTED_AUDIO_TYPES: &[&str] = &[
@@ -77,14 +134,112 @@ const SUPPORTED_AUDIO_TYPES: &[&str] = &[
 ];
 
 impl AiClient {
-    pub fn new(config: ApiConfig) -> Self {
+    pub fn new(config: ApiConfig, system_prompt: Option<String>) -> Self {
+        let providers = providers::registry(&config);
+        let limiters = providers
+            .iter()
+            .map(|provider| {
+                let model = provider.model();
+                (
+                    provider.name().to_string(),
+                    RateLimiter::new(model.requests_per_minute, model.max_in_flight),
+                )
+            })
+            .collect();
+        let key_pools = providers
+            .iter()
+            .map(|provider| {
+                (
+                    provider.name().to_string(),
+                    KeyPool::new(provider.api_keys().to_vec()),
+                )
+            })
+            .collect();
+        let cache = ResponseCache::open(&config.cache).map(Arc::new);
         Self {
-            client: Client::new(),
+            client: http::client(&config.http),
+            providers,
+            target: std::sync::Mutex::new(None),
+            limiters,
+            key_pools,
+            stop: watch::Sender::new(0),
             config,
+            system_prompt,
             history: Mutex::new(HashMap::new()),
+            exchanges: Mutex::new(Vec::new()),
+            title: std::sync::Mutex::new(None),
+            cache,
         }
     }
 
+    // The HTTP client, for other requests to share its connections.
+    pub fn http(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn providers(&self) -> &[Arc<dyn Provider>] {
+        &self.providers
+    }
+
+    // Each provider's context_tokens, 0 when unset, keyed by name.
+    pub fn context_limits(&self) -> HashMap<String, u64> {
+        self.providers
+            .iter()
+            .map(|provider| {
+                (
+                    provider.name().to_string(),
+                    provider.model().context_tokens.into(),
+                )
+            })
+            .collect()
+    }
+
+    // Ends every answer streaming now, keeping what has arrived, and fails
+    // the requests still waiting for a response.
+    pub fn stop(&self) {
+        self.stop.send_modify(|stops| *stops += 1);
+    }
+
+    // Sends later prompts only to the named provider, or to every provider
+    // again for "all". Returns the provider's name as configured.
+    pub fn set_target(&self, name: &str) -> Result<Option<String>> {
+        let target = if name.eq_ignore_ascii_case("all") {
+            None
+        } else {
+            let provider = self
+                .providers
+                .iter()
+                .find(|provider| provider.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    let names: Vec<&str> = self.providers.iter().map(|p| p.name()).collect();
+                    anyhow!(
+                        "Unknown provider '{}'. Available: {}, all",
+                        name,
+                        names.join(", ")
+                    )
+                })?;
+            Some(provider.name().to_string())
+        };
+        *self.target.lock().unwrap() = target.clone();
+        Ok(target)
+    }
+
+    // The provider chosen with `/ask @<name>`, None when every provider is
+    // asked.
+    pub fn target(&self) -> Option<String> {
+        self.target.lock().unwrap().clone()
+    }
+
+    // The providers the next prompt is sent to.
+    pub fn active_providers(&self) -> Vec<Arc<dyn Provider>> {
+        let target = self.target.lock().unwrap();
+        self.providers
+            .iter()
+            .filter(|provider| target.as_deref().is_none_or(|name| provider.name() == name))
+            .cloned()
+            .collect()
+    }
+
     pub async fn add_history_entry(
         &self,
         provider: &str,
@@ -103,210 +258,357 @@ impl AiClient {
         });
     }
 
+    // The last answer of every provider that has answered, by provider name.
+    pub async fn latest_responses(&self) -> Vec<(String, String)> {
+        let history = self.history.lock().await;
+        let mut responses: Vec<(String, String)> = history
+            .iter()
+            .filter_map(|(provider, messages)| {
+                let last = messages.iter().rev().find(|m| m.role == "assistant")?;
+                Some((provider.clone(), last.content.as_str()?.to_string()))
+            })
+            .collect();
+        responses.sort();
+        responses
+    }
+
+    pub async fn history(&self) -> HashMap<String, Vec<Message>> {
+        self.history.lock().await.clone()
+    }
+
+    // Replaces the history, e.g. with a conversation synced from another
+    // machine.
+    pub async fn restore_history(&self, history: HashMap<String, Vec<Message>>) {
+        *self.history.lock().await = history;
+    }
+
+    pub async fn add_exchange(&self, exchange: Exchange) {
+        self.exchanges.lock().await.push(exchange);
+    }
+
+    pub async fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().await.clone()
+    }
+
+    pub async fn restore_exchanges(&self, exchanges: Vec<Exchange>) {
+        *self.exchanges.lock().await = exchanges;
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.title.lock().unwrap().clone()
+    }
+
+    pub fn set_title(&self, title: Option<String>) {
+        *self.title.lock().unwrap() = title;
+    }
+
     pub async fn clear_history(&self) {
+        self.set_title(None);
+        self.exchanges.lock().await.clear();
         let mut history = self.history.lock().await;
         history.clear();
     }
 
-    pub async fn chat_ollama(
+    // Sends the prompt to a provider with its part of the history. Returns
+    // the answer stream, the user message to add to the history and the
+    // request's token count.
+    pub async fn chat(
         &self,
+        provider: &Arc<dyn Provider>,
         prompt_data: &PromptData,
-    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, Value)> {
-        let history_guard = self.history.lock().await;
-        let past_messages = history_guard
-            .get("Ollama")
-            .map_or(&[][..], |v| v.as_slice());
-
-        let mut content_parts: Vec<Value> = vec![json!({
-            "type": "text",
-            "text": prompt_data.text
-        })];
-
-        for media in &prompt_data.media {
-            if media.mime_type.starts_with("image/") {
-                let image_url = format!("data:{};base64,{}", media.mime_type, media.data);
-                content_parts.push(json!({
-                    "type": "image_url",
-                    "image_url": { "url": image_url }
-                }));
-            } else {
-                eprintln!(
-                    "\n[Warning (Ollama Chat)]: Skipping media with unsupported MIME type '{}'.",
-                    media.mime_type
+        overrides: &Overrides,
+        use_cache: bool,
+    ) -> Result<ChatReply> {
+        // Answers to prompts with overrides are neither reused nor kept
+        let cache = self.cache.as_ref().filter(|_| overrides.is_empty());
+        let cache = match cache {
+            Some(cache) => {
+                let history = self.history.lock().await;
+                let key = response_cache::cache_key(
+                    provider.name(),
+                    &provider.model().model,
+                    self.system_prompt.as_deref(),
+                    history.get(provider.name()).map_or(&[][..], Vec::as_slice),
+                    prompt_data,
                 );
+                Some((Arc::clone(cache), key))
             }
+            None => None,
+        };
+        if use_cache
+            && let Some((cache, key)) = &cache
+            && let Some(cached) = cache.get(key)
+        {
+            return Ok(ChatReply {
+                stream: Box::pin(tokio_stream::once(Ok(Chunk::Answer(cached.response)))),
+                user_content: cached.user_content,
+                prompt_tokens: 0,
+                cached: true,
+            });
         }
+        let (text, media) = self.prepare_media(provider.as_ref(), prompt_data).await?;
+        let user_content = provider.user_content(&text, &media);
 
-        let user_content = json!(content_parts);
-
-        let mut messages: Vec<Value> = past_messages.iter().map(|m| json!(m)).collect();
-
-        messages.push(json!({
-            "role": "user",
-            "content": user_content.clone()
-        }));
-
-        let payload = json!({
-            "model": &self.config.ollama.model,
-            "messages": messages,
-            "max_tokens": self.config.ollama.max_tokens,
-            "temperature": self.config.ollama.temperature,
-            "top_p": self.config.ollama.top_p,
-            "stream": true
-        });
-
-        let api_url = self.config.ollama.api_base.trim_end_matches('/');
-
-        let response = self
-            .client
-            .post(api_url)
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
+        let mut stop = self.stop.subscribe();
+        let permit = match self.limiters.get(provider.name()) {
+            Some(limiter) => limiter.acquire(provider.name()).await,
+            None => None,
+        };
+        let model = overrides.apply(provider.model());
+        let key_pool = self.key_pools.get(provider.name());
+        // Sent again with the next key while keys are rate limited
+        let (response, prompt_tokens) = loop {
+            let key = key_pool.and_then(KeyPool::current);
+            let (request, prompt_tokens) = {
+                let history = self.history.lock().await;
+                let all_messages = history
+                    .get(provider.name())
+                    .map_or(&[][..], |v| v.as_slice());
+                let reserved = history_budget::content_tokens(&model.model, &user_content)
+                    + self
+                        .system_prompt
+                        .as_deref()
+                        .map_or(0, |prompt| count_tokens(&model.model, prompt));
+                let past_messages = history_budget::fit_history(
+                    &model.model,
+                    all_messages,
+                    provider.model().context_tokens.into(),
+                    reserved,
+                );
+                if past_messages.len() < all_messages.len() {
+                    eprintln!(
+                        "[INFO] {}: leaving out the {} oldest message(s) to stay within context_tokens",
+                        provider.name(),
+                        all_messages.len() - past_messages.len()
+                    );
+                }
+                let request = provider.build_request(
+                    &self.client,
+                    key.as_deref(),
+                    &model,
+                    self.system_prompt.as_deref(),
+                    past_messages,
+                    &user_content,
+                )?;
+                let history_tokens: u64 = past_messages
+                    .iter()
+                    .map(|message| history_budget::content_tokens(&model.model, &message.content))
+                    .sum();
+                (request, reserved + history_tokens)
+            };
+            let response = tokio::select! {
+                response = request.send() => response?,
+                Ok(()) = stop.changed() => return Err(anyhow!("Stopped")),
+            };
 
-        if !response.status().is_success() {
             let status = response.status();
+            if status.is_success() {
+                break (response, prompt_tokens);
+            }
+            if key_pool::is_limited(status)
+                && let (Some(pool), Some(key)) = (key_pool, &key)
+                && pool.rest(key, key_pool::rest_time(response.headers()))
+            {
+                let (keys, resting) = pool.resting();
+                eprintln!(
+                    "[INFO] {}: key rate limited ({}), switching keys ({} of {} resting)",
+                    provider.name(),
+                    status,
+                    resting,
+                    keys
+                );
+                continue;
+            }
             let error_body = response.text().await?;
-            return Err(anyhow!("Ollama API Error ({}): {}", status, error_body));
-        }
+            return Err(anyhow!(
+                "{} API Error ({}): {}",
+                provider.name(),
+                status,
+                error_body
+            ));
+        };
 
+        let provider = Arc::clone(provider);
         let stream = response.bytes_stream();
+        let sent_content = user_content.clone();
         let s = stream! {
-            for await chunk_result in stream {
-                let chunk = chunk_result.map_err(|e| anyhow!("Stream error: {}", e))?;
+            // Holds the in-flight slot until the answer has streamed
+            let _permit = permit;
+            let mut stream = std::pin::pin!(stream);
+            let mut full_response = String::new();
+            let mut think_tags = ThinkTags::default();
+            let mut stopped = false;
+            loop {
+                let next = tokio::select! {
+                    next = stream.next() => next,
+                    Ok(()) = stop.changed() => {
+                        stopped = true;
+                        None
+                    }
+                };
+                let Some(chunk_result) = next else { break };
+                let chunk = chunk_result
+                    .map_err(|e| anyhow!("Stream error from {}: {}", provider.name(), e))?;
                 let s = String::from_utf8_lossy(&chunk);
-                for line in s.split('\n') {
-                    if line.starts_with("data: ") {
-                        let data = &line[6..];
+                for line in s.lines() {
+                    if let Some(data) = line.strip_prefix("data: ") {
                         if data == "[DONE]" {
                             break;
                         }
-                        if let Ok(json) = serde_json::from_str::<Value>(data) {
-                            if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                                yield Ok(content.to_string());
+                        if let Some(reasoning) = provider.parse_reasoning_chunk(data) {
+                            yield Ok(Chunk::Reasoning(reasoning));
+                        }
+                        if let Some(content) = provider.parse_stream_chunk(data) {
+                            for chunk in think_tags.split(&content) {
+                                if let Chunk::Answer(text) = &chunk {
+                                    full_response.push_str(text);
+                                }
+                                yield Ok(chunk);
                             }
                         }
                     }
                 }
             }
+            // Only complete answers are reused
+            if !stopped
+                && !full_response.is_empty()
+                && let Some((cache, key)) = cache
+            {
+                let entry = CachedResponse {
+                    user_content: sent_content,
+                    response: full_response,
+                };
+                if let Err(e) = cache.put(&key, &entry) {
+                    eprintln!("[ERROR] Failed to cache the {} answer: {}", provider.name(), e);
+                }
+            }
         };
+        Ok(ChatReply {
+            stream: Box::pin(s),
+            user_content,
+            prompt_tokens,
+            cached: false,
+        })
+    }
 
-        Ok((Box::pin(s), user_content))
+    // Asks a provider a single question outside the conversation, with
+    // `model` in place of its own, and returns the whole answer.
+    pub async fn complete(
+        &self,
+        provider: &dyn Provider,
+        model: &ModelConfig,
+        prompt: &str,
+    ) -> Result<String> {
+        let key = self
+            .key_pools
+            .get(provider.name())
+            .and_then(KeyPool::current);
+        let user_content = provider.user_content(prompt, &[]);
+        let request = provider.build_request(
+            &self.client,
+            key.as_deref(),
+            model,
+            None,
+            &[],
+            &user_content,
+        )?;
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!(
+                "{} API Error ({}): {}",
+                provider.name(),
+                status,
+                body
+            ));
+        }
+        Ok(body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]")
+            .filter_map(|data| provider.parse_stream_chunk(data))
+            .collect())
     }
 
-    pub async fn chat_openrouter(
+    // The prompt text and the media left to attach, after the provider's
+    // media preprocessing.
+    async fn prepare_media(
         &self,
+        provider: &dyn Provider,
         prompt_data: &PromptData,
-    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, Value)> {
-        let api_key = self
-            .config
-            .get_key("openrouter")
-            .ok_or_else(|| anyhow!("OpenRouter API key not found"))?;
-
-        let history_guard = self.history.lock().await;
-        let past_messages = history_guard
-            .get("OpenRouter")
-            .map_or(&[][..], |v| v.as_slice());
-
-        let mut content_parts: Vec<Value> = Vec::new();
-        let mut ocr_text = String::new();
-
-        for media in &prompt_data.media {
-            if media.mime_type.starts_with("image/") {
-                match self
-                    .extract_text_with_gemini(&media.data, &media.mime_type)
-                    .await
-                {
-                    Ok(extracted_text) => {
-                        if !extracted_text.trim().is_empty() {
-                            ocr_text.push_str(&format!("\n\n[OCR from image]: {}", extracted_text));
-                        }
+    ) -> Result<(String, Vec<Media>)> {
+        match provider.media_prep() {
+            MediaPrep::None => Ok((prompt_data.text.clone(), prompt_data.media.clone())),
+            MediaPrep::TranscribeOpenAi | MediaPrep::TranscribeGemini => {
+                let mut transcribed_text = String::new();
+                for media in &prompt_data.media {
+                    if !SUPPORTED_AUDIO_TYPES.contains(&media.mime_type.as_str()) {
+                        continue;
                     }
-                    Err(e) => {
-                        eprintln!(
-                            "[Warning]: Failed to extract text from image with Gemini: {}",
-                            e
-                        );
-                        let image_url = format!("data:{};base64,{}", media.mime_type, media.data);
-                        content_parts.push(json!({
-                            "type": "image_url",
-                            "image_url": { "url": image_url }
-                        }));
+                    eprint!("\n[{}] Transcribing supported audio... ", provider.name());
+                    io::stderr().flush()?;
+                    let transcript = if provider.media_prep() == MediaPrep::TranscribeOpenAi {
+                        self.transcribe_audio_openai(media).await
+                    } else {
+                        self.transcribe_audio_gemini(media).await
+                    };
+                    match transcript {
+                        Ok(transcript) => {
+                            eprintln!("Done.");
+                            transcribed_text.push_str(&transcript);
+                            transcribed_text.push_str("\n\n");
+                        }
+                        Err(e) => {
+                            eprintln!("\n[{}] Transcription failed: {}", provider.name(), e);
+                            transcribed_text
+                                .push_str(&format!("[Audio Transcription Failed: {}]\n\n", e));
+                        }
                     }
                 }
-            } else {
-                eprintln!(
-                    "\n[Warning (OpenRouter Chat)]: Skipping media with unsupported MIME type '{}'.",
-                    media.mime_type
-                );
+                Ok((
+                    format!("{}{}", transcribed_text, prompt_data.text),
+                    prompt_data.media.clone(),
+                ))
             }
-        }
-
-        let combined_text = if ocr_text.is_empty() {
-            prompt_data.text.clone()
-        } else {
-            format!("{}{}", prompt_data.text, ocr_text)
-        };
-
-        content_parts.insert(
-            0,
-            json!({
-                "type": "text",
-                "text": combined_text
-            }),
-        );
-        let user_content = json!(content_parts);
-
-        let mut messages: Vec<Value> = past_messages.iter().map(|m| json!(m)).collect();
-        messages.push(json!({
-            "role": "user",
-            "content": user_content.clone()
-        }));
-
-        let payload = json!({
-            "model": &self.config.openrouter.model,
-            "messages": messages,
-            "max_tokens": self.config.openrouter.max_tokens,
-            "temperature": self.config.openrouter.temperature,
-            "top_p": self.config.openrouter.top_p,
-            "stream": true
-        });
-
-        let response = self
-            .client
-            .post(&self.config.openrouter.api_base)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_body = response.text().await?;
-            return Err(anyhow!("OpenRouter API Error ({}): {}", status, error_body));
-        }
-
-        let stream = response.bytes_stream();
-        let s = stream! {
-            for await chunk_result in stream {
-                let chunk = chunk_result.map_err(|e| anyhow!("Stream error: {}", e))?;
-                let s = String::from_utf8_lossy(&chunk);
-                for line in s.split('\n') {
-                    if line.starts_with("data: ") {
-                        let data = &line[6..];
-                        if data == "[DONE]" { break; }
-                        if let Ok(json) = serde_json::from_str::<Value>(data) {
-                            if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                                yield Ok(content.to_string());
+            MediaPrep::OcrImages => {
+                let mut ocr_text = String::new();
+                let mut media_left = Vec::new();
+                for media in &prompt_data.media {
+                    if !media.mime_type.starts_with("image/") {
+                        media_left.push(media.clone());
+                        continue;
+                    }
+                    match self.ocr_image(media).await {
+                        Ok(extracted_text) => {
+                            if !extracted_text.trim().is_empty() {
+                                ocr_text
+                                    .push_str(&format!("\n\n[OCR from image]: {}", extracted_text));
                             }
                         }
+                        Err(e) => {
+                            eprintln!("[Warning]: Failed to extract text from image: {}", e);
+                            media_left.push(media.clone());
+                        }
                     }
                 }
+                Ok((format!("{}{}", prompt_data.text, ocr_text), media_left))
             }
-        };
-        Ok((Box::pin(s), user_content))
+        }
+    }
+
+    // The text in an image, read with the `[ocr]` backend.
+    pub async fn ocr_image(&self, media: &Media) -> Result<String> {
+        match self.config.ocr.backend {
+            OcrBackend::Tesseract => {
+                let image = general_purpose::STANDARD.decode(&media.data)?;
+                ocr::tesseract(&self.config.ocr, &image).await
+            }
+            OcrBackend::Gemini => {
+                self.extract_text_with_gemini(&media.data, &media.mime_type)
+                    .await
+            }
+        }
     }
 
     async fn extract_text_with_gemini(&self, image_data: &str, mime_type: &str) -> Result<String> {
@@ -427,113 +729,6 @@ impl AiClient {
         Ok(transcription)
     }
 
-    pub async fn chat_openai(
-        &self,
-        prompt_data: &PromptData,
-    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, Value)> {
-        let api_key = self
-            .config
-            .get_key("openai")
-            .ok_or_else(|| anyhow!("OpenAI API key not found"))?;
-
-        let history_guard = self.history.lock().await;
-        let past_messages = history_guard
-            .get("OpenAI")
-            .map_or(&[][..], |v| v.as_slice());
-
-        let mut transcribed_text = String::new();
-        for media in &prompt_data.media {
-            if SUPPORTED_AUDIO_TYPES.contains(&media.mime_type.as_str()) {
-                print!("\n[OpenAI] Transcribing supported audio... ");
-                io::stdout().flush()?;
-                match self.transcribe_audio_openai(media).await {
-                    Ok(transcript) => {
-                        println!("Done.");
-                        transcribed_text.push_str(&transcript);
-                        transcribed_text.push_str("\n\n");
-                    }
-                    Err(e) => {
-                        eprintln!("\n[OpenAI] Transcription failed: {}", e);
-                        transcribed_text
-                            .push_str(&format!("[Audio Transcription Failed: {}]\n\n", e));
-                    }
-                }
-            }
-        }
-
-        let final_text = format!("{}{}", transcribed_text, prompt_data.text);
-        let mut content_parts: Vec<Value> = vec![json!({
-            "type": "text",
-            "text": final_text
-        })];
-
-        for media in &prompt_data.media {
-            if media.mime_type.starts_with("image/") {
-                let image_url = format!("data:{};base64,{}", media.mime_type, media.data);
-                content_parts.push(json!({
-                    "type": "image_url",
-                    "image_url": { "url": image_url }
-                }));
-            } else if !media.mime_type.starts_with("audio/") {
-                eprintln!(
-                    "\n[Warning (OpenAI Chat)]: Skipping media with unsupported MIME type '{}'.",
-                    media.mime_type
-                );
-            }
-        }
-        let user_content = json!(content_parts);
-
-        let mut messages: Vec<Value> = past_messages.iter().map(|m| json!(m)).collect();
-        messages.push(json!({
-            "role": "user",
-            "content": user_content.clone()
-        }));
-
-        let payload = json!({
-            "model": &self.config.openai.model,
-            "messages": messages,
-            "max_tokens": self.config.openai.max_tokens,
-            "temperature": self.config.openai.temperature,
-            "top_p": self.config.openai.top_p,
-            "stream": true
-        });
-
-        let response = self
-            .client
-            .post(&self.config.openai.api_base)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_body = response.text().await?;
-            return Err(anyhow!("OpenAI API Error ({}): {}", status, error_body));
-        }
-
-        let stream = response.bytes_stream();
-        let s = stream! {
-            for await chunk_result in stream {
-                let chunk = chunk_result.map_err(|e| anyhow!("Stream error: {}", e))?;
-                let s = String::from_utf8_lossy(&chunk);
-                for line in s.split('\n') {
-                    if line.starts_with("data: ") {
-                        let data = &line[6..];
-                        if data == "[DONE]" { break; }
-                        if let Ok(json) = serde_json::from_str::<Value>(data) {
-                            if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                                yield Ok(content.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-        };
-        Ok((Box::pin(s), user_content))
-    }
-
     async fn transcribe_audio_gemini(&self, media: &Media) -> Result<String> {
         let api_key = self
             .config
@@ -593,281 +788,4 @@ impl AiClient {
             Err(anyhow!("No text transcribed from audio by Gemini"))
         }
     }
-
-    pub async fn chat_gemini(
-        &self,
-        prompt_data: &PromptData,
-    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, Value)> {
-        let api_key = self
-            .config
-            .get_key("gemini")
-            .ok_or_else(|| anyhow!("Gemini API key not found"))?;
-
-        let history_guard = self.history.lock().await;
-        let gemini_history = history_guard
-            .get("Gemini")
-            .map_or(&[][..], |v| v.as_slice());
-
-        let mut contents: Vec<Value> = gemini_history
-            .iter()
-            .map(|msg| {
-                let role = if msg.role == "assistant" {
-                    "model"
-                } else {
-                    "user"
-                };
-                let parts = if msg.content.is_array() {
-                    msg.content.clone()
-                } else {
-                    json!([{"text": msg.content}])
-                };
-                json!({ "role": role, "parts": parts })
-            })
-            .collect();
-
-        let mut transcribed_text = String::new();
-        for media in &prompt_data.media {
-            if SUPPORTED_AUDIO_TYPES.contains(&media.mime_type.as_str()) {
-                print!("\n[Gemini] Transcribing supported audio... ");
-                io::stdout().flush()?;
-                match self.transcribe_audio_gemini(media).await {
-                    Ok(transcript) => {
-                        println!("Done.");
-                        transcribed_text.push_str(&transcript);
-                        transcribed_text.push_str("\n\n");
-                    }
-                    Err(e) => {
-                        eprintln!("\n[Gemini] Transcription failed: {}", e);
-                        transcribed_text
-                            .push_str(&format!("[Audio Transcription Failed: {}]\n\n", e));
-                    }
-                }
-            }
-        }
-
-        let final_text = format!("{}{}", transcribed_text, prompt_data.text);
-
-        let mut new_user_parts: Vec<Value> = vec![json!({ "text": &final_text })];
-
-        for media in &prompt_data.media {
-            if !media.mime_type.starts_with("audio/") {
-                new_user_parts.push(json!({
-                    "inline_data": {
-                        "mime_type": &media.mime_type,
-                        "data": &media.data
-                    }
-                }));
-            }
-        }
-        let user_content = json!(new_user_parts);
-
-        contents.push(json!({
-            "role": "user",
-            "parts": user_content.clone()
-        }));
-
-        let payload = json!({
-            "contents": contents,
-            "generationConfig": {
-                "maxOutputTokens": self.config.gemini.max_tokens,
-                "temperature": self.config.gemini.temperature,
-                "topP": self.config.gemini.top_p,
-            }
-        });
-
-        let base_url = self
-            .config
-            .gemini
-            .api_base
-            .replace("{model}", &self.config.gemini.model);
-        let url = format!("{}:streamGenerateContent?key={}&alt=sse", base_url, api_key);
-
-        let response = self.client.post(&url).json(&payload).send().await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_body = response.text().await?;
-            return Err(anyhow!("Gemini API Error ({}): {}", status, error_body));
-        }
-
-        let stream = response.bytes_stream();
-        let s = stream! {
-            for await chunk_result in stream {
-                let chunk = chunk_result.map_err(|e| anyhow!("Stream error from Gemini: {}", e))?;
-                let s = String::from_utf8_lossy(&chunk);
-                for line in s.lines() {
-                    if line.starts_with("data: ") {
-                        let data = &line[6..];
-                        if let Ok(json_obj) = serde_json::from_str::<Value>(data) {
-                            if let Some(text) = json_obj.pointer("/candidates/0/content/parts/0/text").and_then(Value::as_str) {
-                                yield Ok(text.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-        };
-        Ok((Box::pin(s), user_content))
-    }
-
-    pub async fn chat_claude(
-        &self,
-        prompt_data: &PromptData,
-    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, Value)> {
-        let api_key = self
-            .config
-            .get_key("claude")
-            .ok_or_else(|| anyhow!("Claude API key not found"))?;
-
-        let history_guard = self.history.lock().await;
-        let past_messages = history_guard
-            .get("Claude")
-            .map_or(&[][..], |v| v.as_slice());
-
-        let mut content_parts: Vec<Value> = vec![json!({
-            "type": "text",
-            "text": &prompt_data.text
-        })];
-
-        for media in &prompt_data.media {
-            if media.mime_type.starts_with("image/") {
-                content_parts.push(json!({
-                    "type": "image",
-                    "source": {
-                        "type": "base64",
-                        "media_type": &media.mime_type,
-                        "data": &media.data,
-                    }
-                }));
-            } else {
-                eprintln!(
-                    "\n[Warning (Claude)]: Skipping non-image file with MIME type '{}'.",
-                    media.mime_type
-                );
-            }
-        }
-        let user_content = json!(content_parts);
-
-        let mut messages: Vec<Value> = past_messages.iter().map(|m| json!(m)).collect();
-        messages.push(json!({
-            "role": "user",
-            "content": user_content.clone()
-        }));
-
-        let payload = json!({
-            "model": &self.config.claude.model,
-            "max_tokens": self.config.claude.max_tokens,
-            "messages": messages,
-            "temperature": self.config.claude.temperature,
-            "top_p": self.config.claude.top_p,
-            "stream": true
-        });
-
-        let response = self
-            .client
-            .post(&self.config.claude.api_base)
-            .header("x-api-key", api_key)
-            .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .json(&payload)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_body = response.text().await?;
-            return Err(anyhow!("Claude API Error ({}): {}", status, error_body));
-        }
-
-        let stream = response.bytes_stream();
-        let s = stream! {
-            for await chunk_result in stream {
-                let chunk = chunk_result.map_err(|e| anyhow!("Stream error: {}", e))?;
-                let s = String::from_utf8_lossy(&chunk);
-                for line in s.split('\n') {
-                    if line.starts_with("data: ") {
-                        let data = &line[6..];
-                        if let Ok(json) = serde_json::from_str::<Value>(data) {
-                           if json["type"] == "content_block_delta" {
-                                if let Some(content) = json["delta"]["text"].as_str() {
-                                    yield Ok(content.to_string());
-                                }
-                           }
-                        }
-                    }
-                }
-            }
-        };
-        Ok((Box::pin(s), user_content))
-    }
-
-    pub async fn chat_xai(
-        &self,
-        prompt_data: &PromptData,
-    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, Value)> {
-        let api_key = self
-            .config
-            .get_key("xai")
-            .ok_or_else(|| anyhow!("XAI API key not found"))?;
-
-        if !prompt_data.media.is_empty() {
-            eprintln!(
-                "\n[Warning (XAI/Grok)]: This model does not support media inputs. Ignoring all attached files."
-            );
-        }
-
-        let history_guard = self.history.lock().await;
-        let past_messages = history_guard.get("XAI").map_or(&[][..], |v| v.as_slice());
-
-        let user_content = json!(prompt_data.text);
-        let mut messages: Vec<Value> = past_messages.iter().map(|m| json!(m)).collect();
-        messages.push(json!({
-            "role": "user",
-            "content": user_content.clone()
-        }));
-
-        let payload = json!({
-            "messages": messages,
-            "model": &self.config.xai.model,
-            "max_tokens": self.config.xai.max_tokens,
-            "temperature": self.config.xai.temperature,
-            "top_p": self.config.xai.top_p,
-            "stream": true,
-        });
-
-        let response = self
-            .client
-            .post(&self.config.xai.api_base)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_body = response.text().await?;
-            return Err(anyhow!("XAI API Error ({}): {}", status, error_body));
-        }
-
-        let stream = response.bytes_stream();
-        let s = stream! {
-            for await chunk_result in stream {
-                let chunk = chunk_result.map_err(|e| anyhow!("Stream error: {}", e))?;
-                let s = String::from_utf8_lossy(&chunk);
-                 for line in s.split('\n') {
-                    if line.starts_with("data: ") {
-                        let data = &line[6..];
-                        if data == "[DONE]" { break; }
-                        if let Ok(json) = serde_json::from_str::<Value>(data) {
-                            if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                                yield Ok(content.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-        };
-        Ok((Box::pin(s), user_content))
-    }
 }
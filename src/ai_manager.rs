@@ -1,12 +1,72 @@
 mod ai_client;
+mod answer_format;
+mod answer_history;
+mod code_extract;
+mod commands;
 mod config;
+mod consensus;
+mod conversation;
+mod dedup;
+mod documents;
+mod git;
+mod heartbeat;
+mod history_budget;
+mod http;
+mod imagine;
+mod ipc;
+mod key_pool;
+// Keys are only looked up here; `aerogel key` stores and deletes them
+#[allow(dead_code)]
+mod keyring;
+mod line_editor;
+mod log_buffer;
+mod ocr;
+mod one_shot;
+mod overrides;
+mod panes;
+mod providers;
+mod question_bank;
+mod rate_limit;
+mod response_cache;
+mod saved_sessions;
+mod search;
+mod shell_tool;
+mod status;
+mod sync;
+mod templates;
+mod titles;
+mod tokens;
+mod usage;
+mod verify;
+mod video;
+mod watch;
 
-use crate::ai_client::{AiClient, PromptData};
-use crate::config::ApiConfig;
+use crate::ai_client::{AiClient, ChatReply, Message, PromptData};
+use crate::answer_format::AnswerFormat;
+use crate::answer_history::AnswerHistory;
+use crate::config::{ApiConfig, QueryMode};
+use crate::consensus::ConsensusConfig;
+use crate::conversation::{Conversation, Exchange, ExportFormat};
+use crate::dedup::DedupConfig;
+use crate::ipc::REASONING_LANG;
+use crate::line_editor::LineEditor;
+use crate::log_buffer::LogBuffer;
+use crate::overrides::Overrides;
+use crate::panes::Panes;
+use crate::providers::{Chunk, Provider};
+use crate::question_bank::{Card, QuestionBank};
+use crate::saved_sessions::{SavedSession, SessionStore};
+use crate::status::StatusFile;
+use crate::sync::{SessionSync, SyncedSession};
+use crate::templates::Placeholders;
+use crate::titles::TitleConfig;
+use crate::tokens::count_tokens;
+use crate::usage::{UsageLog, UsageRecord};
+use crate::video::VideoConfig;
+use crate::watch::WatchContext;
 use anyhow::{Result, anyhow};
 use futures_util::StreamExt;
-use futures_util::stream::Stream;
-use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fs::OpenOptions;
 use std::future::Future;
@@ -14,13 +74,125 @@ use std::io::{self, Write};
 use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc};
 
-async fn process_prompt(
+// Turns per provider `/history` shows without a number
+const DEFAULT_HISTORY_TURNS: usize = 5;
+
+// The event handler creates the workspace's thinking file on Solve; removing
+// it stops the overlay's spinner.
+fn thinking_file(workspace: Option<u32>) -> String {
+    workspace.map_or_else(|| ipc::THINKING_FILE_PREFIX.to_string(), ipc::thinking_file)
+}
+
+// The models' reasoning as the log keeps it, fenced with four backticks so
+// the code blocks it quotes stay inside.
+fn reasoning_block(reasoning: &str) -> String {
+    format!("````{}\n{}\n````\n\n", REASONING_LANG, reasoning.trim_end())
+}
+
+// Touches this workspace's heartbeat file every few seconds so the overlay
+// can show its AI process is alive.
+fn spawn_heartbeat(workspace: Option<u32>) {
+    let path = format!(
+        "{}{}",
+        heartbeat::AI_FILE_PREFIX,
+        workspace.map(|w| w.to_string()).unwrap_or_default()
+    );
+    std::thread::spawn(move || {
+        loop {
+            let _ = std::fs::write(&path, b"");
+            std::thread::sleep(heartbeat::INTERVAL);
+        }
+    });
+}
+
+// Writes this workspace's conversation to the sync folder.
+async fn sync_session(
+    sync: &SessionSync,
+    workspace: u32,
+    client: &AiClient,
+    log_path: Option<&str>,
+) {
+    let transcript = log_path
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    let session = SyncedSession::new(
+        client.title(),
+        client.history().await,
+        client.exchanges().await,
+        transcript,
+    );
+    if let Err(e) = sync.save(workspace, &session) {
+        eprintln!("[ERROR] Failed to sync the conversation: {}", e);
+    }
+}
+
+// Appends streamed answer text to the conversation log, which puts the
+// answer on screen.
+async fn write_streamed(
+    log_file: Option<&Arc<Mutex<std::fs::File>>>,
+    thinking_file: &str,
+    text: Option<String>,
+) {
+    if let (Some(file_arc), Some(text)) = (log_file, text) {
+        let mut file = file_arc.lock().await;
+        write!(file, "{}", text).unwrap();
+        let _ = std::fs::remove_file(thinking_file);
+    }
+}
+
+// The text the transcript gained past `offset` bytes.
+fn transcript_since(log_path: Option<&str>, offset: u64) -> String {
+    log_path
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|bytes| {
+            bytes
+                .get(offset as usize..)
+                .map(|tail| String::from_utf8_lossy(tail).into_owned())
+        })
+        .unwrap_or_default()
+}
+
+// What every round of the workspace's answers goes through.
+struct RoundContext {
     client: Arc<AiClient>,
     log_file: Option<Arc<Mutex<std::fs::File>>>,
+    status: Arc<std::sync::Mutex<StatusFile>>,
+    workspace: Option<u32>,
+    dedup: DedupConfig,
+    flush_interval: Duration,
+}
+
+// How one prompt is asked.
+#[derive(Default)]
+struct PromptOptions {
+    answer_format: Option<AnswerFormat>,
+    overrides: Overrides,
+    // False after /nocache
+    use_cache: bool,
+}
+
+async fn process_prompt(
+    round: &RoundContext,
     prompt_data: PromptData,
-) -> Result<()> {
+    options: PromptOptions,
+) -> Result<Vec<(String, String)>> {
+    let client = Arc::clone(&round.client);
+    let log_file = round.log_file.clone();
+    let status = Arc::clone(&round.status);
+    let (workspace, dedup, flush_interval) = (round.workspace, round.dedup, round.flush_interval);
+    let PromptOptions {
+        answer_format,
+        overrides,
+        use_cache,
+    } = options;
+    let thinking_file = thinking_file(workspace);
+    let panes = Arc::new(Panes::new(workspace));
+    if let Err(e) = panes.clear() {
+        eprintln!("[ERROR] Failed to clear the provider panes: {}", e);
+    }
     if let Some(log_file_arc) = &log_file {
         let mut file = log_file_arc.lock().await;
         if !prompt_data.media.is_empty() {
@@ -32,149 +204,673 @@ async fn process_prompt(
         }
     }
 
-    let spawn_and_process = |model_name: &'static str,
-                             call: Pin<
-        Box<
-            dyn Future<Output = Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, Value)>>
-                + Send,
-        >,
-    >| {
-        let log_file_clone = log_file.clone();
-        tokio::spawn(async move {
-            match call.await {
-                Ok((mut stream, user_content)) => {
-                    print!("{}: ", model_name);
-                    io::stdout().flush().unwrap();
-                    let mut full_response = String::new();
-
-                    while let Some(chunk) = stream.next().await {
-                        match chunk {
-                            Ok(content) => {
-                                print!("{}", content);
-                                io::stdout().flush().unwrap();
-                                full_response.push_str(&content);
-
-                                if let Some(file_arc) = &log_file_clone {
-                                    let mut file = file_arc.lock().await;
-                                    write!(file, "{}", &content).unwrap();
+    let spawn_and_process =
+        |model_name: String, call: Pin<Box<dyn Future<Output = Result<ChatReply>> + Send>>| {
+            let log_file_clone = log_file.clone();
+            let thinking_file = thinking_file.clone();
+            let panes = Arc::clone(&panes);
+            let status = Arc::clone(&status);
+            tokio::spawn(async move {
+                let model_name = model_name.as_str();
+                match call.await {
+                    Ok(ChatReply {
+                        mut stream,
+                        user_content,
+                        prompt_tokens,
+                        cached,
+                    }) => {
+                        print!("{}: ", model_name);
+                        io::stdout().flush().unwrap();
+                        let mut full_response = String::new();
+                        let mut reasoning = String::new();
+                        // Formatted answers are written once they are complete,
+                        // after the post-filter has reshaped them, and
+                        // deduplicated ones once every provider is done.
+                        let live = answer_format.is_none() && !dedup.enabled;
+                        // Whether the log has a reasoning block open
+                        let mut in_reasoning = false;
+                        let log = log_file_clone.as_ref();
+                        let mut buffer = LogBuffer::new(flush_interval);
+
+                        loop {
+                            let next = match buffer.deadline() {
+                                Some(deadline) => {
+                                    let deadline = tokio::time::Instant::from_std(deadline);
+                                    match tokio::time::timeout_at(deadline, stream.next()).await {
+                                        Ok(next) => next,
+                                        // Nothing more arrived in time
+                                        Err(_) => {
+                                            write_streamed(log, &thinking_file, buffer.take())
+                                                .await;
+                                            continue;
+                                        }
+                                    }
                                 }
+                                None => stream.next().await,
+                            };
+                            let Some(chunk) = next else { break };
+                            let chunk = match chunk {
+                                Ok(chunk) => chunk,
+                                Err(e) => {
+                                    write_streamed(log, &thinking_file, buffer.take()).await;
+                                    let err_msg =
+                                        format!("\nError streaming {} response: {}", model_name, e);
+                                    eprint!("{}", err_msg);
+                                    return Err(anyhow!(err_msg));
+                                }
+                            };
+                            if full_response.is_empty() && reasoning.is_empty() {
+                                status.lock().unwrap().responding(model_name);
                             }
-                            Err(e) => {
-                                let err_msg =
-                                    format!("\nError streaming {} response: {}", model_name, e);
-                                eprint!("{}", err_msg);
-                                return Err(anyhow!(err_msg));
+                            match chunk {
+                                Chunk::Reasoning(text) => {
+                                    reasoning.push_str(&text);
+                                    if live {
+                                        let text = if in_reasoning {
+                                            text
+                                        } else {
+                                            in_reasoning = true;
+                                            format!("\n````{}\n{}", REASONING_LANG, text)
+                                        };
+                                        write_streamed(log, &thinking_file, buffer.push(&text))
+                                            .await;
+                                    }
+                                }
+                                Chunk::Answer(content) => {
+                                    print!("{}", content);
+                                    io::stdout().flush().unwrap();
+                                    full_response.push_str(&content);
+                                    if let Err(e) = panes.append(model_name, &content) {
+                                        eprintln!(
+                                            "[ERROR] Failed to write the {} pane: {}",
+                                            model_name, e
+                                        );
+                                    }
+
+                                    if live {
+                                        let text = if std::mem::take(&mut in_reasoning) {
+                                            format!("\n````\n\n{}", content)
+                                        } else {
+                                            content
+                                        };
+                                        write_streamed(log, &thinking_file, buffer.push(&text))
+                                            .await;
+                                    }
+                                }
                             }
                         }
+                        write_streamed(log, &thinking_file, buffer.take()).await;
+                        println!();
+                        if let Some(format) = answer_format
+                            && let Err(e) =
+                                panes.set(model_name, &format.filter_response(&full_response))
+                        {
+                            eprintln!("[ERROR] Failed to write the {} pane: {}", model_name, e);
+                        }
+                        if !dedup.enabled
+                            && let Some(file_arc) = &log_file_clone
+                        {
+                            let mut file = file_arc.lock().await;
+                            if in_reasoning {
+                                write!(file, "\n````\n").unwrap();
+                            }
+                            if let Some(format) = answer_format {
+                                if !reasoning.is_empty() {
+                                    write!(file, "{}", reasoning_block(&reasoning)).unwrap();
+                                }
+                                writeln!(file, "{}", format.filter_response(&full_response))
+                                    .unwrap();
+                            }
+                            writeln!(file).unwrap();
+                        }
+                        Ok((
+                            user_content,
+                            prompt_tokens,
+                            full_response,
+                            reasoning,
+                            cached,
+                        ))
                     }
-                    println!();
-                    if let Some(file_arc) = &log_file_clone {
-                        let mut file = file_arc.lock().await;
-                        writeln!(file).unwrap();
+                    Err(e) => {
+                        let err_msg = format!("Error calling {}: {}", model_name, e);
+                        eprintln!("{}", err_msg);
+                        Err(anyhow!(err_msg))
                     }
-                    Ok((user_content, full_response))
                 }
-                Err(e) => {
-                    let err_msg = format!("Error calling {}: {}", model_name, e);
-                    eprintln!("{}", err_msg);
-                    Err(anyhow!(err_msg))
+            })
+        };
+
+    let tasks: Vec<_> = client
+        .active_providers()
+        .into_iter()
+        .map(|provider| {
+            let name = provider.name().to_string();
+            let model = provider.model().model.clone();
+            let client = Arc::clone(&client);
+            let prompt_data = prompt_data.clone();
+            let call = Box::pin(async move {
+                client
+                    .chat(&provider, &prompt_data, &overrides, use_cache)
+                    .await
+            });
+            (name.clone(), model, spawn_and_process(name, call))
+        })
+        .collect();
+    let usage_log = UsageLog::open();
+
+    // Returns this round's answers by provider, after adding them to the
+    // history.
+    let mut answers = Vec::new();
+    let mut shown = Vec::new();
+    for (provider, model, task) in tasks {
+        let provider = provider.as_str();
+        if let Ok(Ok((user_content, tokens_in, response, reasoning, cached))) = task.await {
+            if cached {
+                println!(
+                    "[INFO] {}: answered from the cache; /nocache asks again",
+                    provider
+                );
+            } else {
+                // Reasoning is billed as output too
+                let tokens_out = count_tokens(&model, &response) + count_tokens(&model, &reasoning);
+                let (cost, usage) = {
+                    let mut status = status.lock().unwrap();
+                    let cost = status.add_answer(provider, tokens_in, tokens_out);
+                    (cost, status.usage_summary(provider, tokens_in, tokens_out))
+                };
+                println!("[INFO] {}: {}", provider, usage);
+                let record = UsageRecord {
+                    day: usage::today(),
+                    workspace,
+                    provider: provider.to_string(),
+                    model,
+                    tokens_in,
+                    tokens_out,
+                    cost,
+                };
+                if let Err(e) = usage_log.add(&record) {
+                    eprintln!("[ERROR] Failed to record usage: {}", e);
                 }
             }
-        })
-    };
+            answers.push((provider.to_string(), response.clone()));
+            let display = match answer_format {
+                Some(format) => format.filter_response(&response),
+                None => response.clone(),
+            };
+            shown.push((provider.to_string(), display));
+            client
+                .add_history_entry(provider, user_content, response)
+                .await;
+        }
+    }
 
-    let ollama_task = {
-        let client = Arc::clone(&client);
-        let prompt_data = prompt_data.clone();
-        let call = Box::pin(async move { client.chat_ollama(&prompt_data).await });
-        spawn_and_process("Ollama", call)
-    };
+    if dedup.enabled
+        && let Some(file_arc) = &log_file
+    {
+        let groups = dedup::group_answers(&shown, dedup.similarity);
+        let mut file = file_arc.lock().await;
+        match groups.as_slice() {
+            // A single answer needs no provider heading
+            [group] if group.providers.len() == 1 => writeln!(file, "{}\n", group.answer)?,
+            groups => {
+                let sections: Vec<String> = groups.iter().map(|g| g.to_markdown()).collect();
+                writeln!(file, "{}\n", sections.join("\n\n"))?;
+            }
+        }
+    }
 
-    let openrouter_task = {
-        let client = Arc::clone(&client);
-        let prompt_data = prompt_data.clone();
-        let call = Box::pin(async move { client.chat_openrouter(&prompt_data).await });
-        spawn_and_process("OpenRouter", call)
-    };
+    Ok(answers)
+}
 
-    let openai_task = {
-        let client = Arc::clone(&client);
-        let prompt_data = prompt_data.clone();
-        let call = Box::pin(async move { client.chat_openai(&prompt_data).await });
-        spawn_and_process("OpenAI", call)
-    };
+// Writes the code blocks of the latest answers into `dir`, one subdirectory
+// per provider when several providers answered, and lists the files in the
+// conversation log so they show up in the overlay.
+async fn extract_code(
+    client: &AiClient,
+    log_file: Option<&Arc<Mutex<std::fs::File>>>,
+    dir: &Path,
+) -> Result<()> {
+    let responses = client.latest_responses().await;
+    if responses.is_empty() {
+        println!("[INFO] No answer to extract code from yet.");
+        return Ok(());
+    }
 
-    let claude_task = {
-        let client = Arc::clone(&client);
-        let prompt_data = prompt_data.clone();
-        let call = Box::pin(async move { client.chat_claude(&prompt_data).await });
-        spawn_and_process("Claude", call)
-    };
+    let mut paths = Vec::new();
+    for (provider, response) in &responses {
+        let blocks = code_extract::extract_code_blocks(response);
+        let provider_dir = if responses.len() > 1 {
+            dir.join(provider.to_lowercase())
+        } else {
+            dir.to_path_buf()
+        };
+        paths.extend(code_extract::write_code_blocks(&provider_dir, &blocks)?);
+    }
 
-    let gemini_task = {
-        let client = Arc::clone(&client);
-        let prompt_data = prompt_data.clone();
-        let call = Box::pin(async move { client.chat_gemini(&prompt_data).await });
-        spawn_and_process("Gemini", call)
+    let summary = if paths.is_empty() {
+        "No code blocks in the latest answer.".to_string()
+    } else {
+        let list = paths
+            .iter()
+            .map(|path| format!("- `{}`", path.display()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("Saved {} code block(s):\n\n{}", paths.len(), list)
     };
+    println!("[INFO] {}", summary);
+    if let Some(log_file) = log_file {
+        let mut file = log_file.lock().await;
+        writeln!(file, "\n{}\n", summary)?;
+    }
+    Ok(())
+}
 
-    let xai_task = {
-        let client = Arc::clone(&client);
-        let prompt_data = prompt_data.clone();
-        let call = Box::pin(async move { client.chat_xai(&prompt_data).await });
-        spawn_and_process("XAI", call)
-    };
+// Checks the code blocks of the latest answers and appends the results
+// under them in the conversation log.
+async fn verify_answers(
+    client: &AiClient,
+    log_file: Option<&Arc<Mutex<std::fs::File>>>,
+    config: &verify::VerifyConfig,
+) -> Result<()> {
+    let responses = client.latest_responses().await;
+    let mut sections = Vec::new();
+    for (provider, response) in &responses {
+        if let Some(results) = verify::verify_answer(config, response).await {
+            sections.push(if responses.len() > 1 {
+                format!("{}:\n{}", provider, results)
+            } else {
+                results
+            });
+        }
+    }
+    if sections.is_empty() {
+        return Ok(());
+    }
+    let report = format!("**Verification**\n\n{}", sections.join("\n\n"));
+    println!("[INFO] {}", report);
+    if let Some(log_file) = log_file {
+        let mut file = log_file.lock().await;
+        writeln!(file, "\n{}\n", report)?;
+    }
+    Ok(())
+}
 
-    let (ollama_res, openrouter_res, openai_res, claude_res, gemini_res, xai_res) = tokio::join!(
-        ollama_task,
-        openrouter_task,
-        openai_task,
-        claude_task,
-        gemini_task,
-        xai_task
-    );
+// Runs a round while still reading input: `/stop` ends the answers
+// streaming now and sets `stopped`, other lines wait in `queued` until the
+// round is done.
+async fn run_stoppable<T>(
+    round: impl Future<Output = T>,
+    client: &AiClient,
+    input_rx: &mut mpsc::UnboundedReceiver<String>,
+    queued: &mut VecDeque<String>,
+    stopped: &mut bool,
+) -> T {
+    let mut round = std::pin::pin!(round);
+    loop {
+        tokio::select! {
+            result = &mut round => return result,
+            Some(line) = input_rx.recv() => {
+                if line.trim().eq_ignore_ascii_case("/stop") {
+                    println!("[INFO] Stopping the answers.");
+                    client.stop();
+                    *stopped = true;
+                } else {
+                    queued.push_back(line);
+                }
+            }
+        }
+    }
+}
 
-    if let Ok(Ok((user_content, response))) = ollama_res {
-        client
-            .add_history_entry("Ollama", user_content, response)
-            .await;
+// Fills in a prompt template with the typed prompt, the clipboard and the
+// text in the attached images. A placeholder that cannot be filled is left
+// empty.
+async fn apply_template(
+    client: &AiClient,
+    template: &str,
+    input: String,
+    attached_files: &[String],
+) -> String {
+    let mut values = Placeholders {
+        input,
+        ..Placeholders::default()
+    };
+    if templates::uses(template, "clipboard") {
+        match templates::read_clipboard().await {
+            Ok(text) => values.clipboard = Some(text),
+            Err(e) => eprintln!("[ERROR] Failed to read the clipboard: {}", e),
+        }
     }
-    if let Ok(Ok((user_content, response))) = openrouter_res {
-        client
-            .add_history_entry("OpenRouter", user_content, response)
-            .await;
+    if templates::uses(template, "ocr") {
+        let images: Vec<String> = attached_files
+            .iter()
+            .filter(|path| {
+                mime_guess::from_path(path)
+                    .first()
+                    .is_some_and(|mime| mime.type_() == mime_guess::mime::IMAGE)
+            })
+            .cloned()
+            .collect();
+        match PromptData::new(String::new(), &images, &VideoConfig::default()).await {
+            Ok(prompt_data) => {
+                let mut texts = Vec::new();
+                for media in &prompt_data.media {
+                    match client.ocr_image(media).await {
+                        Ok(text) => texts.push(text.trim().to_string()),
+                        Err(e) => eprintln!("[ERROR] Failed to read text from an image: {}", e),
+                    }
+                }
+                values.ocr = Some(texts.join("\n\n"));
+            }
+            Err(e) => eprintln!("[ERROR] Failed to read the attached files: {}", e),
+        }
     }
-    if let Ok(Ok((user_content, response))) = openai_res {
-        client
-            .add_history_entry("OpenAI", user_content, response)
-            .await;
+    templates::expand(template, &values)
+}
+
+// Sets the conversation's title and `.aerogel_title<workspace>`, which the
+// overlay labels the workspace's tab with.
+fn set_title(client: &AiClient, workspace: Option<u32>, title: Option<String>) {
+    let path = titles::title_file(workspace);
+    let result = match &title {
+        Some(title) => std::fs::write(&path, title),
+        None => std::fs::remove_file(&path).or_else(|e| match e.kind() {
+            io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        }),
+    };
+    if let Err(e) = result {
+        eprintln!("[ERROR] Failed to write the conversation title: {}", e);
     }
-    if let Ok(Ok((user_content, response))) = claude_res {
-        client
-            .add_history_entry("Claude", user_content, response)
-            .await;
+    client.set_title(title);
+}
+
+// The last `turns` turns each provider is sent with the next prompt, for
+// `/history`.
+async fn history_report(client: &AiClient, turns: usize) -> String {
+    let history = client.history().await;
+    let mut providers: Vec<&String> = history.keys().collect();
+    providers.sort();
+    let sections: Vec<String> = providers
+        .into_iter()
+        .filter(|provider| !history[*provider].is_empty())
+        .map(|provider| {
+            let (model, budget) =
+                find_provider(client, provider).map_or((String::new(), 0), |found| {
+                    (
+                        found.model().model.clone(),
+                        found.model().context_tokens.into(),
+                    )
+                });
+            history_budget::describe(provider, &model, &history[provider], turns, budget)
+        })
+        .collect();
+    if sections.is_empty() {
+        return "No history yet; it starts with the first answer.".to_string();
     }
-    if let Ok(Ok((user_content, response))) = gemini_res {
-        client
-            .add_history_entry("Gemini", user_content, response)
-            .await;
+    format!("**History**\n\n{}", sections.join("\n\n"))
+}
+
+// The provider called `name`, ignoring case.
+fn find_provider<'a>(client: &'a AiClient, name: &str) -> Option<&'a Arc<dyn Provider>> {
+    client
+        .providers()
+        .iter()
+        .find(|provider| provider.name().eq_ignore_ascii_case(name))
+}
+
+// Titles the conversation from its first exchange, in the background so the
+// next prompt need not wait. The title is dropped if the conversation was
+// cleared or replaced in the meantime.
+fn spawn_title(
+    client: Arc<AiClient>,
+    config: TitleConfig,
+    workspace: Option<u32>,
+    question: String,
+    (answered_by, answer): (String, String),
+) {
+    tokio::spawn(async move {
+        let name = config.provider.as_deref().unwrap_or(&answered_by);
+        let Some(provider) = find_provider(&client, name) else {
+            eprintln!(
+                "[ERROR] Failed to title the conversation: unknown provider '{}'",
+                name
+            );
+            return;
+        };
+        let model = titles::title_model(&config, provider.model());
+        let prompt = titles::title_prompt(&question, &answer);
+        match client.complete(provider.as_ref(), &model, &prompt).await {
+            Ok(reply) => {
+                let Some(title) = titles::clean_title(&reply) else {
+                    return;
+                };
+                if client.title().is_some() || client.exchanges().await.len() != 1 {
+                    return;
+                }
+                println!("[INFO] Conversation title: {}", title);
+                set_title(&client, workspace, Some(title));
+            }
+            Err(e) => eprintln!("[ERROR] Failed to title the conversation: {}", e),
+        }
+    });
+}
+
+// Asks one model to merge or rank the round's answers and writes the result
+// above them, at `round_start` in the conversation log.
+async fn write_consensus(
+    client: &AiClient,
+    log_file: Option<&Arc<Mutex<std::fs::File>>>,
+    log_path: Option<&str>,
+    round_start: u64,
+    config: &ConsensusConfig,
+    question: &str,
+    answers: &[(String, String)],
+) -> Result<()> {
+    let name = config.provider.as_deref().unwrap_or(&answers[0].0);
+    let provider =
+        find_provider(client, name).ok_or_else(|| anyhow!("unknown provider '{}'", name))?;
+    println!("[INFO] Asking {} for a consensus...", provider.name());
+    let model = consensus::consensus_model(config, provider.model());
+    let prompt = consensus::consensus_prompt(config.mode, question, answers);
+    let reply = client.complete(provider.as_ref(), &model, &prompt).await?;
+    println!("{}", reply);
+    let block = consensus::consensus_block(config.mode, provider.name(), &reply);
+    if let (Some(log_file), Some(path)) = (log_file, log_path) {
+        let mut file = log_file.lock().await;
+        let mut transcript = std::fs::read(path)?;
+        let round_start = (round_start as usize).min(transcript.len());
+        transcript.splice(round_start..round_start, block.into_bytes());
+        file.set_len(0)?;
+        file.write_all(&transcript)?;
     }
-    if let Ok(Ok((user_content, response))) = xai_res {
-        client
-            .add_history_entry("XAI", user_content, response)
-            .await;
+    Ok(())
+}
+
+// Replaces the conversation with an imported or saved one, and the transcript
+// the overlay shows with `transcript`.
+async fn replace_conversation(
+    client: &AiClient,
+    log_file: Option<&Arc<Mutex<std::fs::File>>>,
+    workspace: Option<u32>,
+    title: Option<String>,
+    history: HashMap<String, Vec<Message>>,
+    exchanges: Vec<Exchange>,
+    transcript: &str,
+) -> Result<()> {
+    set_title(client, workspace, title);
+    client.restore_history(history).await;
+    client.restore_exchanges(exchanges).await;
+    if let Some(log_file) = log_file {
+        let mut file = log_file.lock().await;
+        file.set_len(0)?;
+        write!(file, "{}", transcript)?;
     }
+    Ok(())
+}
 
+// Chooses the provider later prompts go to and notes it in the
+// conversation log, so the overlay shows who is asked.
+async fn set_target(
+    client: &AiClient,
+    log_file: Option<&Arc<Mutex<std::fs::File>>>,
+    name: &str,
+) -> Result<()> {
+    let note = match client.set_target(name)? {
+        Some(provider) => format!(
+            "Asking only {} from now on; `/ask @all` asks every provider again.",
+            provider
+        ),
+        None => "Asking every provider again.".to_string(),
+    };
+    println!("[INFO] {}", note);
+    if let Some(log_file) = log_file {
+        let mut file = log_file.lock().await;
+        writeln!(file, "\n{}\n", note)?;
+    }
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load config from both .env and aerogel.toml
-    let config = ApiConfig::load()?;
-    let client = Arc::new(AiClient::new(config));
+    let mut config = ApiConfig::load()?;
+
+    // `--ask` answers one prompt on stdout and exits, for shell scripts.
+    let args: Vec<String> = env::args().skip(1).collect();
+    match one_shot::parse(&args) {
+        Ok(Some(one_shot)) => std::process::exit(one_shot::run(config, one_shot).await),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("[ERROR] {}\n{}", e, one_shot::USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    // The event handler passes the workspace number after the log path, so
+    // each workspace can have its own answer preferences.
+    let workspace = env::args().nth(2).and_then(|arg| arg.parse::<u32>().ok());
+    spawn_heartbeat(workspace);
+    // `--new-session` is passed when the workspace was cleared, so the synced
+    // conversation is reset instead of restored.
+    let new_session = env::args().any(|arg| arg == "--new-session");
+    let sync = workspace
+        .and_then(|workspace| SessionSync::from_config(&config).map(|sync| (sync, workspace)));
+    let question_bank = config.question_bank.then(QuestionBank::open);
+    let session_store = SessionStore::open();
+    let watch_config = config.watch.clone();
+    let git_config = config.git.clone();
+    let dedup_config = config.dedup;
+    let flush_interval = config.streaming.flush_interval();
+    let answer_history = AnswerHistory::new(workspace, config.history);
+    let verify_config = config.verify.enabled.then(|| config.verify.clone());
+    let prompt_templates = config.templates.clone();
+    let search_config = config.search.clone();
+    let pricing = config.pricing.clone();
+    let shell_config = config.shell.enabled.then(|| config.shell.clone());
+    let video_config = config.video.clone();
+    let imagine_config = config.imagine.clone();
+    let title_config = config.titles.clone();
+    let consensus_config = config.consensus.enabled.then(|| config.consensus.clone());
+    let openai_key = config.get_key("openai").cloned();
+    let mut system_prompt = workspace.and_then(|workspace| config.system_prompt(workspace));
+    if shell_config.is_some() {
+        system_prompt = Some(match system_prompt {
+            Some(prompt) => format!("{}\n\n{}", prompt, shell_tool::SYSTEM_PROMPT),
+            None => shell_tool::SYSTEM_PROMPT.to_string(),
+        });
+    }
+    if let Some(prompt) = &system_prompt {
+        println!("[INFO] System prompt: {}", prompt);
+    }
+    // The workspace's persona may pick the provider to ask and its model
+    let persona = workspace
+        .and_then(|workspace| config.workspace_persona(workspace))
+        .map(|(name, persona)| (name.to_string(), persona.clone()));
+    if let Some((name, persona)) = &persona {
+        println!("[INFO] Persona: {}", name);
+        if let (Some(provider), Some(model)) = (&persona.provider, &persona.model) {
+            match config.model_config_mut(provider) {
+                Some(model_config) => model_config.model = model.clone(),
+                None => eprintln!(
+                    "[ERROR] Persona '{}': unknown provider '{}'",
+                    name, provider
+                ),
+            }
+        }
+    }
+    let preconnect = config.http.preconnect;
+    let mut query_mode = config.mode;
+    let primary = config.primary_provider(workspace).map(str::to_string);
+    let client = Arc::new(AiClient::new(config, system_prompt));
+    // In single mode only the primary provider is asked, the first one
+    // unless configured
+    let primary = primary.or_else(|| {
+        client
+            .providers()
+            .first()
+            .map(|provider| provider.name().to_string())
+    });
+    let target = match query_mode {
+        QueryMode::Single => primary.clone(),
+        QueryMode::All => persona.and_then(|(_, persona)| persona.provider),
+    };
+    if let Some(provider) = target
+        && let Err(e) = client.set_target(&provider)
+    {
+        eprintln!("[ERROR] {}", e);
+    }
+    if preconnect {
+        http::preconnect(
+            client.http(),
+            client
+                .providers()
+                .iter()
+                .map(|provider| provider.model().api_base.as_str()),
+        );
+    }
+    let status = Arc::new(std::sync::Mutex::new(StatusFile::new(
+        workspace,
+        usage::prices(client.providers(), &pricing),
+        client.context_limits(),
+    )));
+    let log_path = env::args().nth(1);
 
-    let log_file: Option<Arc<Mutex<std::fs::File>>> = if let Some(path) = env::args().nth(1) {
+    // A title left by the workspace's previous conversation is removed
+    // unless that conversation is continued.
+    let mut title = None;
+    if let Some((sync, workspace)) = &sync {
+        if new_session {
+            sync_session(sync, *workspace, &client, None).await;
+        } else {
+            match sync.load(*workspace) {
+                Ok(Some(session)) => {
+                    println!(
+                        "[INFO] Continuing the synced conversation of workspace {}",
+                        workspace
+                    );
+                    client.restore_history(session.history).await;
+                    client.restore_exchanges(session.exchanges).await;
+                    title = session.title;
+                    // Show the transcript unless this log already has text.
+                    if let Some(path) = &log_path
+                        && std::fs::read_to_string(path)
+                            .unwrap_or_default()
+                            .trim()
+                            .is_empty()
+                    {
+                        std::fs::write(path, &session.transcript)?;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("[ERROR] Failed to load the synced conversation: {}", e),
+            }
+        }
+    }
+
+    set_title(&client, workspace, title);
+
+    let log_file: Option<Arc<Mutex<std::fs::File>>> = if let Some(path) = &log_path {
         println!("[INFO] Logging conversation to '{}'", path);
         Some(Arc::new(Mutex::new(
             OpenOptions::new().create(true).append(true).open(path)?,
@@ -182,25 +878,106 @@ async fn main() -> Result<()> {
     } else {
         None
     };
+    let round = RoundContext {
+        client: Arc::clone(&client),
+        log_file: log_file.clone(),
+        status: Arc::clone(&status),
+        workspace,
+        dedup: dedup_config,
+        flush_interval,
+    };
 
     println!("--- AI Client ---");
-    println!("Commands: /upload <file_path>, /ask, /new, /quit");
+    println!("Commands: /upload <file_path>, /ask, /new, /quit; /help lists them all.");
     println!("You can upload text, image, and audio files.");
     println!("Type your prompt (multi-line is okay), then use /ask to send.");
 
     let mut attached_files: Vec<String> = Vec::new();
     let mut multi_line_prompt = String::new();
+    let mut pending_format: Option<AnswerFormat> = None;
+    // (name, template) chosen with /template or /t, applied by the next /ask
+    let mut pending_template: Option<(String, String)> = None;
+    // Set by /nocache; the next /ask skips the response cache
+    let mut skip_cache = false;
+    // The flashcard shown by /quiz; the next /ask grades an attempt at it
+    let mut quiz_card: Option<Card> = None;
+    // Files tailed with /watch; their new lines go along with the next prompt
+    let watch_context = Arc::new(Mutex::new(WatchContext::default()));
+    let mut watchers = Vec::new();
+    // Output of /diff, sent along with the next prompt
+    let mut attached_diff: Option<String> = None;
+    // (query, results) of /search, sent along with the next prompt
+    let mut attached_search: Option<(String, String)> = None;
+    // Command a model proposed in the last round; /run runs it
+    let mut proposed_command: Option<String> = None;
+    // (question, prompt as sent, attached files) of the last round, which
+    // /regen sends again
+    let mut last_round: Option<(String, String, Vec<String>)> = None;
+
+    // Commands come from stdin, and from watchers asking about new errors.
+    let (input_tx, mut input_rx) = mpsc::unbounded_channel::<String>();
+    // Lines that arrived while a round was running
+    let mut queued: VecDeque<String> = VecDeque::new();
+    // The prompt the main loop waits at, which the line editor shows
+    let prompt = Arc::new(std::sync::Mutex::new(None));
+    {
+        let input_tx = input_tx.clone();
+        let prompt = Arc::clone(&prompt);
+        std::thread::spawn(move || {
+            if line_editor::interactive() {
+                let mut editor = match LineEditor::new(prompt) {
+                    Ok(editor) => editor,
+                    Err(e) => {
+                        eprintln!("[ERROR] Failed to set up line editing: {}", e);
+                        return;
+                    }
+                };
+                loop {
+                    match editor.read_line() {
+                        Ok(Some(line)) => {
+                            if input_tx.send(line).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            eprintln!("[ERROR] Failed to read input: {}", e);
+                            break;
+                        }
+                    }
+                }
+                return;
+            }
+            for line in io::stdin().lines() {
+                let Ok(line) = line else { break };
+                if input_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
 
     loop {
-        if attached_files.is_empty() && multi_line_prompt.is_empty() {
-            print!("> ");
+        let shown = if attached_files.is_empty() && multi_line_prompt.is_empty() {
+            "> "
         } else {
-            print!(". ");
+            ". "
+        };
+        if !line_editor::interactive() {
+            print!("{}", shown);
+            io::stdout().flush()?;
+        } else if queued.is_empty() {
+            *prompt.lock().unwrap() = Some(shown.to_string());
         }
-        io::stdout().flush()?;
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let Some(input) = (match queued.pop_front() {
+            Some(line) => Some(line),
+            None => input_rx.recv().await,
+        }) else {
+            break;
+        };
+        // Not shown yet when the line came from elsewhere, such as a watcher
+        prompt.lock().unwrap().take();
         let input_trimmed = input.trim();
 
         if input_trimmed.starts_with("/upload ") {
@@ -214,6 +991,370 @@ async fn main() -> Result<()> {
             continue;
         }
 
+        if input_trimmed.eq_ignore_ascii_case("/files") {
+            let mut lines: Vec<String> = attached_files
+                .iter()
+                .map(|path| format!("- `{}`", path))
+                .collect();
+            if attached_diff.is_some() {
+                lines.push("- the git diff from `/diff`".to_string());
+            }
+            if let Some((query, _)) = &attached_search {
+                lines.push(format!("- web results for \"{}\"", query));
+            }
+            let note = if lines.is_empty() {
+                "Nothing is attached; `/upload <file>` attaches a file.".to_string()
+            } else {
+                format!("Attached to the next prompt:\n\n{}", lines.join("\n"))
+            };
+            println!("[INFO] {}", note);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/clear-files") {
+            let count = attached_files.len()
+                + usize::from(attached_diff.is_some())
+                + usize::from(attached_search.is_some());
+            attached_files.clear();
+            attached_diff = None;
+            attached_search = None;
+            let note = match count {
+                0 => "Nothing was attached.".to_string(),
+                count => format!(
+                    "Dropped {} attachment(s); the prompt typed so far is kept.",
+                    count
+                ),
+            };
+            println!("[INFO] {}", note);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if let Some(path) = input_trimmed.strip_prefix("/watch ") {
+            let path = path.trim();
+            println!("[INFO] Watching '{}' for new output", path);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(
+                    file,
+                    "\nWatching `{}`; new output is added to the next prompt.\n",
+                    path
+                )?;
+            }
+            watchers.push(watch::spawn_watch(
+                path.into(),
+                Arc::clone(&watch_context),
+                input_tx.clone(),
+                watch_config.clone(),
+            ));
+            continue;
+        }
+
+        if input_trimmed == "/diff" || input_trimmed.starts_with("/diff ") {
+            let spec = input_trimmed["/diff".len()..].trim();
+            let note = match git::diff(&git_config, spec).await {
+                Ok(diff) if diff.trim().is_empty() => {
+                    attached_diff = None;
+                    "No changes to attach.".to_string()
+                }
+                Ok(diff) => {
+                    let note = format!(
+                        "Attached git diff ({} file(s) changed); it goes along with the next prompt.",
+                        git::changed_files(&diff)
+                    );
+                    attached_diff = Some(diff);
+                    note
+                }
+                Err(e) => {
+                    eprintln!("[ERROR] {}", e);
+                    format!("Could not get the diff: {}", e)
+                }
+            };
+            println!("[INFO] {}", note);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/unwatch") {
+            for watcher in watchers.drain(..) {
+                watcher.abort();
+            }
+            watch_context.lock().await.take_prompt_context();
+            println!("[INFO] Stopped watching files.");
+            continue;
+        }
+
+        // Sent by a watcher before its "/ask" when a failure shows up
+        if let Some(path) = input_trimmed.strip_prefix("/watch-error ") {
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n**New error in `{}`**\n", path.trim())?;
+            }
+            if !multi_line_prompt.is_empty() {
+                multi_line_prompt.push('\n');
+            }
+            multi_line_prompt.push_str("What does this new error mean, and how do I fix it?");
+            continue;
+        }
+
+        if let Some(name) = input_trimmed.strip_prefix("/format ") {
+            match AnswerFormat::from_name(name) {
+                Some(format) => {
+                    println!(
+                        "[INFO] Next answer will use the '{}' format.",
+                        format.name()
+                    );
+                    pending_format = Some(format);
+                }
+                None => {
+                    eprintln!(
+                        "[ERROR] Unknown answer format '{}'. Available: code_only, bullets, steps",
+                        name.trim()
+                    );
+                }
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/nocache") {
+            println!("[INFO] The next prompt is sent without checking the response cache.");
+            skip_cache = true;
+            continue;
+        }
+
+        let template_command = input_trimmed
+            .strip_prefix("/template ")
+            .map(|name| (name, false))
+            .or_else(|| input_trimmed.strip_prefix("/t ").map(|name| (name, true)));
+        if let Some((name, ask)) = template_command {
+            let name = name.trim();
+            // Names are matched ignoring case, as the keybinding config
+            // lowercases them
+            match prompt_templates
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            {
+                Some((name, template)) => {
+                    println!("[INFO] Next prompt will use the '{}' template.", name);
+                    pending_template = Some((name.clone(), template.clone()));
+                    if ask {
+                        queued.push_front("/ask".to_string());
+                    }
+                }
+                None => {
+                    let mut names: Vec<&str> =
+                        prompt_templates.keys().map(String::as_str).collect();
+                    names.sort_unstable();
+                    eprintln!(
+                        "[ERROR] Unknown template '{}'. Available: {}",
+                        name,
+                        names.join(", ")
+                    );
+                    if ask {
+                        let _ = std::fs::remove_file(thinking_file(workspace));
+                    }
+                }
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/extract") {
+            let dir = match workspace {
+                Some(workspace) => Path::new("answers").join(format!("ws{}", workspace)),
+                None => Path::new("answers").to_path_buf(),
+            };
+            if let Err(e) = extract_code(&client, log_file.as_ref(), &dir).await {
+                eprintln!("[ERROR] Failed to extract code blocks: {}", e);
+            }
+            continue;
+        }
+
+        let export_args = input_trimmed
+            .strip_prefix("/export")
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '));
+        if let Some(args) = export_args {
+            let args = args.trim();
+            let (format, path) = args.split_once(' ').unwrap_or((args, ""));
+            let format = if format.is_empty() { "md" } else { format };
+            let path = Some(path.trim()).filter(|path| !path.is_empty());
+            let conversation =
+                Conversation::new(workspace, client.title(), client.exchanges().await);
+            let note = match ExportFormat::parse(format)
+                .and_then(|format| conversation::export(&conversation, format, path))
+            {
+                Ok(path) => format!(
+                    "Exported {} prompt(s) to `{}`.",
+                    conversation.exchanges.len(),
+                    path.display()
+                ),
+                Err(e) => format!("Could not export the conversation: {}", e),
+            };
+            println!("[INFO] {}", note);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if let Some(path) = input_trimmed.strip_prefix("/import ") {
+            let path = path.trim();
+            match conversation::load(path) {
+                Ok(imported) => {
+                    let note = match imported.workspace {
+                        Some(from) if Some(from) != workspace => format!(
+                            "Imported {} prompt(s) of workspace {} from `{}`.",
+                            imported.exchanges.len(),
+                            from,
+                            path
+                        ),
+                        _ => format!(
+                            "Imported {} prompt(s) from `{}`.",
+                            imported.exchanges.len(),
+                            path
+                        ),
+                    };
+                    let transcript = imported.transcript();
+                    replace_conversation(
+                        &client,
+                        log_file.as_ref(),
+                        workspace,
+                        imported.title.clone(),
+                        imported.history(),
+                        imported.exchanges,
+                        &format!("{}{}\n\n", transcript, note),
+                    )
+                    .await?;
+                    last_round = None;
+                    println!("[INFO] {}", note);
+                    if let Some((sync, workspace)) = &sync {
+                        sync_session(sync, *workspace, &client, log_path.as_deref()).await;
+                    }
+                }
+                Err(e) => eprintln!("[ERROR] {}", e),
+            }
+            continue;
+        }
+
+        if let Some(name) = input_trimmed
+            .strip_prefix("/save ")
+            .or(input_trimmed.eq_ignore_ascii_case("/save").then_some(""))
+        {
+            // Without a name the session is saved under its title
+            let name = match name.trim() {
+                "" => match client.title() {
+                    Some(title) => titles::slug(&title),
+                    None => {
+                        eprintln!("[ERROR] The conversation has no title yet; use /save <name>");
+                        continue;
+                    }
+                },
+                name => name.to_string(),
+            };
+            let name = name.as_str();
+            let transcript = log_path
+                .as_deref()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .unwrap_or_default();
+            let session = SavedSession::new(
+                workspace,
+                client.title(),
+                client.history().await,
+                client.exchanges().await,
+                transcript,
+            );
+            match session_store.save(name, &session) {
+                Ok(()) => println!("[INFO] Saved the conversation as '{}'.", name),
+                Err(e) => eprintln!("[ERROR] Failed to save the session: {}", e),
+            }
+            continue;
+        }
+
+        if let Some(name) = input_trimmed.strip_prefix("/load ") {
+            let name = name.trim();
+            match session_store.load(name) {
+                Ok(session) => {
+                    replace_conversation(
+                        &client,
+                        log_file.as_ref(),
+                        workspace,
+                        session.title,
+                        session.history,
+                        session.exchanges,
+                        &session.transcript,
+                    )
+                    .await?;
+                    last_round = None;
+                    println!("[INFO] Loaded the session '{}'.", name);
+                    if let Some((sync, workspace)) = &sync {
+                        sync_session(sync, *workspace, &client, log_path.as_deref()).await;
+                    }
+                }
+                Err(e) => eprintln!("[ERROR] {}", e),
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/sessions") {
+            let note = match session_store.list() {
+                Ok(sessions) if sessions.is_empty() => {
+                    "No saved sessions yet; `/save <name>` keeps this one.".to_string()
+                }
+                Ok(sessions) => {
+                    let lines: Vec<String> = sessions
+                        .iter()
+                        .map(|(name, saved)| {
+                            let saved: chrono::DateTime<chrono::Local> = (*saved).into();
+                            format!("- `{}`, saved {}", name, saved.format("%Y-%m-%d %H:%M"))
+                        })
+                        .collect();
+                    format!("Saved sessions (`/load <name>`):\n\n{}", lines.join("\n"))
+                }
+                Err(e) => format!("Could not list the saved sessions: {}", e),
+            };
+            println!("[INFO] {}", note);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/quiz") {
+            match QuestionBank::open().next_card() {
+                Ok(Some(card)) => {
+                    println!("[INFO] Quiz: {}", card.question);
+                    if let Some(log_file) = &log_file {
+                        let mut file = log_file.lock().await;
+                        writeln!(
+                            file,
+                            "\n**Quiz:** {}\n\nType your answer and solve to have it graded.\n",
+                            card.question
+                        )?;
+                    }
+                    quiz_card = Some(card);
+                }
+                Ok(None) => println!("[INFO] The question bank is empty."),
+                Err(e) => eprintln!("[ERROR] Failed to read the question bank: {}", e),
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/help") {
+            println!("{}", commands::help());
+            continue;
+        }
+
         if input_trimmed.eq_ignore_ascii_case("/quit")
             || input_trimmed.eq_ignore_ascii_case("/exit")
         {
@@ -221,42 +1362,519 @@ async fn main() -> Result<()> {
             break;
         }
 
+        if input_trimmed.eq_ignore_ascii_case("/stop") {
+            println!("[INFO] No answer is streaming.");
+            continue;
+        }
+
+        let history_args = input_trimmed
+            .strip_prefix("/history")
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '));
+        if let Some(args) = history_args {
+            let args = args.trim();
+            let note = match args.parse::<usize>() {
+                _ if args.is_empty() => history_report(&client, DEFAULT_HISTORY_TURNS).await,
+                Ok(turns) if turns > 0 => history_report(&client, turns).await,
+                _ => format!("`/history` takes a number of turns, not \"{}\".", args),
+            };
+            println!("[INFO] {}", note);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/usage") {
+            match UsageLog::open().load() {
+                Ok(records) => {
+                    let report = format!(
+                        "**Estimated usage**\n\n- This session: {}\n{}",
+                        status.lock().unwrap().session_summary(),
+                        usage::summarize(&records, &usage::today())
+                    );
+                    println!("{}", report);
+                    if let Some(log_file) = &log_file {
+                        let mut file = log_file.lock().await;
+                        writeln!(file, "\n{}\n", report)?;
+                    }
+                }
+                Err(e) => eprintln!("[ERROR] Failed to read the usage log: {}", e),
+            }
+            continue;
+        }
+
         if input_trimmed.eq_ignore_ascii_case("/new") {
             client.clear_history().await;
+            set_title(&client, workspace, None);
             attached_files.clear();
             multi_line_prompt.clear();
+            attached_diff = None;
+            attached_search = None;
+            proposed_command = None;
+            last_round = None;
+            pending_format = None;
+            pending_template = None;
+            skip_cache = false;
+            if let Some((sync, workspace)) = &sync {
+                sync_session(sync, *workspace, &client, None).await;
+            }
             println!("New conversation started. History cleared.");
             continue;
         }
 
-        if input_trimmed.eq_ignore_ascii_case("/ask") {
-            if multi_line_prompt.is_empty() && attached_files.is_empty() {
+        if let Some(mode) = input_trimmed.strip_prefix("/mode ") {
+            let mode = match QueryMode::parse(mode.trim()) {
+                Ok(mode) => mode,
+                Err(e) => {
+                    eprintln!("[ERROR] {}", e);
+                    continue;
+                }
+            };
+            let target = match mode {
+                QueryMode::Single => primary.as_deref().unwrap_or("all"),
+                QueryMode::All => "all",
+            };
+            match client.set_target(target) {
+                Ok(target) => {
+                    query_mode = mode;
+                    let note = match target {
+                        Some(provider) => format!(
+                            "Asking only {} from now on; `/ask @<provider>` asks another provider for one prompt.",
+                            provider
+                        ),
+                        None => "Asking every provider from now on.".to_string(),
+                    };
+                    println!("[INFO] {}", note);
+                    if let Some(log_file) = &log_file {
+                        let mut file = log_file.lock().await;
+                        writeln!(file, "\n{}\n", note)?;
+                    }
+                }
+                Err(e) => eprintln!("[ERROR] {}", e),
+            }
+            continue;
+        }
+
+        if let Some(name) = input_trimmed.strip_prefix("/target ") {
+            if let Err(e) = set_target(&client, log_file.as_ref(), name.trim()).await {
+                eprintln!("[ERROR] {}", e);
+            }
+            continue;
+        }
+
+        if let Some(query) = input_trimmed.strip_prefix("/search ") {
+            let query = query.trim();
+            let note = match search::search(client.http(), &search_config, query).await {
+                Ok(results) if results.is_empty() => {
+                    format!("No web results for \"{}\".", query)
+                }
+                Ok(results) => {
+                    let note = format!(
+                        "Attached {} web result(s) for \"{}\"; they go along with the next prompt.",
+                        results.len(),
+                        query
+                    );
+                    attached_search =
+                        Some((query.to_string(), search::prompt_context(query, &results)));
+                    note
+                }
+                Err(e) => {
+                    eprintln!("[ERROR] {}", e);
+                    format!("Could not search the web: {}", e)
+                }
+            };
+            println!("[INFO] {}", note);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if let Some(prompt) = input_trimmed.strip_prefix("/imagine ") {
+            let prompt = prompt.trim();
+            println!("[INFO] Generating an image...");
+            let note = match imagine::imagine(
+                client.http(),
+                &imagine_config,
+                openai_key.as_deref(),
+                workspace,
+                prompt,
+            )
+            .await
+            {
+                Ok(path) => {
+                    println!("[INFO] Image saved to '{}'", path.display());
+                    imagine::markdown(prompt, &path)
+                }
+                Err(e) => {
+                    eprintln!("[ERROR] {}", e);
+                    format!("Could not generate an image: {}", e)
+                }
+            };
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n{}\n", note)?;
+            }
+            continue;
+        }
+
+        if input_trimmed.eq_ignore_ascii_case("/run") {
+            let Some(config) = &shell_config else {
+                println!("[INFO] The shell tool is off; set `enabled = true` in [shell].");
+                let _ = std::fs::remove_file(thinking_file(workspace));
+                continue;
+            };
+            let Some(command) = proposed_command.take() else {
+                println!("[INFO] No proposed command to run.");
+                let _ = std::fs::remove_file(thinking_file(workspace));
+                continue;
+            };
+            println!("[INFO] Running: {}", command);
+            match shell_tool::run(config, &command).await {
+                Ok((code, output)) => {
+                    let prompt = shell_tool::output_prompt(&command, code, &output);
+                    if let Some(log_file) = &log_file {
+                        let mut file = log_file.lock().await;
+                        writeln!(file, "\n{}\n", prompt)?;
+                    }
+                    if !multi_line_prompt.is_empty() {
+                        multi_line_prompt.push('\n');
+                    }
+                    multi_line_prompt.push_str(&prompt);
+                    queued.push_front("/ask".to_string());
+                }
+                Err(e) => {
+                    eprintln!("[ERROR] {}", e);
+                    if let Some(log_file) = &log_file {
+                        let mut file = log_file.lock().await;
+                        writeln!(file, "\nCould not run `{}`: {}\n", command, e)?;
+                    }
+                    let _ = std::fs::remove_file(thinking_file(workspace));
+                }
+            }
+            continue;
+        }
+
+        if let Some(name) = input_trimmed.strip_prefix("/regen ") {
+            let Some(provider) = find_provider(&client, name.trim()) else {
+                eprintln!("[ERROR] No provider named '{}'", name.trim());
+                let _ = std::fs::remove_file(thinking_file(workspace));
+                continue;
+            };
+            let provider = provider.name().to_string();
+            // After /load or a restart only the question is known
+            let resent =
+                match &last_round {
+                    Some(round) => Some(round.clone()),
+                    None => client.exchanges().await.last().map(|exchange| {
+                        (exchange.prompt.clone(), exchange.prompt.clone(), Vec::new())
+                    }),
+                };
+            let Some((question, prompt_text, files)) = resent else {
+                println!("[INFO] Nothing to regenerate yet.");
+                let _ = std::fs::remove_file(thinking_file(workspace));
+                continue;
+            };
+            let previous_target = client.target();
+            if let Err(e) = client.set_target(&provider) {
+                eprintln!("[ERROR] {}", e);
+                let _ = std::fs::remove_file(thinking_file(workspace));
+                continue;
+            }
+            println!("[INFO] Asking {} again.", provider);
+            if let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "\n**Regenerated by {}**\n", provider)?;
+            }
+            status.lock().unwrap().start_round();
+            let mut stopped = false;
+            match PromptData::new(prompt_text, &files, &video_config).await {
+                Ok(prompt_data) => match run_stoppable(
+                    process_prompt(&round, prompt_data, PromptOptions::default()),
+                    &client,
+                    &mut input_rx,
+                    &mut queued,
+                    &mut stopped,
+                )
+                .await
+                {
+                    Ok(answers) => {
+                        client
+                            .add_exchange(Exchange::new(&question, &files, answers))
+                            .await;
+                    }
+                    Err(e) => eprintln!(
+                        "[ERROR] An error occurred while processing the prompt: {}",
+                        e
+                    ),
+                },
+                Err(e) => eprintln!("[ERROR] Error preparing prompt data: {}", e),
+            }
+            if stopped && let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "_Stopped._\n")?;
+            }
+            let _ = std::fs::remove_file(thinking_file(workspace));
+            status.lock().unwrap().finish_round();
+            if let Err(e) = client.set_target(previous_target.as_deref().unwrap_or("all")) {
+                eprintln!("[ERROR] {}", e);
+            }
+            if let Some((sync, workspace)) = &sync {
+                sync_session(sync, *workspace, &client, log_path.as_deref()).await;
+            }
+            continue;
+        }
+
+        let ask_args = input_trimmed
+            .strip_prefix("/ask")
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '));
+        if let Some(ask_args) = ask_args {
+            let (ask_target, overrides) = match overrides::parse_ask_args(ask_args) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    eprintln!("[ERROR] {}", e);
+                    let _ = std::fs::remove_file(thinking_file(workspace));
+                    continue;
+                }
+            };
+            // In single mode a provider named with @ is asked for this
+            // prompt only
+            let one_off_target = ask_target.filter(|_| query_mode == QueryMode::Single);
+            if one_off_target.is_none()
+                && let Some(name) = ask_target
+                && let Err(e) = set_target(&client, log_file.as_ref(), name).await
+            {
+                eprintln!("[ERROR] {}", e);
+                let _ = std::fs::remove_file(thinking_file(workspace));
+                continue;
+            }
+            if multi_line_prompt.is_empty()
+                && attached_files.is_empty()
+                && attached_diff.is_none()
+                && attached_search.is_none()
+                && pending_template.is_none()
+            {
                 println!("Cannot send an empty prompt. Type something or upload a file.");
+                let _ = std::fs::remove_file(thinking_file(workspace));
                 continue;
             }
+            let previous_target = client.target();
+            if let Some(name) = one_off_target {
+                match client.set_target(name) {
+                    Ok(target) => println!(
+                        "[INFO] Asking {} for this prompt.",
+                        target.as_deref().unwrap_or("every provider")
+                    ),
+                    Err(e) => {
+                        eprintln!("[ERROR] {}", e);
+                        let _ = std::fs::remove_file(thinking_file(workspace));
+                        continue;
+                    }
+                }
+            }
 
-            let prompt_text = multi_line_prompt.trim().to_string();
+            let answer_format = pending_format.take();
+            let use_cache = !std::mem::take(&mut skip_cache);
+            let mut question = multi_line_prompt.trim().to_string();
+            if let Some((name, template)) = pending_template.take() {
+                println!("[INFO] Using the '{}' template.", name);
+                question = apply_template(&client, &template, question, &attached_files).await;
+            }
+            let search_context = match attached_search.take() {
+                Some((query, context)) => {
+                    if question.is_empty() {
+                        question = query;
+                    }
+                    Some(context)
+                }
+                None if search_config.auto && !question.is_empty() => {
+                    match search::search(client.http(), &search_config, &question).await {
+                        Ok(results) if !results.is_empty() => {
+                            println!("[INFO] Adding {} web result(s)", results.len());
+                            Some(search::prompt_context(&question, &results))
+                        }
+                        Ok(_) => None,
+                        Err(e) => {
+                            eprintln!("[ERROR] {}", e);
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            if question.is_empty() && attached_diff.is_some() {
+                question =
+                    "Review these changes. Point out bugs, risky edits and anything missing."
+                        .to_string();
+            }
+            let quizzed = quiz_card.take();
+            let mut prompt_text = match &quizzed {
+                Some(card) => card.grading_prompt(&question),
+                None => question.clone(),
+            };
+            if let Some(diff) = attached_diff.take() {
+                prompt_text = format!("```diff\n{}```\n\n{}", diff, prompt_text);
+            }
+            if let Some(context) = search_context {
+                prompt_text = format!("{}\n{}", context, prompt_text);
+            }
+            if let Some(context) = watch_context.lock().await.take_prompt_context() {
+                prompt_text = format!("{}\n\n{}", context, prompt_text);
+            }
+            if let Some(format) = answer_format {
+                prompt_text = format.apply_to_prompt(&prompt_text);
+            }
             println!(
                 "\nSending prompt with {} attached file(s)...",
                 attached_files.len()
             );
+            if !overrides.is_empty() {
+                println!("[INFO] For this prompt: {}", overrides);
+            }
 
-            match PromptData::new(prompt_text, &attached_files).await {
-                Ok(prompt_data) => {
-                    if let Err(e) =
-                        process_prompt(Arc::clone(&client), log_file.clone(), prompt_data).await
-                    {
+            // Where this round's answers start in the transcript
+            let round_start = log_path
+                .as_deref()
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map_or(0, |metadata| metadata.len());
+            status.lock().unwrap().start_round();
+            let mut stopped = false;
+            last_round = Some((
+                question.clone(),
+                prompt_text.clone(),
+                attached_files.clone(),
+            ));
+            match PromptData::new(prompt_text, &attached_files, &video_config).await {
+                Ok(prompt_data) => match run_stoppable(
+                    process_prompt(
+                        &round,
+                        prompt_data,
+                        PromptOptions {
+                            answer_format,
+                            overrides,
+                            use_cache,
+                        },
+                    ),
+                    &client,
+                    &mut input_rx,
+                    &mut queued,
+                    &mut stopped,
+                )
+                .await
+                {
+                    Ok(answers) => {
+                        client
+                            .add_exchange(Exchange::new(
+                                &question,
+                                &attached_files,
+                                answers.clone(),
+                            ))
+                            .await;
+                        if title_config.enabled
+                            && client.title().is_none()
+                            && client.exchanges().await.len() == 1
+                            && let Some(first) = answers.first()
+                        {
+                            spawn_title(
+                                Arc::clone(&client),
+                                title_config.clone(),
+                                workspace,
+                                question.clone(),
+                                first.clone(),
+                            );
+                        }
+                        if let Some(config) = &consensus_config
+                            && quizzed.is_none()
+                            && answers.len() > 1
+                            && let Err(e) = write_consensus(
+                                &client,
+                                log_file.as_ref(),
+                                log_path.as_deref(),
+                                round_start,
+                                config,
+                                &question,
+                                &answers,
+                            )
+                            .await
+                        {
+                            eprintln!("[ERROR] Failed to get a consensus: {}", e);
+                        }
+                        let answers: Vec<String> =
+                            answers.into_iter().map(|(_, answer)| answer).collect();
+                        // Grading answers are not questions worth keeping.
+                        if let Some(bank) = &question_bank
+                            && quizzed.is_none()
+                            && !question.is_empty()
+                            && let Some(answer) = question_bank::best_answer(&answers)
+                            && let Err(e) =
+                                bank.add(&Card::new(question.clone(), answer.clone(), workspace))
+                        {
+                            eprintln!("[ERROR] Failed to save to the question bank: {}", e);
+                        }
+                        if let Some(config) = &verify_config
+                            && quizzed.is_none()
+                            && let Err(e) = verify_answers(&client, log_file.as_ref(), config).await
+                        {
+                            eprintln!("[ERROR] Failed to verify the answer: {}", e);
+                        }
+                        proposed_command = None;
+                        if let Some(config) = &shell_config
+                            && let Some(command) =
+                                answers.iter().find_map(|a| shell_tool::proposed_command(a))
+                        {
+                            let sandbox = if config.sandbox {
+                                "sandboxed"
+                            } else {
+                                "unsandboxed"
+                            };
+                            let note = format!(
+                                "**Proposed command** ({}):\n\n```sh\n{}\n```\n\n\
+                                 Press Run Command or type `/run` to run it and send the output back.",
+                                sandbox, command
+                            );
+                            println!("[INFO] {}", note);
+                            if let Some(log_file) = &log_file {
+                                let mut file = log_file.lock().await;
+                                writeln!(file, "\n{}\n", note)?;
+                            }
+                            proposed_command = Some(command);
+                        }
+                    }
+                    Err(e) => {
                         eprintln!(
                             "[ERROR] An error occurred while processing the prompt: {}",
                             e
                         );
                     }
-                }
+                },
                 Err(e) => {
                     eprintln!("[ERROR] Error preparing prompt data: {}", e);
                 }
             }
 
+            if stopped && let Some(log_file) = &log_file {
+                let mut file = log_file.lock().await;
+                writeln!(file, "_Stopped._\n")?;
+            }
+            // Formatted, deduplicated and failed answers are only written now
+            let _ = std::fs::remove_file(thinking_file(workspace));
+            status.lock().unwrap().finish_round();
+            if one_off_target.is_some()
+                && let Err(e) = client.set_target(previous_target.as_deref().unwrap_or("all"))
+            {
+                eprintln!("[ERROR] {}", e);
+            }
+            if let Err(e) = answer_history.add(
+                &question,
+                &transcript_since(log_path.as_deref(), round_start),
+            ) {
+                eprintln!("[ERROR] Failed to save the answer history: {}", e);
+            }
+            if let Some((sync, workspace)) = &sync {
+                sync_session(sync, *workspace, &client, log_path.as_deref()).await;
+            }
             attached_files.clear();
             multi_line_prompt.clear();
             println!("\n------------------------------");
@@ -0,0 +1,114 @@
+// Line diff of two providers' answers, for the overlay's diff view.
+//
+// The lines of both answers are matched up by their longest common
+// subsequence and written as a `diff` code block, which the syntax
+// highlighter colors: lines only the first provider wrote start with "-",
+// lines only the second one wrote with "+", and shared lines with a space.
+// A line above the block counts them, as a quick measure of agreement.
+
+#[derive(Debug, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// Trailing whitespace does not make two lines differ.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let same = |i: usize, j: usize| old[i].trim_end() == new[j].trim_end();
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if same(i, j) {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && same(i, j) {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        } else {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        }
+    }
+    lines
+}
+
+// The diff of two (provider, answer) pairs as markdown.
+pub fn diff_markdown(old: (&str, &str), new: (&str, &str)) -> String {
+    let (old_name, old_answer) = old;
+    let (new_name, new_answer) = new;
+    let old_lines: Vec<&str> = old_answer.trim().lines().collect();
+    let new_lines: Vec<&str> = new_answer.trim().lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let count = |f: fn(&Line) -> bool| lines.iter().filter(|line| f(line)).count();
+    let shared = count(|line| matches!(line, Line::Same(_)));
+    let removed = count(|line| matches!(line, Line::Removed(_)));
+    let added = count(|line| matches!(line, Line::Added(_)));
+
+    let mut body = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for line in &lines {
+        let (mark, text) = match line {
+            Line::Same(text) => (' ', text),
+            Line::Removed(text) => ('-', text),
+            Line::Added(text) => ('+', text),
+        };
+        body.push(mark);
+        body.push_str(text);
+        body.push('\n');
+    }
+    // A fence longer than any backtick run in the answers, so code blocks
+    // inside them cannot end the diff block
+    let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+
+    format!(
+        "## {} vs {}\n\nLines shared: {}, only from {}: {}, only from {}: {}\n\n{}diff\n{}{}\n",
+        old_name, new_name, shared, old_name, removed, new_name, added, fence, body, fence
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "c  ", "e", "d"];
+        assert_eq!(
+            diff_lines(&old, &new),
+            vec![
+                Line::Same("a"),
+                Line::Removed("b"),
+                Line::Same("c"),
+                Line::Added("e"),
+                Line::Same("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_markdown_fence() {
+        let markdown = diff_markdown(("A", "x\n```rust\nfn a() {}\n```"), ("B", "x"));
+        assert!(
+            markdown.starts_with("## A vs B\n\nLines shared: 1, only from A: 3, only from B: 0")
+        );
+        assert!(markdown.contains("\n````diff\n--- A\n+++ B\n x\n-```rust\n"));
+        assert!(markdown.ends_with("\n````\n"));
+    }
+}
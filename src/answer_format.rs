@@ -0,0 +1,219 @@
+// Answer formatting profiles.
+//
+// A profile adds a directive to the outgoing prompt and post-filters the
+// finished answer, so the overlay shows something that can be read at a glance
+// even when the model ignores part of the instruction.
+
+const MAX_BULLETS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnswerFormat {
+    CodeOnly,
+    Bullets,
+    Steps,
+}
+
+impl AnswerFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('-', "_").as_str() {
+            "code_only" | "code" => Some(AnswerFormat::CodeOnly),
+            "bullets" | "bullet_summary" | "summary" => Some(AnswerFormat::Bullets),
+            "steps" | "step_by_step" => Some(AnswerFormat::Steps),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnswerFormat::CodeOnly => "code_only",
+            AnswerFormat::Bullets => "bullets",
+            AnswerFormat::Steps => "steps",
+        }
+    }
+
+    fn directive(&self) -> &'static str {
+        match self {
+            AnswerFormat::CodeOnly => {
+                "Answer with code only, inside a single fenced code block. Do not add any explanation."
+            }
+            AnswerFormat::Bullets => {
+                "Answer as a bullet summary of at most 5 short points. Do not add an introduction or conclusion."
+            }
+            AnswerFormat::Steps => {
+                "Answer step-by-step as a numbered list, one short action per step."
+            }
+        }
+    }
+
+    // Appends the format directive to the user's prompt text.
+    pub fn apply_to_prompt(&self, prompt: &str) -> String {
+        if prompt.is_empty() {
+            self.directive().to_string()
+        } else {
+            format!("{}\n\n{}", prompt, self.directive())
+        }
+    }
+
+    // Reshapes a finished answer so it matches the profile.
+    pub fn filter_response(&self, response: &str) -> String {
+        match self {
+            AnswerFormat::CodeOnly => filter_code_only(response),
+            AnswerFormat::Bullets => filter_bullets(response),
+            AnswerFormat::Steps => filter_steps(response),
+        }
+    }
+}
+
+// Keeps only fenced code blocks. Answers without any fence are returned as-is.
+fn filter_code_only(response: &str) -> String {
+    let mut blocks = Vec::new();
+    let mut current = Vec::new();
+    let mut in_block = false;
+
+    for line in response.lines() {
+        if line.trim_start().starts_with("```") {
+            current.push(line);
+            if in_block {
+                blocks.push(current.join("\n"));
+                current.clear();
+            }
+            in_block = !in_block;
+        } else if in_block {
+            current.push(line);
+        }
+    }
+
+    // Close a block the model left open (e.g. the answer was cut off).
+    if in_block && !current.is_empty() {
+        current.push("```");
+        blocks.push(current.join("\n"));
+    }
+
+    if blocks.is_empty() {
+        response.trim().to_string()
+    } else {
+        blocks.join("\n\n")
+    }
+}
+
+fn strip_list_marker(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    for marker in ["- ", "* ", "• ", "+ "] {
+        if let Some(rest) = trimmed.strip_prefix(marker) {
+            return Some(rest.trim());
+        }
+    }
+
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let rest = &trimmed[digits..];
+        if let Some(rest) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Some(rest.trim());
+        }
+    }
+    None
+}
+
+// Keeps at most MAX_BULLETS list items, falling back to the first sentences
+// when the model did not answer with a list at all.
+fn filter_bullets(response: &str) -> String {
+    let mut items: Vec<String> = response
+        .lines()
+        .filter_map(strip_list_marker)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    if items.is_empty() {
+        items = response
+            .split_inclusive(['.', '!', '?'])
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+    }
+
+    items
+        .into_iter()
+        .take(MAX_BULLETS)
+        .map(|item| format!("- {}", item))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Renumbers list items, or numbers each paragraph when no list is present.
+fn filter_steps(response: &str) -> String {
+    let mut steps: Vec<String> = response
+        .lines()
+        .filter_map(strip_list_marker)
+        .filter(|step| !step.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    if steps.is_empty() {
+        steps = response
+            .split("\n\n")
+            .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|p| !p.is_empty())
+            .collect();
+    }
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| format!("{}. {}", i + 1, step))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            AnswerFormat::from_name("code-only"),
+            Some(AnswerFormat::CodeOnly)
+        );
+        assert_eq!(AnswerFormat::from_name("Steps"), Some(AnswerFormat::Steps));
+        assert_eq!(AnswerFormat::from_name("haiku"), None);
+    }
+
+    #[test]
+    fn test_filter_code_only() {
+        let response = "Here you go:\n```rust\nfn main() {}\n```\nThis prints nothing.";
+        assert_eq!(
+            AnswerFormat::CodeOnly.filter_response(response),
+            "```rust\nfn main() {}\n```"
+        );
+        assert_eq!(
+            AnswerFormat::CodeOnly.filter_response("no code here"),
+            "no code here"
+        );
+    }
+
+    #[test]
+    fn test_filter_bullets_limits_points() {
+        let response = "Summary:\n- a\n- b\n* c\n1. d\n2) e\n- f";
+        assert_eq!(
+            AnswerFormat::Bullets.filter_response(response),
+            "- a\n- b\n- c\n- d\n- e"
+        );
+        assert_eq!(
+            AnswerFormat::Bullets.filter_response("One. Two! Three?"),
+            "- One.\n- Two!\n- Three?"
+        );
+    }
+
+    #[test]
+    fn test_filter_steps() {
+        assert_eq!(
+            AnswerFormat::Steps.filter_response("Open the file\n\nSave it"),
+            "1. Open the file\n2. Save it"
+        );
+        assert_eq!(
+            AnswerFormat::Steps.filter_response("Intro\n3. first\n7. second"),
+            "1. first\n2. second"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+// Past answers of a workspace, for paging back through them in the overlay.
+//
+// After every round, what it added to the transcript is saved with the
+// question as `.aerogel_answers/ws<workspace>/<n>.md`, numbered upwards. Only
+// the newest `[history] answers` files are kept. The overlay lists the
+// directory and shows an older file in place of `.tmp<workspace>` while the
+// user pages back with Ctrl+Left and Ctrl+Right.
+
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct HistoryConfig {
+    // How many past answers each workspace keeps; 0 keeps none
+    #[serde(default = "default_answers")]
+    pub answers: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            answers: default_answers(),
+        }
+    }
+}
+
+fn default_answers() -> usize {
+    20
+}
+
+pub struct AnswerHistory {
+    dir: PathBuf,
+    limit: usize,
+}
+
+// The question as a quote above the answer, so a page shows what it answers.
+fn page(question: &str, answer: &str) -> String {
+    let quoted: Vec<String> = question
+        .trim()
+        .lines()
+        .map(|line| format!("> {}", line).trim_end().to_string())
+        .collect();
+    if quoted.is_empty() {
+        answer.trim().to_string() + "\n"
+    } else {
+        format!("{}\n\n{}\n", quoted.join("\n"), answer.trim())
+    }
+}
+
+fn workspace_dir(workspace: Option<u32>) -> PathBuf {
+    PathBuf::from(".aerogel_answers").join(format!(
+        "ws{}",
+        workspace.map(|w| w.to_string()).unwrap_or_default()
+    ))
+}
+
+// Numbers of the answers saved in `dir`, oldest first.
+fn numbers(dir: &Path) -> io::Result<Vec<u64>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut numbers: Vec<u64> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?.strip_suffix(".md")?.parse().ok()
+        })
+        .collect();
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+impl AnswerHistory {
+    pub fn new(workspace: Option<u32>, config: HistoryConfig) -> Self {
+        Self {
+            dir: workspace_dir(workspace),
+            limit: config.answers,
+        }
+    }
+
+    // The saved answers of a workspace, newest first, as the overlay pages
+    // back through them. Only the overlay reads them.
+    #[allow(dead_code)]
+    pub fn paths_newest_first(workspace: u32) -> Vec<PathBuf> {
+        Self::new(Some(workspace), HistoryConfig::default()).paths()
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        numbers(&self.dir)
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .map(|number| self.dir.join(format!("{}.md", number)))
+            .collect()
+    }
+
+    pub fn add(&self, question: &str, answer: &str) -> io::Result<()> {
+        if self.limit == 0 || answer.trim().is_empty() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        let mut numbers = numbers(&self.dir)?;
+        let next = numbers.last().map_or(1, |last| last + 1);
+        std::fs::write(
+            self.dir.join(format!("{}.md", next)),
+            page(question, answer),
+        )?;
+        numbers.push(next);
+        let excess = numbers.len().saturating_sub(self.limit);
+        for number in &numbers[..excess] {
+            std::fs::remove_file(self.dir.join(format!("{}.md", number)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = AnswerHistory {
+            dir: dir.path().to_path_buf(),
+            limit: 2,
+        };
+        history.add("first?", "one").unwrap();
+        history.add("", "   ").unwrap();
+        history.add("second?\nreally", "two").unwrap();
+        history.add("third?", "three").unwrap();
+
+        assert_eq!(numbers(dir.path()).unwrap(), vec![2, 3]);
+        assert_eq!(
+            history.paths(),
+            vec![dir.path().join("3.md"), dir.path().join("2.md")]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("2.md")).unwrap(),
+            "> second?\n> really\n\ntwo\n"
+        );
+        assert_eq!(page("", " answer "), "answer\n");
+    }
+}
@@ -0,0 +1,25 @@
+// Client bindings for KDE's blur protocol (`org_kde_kwin_blur_manager`),
+// generated from protocols/blur.xml since no protocol crate ships them.
+// KWin blurs whatever is behind a surface that asks for it; other compositors
+// do not announce the global and the overlay stays plainly translucent.
+
+#![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+#![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+#![allow(clippy::all)]
+
+pub mod client {
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    // The generated code names the backend crate, which wayland-client re-exports
+    use wayland_client::backend as wayland_backend;
+
+    pub mod __interfaces {
+        use wayland_client::backend as wayland_backend;
+        use wayland_client::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/blur.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("protocols/blur.xml");
+}
@@ -0,0 +1,180 @@
+// Extraction of the code blocks of an answer into files.
+//
+// A block's file name comes from a hint when the model gave one, either in
+// the fence info ("```rust main.rs" or "```rust title=main.rs") or as a
+// comment on the block's first line ("// main.rs", "# app.py"). Blocks
+// without a hint are named snippet-<n> with an extension for their language.
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    pub lang: String,
+    pub filename: Option<String>,
+    pub code: String,
+}
+
+const COMMENT_PREFIXES: &[&str] = &["//", "#", "--", ";", "/*", "<!--"];
+
+fn extension_for(lang: &str) -> &'static str {
+    match lang.to_lowercase().as_str() {
+        "rust" | "rs" => "rs",
+        "python" | "py" | "python3" => "py",
+        "javascript" | "js" => "js",
+        "typescript" | "ts" => "ts",
+        "c" => "c",
+        "cpp" | "c++" | "cc" => "cpp",
+        "java" => "java",
+        "go" | "golang" => "go",
+        "kotlin" | "kt" => "kt",
+        "swift" => "swift",
+        "ruby" | "rb" => "rb",
+        "bash" | "sh" | "shell" | "zsh" => "sh",
+        "sql" => "sql",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yml",
+        "markdown" | "md" => "md",
+        _ => "txt",
+    }
+}
+
+// A plain file name (no directories), e.g. "main.rs" or "Makefile".
+fn as_filename(candidate: &str) -> Option<String> {
+    let candidate = candidate.trim().trim_matches(['"', '\'', '`']);
+    let looks_like_file = !candidate.is_empty()
+        && !candidate.contains(['/', '\\', ' '])
+        && candidate != "."
+        && candidate != ".."
+        && (candidate.contains('.') || candidate.chars().next()?.is_uppercase());
+    looks_like_file.then(|| candidate.to_string())
+}
+
+fn fence_filename(info: &str) -> Option<String> {
+    info.split_whitespace()
+        .skip(1)
+        .find_map(|word| as_filename(word.strip_prefix("title=").unwrap_or(word)))
+}
+
+fn comment_filename(code: &str) -> Option<String> {
+    let first_line = code.lines().next()?.trim();
+    let rest = COMMENT_PREFIXES
+        .iter()
+        .find_map(|prefix| first_line.strip_prefix(prefix))?;
+    let rest = rest.trim().trim_end_matches("*/").trim_end_matches("-->");
+    let rest = rest
+        .strip_prefix("file:")
+        .or_else(|| rest.strip_prefix("File:"))
+        .unwrap_or(rest);
+    // Only a bare file name counts, so "// Add two numbers" is not a hint.
+    as_filename(rest).filter(|name| name.contains('.'))
+}
+
+pub fn extract_code_blocks(markdown: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(String, Option<String>, String)> = None;
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let (lang, filename) = match kind {
+                    CodeBlockKind::Fenced(info) => (
+                        info.split_whitespace().next().unwrap_or("").to_string(),
+                        fence_filename(&info),
+                    ),
+                    CodeBlockKind::Indented => (String::new(), None),
+                };
+                current = Some((lang, filename, String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, _, code)) = &mut current {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some((lang, filename, code)) = current.take()
+                    && !code.trim().is_empty()
+                {
+                    let filename = filename.or_else(|| comment_filename(&code));
+                    blocks.push(CodeBlock {
+                        lang,
+                        filename,
+                        code,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+// Writes the blocks into `dir` and returns the written paths. Repeated names
+// get a numeric suffix instead of overwriting an earlier block.
+pub fn write_code_blocks(dir: &Path, blocks: &[CodeBlock]) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    for (i, block) in blocks.iter().enumerate() {
+        let name = block
+            .filename
+            .clone()
+            .unwrap_or_else(|| format!("snippet-{}.{}", i + 1, extension_for(&block.lang)));
+        let mut path = dir.join(&name);
+        let mut n = 2;
+        while paths.contains(&path) {
+            let (stem, ext) = name.rsplit_once('.').unwrap_or((&name, ""));
+            let numbered = if ext.is_empty() {
+                format!("{}-{}", stem, n)
+            } else {
+                format!("{}-{}.{}", stem, n, ext)
+            };
+            path = dir.join(numbered);
+            n += 1;
+        }
+        std::fs::write(&path, &block.code)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_code_blocks() {
+        let answer = "Here you go:\n\n```rust\n// main.rs\nfn main() {}\n```\n\n\
+                      ```python title=app.py\nprint(1)\n```\n\n\
+                      ```js\n// Add two numbers\nlet x = 1 + 2;\n```\n";
+        let blocks = extract_code_blocks(answer);
+        let names: Vec<_> = blocks.iter().map(|b| b.filename.as_deref()).collect();
+        assert_eq!(names, vec![Some("main.rs"), Some("app.py"), None]);
+        assert_eq!(blocks[0].lang, "rust");
+        assert_eq!(blocks[1].code, "print(1)\n");
+    }
+
+    #[test]
+    fn test_write_code_blocks() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let block = |filename: Option<&str>| CodeBlock {
+            lang: "python".to_string(),
+            filename: filename.map(str::to_string),
+            code: "pass\n".to_string(),
+        };
+        let paths = write_code_blocks(
+            dir.path(),
+            &[block(Some("app.py")), block(Some("app.py")), block(None)],
+        )?;
+        let names: Vec<_> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["app.py", "app-2.py", "snippet-3.py"]);
+        Ok(())
+    }
+}
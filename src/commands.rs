@@ -0,0 +1,203 @@
+// The commands the AI process reads, for `/help` and tab completion.
+//
+// When the AI process runs in a terminal, Tab completes the command being
+// typed and, after commands that take one, the file path: a single match is
+// filled in, several are filled in as far as they agree and listed.
+
+use crate::conversation::expand_home;
+use std::path::Path;
+
+pub struct Command {
+    pub usage: &'static str,
+    pub help: &'static str,
+}
+
+const fn command(usage: &'static str, help: &'static str) -> Command {
+    Command { usage, help }
+}
+
+pub const COMMANDS: &[Command] = &[
+    command(
+        "/ask [@<provider>|@all] [--temp <x>] [--top-p <x>] [--max-tokens <n>] [--reasoning-tokens <n>]",
+        "Send the prompt typed so far, with the attached files",
+    ),
+    command(
+        "/upload <file>",
+        "Attach a text, image, audio, video or office file to the next prompt",
+    ),
+    command(
+        "/files",
+        "List the files, diff and web results attached to the next prompt",
+    ),
+    command(
+        "/clear-files",
+        "Drop the attachments, keeping the prompt typed so far",
+    ),
+    command(
+        "/regen <provider>",
+        "Ask one provider the last prompt again, for a fresh answer",
+    ),
+    command("/new", "Start a new conversation, clearing the history"),
+    command(
+        "/stop",
+        "Stop the answers streaming, keeping what has arrived",
+    ),
+    command(
+        "/target <provider|all>",
+        "Ask only one provider from now on, or every provider again",
+    ),
+    command(
+        "/mode <single|all>",
+        "Ask only the workspace's primary provider, or every provider",
+    ),
+    command(
+        "/format <name>",
+        "Shape the next answer: code_only, bullets or steps",
+    ),
+    command(
+        "/template <name>",
+        "Fill the next prompt into a template from aerogel.toml",
+    ),
+    command("/t <name>", "Fill the prompt into a template and send it"),
+    command(
+        "/nocache",
+        "Send the next prompt without checking the response cache",
+    ),
+    command(
+        "/watch <file>",
+        "Send new lines of a file, such as a build log, with the next prompt",
+    ),
+    command("/unwatch", "Stop watching files"),
+    command(
+        "/diff [staged|<rev>]",
+        "Attach the git diff of the working tree to the next prompt",
+    ),
+    command(
+        "/search <query>",
+        "Search the web and attach the results to the next prompt",
+    ),
+    command("/imagine <prompt>", "Generate an image"),
+    command(
+        "/extract",
+        "Save the code blocks of the latest answers under answers/",
+    ),
+    command(
+        "/run",
+        "Run the command a model proposed and send its output back",
+    ),
+    command(
+        "/export [md|json] [path]",
+        "Write the conversation to a file",
+    ),
+    command(
+        "/import <file>",
+        "Continue a conversation exported with /export json",
+    ),
+    command(
+        "/save [name]",
+        "Keep the conversation as a named session, by default under its title",
+    ),
+    command("/load <name>", "Continue a saved session"),
+    command("/sessions", "List the saved sessions"),
+    command("/quiz", "Ask a question from the question bank"),
+    command(
+        "/history [n]",
+        "Show the last n turns (5) of each provider's history, as sent with the next prompt",
+    ),
+    command("/usage", "Show the estimated token usage and cost"),
+    command("/help", "List the commands"),
+    command("/quit", "Exit"),
+];
+
+// Commands whose last argument is a file path.
+const PATH_COMMANDS: &[&str] = &["/upload", "/watch", "/import", "/export"];
+
+fn name(command: &Command) -> &'static str {
+    command.usage.split(' ').next().unwrap_or(command.usage)
+}
+
+// The command list `/help` shows.
+pub fn help() -> String {
+    let lines: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| format!("- `{}`: {}", command.usage, command.help))
+        .collect();
+    format!("**Commands**\n\n{}", lines.join("\n"))
+}
+
+// What Tab completes in `line`: the byte offset of the word being completed
+// and the words it may become.
+pub fn complete(line: &str) -> (usize, Vec<String>) {
+    let start = line.rfind(' ').map_or(0, |space| space + 1);
+    let word = &line[start..];
+    if start == 0 {
+        let names = COMMANDS
+            .iter()
+            .map(name)
+            .filter(|name| name.starts_with(word))
+            .map(str::to_string)
+            .collect();
+        return (start, names);
+    }
+    let command = line.split(' ').next().unwrap_or_default();
+    if PATH_COMMANDS.contains(&command) {
+        (start, complete_path(word))
+    } else {
+        (start, Vec::new())
+    }
+}
+
+// The files and directories `word` may name, directories ending in `/`.
+fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(slash) => (&word[..=slash], &word[slash + 1..]),
+        None => ("", word),
+    };
+    let read_from = if dir.is_empty() {
+        Path::new(".").to_path_buf()
+    } else {
+        expand_home(dir)
+    };
+    let Ok(entries) = std::fs::read_dir(read_from) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(complete("/sa"), (0, vec!["/save".to_string()]));
+        assert_eq!(complete("/s").1, ["/stop", "/search", "/save", "/sessions"]);
+        assert_eq!(complete("/format bu"), (8, Vec::new()));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("logs")).unwrap();
+        std::fs::write(dir.path().join("log.txt"), "").unwrap();
+        std::fs::write(dir.path().join(".hidden"), "").unwrap();
+        let base = format!("{}/", dir.path().display());
+        let (start, paths) = complete(&format!("/upload {}lo", base));
+        assert_eq!(start, 8);
+        assert_eq!(
+            paths,
+            [format!("{}log.txt", base), format!("{}logs/", base)]
+        );
+        assert_eq!(complete(&format!("/upload {}", base)).1.len(), 2);
+    }
+}
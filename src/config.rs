@@ -1,5 +1,24 @@
-use anyhow::{Context, Result};
+use crate::answer_history::HistoryConfig;
+use crate::consensus::ConsensusConfig;
+use crate::dedup::DedupConfig;
+use crate::git::GitConfig;
+use crate::http::HttpConfig;
+use crate::imagine::ImagineConfig;
+use crate::key_pool::parse_keys;
+use crate::keyring;
+use crate::log_buffer::StreamingConfig;
+use crate::ocr::{OcrBackend, OcrConfig};
+use crate::response_cache::CacheConfig;
+use crate::search::SearchConfig;
+use crate::shell_tool::ShellConfig;
+use crate::titles::TitleConfig;
+use crate::usage::Price;
+use crate::verify::VerifyConfig;
+use crate::video::VideoConfig;
+use crate::watch::WatchConfig;
+use anyhow::{Context, Result, anyhow};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::Path;
@@ -11,6 +30,114 @@ pub struct ModelConfig {
     pub max_tokens: u32,
     pub temperature: f32,
     pub top_p: f32,
+    // USD per million input and output tokens, for the overlay's cost counter
+    #[serde(default)]
+    pub input_price: f64,
+    #[serde(default)]
+    pub output_price: f64,
+    // Set to false to stop asking this provider
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // Limits on requests started per minute and answers streaming at once;
+    // 0 is unlimited
+    #[serde(default)]
+    pub requests_per_minute: u32,
+    #[serde(default)]
+    pub max_in_flight: u32,
+    // Tokens of history, system prompt and prompt sent at most; the oldest
+    // turns are left out beyond it. 0 sends the whole history
+    #[serde(default)]
+    pub context_tokens: u32,
+    // Tokens Claude and Gemini may spend reasoning before they answer; the
+    // reasoning streams separately (see reasoning in ai_client.rs). 0 leaves
+    // it off for Claude and up to the model for Gemini
+    #[serde(default)]
+    pub reasoning_tokens: u32,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// An Azure OpenAI deployment, from the `[AzureOpenAI]` section. The model
+// parameters and prices still come from `[OpenAI]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzureConfig {
+    // The resource endpoint, e.g. https://my-resource.openai.azure.com
+    pub endpoint: String,
+    pub deployment: String,
+    #[serde(default = "default_azure_api_version")]
+    pub api_version: String,
+}
+
+fn default_azure_api_version() -> String {
+    "2024-10-21".to_string()
+}
+
+impl AzureConfig {
+    pub fn chat_url(&self) -> String {
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            self.endpoint.trim_end_matches('/'),
+            self.deployment,
+            self.api_version
+        )
+    }
+}
+
+// An extra OpenAI-compatible endpoint (Together, Fireworks, LM Studio, vLLM,
+// ...), from a `[[providers]]` entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomProvider {
+    pub name: String,
+    // Environment variable holding the API key; unset for local servers
+    pub key_env: Option<String>,
+    #[serde(skip)]
+    pub api_keys: Vec<String>,
+    // Whether the model reads attached images
+    #[serde(default)]
+    pub images: bool,
+    #[serde(flatten)]
+    pub model: ModelConfig,
+}
+
+// Per-workspace answer preferences, from a `[workspaces.<number>]` section.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WorkspaceProfile {
+    pub answer_language: Option<String>,
+    pub code_style: Option<String>,
+    // Name of a `[personas.<name>]` section to apply
+    pub persona: Option<String>,
+    // The provider asked in single mode, before the persona's
+    pub provider: Option<String>,
+}
+
+// A role for a workspace's AI, from a `[personas.<name>]` section: its
+// system prompt, and optionally the provider to ask and the model it uses.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Persona {
+    pub system_prompt: Option<String>,
+    pub provider: Option<String>,
+    // Replaces the `model` of the persona's provider
+    pub model: Option<String>,
+}
+
+impl WorkspaceProfile {
+    // Builds the system prompt that carries these preferences, if any are set.
+    pub fn system_prompt(&self) -> Option<String> {
+        let mut instructions = Vec::new();
+        if let Some(language) = &self.answer_language {
+            instructions.push(format!("Always answer in this language: {}.", language));
+        }
+        if let Some(style) = &self.code_style {
+            instructions.push(format!("Write any code in this style: {}.", style));
+        }
+        if instructions.is_empty() {
+            None
+        } else {
+            Some(instructions.join(" "))
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -27,17 +154,127 @@ struct TomlConfig {
     claude: ModelConfig,
     #[serde(rename = "Xai")]
     xai: ModelConfig,
+    // Optional, so configs written before it was added still load
+    #[serde(rename = "Mistral", default)]
+    mistral: Option<ModelConfig>,
+    #[serde(rename = "AzureOpenAI", default)]
+    azure: Option<AzureConfig>,
+    #[serde(rename = "Cohere", default)]
+    cohere: Option<ModelConfig>,
+    #[serde(default)]
+    providers: Vec<CustomProvider>,
+    #[serde(default)]
+    workspaces: HashMap<String, WorkspaceProfile>,
+    #[serde(default)]
+    personas: HashMap<String, Persona>,
+    #[serde(default)]
+    templates: HashMap<String, String>,
+    #[serde(default)]
+    pricing: HashMap<String, Price>,
+    #[serde(default)]
+    models: ModelsConfig,
+    #[serde(default)]
+    keys: KeysConfig,
+    #[serde(default)]
+    sync: SyncConfig,
+    #[serde(default)]
+    question_bank: QuestionBankConfig,
+    #[serde(default)]
+    watch: WatchConfig,
+    #[serde(default)]
+    git: GitConfig,
+    #[serde(default)]
+    verify: VerifyConfig,
+    #[serde(default)]
+    search: SearchConfig,
+    #[serde(default)]
+    shell: ShellConfig,
+    #[serde(default)]
+    video: VideoConfig,
+    #[serde(default)]
+    ocr: OcrConfig,
+    #[serde(default)]
+    imagine: ImagineConfig,
+    #[serde(default)]
+    cache: CacheConfig,
+    #[serde(default)]
+    dedup: DedupConfig,
+    #[serde(default)]
+    history: HistoryConfig,
+    #[serde(default)]
+    titles: TitleConfig,
+    #[serde(default)]
+    consensus: ConsensusConfig,
+    #[serde(default)]
+    streaming: StreamingConfig,
+    #[serde(default)]
+    http: HttpConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ModelsConfig {
+    // Use only local backends; cloud providers are disabled
+    #[serde(default)]
+    offline: bool,
+    #[serde(default)]
+    mode: QueryMode,
+    // The provider asked in single mode by workspaces without their own
+    primary: Option<String>,
+}
+
+// Whether a prompt goes to every provider or only to the workspace's primary
+// one, with the others asked when named with `/ask @<provider>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryMode {
+    Single,
+    #[default]
+    All,
+}
+
+impl QueryMode {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "single" => Ok(Self::Single),
+            "all" => Ok(Self::All),
+            _ => Err(anyhow!("Unknown mode '{}'; use single or all", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct KeysConfig {
+    // Look API keys up in the system keyring before the environment
+    #[serde(default)]
+    keyring: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct SyncConfig {
+    // Shared folder the encrypted conversations are synced through
+    folder: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct QuestionBankConfig {
+    // Save each question and its best answer for quizzing later
+    #[serde(default)]
+    enabled: bool,
 }
 
 // The main config struct holds both the loaded model parameters and the API keys.
 #[derive(Debug, Clone)]
 pub struct ApiConfig {
-    // API keys, loaded from environment variables for security
-    pub openrouter_key: Option<String>,
-    pub openai_key: Option<String>,
-    pub claude_key: Option<String>,
-    pub gemini_key: Option<String>,
-    pub xai_key: Option<String>,
+    // API keys, loaded from the keyring or environment variables for
+    // security; several keys per provider are used in turn (see key_pool.rs)
+    pub openrouter_keys: Vec<String>,
+    pub openai_keys: Vec<String>,
+    pub claude_keys: Vec<String>,
+    pub gemini_keys: Vec<String>,
+    pub xai_keys: Vec<String>,
+    pub mistral_keys: Vec<String>,
+    pub azure_keys: Vec<String>,
+    pub cohere_keys: Vec<String>,
     // Model parameters, loaded from aerogel.toml
     pub ollama: ModelConfig,
     pub openrouter: ModelConfig,
@@ -45,6 +282,71 @@ pub struct ApiConfig {
     pub claude: ModelConfig,
     pub gemini: ModelConfig,
     pub xai: ModelConfig,
+    // Asked only when aerogel.toml has a [Mistral] section
+    pub mistral: Option<ModelConfig>,
+    // An Azure OpenAI deployment asked in place of the public OpenAI API
+    pub azure: Option<AzureConfig>,
+    // Asked only when aerogel.toml has a [Cohere] section
+    pub cohere: Option<ModelConfig>,
+    // OpenAI-compatible endpoints declared in aerogel.toml
+    pub providers: Vec<CustomProvider>,
+    // Answer preferences keyed by workspace number
+    pub workspaces: HashMap<String, WorkspaceProfile>,
+    // Personas workspaces can refer to, by name
+    pub personas: HashMap<String, Persona>,
+    // Whether prompts go to every provider or one (`[models] mode`)
+    pub mode: QueryMode,
+    // The provider asked in single mode by workspaces without their own
+    pub primary: Option<String>,
+    // Prompt templates by name, used with /t <name>
+    pub templates: HashMap<String, String>,
+    // Prices by model name, for providers without input_price/output_price
+    pub pricing: HashMap<String, Price>,
+    // Conversation sync folder and its passphrase (AEROGEL_SYNC_KEY)
+    pub sync_folder: Option<String>,
+    pub sync_key: Option<String>,
+    // Whether answered questions are saved to the question bank
+    pub question_bank: bool,
+    // Failure detection for files tailed with /watch
+    pub watch: WatchConfig,
+    // Repository /diff runs git in
+    pub git: GitConfig,
+    // Checkers run on the code blocks of new answers
+    pub verify: VerifyConfig,
+    // Web search backend for /search and augmented prompts
+    pub search: SearchConfig,
+    // Shell commands the models may propose, run after confirmation
+    pub shell: ShellConfig,
+    // Size limit and downsampling of video attachments
+    pub video: VideoConfig,
+    // How text is read from images for providers without image input
+    pub ocr: OcrConfig,
+    // Image generation backend for /imagine
+    pub imagine: ImagineConfig,
+    // How long answers are reused for identical prompts
+    pub cache: CacheConfig,
+    // Collapsing of near-identical answers from several providers
+    pub dedup: DedupConfig,
+    // How many past answers are kept for paging through in the overlay
+    pub history: HistoryConfig,
+    // The model that titles conversations
+    pub titles: TitleConfig,
+    // The model that merges or ranks the answers of a round
+    pub consensus: ConsensusConfig,
+    // How often streamed answers are written to the conversation log
+    pub streaming: StreamingConfig,
+    // Connection reuse for the requests to the providers
+    pub http: HttpConfig,
+}
+
+// The keys stored with `aerogel key set <provider>` when the keyring is
+// used, or else those in the environment variable `var`.
+fn api_keys(provider: &str, var: Option<&str>, use_keyring: bool) -> Vec<String> {
+    use_keyring
+        .then(|| keyring::lookup(provider))
+        .flatten()
+        .or_else(|| var.and_then(|var| env::var(var).ok()))
+        .map_or_else(Vec::new, |keys| parse_keys(&keys))
 }
 
 impl ApiConfig {
@@ -109,11 +411,6 @@ impl ApiConfig {
     pub fn load() -> Result<Self> {
         // 1. Load API keys from .env file or system environment
         dotenv::dotenv().ok();
-        let openrouter_key = env::var("OPENROUTER_API_KEY").ok();
-        let openai_key = env::var("OPENAI_API_KEY").ok();
-        let claude_key = env::var("CLAUDE_API_KEY").ok();
-        let gemini_key = env::var("GEMINI_API_KEY").ok();
-        let xai_key = env::var("XAI_API_KEY").ok();
 
         // 2. Find and read model parameters from aerogel.toml
         let (toml_str, config_path) = Self::find_and_read_config()
@@ -122,30 +419,301 @@ impl ApiConfig {
         let toml_config: TomlConfig = toml::from_str(&toml_str)
             .with_context(|| format!("Failed to parse configuration file at {}", config_path))?;
 
+        Ok(Self::from_toml(toml_config))
+    }
+
+    // Combines the parsed aerogel.toml with the API keys in the environment.
+    fn from_toml(toml_config: TomlConfig) -> Self {
+        let keyring = toml_config.keys.keyring;
+        let openrouter_keys = api_keys("openrouter", Some("OPENROUTER_API_KEY"), keyring);
+        let openai_keys = api_keys("openai", Some("OPENAI_API_KEY"), keyring);
+        let claude_keys = api_keys("claude", Some("CLAUDE_API_KEY"), keyring);
+        let gemini_keys = api_keys("gemini", Some("GEMINI_API_KEY"), keyring);
+        let xai_keys = api_keys("xai", Some("XAI_API_KEY"), keyring);
+        let mistral_keys = api_keys("mistral", Some("MISTRAL_API_KEY"), keyring);
+        let azure_keys = api_keys("azure", Some("AZURE_OPENAI_API_KEY"), keyring);
+        let cohere_keys = api_keys("cohere", Some("COHERE_API_KEY"), keyring);
+        let sync_key = env::var("AEROGEL_SYNC_KEY").ok();
+
+        let offline = toml_config.models.offline || env::var_os("AEROGEL_OFFLINE").is_some();
+
         // 3. Combine them into the final ApiConfig struct
-        Ok(ApiConfig {
-            openrouter_key,
-            openai_key,
-            claude_key,
-            gemini_key,
-            xai_key,
+        let mut config = ApiConfig {
+            openrouter_keys,
+            openai_keys,
+            claude_keys,
+            gemini_keys,
+            xai_keys,
+            mistral_keys,
+            azure_keys,
+            cohere_keys,
             ollama: toml_config.ollama,
             openrouter: toml_config.openrouter,
             openai: toml_config.openai,
             claude: toml_config.claude,
             gemini: toml_config.gemini,
             xai: toml_config.xai,
-        })
+            mistral: toml_config.mistral,
+            azure: toml_config.azure,
+            cohere: toml_config.cohere,
+            providers: toml_config.providers,
+            workspaces: toml_config.workspaces,
+            personas: toml_config.personas,
+            mode: toml_config.models.mode,
+            primary: toml_config.models.primary,
+            templates: toml_config.templates,
+            pricing: toml_config.pricing,
+            sync_folder: toml_config.sync.folder,
+            sync_key,
+            question_bank: toml_config.question_bank.enabled,
+            watch: toml_config.watch,
+            git: toml_config.git,
+            verify: toml_config.verify,
+            search: toml_config.search,
+            shell: toml_config.shell,
+            video: toml_config.video,
+            ocr: toml_config.ocr,
+            imagine: toml_config.imagine,
+            cache: toml_config.cache,
+            dedup: toml_config.dedup,
+            history: toml_config.history,
+            titles: toml_config.titles,
+            consensus: toml_config.consensus,
+            streaming: toml_config.streaming,
+            http: toml_config.http,
+        };
+
+        for provider in &mut config.providers {
+            provider.api_keys = api_keys(&provider.name, provider.key_env.as_deref(), keyring);
+        }
+
+        // Offline mode (`[models] offline` or `aerogel --offline`) drops the
+        // cloud API keys, so only Ollama is asked, turns web search and image
+        // generation off and reads images with Tesseract.
+        if offline {
+            eprintln!("Offline mode: cloud providers are disabled.");
+            config.search.backend = None;
+            config.imagine.backend = None;
+            config.ocr.backend = OcrBackend::Tesseract;
+            for keys in [
+                &mut config.openrouter_keys,
+                &mut config.openai_keys,
+                &mut config.claude_keys,
+                &mut config.gemini_keys,
+                &mut config.xai_keys,
+                &mut config.mistral_keys,
+                &mut config.azure_keys,
+                &mut config.cohere_keys,
+            ]
+            .into_iter()
+            .chain(config.providers.iter_mut().map(|p| &mut p.api_keys))
+            {
+                keys.clear();
+            }
+        }
+        config
     }
 
+    pub fn workspace_profile(&self, workspace: u32) -> Option<&WorkspaceProfile> {
+        self.workspaces.get(&workspace.to_string())
+    }
+
+    pub fn workspace_persona(&self, workspace: u32) -> Option<(&str, &Persona)> {
+        let name = self.workspace_profile(workspace)?.persona.as_deref()?;
+        match self.personas.get(name) {
+            Some(persona) => Some((name, persona)),
+            None => {
+                eprintln!(
+                    "Warning: workspace {} names an unknown persona '{}'",
+                    workspace, name
+                );
+                None
+            }
+        }
+    }
+
+    // The provider a workspace asks in single mode: its own, its persona's,
+    // or `[models] primary`. None leaves the choice to the caller.
+    pub fn primary_provider(&self, workspace: Option<u32>) -> Option<&str> {
+        let profile = workspace.and_then(|workspace| self.workspace_profile(workspace));
+        let persona = workspace.and_then(|workspace| self.workspace_persona(workspace));
+        profile
+            .and_then(|profile| profile.provider.as_deref())
+            .or_else(|| persona.and_then(|(_, persona)| persona.provider.as_deref()))
+            .or(self.primary.as_deref())
+    }
+
+    // The system prompt of a workspace: its persona's, followed by its
+    // answer preferences.
+    pub fn system_prompt(&self, workspace: u32) -> Option<String> {
+        let parts: Vec<String> = [
+            self.workspace_persona(workspace)
+                .and_then(|(_, persona)| persona.system_prompt.clone()),
+            self.workspace_profile(workspace)
+                .and_then(|profile| profile.system_prompt()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| parts.join("\n\n"))
+    }
+
+    // The model settings of a provider, by its name as shown in answers.
+    pub fn model_config_mut(&mut self, provider: &str) -> Option<&mut ModelConfig> {
+        match provider.to_lowercase().as_str() {
+            "ollama" => Some(&mut self.ollama),
+            "openrouter" => Some(&mut self.openrouter),
+            "openai" | "azure" => Some(&mut self.openai),
+            "claude" => Some(&mut self.claude),
+            "gemini" => Some(&mut self.gemini),
+            "xai" => Some(&mut self.xai),
+            "mistral" => self.mistral.as_mut(),
+            "cohere" => self.cohere.as_mut(),
+            _ => self
+                .providers
+                .iter_mut()
+                .find(|custom| custom.name.eq_ignore_ascii_case(provider))
+                .map(|custom| &mut custom.model),
+        }
+    }
+
+    // The first key of a provider.
     pub fn get_key(&self, provider: &str) -> Option<&String> {
+        self.get_keys(provider).first()
+    }
+
+    pub fn get_keys(&self, provider: &str) -> &[String] {
         match provider.to_lowercase().as_str() {
-            "openrouter" => self.openrouter_key.as_ref(),
-            "openai" => self.openai_key.as_ref(),
-            "claude" | "anthropic" => self.claude_key.as_ref(),
-            "gemini" | "google" => self.gemini_key.as_ref(),
-            "xai" => self.xai_key.as_ref(),
-            _ => None,
+            "openrouter" => &self.openrouter_keys,
+            "openai" => &self.openai_keys,
+            "claude" | "anthropic" => &self.claude_keys,
+            "gemini" | "google" => &self.gemini_keys,
+            "xai" => &self.xai_keys,
+            "mistral" => &self.mistral_keys,
+            "azure" => &self.azure_keys,
+            "cohere" => &self.cohere_keys,
+            _ => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_provider() {
+        let toml_str = r#"
+            [[providers]]
+            name = "LM Studio"
+            api_base = "http://localhost:1234/v1/chat/completions"
+            model = "qwen3-8b"
+            max_tokens = 8000
+            temperature = 0
+            top_p = 1.0
+        "#;
+        #[derive(Deserialize)]
+        struct Providers {
+            providers: Vec<CustomProvider>,
+        }
+        let parsed: Providers = toml::from_str(toml_str).unwrap();
+        let provider = &parsed.providers[0];
+        assert_eq!(provider.name, "LM Studio");
+        assert_eq!(provider.key_env, None);
+        assert!(!provider.images);
+        assert_eq!(provider.model.model, "qwen3-8b");
+        assert_eq!(provider.model.temperature, 0.0);
+        assert!(provider.model.enabled);
+    }
+
+    // A config with the required provider sections and nothing else.
+    fn test_config() -> ApiConfig {
+        let model = r#"api_base = "http://localhost"
+            model = "m"
+            max_tokens = 100
+            temperature = 0.5
+            top_p = 1.0"#;
+        let toml_str = ["Ollama", "OpenRouter", "Gemini", "OpenAI", "Claude", "Xai"]
+            .iter()
+            .map(|section| format!("[{}]\n{}\n", section, model))
+            .collect::<String>();
+        ApiConfig::from_toml(toml::from_str(&toml_str).unwrap())
+    }
+
+    #[test]
+    fn test_persona_system_prompt() {
+        let toml_str = r#"
+            [workspaces.1]
+            persona = "rust"
+            code_style = "rustfmt"
+
+            [workspaces.2]
+            answer_language = "de"
+
+            [personas.rust]
+            system_prompt = "You are a Rust expert."
+            provider = "Claude"
+        "#;
+        #[derive(Deserialize)]
+        struct Sections {
+            workspaces: HashMap<String, WorkspaceProfile>,
+            personas: HashMap<String, Persona>,
+        }
+        let sections: Sections = toml::from_str(toml_str).unwrap();
+        let config = ApiConfig {
+            workspaces: sections.workspaces,
+            personas: sections.personas,
+            ..test_config()
+        };
+
+        assert_eq!(
+            config.system_prompt(1).unwrap(),
+            "You are a Rust expert.\n\nWrite any code in this style: rustfmt."
+        );
+        assert_eq!(
+            config.system_prompt(2).unwrap(),
+            "Always answer in this language: de."
+        );
+        assert_eq!(config.system_prompt(3), None);
+        let (name, persona) = config.workspace_persona(1).unwrap();
+        assert_eq!(
+            (name, persona.provider.as_deref()),
+            ("rust", Some("Claude"))
+        );
+    }
+
+    #[test]
+    fn test_primary_provider() {
+        let toml_str = r#"
+            [workspaces.1]
+            persona = "rust"
+
+            [workspaces.2]
+            persona = "rust"
+            provider = "Gemini"
+
+            [personas.rust]
+            provider = "Claude"
+        "#;
+        #[derive(Deserialize)]
+        struct Sections {
+            workspaces: HashMap<String, WorkspaceProfile>,
+            personas: HashMap<String, Persona>,
         }
+        let sections: Sections = toml::from_str(toml_str).unwrap();
+        let config = ApiConfig {
+            workspaces: sections.workspaces,
+            personas: sections.personas,
+            primary: Some("Ollama".to_string()),
+            ..test_config()
+        };
+
+        assert_eq!(config.primary_provider(Some(1)), Some("Claude"));
+        assert_eq!(config.primary_provider(Some(2)), Some("Gemini"));
+        assert_eq!(config.primary_provider(Some(3)), Some("Ollama"));
+        assert_eq!(config.primary_provider(None), Some("Ollama"));
+        assert_eq!(test_config().mode, QueryMode::All);
+        assert_eq!(QueryMode::parse("Single").unwrap(), QueryMode::Single);
+        assert!(QueryMode::parse("some").is_err());
     }
 }
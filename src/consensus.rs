@@ -0,0 +1,118 @@
+// One answer made from the answers of every provider asked.
+//
+// With `[consensus] enabled = true`, once a round has more than one answer,
+// they go with the question to a single model, `provider` and `model` (by
+// default the first provider that answered, with its own model). In `merge`
+// mode it writes the best combined answer and notes where the providers
+// disagree; in `rank` mode it ranks the answers from best to worst with a
+// reason for each. The result is written as a Consensus block above the
+// round's answers in the conversation log.
+
+use crate::config::ModelConfig;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsensusMode {
+    Merge,
+    Rank,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConsensusConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_mode")]
+    pub mode: ConsensusMode,
+    // Provider name, as in `/ask @<name>`
+    pub provider: Option<String>,
+    // Model asked in place of the provider's own
+    pub model: Option<String>,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: default_mode(),
+            provider: None,
+            model: None,
+        }
+    }
+}
+
+fn default_mode() -> ConsensusMode {
+    ConsensusMode::Merge
+}
+
+// The provider's model settings as the consensus is asked with.
+pub fn consensus_model(config: &ConsensusConfig, model: &ModelConfig) -> ModelConfig {
+    ModelConfig {
+        model: config.model.clone().unwrap_or_else(|| model.model.clone()),
+        ..model.clone()
+    }
+}
+
+pub fn consensus_prompt(
+    mode: ConsensusMode,
+    question: &str,
+    answers: &[(String, String)],
+) -> String {
+    let task = match mode {
+        ConsensusMode::Merge => {
+            "Merge them into the single best answer to the question, keeping what they agree \
+             on and correcting what is wrong. Reply with the merged answer, followed by a short \
+             note on where they disagreed and why you sided as you did, if they did."
+        }
+        ConsensusMode::Rank => {
+            "Rank them from best to worst. Give one line per answer, with the provider's name \
+             and the reason for its place, then the main mistake of any answer that is wrong."
+        }
+    };
+    let answers: Vec<String> = answers
+        .iter()
+        .map(|(provider, answer)| format!("## {}\n\n{}", provider, answer.trim()))
+        .collect();
+    format!(
+        "Several assistants answered the question below. {}\n\n# Question\n\n{}\n\n# Answers\n\n{}",
+        task,
+        question.trim(),
+        answers.join("\n\n")
+    )
+}
+
+// The block written above the round's answers.
+pub fn consensus_block(mode: ConsensusMode, provider: &str, consensus: &str) -> String {
+    let heading = match mode {
+        ConsensusMode::Merge => "Consensus",
+        ConsensusMode::Rank => "Consensus ranking",
+    };
+    format!(
+        "\n**{}** (by {}):\n\n{}\n\n---\n\n",
+        heading,
+        provider,
+        consensus.trim()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consensus_prompt() {
+        let answers = vec![
+            ("Claude".to_string(), "42\n".to_string()),
+            ("Gemini".to_string(), "It is 42.".to_string()),
+        ];
+        let prompt = consensus_prompt(ConsensusMode::Rank, "What is 6 * 7?", &answers);
+        assert!(prompt.contains("Rank them"));
+        assert!(prompt.ends_with(
+            "# Question\n\nWhat is 6 * 7?\n\n# Answers\n\n## Claude\n\n42\n\n## Gemini\n\nIt is 42."
+        ));
+        assert_eq!(
+            consensus_block(ConsensusMode::Merge, "Claude", "42\n"),
+            "\n**Consensus** (by Claude):\n\n42\n\n---\n\n"
+        );
+    }
+}
@@ -0,0 +1,374 @@
+// The conversation of a workspace as a list of exchanges, for `/export` and
+// `/import`.
+//
+// The history sent to each provider holds the prompts as each one was sent,
+// after templates, OCR and transcription, and with attachments inlined as
+// base64. An exchange is kept alongside it for every round: the question as
+// typed, the names and types of the attached files, and each provider's
+// answer. `/export md|json [path]` writes them to a file, by default
+// `exports/ws<n>-<time>.<ext>`, with the conversation's title after the time
+// once it has one.
+//
+// `/import <file>` reads a JSON export back: each provider's history is
+// rebuilt from its answers, with the prompts as text since the attachments
+// themselves were not exported, and the workspace's transcript is replaced so
+// the overlay shows the conversation.
+
+use crate::ai_client::Message;
+use crate::titles;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const EXPORT_DIR: &str = "exports";
+// Bumped when the JSON export changes incompatibly
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub mime_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Answer {
+    pub provider: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    // Local time the question was asked, RFC 3339
+    pub time: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    pub answers: Vec<Answer>,
+}
+
+impl Exchange {
+    // The prompt with a note of what was attached.
+    fn prompt_with_attachments(&self) -> String {
+        if self.attachments.is_empty() {
+            return self.prompt.trim().to_string();
+        }
+        let names: Vec<String> = self
+            .attachments
+            .iter()
+            .map(|a| format!("`{}` ({})", a.name, a.mime_type))
+            .collect();
+        format!("{}\n\nAttached: {}", self.prompt.trim(), names.join(", "))
+            .trim_start()
+            .to_string()
+    }
+
+    pub fn new(prompt: &str, files: &[String], answers: Vec<(String, String)>) -> Self {
+        let attachments = files
+            .iter()
+            .map(|file| Attachment {
+                name: Path::new(file)
+                    .file_name()
+                    .map_or_else(|| file.clone(), |name| name.to_string_lossy().into_owned()),
+                mime_type: mime_guess::from_path(file)
+                    .first_or_octet_stream()
+                    .to_string(),
+            })
+            .collect();
+        Self {
+            time: chrono::Local::now().to_rfc3339(),
+            prompt: prompt.to_string(),
+            attachments,
+            answers: answers
+                .into_iter()
+                .map(|(provider, text)| Answer { provider, text })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Conversation {
+    pub version: u32,
+    pub workspace: Option<u32>,
+    pub exported: String,
+    // Missing from conversations exported before they had titles
+    #[serde(default)]
+    pub title: Option<String>,
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Conversation {
+    pub fn new(workspace: Option<u32>, title: Option<String>, exchanges: Vec<Exchange>) -> Self {
+        Self {
+            version: VERSION,
+            workspace,
+            exported: chrono::Local::now().to_rfc3339(),
+            title,
+            exchanges,
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = match (&self.title, self.workspace) {
+            (Some(title), _) => format!("# {}\n\n", title),
+            (None, Some(workspace)) => format!("# Conversation, workspace {}\n\n", workspace),
+            (None, None) => "# Conversation\n\n".to_string(),
+        };
+        markdown.push_str(&format!("_Exported {}_\n", self.exported));
+        for (i, exchange) in self.exchanges.iter().enumerate() {
+            markdown.push_str(&format!("\n## Prompt {}\n\n", i + 1));
+            markdown.push_str(&format!("_{}_\n\n", exchange.time));
+            let prompt = exchange.prompt_with_attachments();
+            if !prompt.is_empty() {
+                markdown.push_str(&format!("{}\n\n", prompt));
+            }
+            for answer in &exchange.answers {
+                markdown.push_str(&format!(
+                    "### {}\n\n{}\n\n",
+                    answer.provider,
+                    answer.text.trim()
+                ));
+            }
+        }
+        markdown
+    }
+
+    // Each provider's history, as if the conversation had been held here.
+    pub fn history(&self) -> HashMap<String, Vec<Message>> {
+        let mut history: HashMap<String, Vec<Message>> = HashMap::new();
+        for exchange in &self.exchanges {
+            let prompt = exchange.prompt_with_attachments();
+            for answer in &exchange.answers {
+                let messages = history.entry(answer.provider.clone()).or_default();
+                messages.push(Message {
+                    role: "user".to_string(),
+                    content: json!(prompt),
+                });
+                messages.push(Message {
+                    role: "assistant".to_string(),
+                    content: json!(answer.text),
+                });
+            }
+        }
+        history
+    }
+
+    // The transcript the overlay shows: each prompt, as the event handler
+    // logs typed questions, followed by the answers, headed by their
+    // provider when there are several.
+    pub fn transcript(&self) -> String {
+        let mut transcript = String::new();
+        for exchange in &self.exchanges {
+            let prompt = exchange.prompt_with_attachments();
+            if !prompt.is_empty() {
+                transcript.push_str(&format!("{}  \n\n", prompt));
+            }
+            for answer in &exchange.answers {
+                if exchange.answers.len() > 1 {
+                    transcript.push_str(&format!("**{}:**\n\n", answer.provider));
+                }
+                transcript.push_str(&format!("{}\n\n", answer.text.trim()));
+            }
+        }
+        transcript
+    }
+}
+
+// `path` with a leading `~/` replaced by the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// Reads a conversation written by `/export json`.
+pub fn load(path: &str) -> Result<Conversation> {
+    let contents = std::fs::read_to_string(expand_home(path))
+        .map_err(|e| anyhow!("Could not read '{}': {}", path, e))?;
+    let conversation: Conversation = serde_json::from_str(&contents).map_err(|e| {
+        anyhow!(
+            "'{}' is not a JSON export ({}); only `/export json` files can be imported",
+            path,
+            e
+        )
+    })?;
+    if conversation.version > VERSION {
+        return Err(anyhow!(
+            "'{}' was exported by a newer version of aerogel",
+            path
+        ));
+    }
+    Ok(conversation)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "md" | "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("Unknown export format '{}'; use md or json", name)),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+        }
+    }
+}
+
+fn default_path(
+    format: ExportFormat,
+    workspace: Option<u32>,
+    title: Option<&str>,
+    timestamp: &str,
+) -> PathBuf {
+    let mut name = match workspace {
+        Some(workspace) => format!("ws{}-{}", workspace, timestamp),
+        None => format!("conversation-{}", timestamp),
+    };
+    if let Some(slug) = title.map(titles::slug).filter(|slug| !slug.is_empty()) {
+        name = format!("{}-{}", name, slug);
+    }
+    Path::new(EXPORT_DIR).join(format!("{}.{}", name, format.extension()))
+}
+
+// Writes the conversation to `path`, or a new file under exports/, and
+// returns where.
+pub fn export(
+    conversation: &Conversation,
+    format: ExportFormat,
+    path: Option<&str>,
+) -> Result<PathBuf> {
+    let path = match path {
+        Some(path) => expand_home(path),
+        None => {
+            let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+            default_path(
+                format,
+                conversation.workspace,
+                conversation.title.as_deref(),
+                &timestamp,
+            )
+        }
+    };
+    let contents = match format {
+        ExportFormat::Markdown => conversation.to_markdown(),
+        ExportFormat::Json => serde_json::to_string_pretty(conversation)?,
+    };
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation() -> Conversation {
+        let mut exchange = Exchange::new(
+            "What is 6 * 7?",
+            &["shots/question.png".to_string()],
+            vec![
+                ("Claude".to_string(), "42".to_string()),
+                ("Gemini".to_string(), "It is 42.".to_string()),
+            ],
+        );
+        exchange.time = "2026-10-15T12:00:00+02:00".to_string();
+        Conversation {
+            version: VERSION,
+            workspace: Some(2),
+            exported: "2026-10-15T12:05:00+02:00".to_string(),
+            title: None,
+            exchanges: vec![exchange],
+        }
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        assert_eq!(
+            conversation().to_markdown(),
+            "# Conversation, workspace 2\n\n\
+             _Exported 2026-10-15T12:05:00+02:00_\n\n\
+             ## Prompt 1\n\n\
+             _2026-10-15T12:00:00+02:00_\n\n\
+             What is 6 * 7?\n\n\
+             Attached: `question.png` (image/png)\n\n\
+             ### Claude\n\n42\n\n\
+             ### Gemini\n\nIt is 42.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.json");
+        let conversation = conversation();
+        export(
+            &conversation,
+            ExportFormat::Json,
+            Some(path.to_str().unwrap()),
+        )
+        .unwrap();
+        let read: Conversation =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read.exchanges, conversation.exchanges);
+
+        assert_eq!(
+            default_path(ExportFormat::Markdown, Some(2), None, "20261015-120500"),
+            Path::new("exports/ws2-20261015-120500.md")
+        );
+        assert_eq!(
+            default_path(
+                ExportFormat::Json,
+                Some(2),
+                Some("Sorting a Linked List"),
+                "20261015-120500"
+            ),
+            Path::new("exports/ws2-20261015-120500-sorting-a-linked-list.json")
+        );
+        assert!(ExportFormat::parse("pdf").is_err());
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.json");
+        let path = path.to_str().unwrap();
+        export(&conversation(), ExportFormat::Json, Some(path)).unwrap();
+        let conversation = load(path).unwrap();
+
+        let history = conversation.history();
+        let claude = &history["Claude"];
+        assert_eq!(claude.len(), 2);
+        assert_eq!(
+            claude[0].content,
+            "What is 6 * 7?\n\nAttached: `question.png` (image/png)"
+        );
+        assert_eq!(claude[1].content, "42");
+        assert_eq!(
+            conversation.transcript(),
+            "What is 6 * 7?\n\nAttached: `question.png` (image/png)  \n\n\
+             **Claude:**\n\n42\n\n**Gemini:**\n\nIt is 42.\n\n"
+        );
+
+        let markdown = dir.path().join("chat.md");
+        let markdown = markdown.to_str().unwrap();
+        export(&conversation, ExportFormat::Markdown, Some(markdown)).unwrap();
+        assert!(load(markdown).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+// Control socket for compositor integrations.
+//
+// With `aerogel integrate hyprland|sway`, the compositor's own key bindings run
+// `aerogel-ctl`, which sends one command per connection to this socket instead
+// of aerogel reading every key press through the root listener:
+//
+//   trigger <action>   run a keybinding action (solve, type_text, ...) or an
+//                      answer format by name
+//   key <keysym>       a key typed in text input mode, as an xkb keysym name
+//                      with an optional "Shift+" prefix, e.g. "Shift+a"
+//   workspace <n>      switch to workspace n
+//
+// The event handler turns these into the same key lines the listener writes
+// to `.event`, so both input backends share one code path.
+//
+// The socket is `$XDG_RUNTIME_DIR/aerogel.sock`. Without XDG_RUNTIME_DIR it
+// goes in `/tmp/aerogel-<uid>/`, a directory only its owner may enter, so
+// other users can neither send commands nor put a socket of their own where
+// aerogel-ctl connects.
+
+use std::fs::DirBuilder;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+use tokio::net::UnixListener;
+use tokio::sync::mpsc;
+
+// Keys bound while typing, besides letters and digits:
+// (keysym, logged key, logged key with Shift held)
+pub const TYPED_KEYS: &[(&str, &str, &str)] = &[
+    ("minus", "-", "_"),
+    ("equal", "=", "+"),
+    ("bracketleft", "[", "{"),
+    ("bracketright", "]", "}"),
+    ("backslash", "\\", "|"),
+    ("semicolon", ";", ":"),
+    ("apostrophe", "'", "\""),
+    ("comma", ",", "<"),
+    ("period", ".", ">"),
+    ("slash", "/", "?"),
+    ("grave", "`", "~"),
+    ("space", "[Space]", "[Space]"),
+    ("BackSpace", "[Backspace]", "[Backspace]"),
+];
+
+const SHIFTED_DIGITS: &str = ")!@#$%^&*(";
+
+// Creates `dir` for the user alone, or checks that the one there is theirs
+// and closed to others.
+fn private_dir(dir: &Path) -> io::Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    let metadata = std::fs::symlink_metadata(dir)?;
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "'{}' is not a directory only this user can open; remove it or set XDG_RUNTIME_DIR",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn socket_path() -> io::Result<PathBuf> {
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(runtime_dir).join("aerogel.sock"));
+    }
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let dir = std::env::temp_dir().join(format!("aerogel-{}", unsafe { libc::getuid() }));
+    private_dir(&dir)?;
+    Ok(dir.join("aerogel.sock"))
+}
+
+// Listens on the control socket and forwards each received command on `tx`.
+pub fn spawn_listener(tx: mpsc::Sender<String>) -> std::io::Result<()> {
+    let path = socket_path()?;
+    // A socket left behind by a previous run would make bind fail.
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    println!(
+        "Listening for aerogel-ctl commands on '{}'.",
+        path.display()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            let mut stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("[ctl] Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+            let mut command = String::new();
+            if let Err(e) = stream.read_to_string(&mut command).await {
+                eprintln!("[ctl] Failed to read command: {}", e);
+                continue;
+            }
+            for line in command.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                if tx.send(line.to_string()).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+// Converts a keybinding like "Ctrl+Shift+C" into the key lines the listener
+// would log for it: ["[CtrlL]", "[ShiftL]", "C"].
+pub fn keybinding_to_key_lines(keybinding: &str) -> Vec<String> {
+    keybinding
+        .split('+')
+        .map(|part| match part.to_lowercase().as_str() {
+            "ctrl" => "[CtrlL]".to_string(),
+            "alt" => "[AltL]".to_string(),
+            "shift" => "[ShiftL]".to_string(),
+            "super" => "[SuperL]".to_string(),
+            "enter" => "[Enter]".to_string(),
+            "space" => "[Space]".to_string(),
+            _ => part.to_string(),
+        })
+        .collect()
+}
+
+// Converts a typed keysym like "Shift+a" into the key line the listener would
+// log for it ("A").
+pub fn typed_key_line(key: &str) -> Option<String> {
+    let (shift, keysym) = match key.strip_prefix("Shift+") {
+        Some(keysym) => (true, keysym),
+        None => (false, key),
+    };
+
+    let mut chars = keysym.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_lowercase() {
+            return Some(if shift { c.to_ascii_uppercase() } else { c }.to_string());
+        }
+        if let Some(digit) = c.to_digit(10) {
+            return Some(if shift {
+                SHIFTED_DIGITS[digit as usize..=digit as usize].to_string()
+            } else {
+                c.to_string()
+            });
+        }
+    }
+
+    TYPED_KEYS
+        .iter()
+        .find(|(name, _, _)| *name == keysym)
+        .map(|(_, plain, shifted)| if shift { shifted } else { plain }.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_dir() {
+        let base = tempfile::tempdir().unwrap();
+        let dir = base.path().join("aerogel-test");
+        private_dir(&dir).unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        // Created before, by this user
+        private_dir(&dir).unwrap();
+
+        let open = base.path().join("open");
+        DirBuilder::new().mode(0o755).create(&open).unwrap();
+        assert!(private_dir(&open).is_err());
+    }
+
+    #[test]
+    fn test_keybinding_to_key_lines() {
+        assert_eq!(
+            keybinding_to_key_lines("Ctrl+Shift+C"),
+            vec!["[CtrlL]", "[ShiftL]", "C"]
+        );
+        assert_eq!(
+            keybinding_to_key_lines("Ctrl+Enter"),
+            vec!["[CtrlL]", "[Enter]"]
+        );
+        assert_eq!(keybinding_to_key_lines("Alt"), vec!["[AltL]"]);
+    }
+
+    #[test]
+    fn test_typed_key_line() {
+        assert_eq!(typed_key_line("a").as_deref(), Some("a"));
+        assert_eq!(typed_key_line("Shift+a").as_deref(), Some("A"));
+        assert_eq!(typed_key_line("Shift+1").as_deref(), Some("!"));
+        assert_eq!(typed_key_line("Shift+apostrophe").as_deref(), Some("\""));
+        assert_eq!(typed_key_line("space").as_deref(), Some("[Space]"));
+        assert_eq!(typed_key_line("F13"), None);
+    }
+}
@@ -0,0 +1,129 @@
+// Collapsing of near-identical answers from several providers.
+//
+// Simple questions often get the same answer from every provider. With
+// `[dedup] enabled = true`, the answers of a round are compared once they are
+// complete and answers at least `similarity` alike are shown once, under the
+// first provider, with an "also returned by" note for the others. Similarity
+// is the overlap (Jaccard index) of the answers' word pairs, ignoring case,
+// punctuation and whitespace.
+
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DedupConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // 0.0 to 1.0; answers at least this similar are collapsed
+    #[serde(default = "default_similarity")]
+    pub similarity: f64,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            similarity: default_similarity(),
+        }
+    }
+}
+
+fn default_similarity() -> f64 {
+    0.9
+}
+
+fn shingles(answer: &str) -> HashSet<(String, String)> {
+    let words: Vec<String> = answer
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() == 1 {
+        return HashSet::from([(words[0].clone(), String::new())]);
+    }
+    words
+        .windows(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect()
+}
+
+// 1.0 for answers with the same words in the same order, down to 0.0 for
+// answers without a word pair in common.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (shingles(a), shingles(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AnswerGroup {
+    // The provider whose answer is shown, then the ones it stands in for
+    pub providers: Vec<String>,
+    pub answer: String,
+}
+
+impl AnswerGroup {
+    // The answer as a section of the conversation log.
+    pub fn to_markdown(&self) -> String {
+        let mut heading = format!("**{}**", self.providers[0]);
+        if self.providers.len() > 1 {
+            heading.push_str(&format!(
+                " _(also returned by {})_",
+                self.providers[1..].join(", ")
+            ));
+        }
+        format!("{}\n\n{}", heading, self.answer.trim_end())
+    }
+}
+
+// Groups (provider, answer) pairs in order, adding each answer to the first
+// group whose answer is at least `threshold` similar to it.
+pub fn group_answers(answers: &[(String, String)], threshold: f64) -> Vec<AnswerGroup> {
+    let mut groups: Vec<AnswerGroup> = Vec::new();
+    for (provider, answer) in answers {
+        match groups
+            .iter_mut()
+            .find(|group| similarity(&group.answer, answer) >= threshold)
+        {
+            Some(group) => group.providers.push(provider.clone()),
+            None => groups.push(AnswerGroup {
+                providers: vec![provider.clone()],
+                answer: answer.clone(),
+            }),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("The answer is 42.", "the answer is 42"), 1.0);
+        assert_eq!(similarity("alpha beta", "gamma delta"), 0.0);
+        let a = "Use a HashMap to count the words, then sort the entries by count.";
+        let b = "Use a HashMap to count the words, then sort the entries by their count.";
+        assert!(similarity(a, b) > 0.75);
+    }
+
+    #[test]
+    fn test_group_answers() {
+        let answers = vec![
+            ("Claude".to_string(), "Paris is the capital.".to_string()),
+            ("Gemini".to_string(), "Berlin.".to_string()),
+            ("XAI".to_string(), "paris is the capital".to_string()),
+        ];
+        let groups = group_answers(&answers, 0.9);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].providers, vec!["Claude", "XAI"]);
+        assert_eq!(
+            groups[0].to_markdown(),
+            "**Claude** _(also returned by XAI)_\n\nParis is the capital."
+        );
+        assert_eq!(groups[1].to_markdown(), "**Gemini**\n\nBerlin.");
+    }
+}
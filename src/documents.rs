@@ -0,0 +1,448 @@
+// Text of office documents attached with /upload.
+//
+// .docx, .odt and .xlsx files are zip archives of XML parts. Instead of
+// being sent as media, which no provider reads, their text is taken out of
+// those parts and added to the prompt: paragraphs of Word and OpenDocument
+// text, and the cells of every spreadsheet sheet as tab-separated rows.
+
+use anyhow::{Result, anyhow};
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::DeflateDecoder;
+use std::io::Read;
+use std::path::Path;
+
+// Longer documents are cut, so a big report doesn't overflow the context
+const MAX_TEXT_CHARS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentKind {
+    Docx,
+    Odt,
+    Xlsx,
+}
+
+pub fn kind(path: &Path) -> Option<DocumentKind> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "docx" => Some(DocumentKind::Docx),
+        "odt" => Some(DocumentKind::Odt),
+        "xlsx" => Some(DocumentKind::Xlsx),
+        _ => None,
+    }
+}
+
+// The names and contents of the files in a zip archive, read through its
+// central directory. Only stored and deflated entries are supported, which
+// is what office suites write.
+struct Zip<'a> {
+    data: &'a [u8],
+    // (name, method, compressed size, local header offset)
+    entries: Vec<(String, u16, usize, usize)>,
+}
+
+impl<'a> Zip<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let invalid = || anyhow!("Not a valid zip archive");
+        let end = (0..data.len().saturating_sub(21))
+            .rev()
+            .find(|&i| data[i..].starts_with(b"PK\x05\x06"))
+            .ok_or_else(invalid)?;
+        let count = LittleEndian::read_u16(&data[end + 10..]) as usize;
+        let mut offset = LittleEndian::read_u32(&data[end + 16..]) as usize;
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let header = data.get(offset..offset + 46).ok_or_else(invalid)?;
+            if !header.starts_with(b"PK\x01\x02") {
+                return Err(invalid());
+            }
+            let method = LittleEndian::read_u16(&header[10..]);
+            let size = LittleEndian::read_u32(&header[20..]) as usize;
+            let name_len = LittleEndian::read_u16(&header[28..]) as usize;
+            let extra_len = LittleEndian::read_u16(&header[30..]) as usize;
+            let comment_len = LittleEndian::read_u16(&header[32..]) as usize;
+            let local = LittleEndian::read_u32(&header[42..]) as usize;
+            let name = data
+                .get(offset + 46..offset + 46 + name_len)
+                .ok_or_else(invalid)?;
+            entries.push((
+                String::from_utf8_lossy(name).into_owned(),
+                method,
+                size,
+                local,
+            ));
+            offset += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(Self { data, entries })
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, ..)| name.as_str())
+    }
+
+    fn read(&self, name: &str) -> Result<String> {
+        let invalid = || anyhow!("Damaged zip entry {}", name);
+        let &(_, method, size, local) = self
+            .entries
+            .iter()
+            .find(|(entry, ..)| entry == name)
+            .ok_or_else(|| anyhow!("{} is missing", name))?;
+        let header = self.data.get(local..local + 30).ok_or_else(invalid)?;
+        let start = local
+            + 30
+            + LittleEndian::read_u16(&header[26..]) as usize
+            + LittleEndian::read_u16(&header[28..]) as usize;
+        let compressed = self.data.get(start..start + size).ok_or_else(invalid)?;
+        let mut contents = String::new();
+        match method {
+            0 => contents = String::from_utf8_lossy(compressed).into_owned(),
+            8 => {
+                DeflateDecoder::new(compressed).read_to_string(&mut contents)?;
+            }
+            _ => return Err(anyhow!("{} uses an unsupported compression", name)),
+        }
+        Ok(contents)
+    }
+}
+
+enum Node<'a> {
+    // Name and attributes of a start tag, and whether it closes itself
+    Open(&'a str, &'a str, bool),
+    Close(&'a str),
+    Text(String),
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else { break };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// The tags and text of an XML document; declarations and comments are
+// skipped.
+fn nodes(xml: &str) -> Vec<Node<'_>> {
+    let mut nodes = Vec::new();
+    let mut rest = xml;
+    while let Some(lt) = rest.find('<') {
+        if lt > 0 {
+            nodes.push(Node::Text(unescape(&rest[..lt])));
+        }
+        rest = &rest[lt..];
+        let Some(gt) = rest.find('>') else { break };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+        if let Some(name) = tag.strip_prefix('/') {
+            nodes.push(Node::Close(name.trim()));
+        } else if !tag.starts_with(['?', '!']) {
+            let closed = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            nodes.push(Node::Open(name, attrs, closed));
+        }
+    }
+    nodes
+}
+
+fn attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}=\"", name);
+    let start = attrs
+        .match_indices(&pattern)
+        .find(|(i, _)| *i == 0 || attrs[..*i].ends_with(char::is_whitespace))?
+        .0
+        + pattern.len();
+    let len = attrs[start..].find('"')?;
+    Some(&attrs[start..start + len])
+}
+
+fn docx_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut in_text = false;
+    for node in nodes(xml) {
+        match node {
+            Node::Open("w:t", _, closed) => in_text = !closed,
+            Node::Close("w:t") => in_text = false,
+            Node::Open("w:tab", _, _) => text.push('\t'),
+            Node::Open("w:br" | "w:cr", _, _) => text.push('\n'),
+            Node::Close("w:p") => text.push('\n'),
+            Node::Text(t) if in_text => text.push_str(&t),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn odt_text(xml: &str) -> String {
+    let mut text = String::new();
+    // Paragraphs and headings can nest, e.g. in list items and notes
+    let mut depth = 0;
+    for node in nodes(xml) {
+        match node {
+            Node::Open("text:p" | "text:h", _, false) => depth += 1,
+            Node::Open("text:s", attrs, _) => {
+                let count = attr(attrs, "text:c").and_then(|c| c.parse().ok());
+                text.push_str(&" ".repeat(count.unwrap_or(1)));
+            }
+            Node::Open("text:tab", _, _) => text.push('\t'),
+            Node::Open("text:line-break", _, _) => text.push('\n'),
+            Node::Close("text:p" | "text:h") => {
+                depth -= 1;
+                text.push('\n');
+            }
+            Node::Text(t) if depth > 0 => text.push_str(&t),
+            _ => {}
+        }
+    }
+    text
+}
+
+// The strings cells refer to by index.
+fn shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    for node in nodes(xml) {
+        match node {
+            Node::Open("si", _, _) => current.clear(),
+            Node::Close("si") => strings.push(std::mem::take(&mut current)),
+            Node::Open("t", _, closed) => in_text = !closed,
+            Node::Close("t") => in_text = false,
+            Node::Text(t) if in_text => current.push_str(&t),
+            _ => {}
+        }
+    }
+    strings
+}
+
+// Zero-based column of a cell reference, e.g. 2 for "C7".
+fn column(reference: &str) -> usize {
+    reference
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .fold(0, |col, c| {
+            col * 26 + (c.to_ascii_uppercase() as usize - 'A' as usize + 1)
+        })
+        .saturating_sub(1)
+}
+
+fn sheet_text(xml: &str, strings: &[String]) -> String {
+    let mut rows = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    // (column, type) of the open cell
+    let mut cell: Option<(usize, String)> = None;
+    let mut value = String::new();
+    let mut in_value = false;
+    for node in nodes(xml) {
+        match node {
+            Node::Open("row", _, _) => row.clear(),
+            Node::Close("row") => rows.push(row.join("\t")),
+            Node::Open("c", attrs, false) => {
+                let col = attr(attrs, "r").map_or(row.len(), column);
+                cell = Some((col, attr(attrs, "t").unwrap_or_default().to_string()));
+                value.clear();
+            }
+            Node::Close("c") => {
+                if let Some((col, kind)) = cell.take() {
+                    let text = match kind.as_str() {
+                        "s" => value
+                            .trim()
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|i| strings.get(i).cloned())
+                            .unwrap_or_default(),
+                        _ => std::mem::take(&mut value),
+                    };
+                    if row.len() <= col {
+                        row.resize(col + 1, String::new());
+                    }
+                    row[col] = text;
+                }
+            }
+            Node::Open("v" | "t", _, closed) => in_value = !closed && cell.is_some(),
+            Node::Close("v" | "t") => in_value = false,
+            Node::Text(t) if in_value => value.push_str(&t),
+            _ => {}
+        }
+    }
+    let end = rows
+        .iter()
+        .rposition(|r| !r.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    rows[..end].join("\n")
+}
+
+fn xlsx_text(zip: &Zip) -> Result<String> {
+    let strings = match zip.read("xl/sharedStrings.xml") {
+        Ok(xml) => shared_strings(&xml),
+        Err(_) => Vec::new(),
+    };
+    let sheet_names: Vec<String> = nodes(&zip.read("xl/workbook.xml")?)
+        .into_iter()
+        .filter_map(|node| match node {
+            Node::Open("sheet", attrs, _) => attr(attrs, "name").map(unescape),
+            _ => None,
+        })
+        .collect();
+    let mut sheets: Vec<(usize, &str)> = zip
+        .names()
+        .filter_map(|name| {
+            let number = name
+                .strip_prefix("xl/worksheets/sheet")?
+                .strip_suffix(".xml")?;
+            Some((number.parse().ok()?, name))
+        })
+        .collect();
+    sheets.sort_unstable();
+    let mut text = String::new();
+    for (number, name) in sheets {
+        let title = sheet_names
+            .get(number - 1)
+            .cloned()
+            .unwrap_or_else(|| format!("Sheet {}", number));
+        text.push_str(&format!(
+            "## {}\n{}\n\n",
+            title,
+            sheet_text(&zip.read(name)?, &strings)
+        ));
+    }
+    Ok(text)
+}
+
+// The text of an office document's contents.
+pub fn extract_text(kind: DocumentKind, data: &[u8]) -> Result<String> {
+    let zip = Zip::new(data)?;
+    let mut text = match kind {
+        DocumentKind::Docx => docx_text(&zip.read("word/document.xml")?),
+        DocumentKind::Odt => odt_text(&zip.read("content.xml")?),
+        DocumentKind::Xlsx => xlsx_text(&zip)?,
+    };
+    if text.len() > MAX_TEXT_CHARS {
+        let mut end = MAX_TEXT_CHARS;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[document truncated]");
+    }
+    Ok(text.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    // A zip archive with deflated entries, enough for Zip to read.
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut directory = Vec::new();
+        for (name, contents) in files {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(contents.as_bytes()).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let mut header = [0u8; 46];
+            header[..4].copy_from_slice(b"PK\x01\x02");
+            LittleEndian::write_u16(&mut header[10..], 8);
+            LittleEndian::write_u32(&mut header[20..], compressed.len() as u32);
+            LittleEndian::write_u16(&mut header[28..], name.len() as u16);
+            LittleEndian::write_u32(&mut header[42..], data.len() as u32);
+            directory.extend_from_slice(&header);
+            directory.extend_from_slice(name.as_bytes());
+
+            let mut local = [0u8; 30];
+            local[..4].copy_from_slice(b"PK\x03\x04");
+            LittleEndian::write_u16(&mut local[26..], name.len() as u16);
+            data.extend_from_slice(&local);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&compressed);
+        }
+        let mut end = [0u8; 22];
+        end[..4].copy_from_slice(b"PK\x05\x06");
+        LittleEndian::write_u16(&mut end[10..], files.len() as u16);
+        LittleEndian::write_u32(&mut end[16..], data.len() as u32);
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(&end);
+        data
+    }
+
+    #[test]
+    fn test_docx_and_odt() {
+        let docx = zip(&[(
+            "word/document.xml",
+            r#"<?xml version="1.0"?><w:document><w:body>
+            <w:p><w:r><w:t>Q3 &amp; Q4</w:t><w:tab/><w:t xml:space="preserve"> report</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Second</w:t></w:r></w:p></w:body></w:document>"#,
+        )]);
+        assert_eq!(
+            extract_text(DocumentKind::Docx, &docx).unwrap(),
+            "Q3 & Q4\t report\nSecond"
+        );
+
+        let odt = zip(&[(
+            "content.xml",
+            r#"<office:document-content><office:font-face-decls>Arial</office:font-face-decls>
+            <office:body><office:text><text:h>Title</text:h>
+            <text:p>a<text:s text:c="2"/>b<text:line-break/>c</text:p></office:text></office:body>
+            </office:document-content>"#,
+        )]);
+        assert_eq!(
+            extract_text(DocumentKind::Odt, &odt).unwrap(),
+            "Title\na  b\nc"
+        );
+    }
+
+    #[test]
+    fn test_xlsx() {
+        let xlsx = zip(&[
+            (
+                "xl/workbook.xml",
+                r#"<workbook><sheets><sheet name="Budget" sheetId="1" r:id="rId1"/></sheets></workbook>"#,
+            ),
+            (
+                "xl/sharedStrings.xml",
+                r#"<sst><si><t>Item</t></si><si><r><t>Co</t></r><r><t>st</t></r></si></sst>"#,
+            ),
+            (
+                "xl/worksheets/sheet1.xml",
+                r#"<worksheet><sheetData>
+                <row r="1"><c r="A1" t="s"><v>0</v></c><c r="C1" t="s"><v>1</v></c></row>
+                <row r="2"><c r="A2" t="inlineStr"><is><t>Rent</t></is></c><c r="C2"><v>1200</v></c></row>
+                </sheetData></worksheet>"#,
+            ),
+        ]);
+        assert_eq!(
+            extract_text(DocumentKind::Xlsx, &xlsx).unwrap(),
+            "## Budget\nItem\t\tCost\nRent\t\t1200"
+        );
+        assert_eq!(column("AA10"), 26);
+    }
+}
@@ -1,21 +1,34 @@
+mod ctl;
+mod focus_watcher;
+mod heartbeat;
+mod ipc;
+mod screen_diff;
+mod session;
+
 use chrono::Local;
 use config::{Config, File as ConfigFile};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::time::{Duration, Instant};
 use tokio::fs::File;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
 use tokio::sync::mpsc;
 
+use crate::focus_watcher::{WorkspaceRule, match_workspace_rule};
+use crate::ipc::{COLLAPSE_FILE, KEYBOARD_GRAB_FILE, WORKSPACE_FILE};
+use crate::session::{Session, SessionConfig};
+
 const LOG_FILE_TO_WATCH: &str = ".event";
 const AI_EXECUTABLE: &str = "./ai_manager";
 const SCREENSHOT_DIR: &str = "screenshots";
 const AUDIO_DIR: &str = "audio_recordings";
-const WORKSPACE_STATE_FILE: &str = ".aerogel_workspace";
+// How often the state file is checked for a workspace picked in the overlay
+const WORKSPACE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Deserialize, Clone)]
 struct Keybindings {
@@ -26,6 +39,27 @@ struct Keybindings {
     solve: String,
     clear: String,
     switch_to_workspace: String,
+    // Writes the code blocks of the latest answer to files
+    #[serde(default)]
+    extract_code: Option<String>,
+    // Shows a question from the question bank to answer
+    #[serde(default)]
+    quiz: Option<String>,
+    // Attaches the changes of the `[git]` repository to the next prompt
+    #[serde(default)]
+    git_diff: Option<String>,
+    // Collapses the overlay to its workspace chip, or expands it again
+    #[serde(default)]
+    collapse: Option<String>,
+    // Toggles whether the overlay grabs keyboard input
+    #[serde(default)]
+    keyboard_grab: Option<String>,
+    // Ends the answers streaming in the current workspace
+    #[serde(default)]
+    stop: Option<String>,
+    // Runs the shell command a model proposed and sends its output back
+    #[serde(default)]
+    run_command: Option<String>,
 }
 
 #[derive(Debug)]
@@ -37,11 +71,43 @@ struct CanonicalKeybindings {
     solve: String,
     clear: String,
     switch_to_workspace: String,
+    extract_code: Option<String>,
+    quiz: Option<String>,
+    git_diff: Option<String>,
+    collapse: Option<String>,
+    keyboard_grab: Option<String>,
+    stop: Option<String>,
+    run_command: Option<String>,
+    // (format name, keybinding) pairs that solve with an answer format applied
+    answer_formats: Vec<(String, String)>,
+    // (provider name, keybinding) pairs that solve asking only that provider
+    provider_targets: Vec<(String, String)>,
+    // (template name, keybinding) pairs that solve with a prompt template
+    prompt_templates: Vec<(String, String)>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ScreenshotSettings {
+    // Attach a visual diff when two screenshots are taken in a row
+    #[serde(default)]
+    diff_consecutive: bool,
 }
 
 #[derive(Debug, Deserialize)]
 struct Settings {
     keybindings: Keybindings,
+    #[serde(default)]
+    answer_formats: HashMap<String, String>,
+    #[serde(default)]
+    provider_keybindings: HashMap<String, String>,
+    #[serde(default)]
+    template_keybindings: HashMap<String, String>,
+    #[serde(default)]
+    workspace_rules: Vec<WorkspaceRule>,
+    #[serde(default)]
+    session: SessionConfig,
+    #[serde(default)]
+    screenshots: ScreenshotSettings,
 }
 
 fn canonicalize_keybinding(kb_string: &str) -> String {
@@ -64,14 +130,25 @@ fn get_text_log_output_path(workspace_number: u32) -> String {
 }
 
 // Manages the lifecycle of a single AI subprocess.
-async fn manage_ai_process(mut command_rx: mpsc::Receiver<String>, ai_arg: String) {
+async fn manage_ai_process(
+    mut command_rx: mpsc::Receiver<String>,
+    ai_arg: String,
+    workspace_number: u32,
+) {
+    // Set after a clear, so the respawned process starts a new conversation
+    // instead of continuing a synced one.
+    let mut new_session = false;
     loop {
         println!(
             "[event_handler] Spawning '{}' for workspace file '{}'...",
             AI_EXECUTABLE, ai_arg
         );
-        let mut child = match Command::new(AI_EXECUTABLE)
-            .arg(&ai_arg)
+        let mut command = Command::new(AI_EXECUTABLE);
+        command.arg(&ai_arg).arg(workspace_number.to_string());
+        if std::mem::take(&mut new_session) {
+            command.arg("--new-session");
+        }
+        let mut child = match command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -137,6 +214,7 @@ async fn manage_ai_process(mut command_rx: mpsc::Receiver<String>, ai_arg: Strin
                         if let Err(e) = child.kill().await {
                             eprintln!("[event_handler] Failed to kill AI process for '{}': {}. It may have already exited.", ai_arg, e);
                         }
+                        new_session = true;
                         break;
                     }
 
@@ -169,7 +247,18 @@ async fn manage_ai_process(mut command_rx: mpsc::Receiver<String>, ai_arg: Strin
 
 // Helper function to write the current workspace number to the state file.
 async fn update_workspace_state_file(workspace_number: u32) -> io::Result<()> {
-    tokio::fs::write(WORKSPACE_STATE_FILE, workspace_number.to_string()).await
+    tokio::fs::write(WORKSPACE_FILE, workspace_number.to_string()).await
+}
+
+// The workspace last written to the state file, by this handler or by a
+// click on a workspace tab in the overlay.
+async fn read_workspace_state_file() -> Option<u32> {
+    tokio::fs::read_to_string(WORKSPACE_FILE)
+        .await
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
 }
 
 // Handles the logic of switching to a new workspace.
@@ -203,7 +292,11 @@ async fn switch_workspace(
 
             clear_text_log(&log_path).await?; // Ensure file is clean for new process
 
-            tokio::spawn(manage_ai_process(new_rx, log_path.clone()));
+            tokio::spawn(manage_ai_process(
+                new_rx,
+                log_path.clone(),
+                *current_workspace,
+            ));
             ai_process_senders.insert(*current_workspace, new_tx);
         }
     } else if !new_workspace_str.is_empty() {
@@ -254,6 +347,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build()?
         .try_deserialize::<Settings>()?;
     let raw_keybindings = settings.keybindings;
+    let raw_answer_formats = settings.answer_formats;
+    let raw_provider_keybindings = settings.provider_keybindings;
+    let raw_template_keybindings = settings.template_keybindings;
+    let workspace_rules = settings.workspace_rules;
+    let session = Session::new(&settings.session);
+    let diff_screenshots = settings.screenshots.diff_consecutive;
 
     let keybindings = CanonicalKeybindings {
         show_hide: canonicalize_keybinding(&raw_keybindings.show_hide),
@@ -263,6 +362,40 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         solve: canonicalize_keybinding(&raw_keybindings.solve),
         clear: canonicalize_keybinding(&raw_keybindings.clear),
         switch_to_workspace: canonicalize_keybinding(&raw_keybindings.switch_to_workspace),
+        extract_code: raw_keybindings
+            .extract_code
+            .as_deref()
+            .map(canonicalize_keybinding),
+        quiz: raw_keybindings.quiz.as_deref().map(canonicalize_keybinding),
+        git_diff: raw_keybindings
+            .git_diff
+            .as_deref()
+            .map(canonicalize_keybinding),
+        collapse: raw_keybindings
+            .collapse
+            .as_deref()
+            .map(canonicalize_keybinding),
+        keyboard_grab: raw_keybindings
+            .keyboard_grab
+            .as_deref()
+            .map(canonicalize_keybinding),
+        stop: raw_keybindings.stop.as_deref().map(canonicalize_keybinding),
+        run_command: raw_keybindings
+            .run_command
+            .as_deref()
+            .map(canonicalize_keybinding),
+        answer_formats: raw_answer_formats
+            .iter()
+            .map(|(name, kb)| (name.clone(), canonicalize_keybinding(kb)))
+            .collect(),
+        provider_targets: raw_provider_keybindings
+            .iter()
+            .map(|(name, kb)| (name.clone(), canonicalize_keybinding(kb)))
+            .collect(),
+        prompt_templates: raw_template_keybindings
+            .iter()
+            .map(|(name, kb)| (name.clone(), canonicalize_keybinding(kb)))
+            .collect(),
     };
 
     tokio::fs::create_dir_all(SCREENSHOT_DIR).await?;
@@ -276,7 +409,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Start the initial AI process for workspace 1
     let (initial_tx, initial_rx) = mpsc::channel(32);
     let initial_log_path = get_text_log_output_path(current_workspace);
-    tokio::spawn(manage_ai_process(initial_rx, initial_log_path.clone()));
+    tokio::spawn(manage_ai_process(
+        initial_rx,
+        initial_log_path.clone(),
+        current_workspace,
+    ));
     ai_process_senders.insert(current_workspace, initial_tx);
     clear_text_log(&initial_log_path).await?;
 
@@ -302,6 +439,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "  - Switch Workspace: {}",
         raw_keybindings.switch_to_workspace
     );
+    if let Some(kb) = &raw_keybindings.extract_code {
+        println!("  - Extract Code: {}", kb);
+    }
+    if let Some(kb) = &raw_keybindings.quiz {
+        println!("  - Quiz: {}", kb);
+    }
+    if let Some(kb) = &raw_keybindings.git_diff {
+        println!("  - Git Diff: {}", kb);
+    }
+    if let Some(kb) = &raw_keybindings.collapse {
+        println!("  - Collapse: {}", kb);
+    }
+    if let Some(kb) = &raw_keybindings.keyboard_grab {
+        println!("  - Keyboard Grab: {}", kb);
+    }
+    if let Some(kb) = &raw_keybindings.stop {
+        println!("  - Stop: {}", kb);
+    }
+    if let Some(kb) = &raw_keybindings.run_command {
+        println!("  - Run Command: {}", kb);
+    }
+    for (name, kb) in &raw_answer_formats {
+        println!("  - Solve ({}): {}", name, kb);
+    }
+    for (name, kb) in &raw_provider_keybindings {
+        println!("  - Solve (only {}): {}", name, kb);
+    }
+    for (name, kb) in &raw_template_keybindings {
+        println!("  - Solve (template {}): {}", name, kb);
+    }
     println!("\nCurrently on Workspace: {}", current_workspace);
 
     let (tx, mut rx) = mpsc::channel(1);
@@ -329,9 +496,112 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // State for workspace switching
     let mut waiting_for_workspace_number = false;
 
+    // Last screenshot per workspace, for diffing consecutive screenshots
+    let mut last_screenshots = HashMap::<u32, PathBuf>::new();
+
+    // Focused-window watcher for automatic workspace switching. The sender is
+    // kept alive here so the channel stays open when no rules are configured.
+    let (focus_tx, mut focus_rx) = mpsc::channel(8);
+    if !workspace_rules.is_empty() {
+        println!(
+            "Watching focused windows for {} workspace rule(s).",
+            workspace_rules.len()
+        );
+        focus_watcher::spawn(focus_tx.clone());
+    }
+
+    // Session limits. A pause file left over from a previous run is cleared.
+    session::update_pause_file(None).await?;
+    let mut capture_paused = false;
+    let mut session_check = tokio::time::interval(Duration::from_secs(30));
+
+    // The overlay's workspace tabs switch by writing the state file
+    let mut workspace_check = tokio::time::interval(WORKSPACE_CHECK_INTERVAL);
+
+    tokio::spawn(async {
+        let mut heartbeat = tokio::time::interval(heartbeat::INTERVAL);
+        loop {
+            heartbeat.tick().await;
+            let _ = tokio::fs::write(heartbeat::HANDLER_FILE, b"").await;
+        }
+    });
+
+    // Commands from compositor integrations (aerogel-ctl)
+    let (ctl_tx, mut ctl_rx) = mpsc::channel(32);
+    if let Err(e) = ctl::spawn_listener(ctl_tx) {
+        eprintln!("Failed to open the aerogel-ctl socket: {}", e);
+    }
+
     // Main Event Loop
-    while let Some(_) = rx.recv().await {
-        let new_content = read_new_content(log_path_str, &mut file_pos).await?;
+    loop {
+        let new_content = tokio::select! {
+            _ = session_check.tick(), if session.is_limited() => {
+                let reason = session.pause_reason(Instant::now(), Local::now().time());
+                if reason.is_some() != capture_paused {
+                    capture_paused = reason.is_some();
+                    match reason {
+                        Some(reason) => println!("\n>>> Capture paused: {}", reason),
+                        None => println!("\n>>> Capture resumed"),
+                    }
+                    session::update_pause_file(reason).await?;
+                }
+                continue;
+            }
+            _ = workspace_check.tick() => {
+                if let Some(workspace) = read_workspace_state_file().await
+                    && workspace != current_workspace
+                {
+                    println!("\n>>> Overlay selected workspace {}", workspace);
+                    switch_workspace(
+                        &workspace.to_string(),
+                        &mut current_workspace,
+                        &mut ai_process_senders,
+                    )
+                    .await?;
+                }
+                continue;
+            }
+            Some(window) = focus_rx.recv() => {
+                // Never switch away while the user is typing a prompt.
+                if !in_recording_mode
+                    && let Some(workspace) = match_workspace_rule(&workspace_rules, &window)
+                    && workspace != current_workspace
+                {
+                    println!(
+                        "\n>>> Focus: '{}' ({}) matches workspace {}",
+                        window.title, window.app_id, workspace
+                    );
+                    switch_workspace(
+                        &workspace.to_string(),
+                        &mut current_workspace,
+                        &mut ai_process_senders,
+                    )
+                    .await?;
+                }
+                continue;
+            }
+            Some(command) = ctl_rx.recv() => {
+                match ctl_command_to_key_lines(&command, &keybindings) {
+                    Some(key_lines) => key_lines.join("\n"),
+                    None => {
+                        eprintln!("Unknown aerogel-ctl command: '{}'", command);
+                        continue;
+                    }
+                }
+            }
+            event = rx.recv() => {
+                if event.is_none() {
+                    break;
+                }
+                let new_content = read_new_content(log_path_str, &mut file_pos).await?;
+                if capture_paused {
+                    // Drop keys that were logged before the listener saw the pause.
+                    continue;
+                }
+                new_content
+            }
+        };
+
         for line in new_content.lines() {
             let trimmed_line = line.trim();
             if trimmed_line.is_empty() {
@@ -365,7 +635,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             let is_modifier = matches!(
                 trimmed_line,
-                "[CtrlL]" | "[CtrlR]" | "[AltL]" | "[AltR]" | "[ShiftL]" | "[ShiftR]" | "[SuperL]"
+                "[CtrlL]"
+                    | "[CtrlR]"
+                    | "[AltL]"
+                    | "[AltR]"
+                    | "[ShiftL]"
+                    | "[ShiftR]"
+                    | "[SuperL]"
+                    | "[SuperR]"
             );
 
             if is_modifier {
@@ -403,6 +680,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                     let mut combo_matched = true;
 
+                    let format_trigger = keybindings
+                        .answer_formats
+                        .iter()
+                        .find(|(_, kb)| combo_string.eq_ignore_ascii_case(kb))
+                        .map(|(name, _)| name.clone());
+                    let target_trigger = keybindings
+                        .provider_targets
+                        .iter()
+                        .find(|(_, kb)| combo_string.eq_ignore_ascii_case(kb))
+                        .map(|(name, _)| name.clone());
+                    let template_trigger = keybindings
+                        .prompt_templates
+                        .iter()
+                        .find(|(_, kb)| combo_string.eq_ignore_ascii_case(kb))
+                        .map(|(name, _)| name.clone());
+
                     if combo_string.eq_ignore_ascii_case(&keybindings.take_screenshot) {
                         println!(
                             "\n>>> Trigger: Screenshot ({}) on ws {}",
@@ -424,6 +717,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             if let Err(e) = ai_tx.send(command).await {
                                 eprintln!("Error sending upload command to AI manager: {}", e);
                             }
+
+                            if diff_screenshots
+                                && let Some(previous) = last_screenshots.get(&current_workspace)
+                            {
+                                let diff_path = Path::new(SCREENSHOT_DIR).join(format!(
+                                    "diff-ws{}-{}.jpeg",
+                                    current_workspace, timestamp
+                                ));
+                                attach_screenshot_diff(previous, &path, &diff_path, ai_tx).await;
+                            }
+                            last_screenshots.insert(current_workspace, path);
                         } else {
                             eprintln!("Error taking screenshot. Is 'grim' installed?");
                         }
@@ -510,6 +814,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             &combo_string, current_workspace
                         );
                         clear_text_log(&current_log_path).await?;
+                        last_screenshots.remove(&current_workspace);
                         if in_recording_mode {
                             key_buffer.clear();
                             log_snapshot_before_recording.clear();
@@ -523,7 +828,128 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         if let Err(e) = ai_tx.send("/restart".to_string()).await {
                             eprintln!("Error sending restart command to AI manager: {}", e);
                         }
-                    } else if combo_string.eq_ignore_ascii_case(&keybindings.solve) {
+                    } else if keybindings
+                        .extract_code
+                        .as_ref()
+                        .is_some_and(|kb| combo_string.eq_ignore_ascii_case(kb))
+                    {
+                        println!(
+                            "\n>>> Trigger: Extract Code ({}) on ws {}",
+                            &combo_string, current_workspace
+                        );
+                        if let Err(e) = ai_tx.send("/extract".to_string()).await {
+                            eprintln!("Error sending extract command to AI manager: {}", e);
+                        }
+                    } else if keybindings
+                        .stop
+                        .as_ref()
+                        .is_some_and(|kb| combo_string.eq_ignore_ascii_case(kb))
+                    {
+                        println!(
+                            "\n>>> Trigger: Stop ({}) on ws {}",
+                            &combo_string, current_workspace
+                        );
+                        if let Err(e) = ai_tx.send("/stop".to_string()).await {
+                            eprintln!("Error sending stop command to AI manager: {}", e);
+                        }
+                    } else if keybindings
+                        .run_command
+                        .as_ref()
+                        .is_some_and(|kb| combo_string.eq_ignore_ascii_case(kb))
+                    {
+                        println!(
+                            "\n>>> Trigger: Run Command ({}) on ws {}",
+                            &combo_string, current_workspace
+                        );
+                        // The output is asked about like a solved prompt
+                        let thinking_file = ipc::thinking_file(current_workspace);
+                        if let Err(e) = tokio::fs::write(&thinking_file, "").await {
+                            eprintln!("Error writing {}: {}", thinking_file, e);
+                        }
+                        if let Err(e) = ai_tx.send("/run".to_string()).await {
+                            eprintln!("Error sending run command to AI manager: {}", e);
+                        }
+                    } else if keybindings
+                        .quiz
+                        .as_ref()
+                        .is_some_and(|kb| combo_string.eq_ignore_ascii_case(kb))
+                    {
+                        println!(
+                            "\n>>> Trigger: Quiz ({}) on ws {}",
+                            &combo_string, current_workspace
+                        );
+                        if let Err(e) = ai_tx.send("/quiz".to_string()).await {
+                            eprintln!("Error sending quiz command to AI manager: {}", e);
+                        }
+                    } else if keybindings
+                        .git_diff
+                        .as_ref()
+                        .is_some_and(|kb| combo_string.eq_ignore_ascii_case(kb))
+                    {
+                        println!(
+                            "\n>>> Trigger: Git Diff ({}) on ws {}",
+                            &combo_string, current_workspace
+                        );
+                        if let Err(e) = ai_tx.send("/diff".to_string()).await {
+                            eprintln!("Error sending diff command to AI manager: {}", e);
+                        }
+                    } else if keybindings
+                        .collapse
+                        .as_ref()
+                        .is_some_and(|kb| combo_string.eq_ignore_ascii_case(kb))
+                    {
+                        println!("\n>>> Trigger: Collapse Overlay ({})", &combo_string);
+                        // The overlay picks the change up from the file
+                        let result = if Path::new(COLLAPSE_FILE).exists() {
+                            tokio::fs::remove_file(COLLAPSE_FILE).await
+                        } else {
+                            tokio::fs::write(COLLAPSE_FILE, "").await
+                        };
+                        if let Err(e) = result {
+                            eprintln!("Error toggling {}: {}", COLLAPSE_FILE, e);
+                        }
+                    } else if keybindings
+                        .keyboard_grab
+                        .as_ref()
+                        .is_some_and(|kb| combo_string.eq_ignore_ascii_case(kb))
+                    {
+                        println!("\n>>> Trigger: Toggle Keyboard Grab ({})", &combo_string);
+                        // The overlay picks the change up from the file
+                        let result = if Path::new(KEYBOARD_GRAB_FILE).exists() {
+                            tokio::fs::remove_file(KEYBOARD_GRAB_FILE).await
+                        } else {
+                            tokio::fs::write(KEYBOARD_GRAB_FILE, "").await
+                        };
+                        if let Err(e) = result {
+                            eprintln!("Error toggling {}: {}", KEYBOARD_GRAB_FILE, e);
+                        }
+                    } else if combo_string.eq_ignore_ascii_case(&keybindings.solve)
+                        || format_trigger.is_some()
+                        || target_trigger.is_some()
+                        || template_trigger.is_some()
+                    {
+                        let thinking_file = ipc::thinking_file(current_workspace);
+                        if let Err(e) = tokio::fs::write(&thinking_file, "").await {
+                            eprintln!("Error writing {}: {}", thinking_file, e);
+                        }
+                        if let Some(format_name) = &format_trigger {
+                            println!("\n>>> Answer format: {}", format_name);
+                            if let Err(e) = ai_tx.send(format!("/format {}", format_name)).await {
+                                eprintln!("Error sending format command to AI manager: {}", e);
+                            }
+                        }
+                        if let Some(provider) = &target_trigger {
+                            println!("\n>>> Asking only: {}", provider);
+                            if let Err(e) = ai_tx.send(format!("/target {}", provider)).await {
+                                eprintln!("Error sending target command to AI manager: {}", e);
+                            }
+                        }
+                        if let Some(template) = &template_trigger {
+                            println!("\n>>> Prompt template: {}", template);
+                            if let Err(e) = ai_tx.send(format!("/template {}", template)).await {
+                                eprintln!("Error sending template command to AI manager: {}", e);
+                            }
+                        }
                         if in_recording_mode {
                             println!(
                                 "\n>>> Trigger: Stopped Recording & Processing ({}) on ws {}",
@@ -626,12 +1052,56 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 // --- Helper Functions ---
 
+// Translates an aerogel-ctl command into the key lines the listener would have
+// logged for it.
+fn ctl_command_to_key_lines(
+    command: &str,
+    keybindings: &CanonicalKeybindings,
+) -> Option<Vec<String>> {
+    let (verb, arg) = command.split_once(' ')?;
+    let arg = arg.trim();
+    match verb {
+        "key" => Some(vec![ctl::typed_key_line(arg)?]),
+        "workspace" if arg.parse::<u32>().is_ok() => {
+            let mut lines = ctl::keybinding_to_key_lines(&keybindings.switch_to_workspace);
+            lines.push(arg.to_string());
+            Some(lines)
+        }
+        "trigger" => {
+            let keybinding = match arg {
+                "show_hide" => &keybindings.show_hide,
+                "type_text" => &keybindings.type_text,
+                "take_screenshot" => &keybindings.take_screenshot,
+                "record_audio" => &keybindings.record_audio,
+                "solve" => &keybindings.solve,
+                "clear" => &keybindings.clear,
+                "extract_code" => keybindings.extract_code.as_ref()?,
+                "quiz" => keybindings.quiz.as_ref()?,
+                "git_diff" => keybindings.git_diff.as_ref()?,
+                "collapse" => keybindings.collapse.as_ref()?,
+                "keyboard_grab" => keybindings.keyboard_grab.as_ref()?,
+                "stop" => keybindings.stop.as_ref()?,
+                "run_command" => keybindings.run_command.as_ref()?,
+                format_name => {
+                    &keybindings
+                        .answer_formats
+                        .iter()
+                        .find(|(name, _)| name == format_name)?
+                        .1
+                }
+            };
+            Some(ctl::keybinding_to_key_lines(keybinding))
+        }
+        _ => None,
+    }
+}
+
 fn get_canonical_modifier(modifier: &str) -> &'static str {
     match modifier {
         "[CtrlL]" | "[CtrlR]" => "Ctrl",
         "[AltL]" | "[AltR]" => "Alt",
         "[ShiftL]" | "[ShiftR]" => "Shift",
-        "[SuperL]" => "Super",
+        "[SuperL]" | "[SuperR]" => "Super",
         _ => "",
     }
 }
@@ -721,6 +1191,50 @@ async fn detect_audio_backend() -> (&'static str, &'static str) {
     }
 }
 
+// Diffs two screenshots and sends the composite plus a note about the changed
+// regions to the AI manager.
+async fn attach_screenshot_diff(
+    before: &Path,
+    after: &Path,
+    diff_path: &Path,
+    ai_tx: &mpsc::Sender<String>,
+) {
+    let (before, after, out) = (
+        before.to_path_buf(),
+        after.to_path_buf(),
+        diff_path.to_path_buf(),
+    );
+    let result =
+        tokio::task::spawn_blocking(move || screen_diff::diff_screenshots(&before, &after, &out))
+            .await;
+
+    let note = match result {
+        Ok(Ok(Some(note))) => note,
+        Ok(Ok(None)) => {
+            println!("Screenshots have different sizes, skipping the diff.");
+            return;
+        }
+        Ok(Err(e)) => {
+            eprintln!("Error diffing screenshots: {}", e);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Screenshot diff task failed: {}", e);
+            return;
+        }
+    };
+
+    if diff_path.exists() {
+        println!("Screenshot diff saved to '{}'", diff_path.display());
+        if let Err(e) = ai_tx.send(format!("/upload {}", diff_path.display())).await {
+            eprintln!("Error sending diff upload command to AI manager: {}", e);
+        }
+    }
+    if let Err(e) = ai_tx.send(note).await {
+        eprintln!("Error sending diff note to AI manager: {}", e);
+    }
+}
+
 async fn start_audio_recording(path: &Path) -> io::Result<tokio::process::Child> {
     let (audio_backend, audio_device) = detect_audio_backend().await;
 
@@ -1,22 +1,78 @@
+mod heartbeat;
 mod os;
+// Only PAUSE_FILE is used here; the limits are the event handler's
+#[allow(dead_code)]
+mod session;
 use clap::Parser;
 use std::fs;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    #[clap(short, long)]
+    #[clap(short, long, default_value = ".event")]
     logfile: String,
-    #[clap(short, long)]
-    timeout: u64,
+    // Batch key presses for up to this many milliseconds before writing them
+    #[clap(long, default_value_t = 0)]
+    flush_ms: u64,
+
+    // Write pending keys as soon as a key is pressed with Ctrl, Alt or Super
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    flush_on_modifier: bool,
 
     #[clap(short, long, default_value_t = 1)]
     count: u8,
+
+    // Exit after this many hours without a logged key press
+    #[clap(long)]
+    idle_stop_hours: Option<f64>,
+}
+
+fn spawn_heartbeat() {
+    std::thread::spawn(|| {
+        loop {
+            let _ = fs::write(heartbeat::LISTENER_FILE, b"");
+            std::thread::sleep(heartbeat::INTERVAL);
+        }
+    });
+}
+
+// Exits the process once the key log has not been written to for `idle`.
+// Paused capture does not write to the log, so it counts as idle too.
+fn spawn_idle_watchdog(logfile: String, idle: Duration) {
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(Duration::from_secs(60));
+            let idle_for = fs::metadata(&logfile)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            if idle_for.is_some_and(|idle_for| idle_for >= idle) {
+                println!("No key presses for {:?}, stopping the listener.", idle);
+                std::process::exit(0);
+            }
+        }
+    });
 }
 
 fn main() {
-    if let Err(e) = fs::File::create(".event") {
-        eprintln!("Warning: Failed to create or clear .event file: {}", e);
+    let args = Args::parse();
+    if let Err(e) = fs::File::create(&args.logfile) {
+        eprintln!(
+            "Warning: Failed to create or clear {} file: {}",
+            args.logfile, e
+        );
+    }
+    if let Some(hours) = args.idle_stop_hours.filter(|hours| *hours > 0.0) {
+        spawn_idle_watchdog(
+            args.logfile.clone(),
+            Duration::from_secs_f64(hours * 3600.0),
+        );
     }
-    let _res = os::start_eventlistener(".event".to_owned(), 0);
+    spawn_heartbeat();
+    let _res = os::start_eventlistener(
+        args.logfile,
+        Duration::from_millis(args.flush_ms),
+        args.flush_on_modifier,
+    );
 }
@@ -0,0 +1,231 @@
+// Watches the focused window through the wlr foreign-toplevel protocol and
+// maps it to a workspace with user-defined rules, so switching between e.g. an
+// IDE and a browser also switches the AI context.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle,
+    backend::ObjectId,
+    event_created_child,
+    protocol::wl_registry::{self, WlRegistry},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+// A rule switches to `workspace` when the focused window matches every
+// pattern it sets. Patterns are case-insensitive substrings.
+#[derive(Debug, Deserialize, Clone)]
+pub struct WorkspaceRule {
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub workspace: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FocusedWindow {
+    pub app_id: String,
+    pub title: String,
+}
+
+fn pattern_matches(pattern: &Option<String>, value: &str) -> bool {
+    match pattern {
+        Some(pattern) => value.to_lowercase().contains(&pattern.to_lowercase()),
+        None => true,
+    }
+}
+
+// Returns the workspace of the first rule matching the window.
+pub fn match_workspace_rule(rules: &[WorkspaceRule], window: &FocusedWindow) -> Option<u32> {
+    rules
+        .iter()
+        .filter(|rule| rule.app_id.is_some() || rule.title.is_some())
+        .find(|rule| {
+            pattern_matches(&rule.app_id, &window.app_id)
+                && pattern_matches(&rule.title, &window.title)
+        })
+        .map(|rule| rule.workspace)
+}
+
+#[derive(Default)]
+struct ToplevelInfo {
+    app_id: String,
+    title: String,
+    activated: bool,
+}
+
+struct FocusState {
+    manager: Option<ZwlrForeignToplevelManagerV1>,
+    toplevels: HashMap<ObjectId, ToplevelInfo>,
+    last_sent: Option<FocusedWindow>,
+    tx: mpsc::Sender<FocusedWindow>,
+}
+
+impl Dispatch<WlRegistry, ()> for FocusState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+            && interface == "zwlr_foreign_toplevel_manager_v1"
+        {
+            state.manager = Some(registry.bind(name, version.min(3), qh, ()));
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for FocusState {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            state
+                .toplevels
+                .insert(toplevel.id(), ToplevelInfo::default());
+        }
+    }
+
+    event_created_child!(FocusState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for FocusState {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::Event;
+
+        let id = handle.id();
+        match event {
+            Event::Title { title } => {
+                state.toplevels.entry(id).or_default().title = title;
+            }
+            Event::AppId { app_id } => {
+                state.toplevels.entry(id).or_default().app_id = app_id;
+            }
+            Event::State { state: raw_states } => {
+                // The state array is a list of native-endian u32 enum values.
+                let activated = raw_states
+                    .chunks_exact(4)
+                    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                    .any(|s| s == zwlr_foreign_toplevel_handle_v1::State::Activated as u32);
+                state.toplevels.entry(id).or_default().activated = activated;
+            }
+            Event::Done => {
+                if let Some(info) = state.toplevels.get(&id)
+                    && info.activated
+                {
+                    let window = FocusedWindow {
+                        app_id: info.app_id.clone(),
+                        title: info.title.clone(),
+                    };
+                    if state.last_sent.as_ref() != Some(&window) {
+                        state.last_sent = Some(window.clone());
+                        let _ = state.tx.blocking_send(window);
+                    }
+                }
+            }
+            Event::Closed => {
+                state.toplevels.remove(&id);
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn run(tx: mpsc::Sender<FocusedWindow>) -> Result<(), Box<dyn std::error::Error>> {
+    let conn = Connection::connect_to_env()?;
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+    let _registry = conn.display().get_registry(&qh, ());
+
+    let mut state = FocusState {
+        manager: None,
+        toplevels: HashMap::new(),
+        last_sent: None,
+        tx,
+    };
+    event_queue.roundtrip(&mut state)?;
+
+    if state.manager.is_none() {
+        return Err(
+            "compositor does not support zwlr_foreign_toplevel_manager_v1; workspace rules are disabled"
+                .into(),
+        );
+    }
+
+    loop {
+        event_queue.blocking_dispatch(&mut state)?;
+    }
+}
+
+// Starts the watcher on its own thread. Focus changes are sent on `tx`.
+pub fn spawn(tx: mpsc::Sender<FocusedWindow>) {
+    std::thread::spawn(move || {
+        if let Err(e) = run(tx) {
+            eprintln!("[focus_watcher] {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(app_id: Option<&str>, title: Option<&str>, workspace: u32) -> WorkspaceRule {
+        WorkspaceRule {
+            app_id: app_id.map(str::to_string),
+            title: title.map(str::to_string),
+            workspace,
+        }
+    }
+
+    #[test]
+    fn test_match_workspace_rule() {
+        let rules = vec![
+            rule(Some("firefox"), Some("leetcode"), 2),
+            rule(Some("code"), None, 1),
+            rule(None, None, 9),
+        ];
+        let window = |app_id: &str, title: &str| FocusedWindow {
+            app_id: app_id.to_string(),
+            title: title.to_string(),
+        };
+
+        assert_eq!(
+            match_workspace_rule(&rules, &window("firefox", "Two Sum - LeetCode")),
+            Some(2)
+        );
+        assert_eq!(
+            match_workspace_rule(&rules, &window("firefox", "Docs")),
+            None
+        );
+        assert_eq!(
+            match_workspace_rule(&rules, &window("Code", "main.rs")),
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,446 @@
+// Font discovery through fontconfig, with automatic per-script fallbacks, and
+// text shaping through rustybuzz.
+//
+// The primary font is resolved from a family name (via `fc-match`) or a file
+// path. Characters the primary font does not cover are looked up lazily with
+// `fc-match :charset=<codepoint>`, so CJK, Arabic, Devanagari etc. render with
+// whatever font the system has for them instead of as empty boxes.
+//
+// Runs of text that use the same font are shaped as a whole, so ligatures,
+// kerning and complex scripts come out right. rusttype only rasterizes the
+// glyph ids the shaper picked, and each glyph is rasterized once per size and
+// subpixel offset: redraws reuse the cached coverage.
+
+use rusttype::{Font, GlyphId, Point, Scale, VMetrics, point};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::process::Command;
+
+// Subpixel offsets a glyph is rasterized at, per axis
+const SUBPIXEL_STEPS: f32 = 4.0;
+// Rasterized glyphs kept before the cache starts over, e.g. after zooming
+const GLYPH_CACHE_LIMIT: usize = 4096;
+
+struct LoadedFont {
+    font: Font<'static>,
+    face: rustybuzz::Face<'static>,
+    // Pixels per font unit at a scale of 1.0
+    units_to_px: f32,
+}
+
+pub struct FontSet {
+    // Font size in pixels, changed at runtime by zooming
+    size: f32,
+    primary: LoadedFont,
+    fallbacks: RefCell<Vec<(String, LoadedFont)>>,
+    // Maps a character to the index of the fallback font covering it, or None
+    // when no installed font covers it.
+    char_fallback: RefCell<HashMap<char, Option<usize>>>,
+    glyph_cache: RefCell<HashMap<GlyphKey, RasterGlyph>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    slot: usize,
+    id: GlyphId,
+    // Scale as f32 bits
+    scale: (u32, u32),
+    // Offset from the whole pixel, in 1/SUBPIXEL_STEPS of a pixel
+    offset: (u8, u8),
+}
+
+// A glyph's coverage, relative to the whole pixel its origin falls in.
+struct RasterGlyph {
+    min_x: i32,
+    min_y: i32,
+    width: usize,
+    coverage: Vec<f32>,
+}
+
+// Splits a coordinate into a whole pixel and a subpixel step.
+fn split_subpixel(v: f32) -> (i32, u8) {
+    let pixel = v.floor();
+    let step = ((v - pixel) * SUBPIXEL_STEPS).round();
+    if step >= SUBPIXEL_STEPS {
+        (pixel as i32 + 1, 0)
+    } else {
+        (pixel as i32, step as u8)
+    }
+}
+
+// A shaped glyph, with its pen position relative to the start of the line.
+struct ShapedGlyph {
+    slot: usize,
+    id: GlyphId,
+    x: f32,
+    y: f32,
+}
+
+// Asks fontconfig for the file that best matches a pattern.
+fn fc_match(pattern: &str) -> Option<String> {
+    let output = Command::new("fc-match")
+        .arg("--format=%{file}")
+        .arg(pattern)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if path.is_empty() { None } else { Some(path) }
+}
+
+// Generic families fontconfig resolves to whatever font is configured for
+// them, so any match is the one asked for
+const GENERIC_FAMILIES: &[&str] = &["monospace", "mono", "sans-serif", "sans", "serif"];
+
+// Whether fontconfig's match, with its comma-separated family names, is the
+// family asked for rather than a substitute. Options after `:` in the
+// pattern, such as a style, are not compared.
+fn family_matches(pattern: &str, matched: &str) -> bool {
+    let normalize = |name: &str| name.trim().replace(' ', "").to_lowercase();
+    let family = normalize(pattern.split(':').next().unwrap_or(pattern));
+    GENERIC_FAMILIES.contains(&family.as_str())
+        || matched.split(',').any(|name| normalize(name) == family)
+}
+
+// The file of a font family, None when fontconfig only has a substitute.
+fn fc_match_family(family: &str) -> Option<String> {
+    let output = Command::new("fc-match")
+        .arg("--format=%{family}\n%{file}")
+        .arg(family)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    let (matched, file) = output.split_once('\n')?;
+    let file = file.trim();
+    (family_matches(family, matched) && !file.is_empty()).then(|| file.to_string())
+}
+
+fn load_font_file(path: &str) -> Option<LoadedFont> {
+    let data = std::fs::read(path).ok()?;
+    // Fonts live for the whole lifetime of the overlay, and the shaper and
+    // rasterizer both need to borrow the same bytes.
+    let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+    let font = Font::try_from_bytes(data)?;
+    let face = rustybuzz::Face::from_slice(data, 0)?;
+    let unscaled = font.v_metrics_unscaled();
+    let units_to_px = 1.0 / (unscaled.ascent - unscaled.descent);
+    Some(LoadedFont {
+        font,
+        face,
+        units_to_px,
+    })
+}
+
+// True when the first strongly directional character is right-to-left.
+fn is_rtl_text(text: &str) -> bool {
+    use unicode_bidi::BidiClass;
+
+    text.chars()
+        .map(unicode_bidi::bidi_class)
+        .find(|class| matches!(class, BidiClass::L | BidiClass::R | BidiClass::AL))
+        .is_some_and(|class| class != BidiClass::L)
+}
+
+fn covers(font: &Font, c: char) -> bool {
+    c.is_control() || font.glyph(c).id() != GlyphId(0)
+}
+
+impl FontSet {
+    // Resolves the primary font: the configured family first, then the
+    // configured path, then fontconfig's default monospace and sans fonts.
+    pub fn load(family: Option<&str>, path: Option<&str>, size: f32) -> Self {
+        let from_family = family.and_then(|family| {
+            let font = fc_match_family(family).and_then(|file| load_font_file(&file));
+            if font.is_none() {
+                eprintln!(
+                    "Warning: Font family '{}' is not installed; using the configured path.",
+                    family
+                );
+            }
+            font
+        });
+
+        let from_path = || {
+            path.and_then(|path| {
+                let font = load_font_file(path);
+                if font.is_none() {
+                    eprintln!("Warning: Could not load font from config path: '{}'.", path);
+                }
+                font
+            })
+        };
+
+        let primary = from_family
+            .or_else(from_path)
+            .or_else(|| {
+                eprintln!("Warning: Falling back to the system default font.");
+                ["monospace", "sans-serif"]
+                    .iter()
+                    .find_map(|pattern| fc_match(pattern).and_then(|file| load_font_file(&file)))
+            })
+            .expect("No usable font found. Is fontconfig (fc-match) installed?");
+
+        Self {
+            size,
+            primary,
+            fallbacks: RefCell::new(Vec::new()),
+            char_fallback: RefCell::new(HashMap::new()),
+            glyph_cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: f32) {
+        self.size = size;
+    }
+
+    pub fn v_metrics(&self, scale: Scale) -> VMetrics {
+        self.primary.font.v_metrics(scale)
+    }
+
+    // Returns the index of the fallback font for `c`, resolving it through
+    // fontconfig the first time the character is seen.
+    fn fallback_for(&self, c: char) -> Option<usize> {
+        if let Some(cached) = self.char_fallback.borrow().get(&c) {
+            return *cached;
+        }
+
+        let mut fallbacks = self.fallbacks.borrow_mut();
+        let index = fallbacks
+            .iter()
+            .position(|(_, loaded)| covers(&loaded.font, c))
+            .or_else(|| {
+                let file = fc_match(&format!(":charset={:x}", c as u32))?;
+                if fallbacks.iter().any(|(path, _)| *path == file) {
+                    // Already loaded and checked above: fontconfig's best
+                    // match doesn't actually cover this character.
+                    return None;
+                }
+                let loaded = load_font_file(&file)?;
+                if !covers(&loaded.font, c) {
+                    return None;
+                }
+                println!(
+                    "[overlay] Using fallback font '{}' for U+{:04X}",
+                    file, c as u32
+                );
+                fallbacks.push((file, loaded));
+                Some(fallbacks.len() - 1)
+            });
+
+        self.char_fallback.borrow_mut().insert(c, index);
+        index
+    }
+
+    // Returns the font slot used for `c`: 0 is the primary font, n is
+    // fallback n - 1.
+    fn slot_for(&self, c: char) -> usize {
+        if covers(&self.primary.font, c) {
+            return 0;
+        }
+        self.fallback_for(c).map_or(0, |index| index + 1)
+    }
+
+    // Splits text into runs of characters sharing a font slot, shapes each run
+    // and returns the glyphs with their pen positions plus the total advance.
+    fn shape(&self, text: &str, scale: Scale) -> (Vec<ShapedGlyph>, f32) {
+        let mut runs: Vec<(usize, &str)> = Vec::new();
+        let mut run_start = 0;
+        let mut run_slot = None;
+        for (i, c) in text.char_indices() {
+            let slot = self.slot_for(c);
+            if let Some(current) = run_slot
+                && current != slot
+            {
+                runs.push((current, &text[run_start..i]));
+                run_start = i;
+            }
+            run_slot = Some(slot);
+        }
+        if let Some(slot) = run_slot {
+            runs.push((slot, &text[run_start..]));
+        }
+
+        // Text handed to the shaper is one bidi run. When it is right-to-left
+        // the font runs are laid out right to left as well.
+        let rtl = is_rtl_text(text);
+        if rtl {
+            runs.reverse();
+        }
+
+        let fallbacks = self.fallbacks.borrow();
+        let mut glyphs = Vec::with_capacity(text.len());
+        let mut caret = 0.0;
+
+        for (slot, run) in runs {
+            let loaded = if slot == 0 {
+                &self.primary
+            } else {
+                &fallbacks[slot - 1].1
+            };
+            let px_x = loaded.units_to_px * scale.x;
+            let px_y = loaded.units_to_px * scale.y;
+
+            let mut buffer = rustybuzz::UnicodeBuffer::new();
+            buffer.push_str(run);
+            buffer.guess_segment_properties();
+            if rtl {
+                buffer.set_direction(rustybuzz::Direction::RightToLeft);
+            }
+            let shaped = rustybuzz::shape(&loaded.face, &[], buffer);
+
+            for (info, pos) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                glyphs.push(ShapedGlyph {
+                    slot,
+                    id: GlyphId(info.glyph_id as u16),
+                    x: caret + pos.x_offset as f32 * px_x,
+                    y: -(pos.y_offset as f32) * px_y,
+                });
+                caret += pos.x_advance as f32 * px_x;
+            }
+        }
+
+        (glyphs, caret)
+    }
+
+    // Draws a single line of text starting at the given baseline origin,
+    // calling `plot` with the coverage of every pixel a glyph touches.
+    pub fn draw(
+        &self,
+        text: &str,
+        scale: Scale,
+        start: Point<f32>,
+        mut plot: impl FnMut(i32, i32, f32),
+    ) {
+        let (shaped, _) = self.shape(text, scale);
+        let mut cache = self.glyph_cache.borrow_mut();
+        if cache.len() > GLYPH_CACHE_LIMIT {
+            cache.clear();
+        }
+
+        for glyph in shaped {
+            let (x, x_step) = split_subpixel(start.x + glyph.x);
+            let (y, y_step) = split_subpixel(start.y + glyph.y);
+            let key = GlyphKey {
+                slot: glyph.slot,
+                id: glyph.id,
+                scale: (scale.x.to_bits(), scale.y.to_bits()),
+                offset: (x_step, y_step),
+            };
+            let raster = cache.entry(key).or_insert_with(|| self.rasterize(key));
+            for (i, &v) in raster.coverage.iter().enumerate() {
+                plot(
+                    x + raster.min_x + (i % raster.width) as i32,
+                    y + raster.min_y + (i / raster.width) as i32,
+                    v,
+                );
+            }
+        }
+    }
+
+    fn rasterize(&self, key: GlyphKey) -> RasterGlyph {
+        let fallbacks = self.fallbacks.borrow();
+        let font = if key.slot == 0 {
+            &self.primary.font
+        } else {
+            &fallbacks[key.slot - 1].1.font
+        };
+        let scale = Scale {
+            x: f32::from_bits(key.scale.0),
+            y: f32::from_bits(key.scale.1),
+        };
+        let offset = point(
+            key.offset.0 as f32 / SUBPIXEL_STEPS,
+            key.offset.1 as f32 / SUBPIXEL_STEPS,
+        );
+        let glyph = font.glyph(key.id).scaled(scale).positioned(offset);
+        let Some(bounding_box) = glyph.pixel_bounding_box() else {
+            return RasterGlyph {
+                min_x: 0,
+                min_y: 0,
+                width: 0,
+                coverage: Vec::new(),
+            };
+        };
+        let width = bounding_box.width() as usize;
+        let mut coverage = vec![0.0; width * bounding_box.height() as usize];
+        glyph.draw(|x, y, v| coverage[y as usize * width + x as usize] = v);
+        RasterGlyph {
+            min_x: bounding_box.min.x,
+            min_y: bounding_box.min.y,
+            width,
+            coverage,
+        }
+    }
+
+    // Width of the shaped text in pixels.
+    pub fn text_width(&self, text: &str, scale: Scale) -> f32 {
+        self.shape(text, scale).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLED_FONT: &str = "fonts/JetBrainsMono-Regular.ttf";
+
+    #[test]
+    fn test_family_matches() {
+        assert!(family_matches(
+            "JetBrains Mono",
+            "JetBrains Mono,JetBrains Mono NL"
+        ));
+        assert!(family_matches("jetbrainsmono:style=Bold", "JetBrains Mono"));
+        assert!(family_matches("monospace", "DejaVu Sans Mono"));
+        // A misspelled family gets fontconfig's default font
+        assert!(!family_matches("JetBrians Mono", "DejaVu Sans"));
+    }
+
+    #[test]
+    fn test_shaped_width_matches_monospace_advance() {
+        let fonts = FontSet::load(None, Some(BUNDLED_FONT), 20.0);
+        let scale = Scale::uniform(20.0);
+        let advance = fonts
+            .primary
+            .font
+            .glyph('a')
+            .scaled(scale)
+            .h_metrics()
+            .advance_width;
+
+        let width = fonts.text_width("hello", scale);
+        assert!((width - advance * 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_draw_reuses_rasterized_glyphs() {
+        let fonts = FontSet::load(None, Some(BUNDLED_FONT), 20.0);
+        let scale = Scale::uniform(20.0);
+        let mut first = Vec::new();
+        fonts.draw("aa", scale, point(0.0, 20.0), |x, y, v| {
+            first.push((x, y, v))
+        });
+        assert_eq!(fonts.glyph_cache.borrow().len(), 1);
+
+        // The second 'a' starts a whole number of pixels to the right
+        let mut moved = Vec::new();
+        fonts.draw("a", scale, point(100.0, 20.0), |x, y, v| {
+            moved.push((x - 100, y, v))
+        });
+        assert_eq!(fonts.glyph_cache.borrow().len(), 1);
+        assert_eq!(moved, first[..moved.len()]);
+
+        assert_eq!(split_subpixel(2.1), (2, 0));
+        assert_eq!(split_subpixel(2.5), (2, 2));
+        assert_eq!(split_subpixel(2.9), (3, 0));
+    }
+}
@@ -0,0 +1,104 @@
+// Git changes as prompt context.
+//
+// `/diff` runs git in the repository set with `[git] repo` (the current
+// directory when unset) and attaches the output to the next prompt:
+//
+//   /diff            staged and unstaged changes (`git diff HEAD`)
+//   /diff staged     staged changes only (`git diff --cached`)
+//   /diff <rev>      a commit (`git show <rev>`)
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+// Larger diffs are cut, so a big refactor doesn't overflow the context window
+const MAX_DIFF_CHARS: usize = 60_000;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GitConfig {
+    pub repo: Option<String>,
+}
+
+impl GitConfig {
+    fn repo_dir(&self) -> PathBuf {
+        match self.repo.as_deref() {
+            Some(repo) => match (repo.strip_prefix("~/"), dirs::home_dir()) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(repo),
+            },
+            None => PathBuf::from("."),
+        }
+    }
+}
+
+fn git_args(spec: &str) -> Vec<&str> {
+    match spec {
+        "" => vec!["diff", "HEAD"],
+        "staged" | "cached" => vec!["diff", "--cached"],
+        rev => vec!["show", rev],
+    }
+}
+
+fn truncate(mut diff: String) -> String {
+    if diff.len() > MAX_DIFF_CHARS {
+        let mut end = MAX_DIFF_CHARS;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        diff.truncate(end);
+        diff.push_str("\n[diff truncated]\n");
+    }
+    diff
+}
+
+// Runs git for `spec` and returns its output, or an error with git's message.
+pub async fn diff(config: &GitConfig, spec: &str) -> Result<String> {
+    let repo = config.repo_dir();
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(&repo)
+        .args(["--no-pager", "-c", "color.ui=never"])
+        .args(git_args(spec.trim()))
+        .output()
+        .await
+        .map_err(|e| anyhow!("Could not run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git failed in {}: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(truncate(
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+// Number of files a diff touches, for the note shown in the overlay.
+pub fn changed_files(diff: &str) -> usize {
+    diff.lines()
+        .filter(|line| line.starts_with("diff --git "))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_args() {
+        assert_eq!(git_args(""), vec!["diff", "HEAD"]);
+        assert_eq!(git_args("staged"), vec!["diff", "--cached"]);
+        assert_eq!(git_args("HEAD~1"), vec!["show", "HEAD~1"]);
+    }
+
+    #[test]
+    fn test_truncate_and_count() {
+        let diff = "diff --git a/x b/x\n+1\ndiff --git a/y b/y\n-2\n".to_string();
+        assert_eq!(changed_files(&diff), 2);
+        assert_eq!(truncate(diff.clone()), diff);
+        let long = "é".repeat(MAX_DIFF_CHARS);
+        assert!(truncate(long).ends_with("[diff truncated]\n"));
+    }
+}
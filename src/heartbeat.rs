@@ -0,0 +1,15 @@
+// Heartbeat files: the listener, the event handler and each workspace's AI
+// process touch their own every INTERVAL, and the overlay shows a component
+// whose file is older than TIMEOUT as dead. Every binary uses only its own
+// names from here.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+pub const INTERVAL: Duration = Duration::from_secs(2);
+pub const TIMEOUT: Duration = Duration::from_secs(6);
+
+pub const LISTENER_FILE: &str = ".aerogel_heartbeat_listener";
+pub const HANDLER_FILE: &str = ".aerogel_heartbeat_handler";
+// Followed by the AI process's workspace number
+pub const AI_FILE_PREFIX: &str = ".aerogel_heartbeat_ai";
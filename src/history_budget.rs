@@ -0,0 +1,201 @@
+// Keeps long conversations inside a model's context window.
+//
+// With `context_tokens` set in a provider's section, the oldest turns of its
+// history are left out of a request until the system prompt, the rest of
+// the history and the new prompt fit the budget. The history itself is kept
+// whole. Tokens are counted like the status line does, for the provider's
+// model, with a flat amount per attached image. `/history` shows the newest turns and which of them
+// the next request leaves out.
+
+use crate::ai_client::Message;
+use crate::tokens::count_tokens;
+use serde_json::Value;
+
+// Roughly what an image costs with the larger providers
+const IMAGE_TOKENS: u64 = 1000;
+
+// Characters of each message `/history` shows
+const PREVIEW_CHARS: usize = 200;
+
+pub fn content_tokens(model: &str, content: &Value) -> u64 {
+    match content {
+        Value::String(text) => count_tokens(model, text),
+        Value::Array(parts) => parts
+            .iter()
+            .map(|part| match part["text"].as_str() {
+                Some(text) => count_tokens(model, text),
+                None => IMAGE_TOKENS,
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+// The newest turns of `history` that fit in `budget` tokens, besides the
+// `reserved` ones of the system prompt and the new prompt. A budget of 0
+// keeps everything.
+pub fn fit_history<'a>(
+    model: &str,
+    history: &'a [Message],
+    budget: u64,
+    reserved: u64,
+) -> &'a [Message] {
+    if budget == 0 {
+        return history;
+    }
+    let mut left = budget.saturating_sub(reserved);
+    let mut start = history.len();
+    // Whole turns are taken, newest first, so a kept answer always has its
+    // question
+    for (i, message) in history.iter().enumerate().rev() {
+        let tokens = content_tokens(model, &message.content);
+        if tokens > left {
+            break;
+        }
+        left -= tokens;
+        if message.role == "user" {
+            start = i;
+        }
+    }
+    &history[start..]
+}
+
+// A message's text on one line, shortened for `/history`.
+fn preview(content: &Value) -> String {
+    let text = match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(|part| part["text"].as_str().unwrap_or("[image]"))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+// The last `turns` turns of a provider's history for `/history`, marking
+// those left out of the next request to fit `budget`.
+pub fn describe(
+    provider: &str,
+    model: &str,
+    history: &[Message],
+    turns: usize,
+    budget: u64,
+) -> String {
+    let sent = fit_history(model, history, budget, 0).len();
+    let first_sent = history.len() - sent;
+    let user_turns: Vec<usize> = history
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.role == "user")
+        .map(|(i, _)| i)
+        .collect();
+    let start = user_turns
+        .len()
+        .checked_sub(turns)
+        .map_or(0, |skip| user_turns.get(skip).copied().unwrap_or(0));
+    let mut heading = format!("**{}**, {} turn(s)", provider, user_turns.len());
+    if first_sent > 0 {
+        let left_out = user_turns.iter().filter(|&&i| i < first_sent).count();
+        heading.push_str(&format!(
+            ", the oldest {} left out of the next request to fit `context_tokens`",
+            left_out
+        ));
+    }
+    let lines: Vec<String> = history[start..]
+        .iter()
+        .enumerate()
+        .map(|(i, message)| {
+            let speaker = if message.role == "user" {
+                "You"
+            } else {
+                provider
+            };
+            let left_out = if start + i < first_sent {
+                " (left out)"
+            } else {
+                ""
+            };
+            format!("- {}{}: {}", speaker, left_out, preview(&message.content))
+        })
+        .collect();
+    format!("{}\n\n{}", heading, lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Estimated, so the counts below are the same whatever tiktoken does
+    const MODEL: &str = "claude-sonnet-4";
+
+    fn message(role: &str, content: Value) -> Message {
+        Message {
+            role: role.to_string(),
+            content,
+        }
+    }
+
+    #[test]
+    fn test_fit_history() {
+        let history = [
+            message("user", json!("a".repeat(400))),
+            message("assistant", json!("b".repeat(400))),
+            message("user", json!([{ "type": "text", "text": "c".repeat(40) }])),
+            message("assistant", json!("d".repeat(40))),
+        ];
+        assert_eq!(content_tokens(MODEL, &history[0].content), 100);
+        assert_eq!(
+            content_tokens(MODEL, &json!([{ "type": "image_url" }, { "text": "abcd" }])),
+            IMAGE_TOKENS + 1
+        );
+
+        assert_eq!(fit_history(MODEL, &history, 0, 50).len(), 4);
+        assert_eq!(fit_history(MODEL, &history, 220, 0).len(), 4);
+        // The older turn does not fit next to the new prompt
+        assert_eq!(fit_history(MODEL, &history, 220, 10).len(), 2);
+        // Half a turn is dropped as a whole
+        assert_eq!(fit_history(MODEL, &history, 15, 0).len(), 0);
+    }
+
+    #[test]
+    fn test_describe() {
+        let history = [
+            message("user", json!("What is\n6 * 7?")),
+            message("assistant", json!("42")),
+            message(
+                "user",
+                json!([{ "type": "image_url" }, { "text": "And this?" }]),
+            ),
+            message("assistant", json!("x".repeat(250))),
+        ];
+        assert_eq!(
+            describe("Claude", MODEL, &history, 5, 0),
+            format!(
+                "**Claude**, 2 turn(s)\n\n- You: What is 6 * 7?\n- Claude: 42\n\
+                 - You: [image] And this?\n- Claude: {}…",
+                "x".repeat(200)
+            )
+        );
+        // Only the newer turn fits
+        let budget =
+            content_tokens(MODEL, &history[2].content) + content_tokens(MODEL, &history[3].content);
+        assert_eq!(
+            describe("Claude", MODEL, &history, 1, budget),
+            format!(
+                "**Claude**, 2 turn(s), the oldest 1 left out of the next request to fit \
+                 `context_tokens`\n\n- You: [image] And this?\n- Claude: {}…",
+                "x".repeat(200)
+            )
+        );
+        assert!(
+            describe("Claude", MODEL, &history, 2, budget).contains("- You (left out): What is")
+        );
+    }
+}
@@ -0,0 +1,132 @@
+// The HTTP client every request of the AI process goes through.
+//
+// One client is kept for the whole process, so connections to each
+// provider's host stay open between prompts and a Solve does not wait for a
+// new TCP and TLS handshake. HTTPS connections use HTTP/2 where the API
+// offers it, so the answers of one round stream over a single connection.
+// Idle connections are kept for `[http] pool_idle_timeout` seconds, and
+// TCP keep-alive and HTTP/2 pings every `keep_alive` seconds stop routers
+// from dropping them silently. With `preconnect`, a connection to each
+// provider is opened when the AI process starts, before the first prompt.
+// Responses may come gzip or brotli compressed, and are decompressed as they
+// stream in.
+
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpConfig {
+    // Seconds an unused connection is kept open
+    #[serde(default = "default_pool_idle_timeout")]
+    pub pool_idle_timeout: u64,
+    // Unused connections kept per host
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    // Seconds between keep-alive probes on open connections; 0 sends none
+    #[serde(default = "default_keep_alive")]
+    pub keep_alive: u64,
+    #[serde(default = "default_preconnect")]
+    pub preconnect: bool,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            pool_idle_timeout: default_pool_idle_timeout(),
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            keep_alive: default_keep_alive(),
+            preconnect: default_preconnect(),
+        }
+    }
+}
+
+fn default_pool_idle_timeout() -> u64 {
+    300
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    8
+}
+
+fn default_keep_alive() -> u64 {
+    30
+}
+
+fn default_preconnect() -> bool {
+    true
+}
+
+pub fn client(config: &HttpConfig) -> Client {
+    let mut builder = Client::builder()
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout))
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .http2_adaptive_window(true)
+        .gzip(true)
+        .brotli(true);
+    if config.keep_alive > 0 {
+        let interval = Duration::from_secs(config.keep_alive);
+        builder = builder
+            .tcp_keepalive(interval)
+            .http2_keep_alive_interval(interval)
+            .http2_keep_alive_while_idle(true);
+    }
+    builder.build().unwrap_or_else(|e| {
+        eprintln!(
+            "[ERROR] Failed to set up the HTTP client, using the defaults: {}",
+            e
+        );
+        Client::new()
+    })
+}
+
+// The scheme, host and port of each URL, once each.
+fn origins<'a>(urls: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut origins: Vec<String> = Vec::new();
+    for url in urls {
+        let Ok(url) = Url::parse(url) else { continue };
+        let origin = url.origin();
+        if !origin.is_tuple() {
+            continue;
+        }
+        let origin = origin.ascii_serialization();
+        if !origins.contains(&origin) {
+            origins.push(origin);
+        }
+    }
+    origins
+}
+
+// Opens a connection to the host of each URL in the background, so the
+// first prompt finds them open. What the hosts answer does not matter.
+pub fn preconnect<'a>(client: &Client, urls: impl IntoIterator<Item = &'a str>) {
+    for origin in origins(urls) {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let _ = client.head(&origin).send().await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origins() {
+        assert_eq!(
+            origins([
+                "https://api.openai.com/v1/chat/completions",
+                "https://api.openai.com/v1/audio/transcriptions",
+                "http://localhost:11434/api/chat",
+                "https://generativelanguage.googleapis.com/v1beta/models/{model}:streamGenerateContent",
+                "not a url",
+            ]),
+            [
+                "https://api.openai.com",
+                "http://localhost:11434",
+                "https://generativelanguage.googleapis.com"
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+// Image generation for the `/imagine <prompt>` command.
+//
+// The image is saved under screenshots/ next to the captured screenshots,
+// and a markdown image pointing at it goes into the workspace log, which the
+// overlay draws inline. The backend is chosen in `[imagine]`:
+//
+//   openai     OpenAI's Images API, with the OpenAI key
+//   stability  Stability AI's Stable Image API, with the key in
+//              STABILITY_API_KEY
+
+use anyhow::{Result, anyhow};
+use base64::{Engine as _, engine::general_purpose};
+use reqwest::{Client, multipart};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const OPENAI_URL: &str = "https://api.openai.com/v1/images/generations";
+const STABILITY_URL: &str = "https://api.stability.ai/v2beta/stable-image/generate";
+// Must match SCREENSHOT_DIR in event_handler.rs.
+const IMAGE_DIR: &str = "screenshots";
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageBackend {
+    Openai,
+    Stability,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImagineConfig {
+    // Unset turns /imagine off
+    #[serde(default = "default_backend")]
+    pub backend: Option<ImageBackend>,
+    // OpenAI model, or Stability service (core, ultra or sd3)
+    pub model: Option<String>,
+    // OpenAI image size, e.g. "1024x1024"
+    #[serde(default = "default_size")]
+    pub size: String,
+}
+
+impl Default for ImagineConfig {
+    fn default() -> Self {
+        Self {
+            backend: default_backend(),
+            model: None,
+            size: default_size(),
+        }
+    }
+}
+
+fn default_backend() -> Option<ImageBackend> {
+    Some(ImageBackend::Openai)
+}
+
+fn default_size() -> String {
+    "1024x1024".to_string()
+}
+
+async fn generate_openai(
+    client: &Client,
+    api_key: &str,
+    config: &ImagineConfig,
+    prompt: &str,
+) -> Result<Vec<u8>> {
+    let model = config.model.as_deref().unwrap_or("gpt-image-1");
+    let mut payload = json!({
+        "model": model,
+        "prompt": prompt,
+        "size": config.size,
+        "n": 1,
+    });
+    // gpt-image-1 always answers with base64; older models send a URL
+    // unless asked not to
+    if model.starts_with("dall-e") {
+        payload["response_format"] = json!("b64_json");
+    }
+    let response = client
+        .post(OPENAI_URL)
+        .bearer_auth(api_key)
+        .json(&payload)
+        .timeout(TIMEOUT)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(anyhow!(
+            "OpenAI image error ({}): {}",
+            status,
+            response.text().await?
+        ));
+    }
+    let body: Value = response.json().await?;
+    let data = body["data"][0]["b64_json"]
+        .as_str()
+        .ok_or_else(|| anyhow!("OpenAI sent no image"))?;
+    Ok(general_purpose::STANDARD.decode(data)?)
+}
+
+async fn generate_stability(
+    client: &Client,
+    api_key: &str,
+    config: &ImagineConfig,
+    prompt: &str,
+) -> Result<Vec<u8>> {
+    let service = config.model.as_deref().unwrap_or("core");
+    let form = multipart::Form::new()
+        .text("prompt", prompt.to_string())
+        .text("output_format", "png");
+    let response = client
+        .post(format!("{}/{}", STABILITY_URL, service))
+        .bearer_auth(api_key)
+        .header("Accept", "image/*")
+        .multipart(form)
+        .timeout(TIMEOUT)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(anyhow!(
+            "Stability error ({}): {}",
+            status,
+            response.text().await?
+        ));
+    }
+    Ok(response.bytes().await?.to_vec())
+}
+
+// Where an image generated at `timestamp` is saved.
+fn image_path(workspace: Option<u32>, timestamp: &str) -> PathBuf {
+    let name = match workspace {
+        Some(workspace) => format!("imagine-ws{}-{}.png", workspace, timestamp),
+        None => format!("imagine-{}.png", timestamp),
+    };
+    Path::new(IMAGE_DIR).join(name)
+}
+
+// The markdown the overlay shows a saved image with.
+pub fn markdown(prompt: &str, path: &Path) -> String {
+    let alt: String = prompt
+        .chars()
+        .map(|c| if matches!(c, '[' | ']') { ' ' } else { c })
+        .collect();
+    format!("![{}](<{}>)", alt.trim(), path.display())
+}
+
+// Generates an image for `prompt` and returns where it was saved.
+pub async fn imagine(
+    client: &Client,
+    config: &ImagineConfig,
+    openai_key: Option<&str>,
+    workspace: Option<u32>,
+    prompt: &str,
+) -> Result<PathBuf> {
+    let backend = config
+        .backend
+        .ok_or_else(|| anyhow!("Image generation is off; set `backend` in [imagine]"))?;
+    let image = match backend {
+        ImageBackend::Openai => {
+            let key = openai_key.ok_or_else(|| anyhow!("OpenAI API key not found"))?;
+            generate_openai(client, key, config, prompt).await?
+        }
+        ImageBackend::Stability => {
+            let key = std::env::var("STABILITY_API_KEY")
+                .map_err(|_| anyhow!("STABILITY_API_KEY is not set"))?;
+            generate_stability(client, &key, config, prompt).await?
+        }
+    };
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let path = image_path(workspace, &timestamp);
+    tokio::fs::create_dir_all(IMAGE_DIR).await?;
+    tokio::fs::write(&path, image).await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown() {
+        let path = image_path(Some(2), "20261015-120000");
+        assert_eq!(
+            path,
+            Path::new("screenshots/imagine-ws2-20261015-120000.png")
+        );
+        assert_eq!(
+            markdown("a [red] fox", &path),
+            "![a  red  fox](<screenshots/imagine-ws2-20261015-120000.png>)"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+// Images referenced from the transcript, drawn inline by the overlay.
+//
+// An `![alt](path)` block, such as the ones /imagine writes, is drawn scaled
+// down to the text width and at most MAX_HEIGHT pixels tall; images are never
+// scaled up. The text is laid out and drawn again on every change, so decoded
+// images are kept by path, along with the last size each was scaled to.
+
+use image::RgbaImage;
+use image::imageops::{self, FilterType};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const MAX_HEIGHT: u32 = 400;
+
+#[derive(Default)]
+struct Cache {
+    // Decoded images, None for those that could not be read
+    decoded: HashMap<String, Option<Arc<RgbaImage>>>,
+    // The last scaled copy of each image, with the width it was fitted to
+    scaled: HashMap<String, (u32, Arc<RgbaImage>)>,
+}
+
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(Mutex::default);
+
+fn decoded(cache: &mut Cache, path: &str) -> Option<Arc<RgbaImage>> {
+    cache
+        .decoded
+        .entry(path.to_string())
+        .or_insert_with(|| match image::open(path) {
+            Ok(image) => Some(Arc::new(image.to_rgba8())),
+            Err(e) => {
+                eprintln!("Warning: Could not load image '{}': {}", path, e);
+                None
+            }
+        })
+        .clone()
+}
+
+// The size an image of `size` is drawn at within `max_width`.
+fn fitted_size(size: (u32, u32), max_width: u32) -> (u32, u32) {
+    let (width, height) = size;
+    if width == 0 || height == 0 {
+        return size;
+    }
+    let scale = (max_width as f32 / width as f32)
+        .min(MAX_HEIGHT as f32 / height as f32)
+        .min(1.0);
+    (
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    )
+}
+
+// The image at `path` as drawn within `max_width`, None when it cannot be
+// read.
+pub fn fitted(path: &str, max_width: u32) -> Option<Arc<RgbaImage>> {
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((width, image)) = cache.scaled.get(path)
+        && *width == max_width
+    {
+        return Some(Arc::clone(image));
+    }
+    let image = decoded(&mut cache, path)?;
+    let (width, height) = fitted_size(image.dimensions(), max_width);
+    let image = if (width, height) == image.dimensions() {
+        image
+    } else {
+        Arc::new(imageops::resize(
+            &*image,
+            width,
+            height,
+            FilterType::Triangle,
+        ))
+    };
+    cache
+        .scaled
+        .insert(path.to_string(), (max_width, Arc::clone(&image)));
+    Some(image)
+}
+
+// The width an image takes when there is room for all of it.
+pub fn natural_width(path: &str) -> Option<u32> {
+    let mut cache = CACHE.lock().unwrap();
+    let image = decoded(&mut cache, path)?;
+    Some(fitted_size(image.dimensions(), u32::MAX).0)
+}
+
+// Draws `image` over a BGRA buffer with its top left corner at (x, y),
+// clipped to the buffer.
+pub fn draw(buffer: &mut [u8], width: u32, height: u32, image: &RgbaImage, x: i32, y: i32) {
+    for (image_y, row) in image.rows().enumerate() {
+        let buffer_y = y + image_y as i32;
+        if buffer_y < 0 {
+            continue;
+        }
+        if buffer_y >= height as i32 {
+            break;
+        }
+        for (image_x, pixel) in row.enumerate() {
+            let buffer_x = x + image_x as i32;
+            if buffer_x < 0 || buffer_x >= width as i32 {
+                continue;
+            }
+            let [r, g, b, a] = pixel.0;
+            let alpha = a as f32 / 255.0;
+            let index = ((buffer_y as u32 * width + buffer_x as u32) * 4) as usize;
+            let target = &mut buffer[index..index + 4];
+            for (channel, value) in target.iter_mut().zip([b, g, r]) {
+                *channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha) as u8;
+            }
+            target[3] = target[3].max(a);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fitted_size() {
+        assert_eq!(fitted_size((300, 200), 600), (300, 200));
+        assert_eq!(fitted_size((1200, 300), 600), (600, 150));
+        assert_eq!(fitted_size((1024, 1024), 600), (400, 400));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut buffer = vec![0u8; 3 * 2 * 4];
+        let image = RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]));
+        draw(&mut buffer, 3, 2, &image, 2, 1);
+        assert_eq!(&buffer[20..24], &[0, 0, 255, 255]);
+        assert!(buffer[..20].iter().all(|&byte| byte == 0));
+    }
+}
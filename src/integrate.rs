@@ -0,0 +1,276 @@
+// Generates compositor key bindings that drive aerogel through `aerogel-ctl`,
+// so Hyprland and Sway users can run without the root key listener.
+//
+// Every keybinding action becomes a compositor binding. The text input
+// binding also enters a submap (Hyprland) or mode (Sway) in which printable
+// keys are forwarded one by one, until a solve, clear or answer format
+// binding (or Escape) leaves it again.
+
+use crate::ctl::TYPED_KEYS;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Compositor {
+    Hyprland,
+    Sway,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Keybindings {
+    show_hide: String,
+    type_text: String,
+    take_screenshot: String,
+    record_audio: String,
+    solve: String,
+    clear: String,
+    switch_to_workspace: String,
+    #[serde(default)]
+    extract_code: Option<String>,
+    #[serde(default)]
+    quiz: Option<String>,
+    #[serde(default)]
+    git_diff: Option<String>,
+    #[serde(default)]
+    collapse: Option<String>,
+    #[serde(default)]
+    keyboard_grab: Option<String>,
+    #[serde(default)]
+    stop: Option<String>,
+    #[serde(default)]
+    run_command: Option<String>,
+}
+
+const TYPING_MODE: &str = "aerogel_type";
+
+struct Binding {
+    modifiers: Vec<&'static str>,
+    key: Option<String>,
+}
+
+// Splits "Ctrl+Shift+C" into canonical modifier names and an xkb keysym.
+fn parse_keybinding(keybinding: &str) -> Binding {
+    let mut modifiers = Vec::new();
+    let mut key = None;
+    for part in keybinding.split('+') {
+        match part.to_lowercase().as_str() {
+            "ctrl" => modifiers.push("Ctrl"),
+            "alt" => modifiers.push("Alt"),
+            "shift" => modifiers.push("Shift"),
+            "super" => modifiers.push("Super"),
+            "enter" => key = Some("Return".to_string()),
+            "space" => key = Some("space".to_string()),
+            other => key = Some(other.to_string()),
+        }
+    }
+    Binding { modifiers, key }
+}
+
+fn modifier_name(compositor: Compositor, modifier: &str) -> &'static str {
+    match (compositor, modifier) {
+        (Compositor::Hyprland, "Ctrl") => "CTRL",
+        (Compositor::Hyprland, "Alt") => "ALT",
+        (Compositor::Hyprland, "Shift") => "SHIFT",
+        (Compositor::Hyprland, _) => "SUPER",
+        (Compositor::Sway, "Ctrl") => "Ctrl",
+        (Compositor::Sway, "Alt") => "Mod1",
+        (Compositor::Sway, "Shift") => "Shift",
+        (Compositor::Sway, _) => "Mod4",
+    }
+}
+
+// One binding line. `commands` run in order; "mode:<name>" switches the
+// submap/mode instead of running aerogel-ctl.
+fn bind_line(
+    compositor: Compositor,
+    modifiers: &[&str],
+    key: &str,
+    commands: &[String],
+) -> Vec<String> {
+    let mods: Vec<&str> = modifiers
+        .iter()
+        .map(|m| modifier_name(compositor, m))
+        .collect();
+    match compositor {
+        Compositor::Hyprland => commands
+            .iter()
+            .map(|command| match command.strip_prefix("mode:") {
+                Some(mode) => format!("bind = {}, {}, submap, {}", mods.join(" "), key, mode),
+                None => format!(
+                    "bind = {}, {}, exec, $aerogel {}",
+                    mods.join(" "),
+                    key,
+                    command
+                ),
+            })
+            .collect(),
+        Compositor::Sway => {
+            let combo = mods
+                .iter()
+                .copied()
+                .chain(std::iter::once(key))
+                .collect::<Vec<_>>()
+                .join("+");
+            let actions = commands
+                .iter()
+                .map(|command| match command.strip_prefix("mode:") {
+                    Some(mode) => format!("mode \"{}\"", mode),
+                    None => format!("exec $aerogel {}", command),
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            vec![format!("bindsym --to-code {} {}", combo, actions)]
+        }
+    }
+}
+
+fn typing_keysyms() -> Vec<String> {
+    ('a'..='z')
+        .chain('0'..='9')
+        .map(|c| c.to_string())
+        .chain(TYPED_KEYS.iter().map(|(keysym, _, _)| keysym.to_string()))
+        .collect()
+}
+
+// Builds the compositor config snippet. `ctl_path` is the absolute path of
+// the aerogel-ctl binary.
+pub fn generate(
+    compositor: Compositor,
+    ctl_path: &str,
+    keybindings: &Keybindings,
+    answer_formats: &HashMap<String, String>,
+) -> String {
+    let default_mode = match compositor {
+        Compositor::Hyprland => "reset",
+        Compositor::Sway => "default",
+    };
+    let enter_typing = format!("mode:{}", TYPING_MODE);
+    let leave_typing = format!("mode:{}", default_mode);
+
+    // (action, keybinding, leaves the typing mode)
+    let mut actions: Vec<(String, &str, bool)> = vec![
+        ("show_hide".to_string(), &keybindings.show_hide, false),
+        (
+            "take_screenshot".to_string(),
+            &keybindings.take_screenshot,
+            false,
+        ),
+        ("record_audio".to_string(), &keybindings.record_audio, false),
+        ("solve".to_string(), &keybindings.solve, true),
+        ("clear".to_string(), &keybindings.clear, true),
+    ];
+    if let Some(extract_code) = &keybindings.extract_code {
+        actions.push(("extract_code".to_string(), extract_code, false));
+    }
+    if let Some(quiz) = &keybindings.quiz {
+        actions.push(("quiz".to_string(), quiz, false));
+    }
+    if let Some(git_diff) = &keybindings.git_diff {
+        actions.push(("git_diff".to_string(), git_diff, false));
+    }
+    if let Some(collapse) = &keybindings.collapse {
+        actions.push(("collapse".to_string(), collapse, false));
+    }
+    if let Some(keyboard_grab) = &keybindings.keyboard_grab {
+        actions.push(("keyboard_grab".to_string(), keyboard_grab, false));
+    }
+    if let Some(stop) = &keybindings.stop {
+        actions.push(("stop".to_string(), stop, false));
+    }
+    if let Some(run_command) = &keybindings.run_command {
+        actions.push(("run_command".to_string(), run_command, false));
+    }
+    let mut formats: Vec<_> = answer_formats.iter().collect();
+    formats.sort();
+    for (name, keybinding) in formats {
+        actions.push((name.clone(), keybinding, true));
+    }
+
+    let mut global = Vec::new();
+    let mut typing = Vec::new();
+    for (action, keybinding, leaves_typing) in &actions {
+        let binding = parse_keybinding(keybinding);
+        let Some(key) = &binding.key else {
+            eprintln!(
+                "Warning: keybinding '{}' for {} has no key, skipping it.",
+                keybinding, action
+            );
+            continue;
+        };
+        let trigger = format!("trigger {}", action);
+        global.extend(bind_line(
+            compositor,
+            &binding.modifiers,
+            key,
+            std::slice::from_ref(&trigger),
+        ));
+        let mut commands = vec![trigger];
+        if *leaves_typing {
+            commands.push(leave_typing.clone());
+        }
+        typing.extend(bind_line(compositor, &binding.modifiers, key, &commands));
+    }
+
+    let type_text = parse_keybinding(&keybindings.type_text);
+    if let Some(key) = &type_text.key {
+        global.extend(bind_line(
+            compositor,
+            &type_text.modifiers,
+            key,
+            &["trigger type_text".to_string(), enter_typing],
+        ));
+    }
+
+    // The workspace keybinding is a modifier followed by a digit.
+    let workspace = parse_keybinding(&keybindings.switch_to_workspace);
+    for digit in 1..=9 {
+        global.extend(bind_line(
+            compositor,
+            &workspace.modifiers,
+            &digit.to_string(),
+            &[format!("workspace {}", digit)],
+        ));
+    }
+
+    for keysym in typing_keysyms() {
+        typing.extend(bind_line(
+            compositor,
+            &[],
+            &keysym,
+            &[format!("key {}", keysym)],
+        ));
+        typing.extend(bind_line(
+            compositor,
+            &["Shift"],
+            &keysym,
+            &[format!("key Shift+{}", keysym)],
+        ));
+    }
+    typing.extend(bind_line(compositor, &[], "Escape", &[leave_typing]));
+
+    let mut out = vec![format!(
+        "# Generated by `aerogel integrate {}`. Set `backend = \"compositor\"` in the [input]\n# section of aerogel.toml so aerogel starts without the root key listener.",
+        match compositor {
+            Compositor::Hyprland => "hyprland",
+            Compositor::Sway => "sway",
+        }
+    )];
+    match compositor {
+        Compositor::Hyprland => {
+            out.push(format!("$aerogel = {}", ctl_path));
+            out.extend(global);
+            out.push(format!("\nsubmap = {}", TYPING_MODE));
+            out.extend(typing);
+            out.push("submap = reset".to_string());
+        }
+        Compositor::Sway => {
+            out.push(format!("set $aerogel {}", ctl_path));
+            out.extend(global);
+            out.push(format!("\nmode \"{}\" {{", TYPING_MODE));
+            out.extend(typing.into_iter().map(|line| format!("    {}", line)));
+            out.push("}".to_string());
+        }
+    }
+    out.join("\n") + "\n"
+}
@@ -0,0 +1,52 @@
+// Files in the working directory through which the binaries tell each other
+// what is going on, and the conventions of what they write there. Every
+// binary that writes or reads one of them names it from here, and uses only
+// its own names.
+#![allow(dead_code)]
+
+// `.aerogel_thinking<workspace>` is present from Solve until the workspace's
+// AI manager writes the first part of the answer; the overlay shows a
+// spinner meanwhile
+pub const THINKING_FILE_PREFIX: &str = ".aerogel_thinking";
+
+pub fn thinking_file(workspace: u32) -> String {
+    format!("{}{}", THINKING_FILE_PREFIX, workspace)
+}
+
+// `.aerogel_status<workspace>`, written by the workspace's AI manager for the
+// overlay's status line; see status.rs for its contents
+pub const STATUS_FILE_PREFIX: &str = ".aerogel_status";
+
+pub fn status_file(workspace: u32) -> String {
+    format!("{}{}", STATUS_FILE_PREFIX, workspace)
+}
+
+// Present while the overlay is collapsed to its workspace chip; both the
+// overlay and the event handler's collapse keybinding toggle it
+pub const COLLAPSE_FILE: &str = ".aerogel_collapsed";
+
+// Present while the overlay's keyboard grab is toggled from the configured
+// `[app] keyboard_interactivity`, by the event handler's keyboard_grab
+// keybinding
+pub const KEYBOARD_GRAB_FILE: &str = ".aerogel_keyboard_grab";
+
+// Progress of a running `aerogel models pull`, one line of text the overlay
+// shows next to the workspace number
+pub const PROGRESS_FILE: &str = ".aerogel_progress";
+
+// `.aerogel_title<workspace>` holds the title the workspace's AI manager gave
+// its conversation, for the overlay's tab
+pub const TITLE_FILE_PREFIX: &str = ".aerogel_title";
+
+pub fn title_file(workspace: u32) -> String {
+    format!("{}{}", TITLE_FILE_PREFIX, workspace)
+}
+
+// The workspace being shown, as a number. The event handler writes it when
+// the workspace changes and the overlay when a tab is clicked, and both
+// follow it
+pub const WORKSPACE_FILE: &str = ".aerogel_workspace";
+
+// Info string of the code blocks the AI manager writes the models' reasoning
+// into the transcript in, which the overlay folds to one line
+pub const REASONING_LANG: &str = "reasoning";
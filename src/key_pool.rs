@@ -0,0 +1,137 @@
+// Several API keys for one provider, used in turn as they get rate limited.
+//
+// A provider's key (in the keyring, the environment or .env) may be a
+// comma-separated list. Requests use one key until the API answers 429 Too
+// Many Requests or 402 Payment Required, as it does once a key's quota or
+// credit is used up. That key then rests for as long as the API's
+// Retry-After header asks, or REST without one, and the request is sent
+// again with the next key that is not resting. When every key rests, the
+// error is shown as usual.
+
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How long a key rests when the API does not say
+const REST: Duration = Duration::from_secs(60);
+
+pub fn parse_keys(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Whether a response means the key it was sent with is used up for now.
+pub fn is_limited(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::PAYMENT_REQUIRED
+}
+
+// How long the API asks to wait, in seconds in the Retry-After header.
+pub fn rest_time(headers: &HeaderMap) -> Duration {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map_or(REST, Duration::from_secs)
+}
+
+struct PoolState {
+    current: usize,
+    // When each key may be used again
+    resting_until: Vec<Option<Instant>>,
+}
+
+pub struct KeyPool {
+    keys: Vec<String>,
+    state: Mutex<PoolState>,
+}
+
+impl KeyPool {
+    pub fn new(keys: Vec<String>) -> Self {
+        let state = PoolState {
+            current: 0,
+            resting_until: vec![None; keys.len()],
+        };
+        Self {
+            keys,
+            state: Mutex::new(state),
+        }
+    }
+
+    // The key to send the next request with, None without keys.
+    pub fn current(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        self.keys.get(state.current).cloned()
+    }
+
+    // Rests `key` for `rest` and switches to the next key that is not
+    // resting. Returns false when there is none.
+    pub fn rest(&self, key: &str, rest: Duration) -> bool {
+        self.rest_at(key, rest, Instant::now())
+    }
+
+    fn rest_at(&self, key: &str, rest: Duration, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some(index) = self.keys.iter().position(|k| k == key) {
+            state.resting_until[index] = Some(now + rest);
+        }
+        let count = self.keys.len();
+        let next = (1..=count)
+            .map(|step| (state.current + step) % count)
+            .find(|&index| state.resting_until[index].is_none_or(|until| until <= now));
+        match next {
+            Some(index) => {
+                state.current = index;
+                state.resting_until[index] = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // How many keys there are and how many are resting, for status messages.
+    pub fn resting(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let resting = state
+            .resting_until
+            .iter()
+            .filter(|until| until.is_some_and(|until| until > now))
+            .count();
+        (self.keys.len(), resting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys("a, b,,c "), ["a", "b", "c"]);
+        assert!(parse_keys(" ").is_empty());
+    }
+
+    #[test]
+    fn test_rest() {
+        let pool = KeyPool::new(parse_keys("a,b,c"));
+        let now = Instant::now();
+        let minute = Duration::from_secs(60);
+        assert_eq!(pool.current().as_deref(), Some("a"));
+        assert!(pool.rest_at("a", minute, now));
+        assert_eq!(pool.current().as_deref(), Some("b"));
+        assert!(pool.rest_at("b", minute, now));
+        assert_eq!(pool.current().as_deref(), Some("c"));
+        assert!(!pool.rest_at("c", minute, now));
+        // a has rested long enough by then
+        assert!(pool.rest_at("c", minute, now + minute));
+        assert_eq!(pool.current().as_deref(), Some("a"));
+
+        assert!(!KeyPool::new(vec!["only".to_string()]).rest("only", minute));
+        assert_eq!(KeyPool::new(Vec::new()).current(), None);
+    }
+}
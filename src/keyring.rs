@@ -0,0 +1,67 @@
+// API keys in the system keyring, through the Secret Service.
+//
+// `aerogel key set <provider>` stores a key with `secret-tool` (libsecret),
+// which asks for it without echoing it. With `[keys] keyring = true` the AI
+// processes look keys up there before falling back to the environment and
+// .env (see config.rs); it is off by default, since a locked keyring asks to
+// be unlocked when a key is looked up.
+// Keys are stored under the attributes `service=aerogel provider=<provider>`,
+// so they can also be managed with Seahorse or KWallet.
+
+use anyhow::{Result, anyhow};
+use std::process::{Command, Stdio};
+
+fn attributes(provider: &str) -> [String; 4] {
+    [
+        "service".to_string(),
+        "aerogel".to_string(),
+        "provider".to_string(),
+        provider.to_lowercase(),
+    ]
+}
+
+fn run(args: &[String], stdin: Stdio) -> Result<()> {
+    let status = Command::new("secret-tool")
+        .args(args)
+        .stdin(stdin)
+        .status()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                anyhow!("secret-tool is not installed; install libsecret-tools (or libsecret)")
+            }
+            _ => anyhow!("Could not run secret-tool: {}", e),
+        })?;
+    if !status.success() {
+        return Err(anyhow!("secret-tool failed ({})", status));
+    }
+    Ok(())
+}
+
+// Stores a key for `provider`, read by secret-tool from the terminal or stdin.
+pub fn store(provider: &str) -> Result<()> {
+    let mut args = vec![
+        "store".to_string(),
+        format!("--label=aerogel {} API key", provider.to_lowercase()),
+    ];
+    args.extend(attributes(provider));
+    run(&args, Stdio::inherit())
+}
+
+pub fn delete(provider: &str) -> Result<()> {
+    let mut args = vec!["clear".to_string()];
+    args.extend(attributes(provider));
+    run(&args, Stdio::null())
+}
+
+// The key stored for `provider`, if there is one.
+pub fn lookup(provider: &str) -> Option<String> {
+    let output = Command::new("secret-tool")
+        .arg("lookup")
+        .args(attributes(provider))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let key = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !key.is_empty()).then_some(key)
+}
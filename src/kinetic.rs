@@ -0,0 +1,108 @@
+// Kinetic scrolling after a flick.
+//
+// While a finger moves, VelocityTracker keeps the scroll deltas of the last
+// VELOCITY_WINDOW. When it lifts fast enough, Kinetic keeps scrolling at that
+// velocity and slows down with FRICTION until it falls below MIN_VELOCITY.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const VELOCITY_WINDOW: Duration = Duration::from_millis(100);
+// Fraction of the velocity kept after one second
+const FRICTION: f32 = 0.05;
+// Pixels per second below which scrolling stops
+const MIN_VELOCITY: f32 = 30.0;
+
+#[derive(Debug, Default)]
+pub struct VelocityTracker {
+    samples: VecDeque<(Instant, f32)>,
+}
+
+impl VelocityTracker {
+    pub fn push(&mut self, at: Instant, delta: f32) {
+        self.samples.push_back((at, delta));
+        while let Some(&(first, _)) = self.samples.front() {
+            if at.duration_since(first) <= VELOCITY_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    // Pixels per second over the recent samples, 0 when the movement
+    // stopped before `now`.
+    pub fn velocity(&self, now: Instant) -> f32 {
+        let Some(&(first, _)) = self.samples.front() else {
+            return 0.0;
+        };
+        if now.duration_since(first) > VELOCITY_WINDOW {
+            return 0.0;
+        }
+        let distance: f32 = self.samples.iter().map(|&(_, delta)| delta).sum();
+        let elapsed = now.duration_since(first).max(Duration::from_millis(10));
+        distance / elapsed.as_secs_f32()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Kinetic {
+    // Pixels per second and the time of the last step, while moving
+    moving: Option<(f32, Instant)>,
+}
+
+impl Kinetic {
+    pub fn start(&mut self, velocity: f32, now: Instant) {
+        self.moving = (velocity.abs() >= MIN_VELOCITY).then_some((velocity, now));
+    }
+
+    pub fn stop(&mut self) {
+        self.moving = None;
+    }
+
+    // The distance to scroll since the last step, or None once stopped.
+    pub fn step(&mut self, now: Instant) -> Option<f32> {
+        let (velocity, last) = self.moving?;
+        let dt = now.duration_since(last).as_secs_f32();
+        let next = velocity * FRICTION.powf(dt);
+        if next.abs() < MIN_VELOCITY {
+            self.moving = None;
+        } else {
+            self.moving = Some((next, now));
+        }
+        // The distance covered while slowing from `velocity` to `next`
+        Some((velocity - next) / -FRICTION.ln())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_velocity_tracker() {
+        let start = Instant::now();
+        let mut tracker = VelocityTracker::default();
+        assert_eq!(tracker.velocity(start), 0.0);
+        for i in 1..=5 {
+            tracker.push(start + Duration::from_millis(i * 10), 10.0);
+        }
+        let velocity = tracker.velocity(start + Duration::from_millis(60));
+        assert!((velocity - 1000.0).abs() < 1.0, "{}", velocity);
+        assert_eq!(tracker.velocity(start + Duration::from_secs(1)), 0.0);
+    }
+
+    #[test]
+    fn test_kinetic_slows_down_and_stops() {
+        let start = Instant::now();
+        let mut kinetic = Kinetic::default();
+        kinetic.start(10.0, start);
+        assert!(kinetic.step(start).is_none());
+
+        kinetic.start(-2000.0, start);
+        let first = kinetic.step(start + Duration::from_millis(16)).unwrap();
+        let second = kinetic.step(start + Duration::from_millis(32)).unwrap();
+        assert!(first < 0.0 && second < 0.0 && second.abs() < first.abs());
+        assert!(kinetic.step(start + Duration::from_secs(5)).is_some());
+        assert!(kinetic.step(start + Duration::from_secs(6)).is_none());
+    }
+}
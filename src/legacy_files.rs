@@ -0,0 +1,144 @@
+// Files older versions left in the working directory.
+//
+// The transcripts (.tmpN), .aerogel_workspace and .event are how the running
+// components talk, and are made afresh on every start; margin.log has given
+// way to the overlay's state file (see state.rs). What an older version left
+// behind is dealt with once, by the launcher before the components start,
+// while there is no state file yet. Each transcript is kept as a saved
+// session named `imported-workspace-<N>`, which `/load` continues, margin.log
+// is moved to the state file, and the old files are removed.
+
+use crate::ipc::WORKSPACE_FILE;
+use crate::state::{LEGACY_LOG, OverlayState, state_path};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Key log and current workspace; nothing in them outlasts a run
+const IPC_FILES: &[&str] = &[".event", WORKSPACE_FILE];
+
+// Where `/save` keeps sessions (see saved_sessions.rs)
+fn sessions_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("aerogel")
+        .join("sessions")
+}
+
+// The workspace of a transcript file name like ".tmp3".
+fn transcript_workspace(name: &str) -> Option<u32> {
+    name.strip_prefix(".tmp")?
+        .parse()
+        .ok()
+        .filter(|workspace| *workspace > 0)
+}
+
+// Moves the files in the working directory, once.
+pub fn migrate() {
+    migrate_in(Path::new("."), &state_path(), &sessions_dir());
+}
+
+fn migrate_in(dir: &Path, path: &Path, sessions: &Path) {
+    if path.exists() {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let result = if let Some(workspace) = transcript_workspace(&name) {
+            import_transcript(&entry.path(), workspace, sessions)
+        } else if IPC_FILES.contains(&name.as_str()) {
+            std::fs::remove_file(entry.path())
+        } else {
+            continue;
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to migrate {}: {}", name, e);
+        }
+    }
+    // Moves margin.log, if any; the state file then marks the move as done
+    let state = OverlayState::load_from(path, &dir.join(LEGACY_LOG));
+    if !path.exists()
+        && let Err(e) = state.save_to(path)
+    {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+    }
+}
+
+// Keeps a transcript as a saved session, in the layout saved_sessions.rs
+// reads, with the transcript alone and no history to send on, then removes
+// it. Empty transcripts are only removed.
+fn import_transcript(file: &Path, workspace: u32, sessions: &Path) -> io::Result<()> {
+    let transcript = std::fs::read_to_string(file)?;
+    if !transcript.trim().is_empty() {
+        let saved: chrono::DateTime<chrono::Local> = std::fs::metadata(file)?.modified()?.into();
+        let session = serde_json::json!({
+            "workspace": workspace,
+            "saved": saved.to_rfc3339(),
+            "title": null,
+            "history": {},
+            "exchanges": [],
+            "transcript": transcript,
+        });
+        std::fs::create_dir_all(sessions)?;
+        let name = format!("imported-workspace-{}", workspace);
+        // Not over a session saved under the same name
+        let target = (1..)
+            .map(|n| match n {
+                1 => sessions.join(format!("{}.json", name)),
+                n => sessions.join(format!("{}-{}.json", name, n)),
+            })
+            .find(|target| !target.exists())
+            .unwrap_or_default();
+        std::fs::write(&target, serde_json::to_vec(&session)?)?;
+        println!(
+            "Kept {} as the saved session {}",
+            file.display(),
+            target.display()
+        );
+    }
+    std::fs::remove_file(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("state").join("overlay.toml");
+        let sessions = dir.path().join("sessions");
+        std::fs::create_dir(&sessions)?;
+        std::fs::write(sessions.join("imported-workspace-2.json"), "{}")?;
+        std::fs::write(dir.path().join(".tmp2"), "**You:** hi\n")?;
+        std::fs::write(dir.path().join(".tmp3"), "\n")?;
+        std::fs::write(dir.path().join(".tmpfile"), "")?;
+        std::fs::write(dir.path().join(".event"), "a\n")?;
+        std::fs::write(dir.path().join(WORKSPACE_FILE), "2")?;
+        std::fs::write(dir.path().join(LEGACY_LOG), "Dragging: x=7, y=8\n")?;
+
+        migrate_in(dir.path(), &path, &sessions);
+        let imported: serde_json::Value = serde_json::from_slice(&std::fs::read(
+            sessions.join("imported-workspace-2-2.json"),
+        )?)?;
+        assert_eq!(imported["workspace"], 2);
+        assert_eq!(imported["transcript"], "**You:** hi\n");
+        assert!(!sessions.join("imported-workspace-3.json").exists());
+        for name in [".tmp2", ".tmp3", ".event", WORKSPACE_FILE, LEGACY_LOG] {
+            assert!(!dir.path().join(name).exists(), "{} is left", name);
+        }
+        assert!(dir.path().join(".tmpfile").exists());
+        assert_eq!(
+            OverlayState::load_from(&path, Path::new("")).margin_x,
+            Some(7)
+        );
+
+        // Only once: later transcripts are the running components'
+        std::fs::write(dir.path().join(".tmp2"), "**You:** again\n")?;
+        migrate_in(dir.path(), &path, &sessions);
+        assert!(dir.path().join(".tmp2").exists());
+        Ok(())
+    }
+}
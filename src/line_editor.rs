@@ -0,0 +1,236 @@
+// Line input for the AI process when it runs in a terminal.
+//
+// Reading stdin a line at a time leaves editing to the terminal, which has no
+// completion or history. In a terminal, lines are read with rustyline
+// instead, which handles wide characters, lines longer than the terminal and
+// multi-line input:
+//
+//   Tab                     completes commands and file paths (commands.rs)
+//   Left, Right, Home, End  move the cursor, as do Ctrl+B, F, A and E
+//   Up, Down                go through earlier lines, as do Ctrl+P and N
+//   Ctrl+R                  searches earlier lines; again for older matches
+//   Alt+Enter               starts a new line in the same input
+//   Ctrl+U, Ctrl+K, Ctrl+W  delete to the start, to the end, the last word
+//   Ctrl+D                  ends input on an empty line
+//   Ctrl+C                  interrupts, as before
+//
+// The main loop asks for a line by setting the prompt to show. While a round
+// runs there is no prompt, and a line is read without one as soon as a key
+// is pressed, so `/stop` still gets through. The history is kept in
+// `$XDG_DATA_HOME/aerogel/input_history`, the last MAX_HISTORY lines. The
+// terminal's settings are restored when the process exits.
+
+use crate::commands;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{
+    Cmd, CompletionType, Config, Context, Editor, Helper, Highlighter, Hinter, KeyCode, KeyEvent,
+    Modifiers, Validator,
+};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+const MAX_HISTORY: usize = 1000;
+
+// Milliseconds between checks for a prompt to show or a key pressed
+const POLL_INTERVAL: i32 = 100;
+
+// Terminal settings from before echo was turned off
+static SAVED: OnceLock<libc::termios> = OnceLock::new();
+
+pub fn interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+extern "C" fn restore_terminal() {
+    if let Some(saved) = SAVED.get() {
+        // SAFETY: `saved` is a termios filled in by tcgetattr.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+        }
+    }
+}
+
+// Turns off echo and line buffering between lines, once, so keys pressed
+// while a round runs reach the editor as they are typed instead of being
+// echoed into the answer. Ctrl+C still interrupts.
+fn quiet_terminal() -> io::Result<()> {
+    if SAVED.get().is_some() {
+        return Ok(());
+    }
+    // SAFETY: tcgetattr fills in the zeroed termios, which is plain data.
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let _ = SAVED.set(termios);
+    // SAFETY: restore_terminal only reads SAVED, which is set by now.
+    unsafe {
+        libc::atexit(restore_terminal);
+    }
+    termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+    termios.c_cc[libc::VMIN] = 1;
+    termios.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Whether a key is waiting on stdin, or its end, within `timeout` ms.
+fn key_waiting(timeout: i32) -> bool {
+    let mut stdin = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: poll fills in the revents of the one pollfd it is given.
+    unsafe { libc::poll(&mut stdin, 1, timeout) > 0 }
+}
+
+fn history_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("aerogel")
+        .join("input_history")
+}
+
+// What Tab puts in place of the word before the cursor. A single choice is
+// followed by a space, unless it is a directory to go on into.
+fn completions(line: &str) -> (usize, Vec<String>) {
+    let (start, mut words) = commands::complete(line);
+    if let [only] = words.as_mut_slice()
+        && !only.ends_with('/')
+    {
+        only.push(' ');
+    }
+    (start, words)
+}
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct InputHelper;
+
+impl Completer for InputHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, words) = completions(&line[..pos]);
+        let pairs = words
+            .into_iter()
+            .map(|word| Pair {
+                display: word.trim_end().to_string(),
+                replacement: word,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+pub struct LineEditor {
+    // The prompt the main loop is waiting at, taken when the line is read
+    prompt: Arc<Mutex<Option<String>>>,
+    editor: Editor<InputHelper, FileHistory>,
+    path: PathBuf,
+}
+
+impl LineEditor {
+    pub fn new(prompt: Arc<Mutex<Option<String>>>) -> rustyline::Result<Self> {
+        let config = Config::builder()
+            .max_history_size(MAX_HISTORY)?
+            .history_ignore_dups(true)?
+            .history_ignore_space(true)
+            .auto_add_history(true)
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(InputHelper));
+        editor.bind_sequence(KeyEvent(KeyCode::Enter, Modifiers::ALT), Cmd::Newline);
+        let path = history_path();
+        // No history yet on the first run
+        let _ = editor.load_history(&path);
+        Ok(Self {
+            prompt,
+            editor,
+            path,
+        })
+    }
+
+    // The prompt to read the next line with: the one the main loop asks
+    // for, or none once a key is pressed without one.
+    fn next_prompt(&self) -> String {
+        loop {
+            if let Some(prompt) = self.prompt.lock().unwrap().take() {
+                return prompt;
+            }
+            if key_waiting(POLL_INTERVAL) {
+                return String::new();
+            }
+        }
+    }
+
+    fn save_history(&mut self) {
+        let result = std::fs::create_dir_all(self.path.parent().unwrap_or(&self.path))
+            .map_err(ReadlineError::from)
+            .and_then(|()| self.editor.append_history(&self.path));
+        if let Err(e) = result {
+            eprintln!("[ERROR] Failed to save the input history: {}", e);
+        }
+    }
+
+    // Reads a line, None at the end of input.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        quiet_terminal()?;
+        let prompt = self.next_prompt();
+        match self.editor.readline(&prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    self.save_history();
+                }
+                Ok(Some(line))
+            }
+            Err(ReadlineError::Eof) => {
+                restore_terminal();
+                Ok(None)
+            }
+            Err(ReadlineError::Interrupted) => {
+                restore_terminal();
+                // SAFETY: raising a signal has no memory effects here.
+                unsafe {
+                    libc::raise(libc::SIGINT);
+                }
+                Ok(Some(String::new()))
+            }
+            Err(ReadlineError::Io(e)) => Err(e),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions() {
+        assert_eq!(completions("/sa"), (0, vec!["/save ".to_string()]));
+        assert_eq!(
+            completions("/s").1,
+            ["/stop", "/search", "/save", "/sessions"]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("logs")).unwrap();
+        let line = format!("/upload {}/lo", dir.path().display());
+        assert_eq!(
+            completions(&line),
+            (8, vec![format!("{}/logs/", dir.path().display())])
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// Coalescing of streamed answer text before it is written to the log.
+//
+// Answers stream in a few characters at a time, and the overlay redraws
+// whenever the conversation log changes. Streamed text is therefore held
+// back and written at most every `[streaming] flush_ms` milliseconds, or
+// sooner at the end of a sentence or line so text still appears in readable
+// steps. `flush_ms = 0` writes every chunk as it arrives.
+
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct StreamingConfig {
+    #[serde(default = "default_flush_ms")]
+    pub flush_ms: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            flush_ms: default_flush_ms(),
+        }
+    }
+}
+
+fn default_flush_ms() -> u64 {
+    75
+}
+
+impl StreamingConfig {
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_millis(self.flush_ms)
+    }
+}
+
+// Whether `text` ends a line or a sentence.
+fn ends_sentence(text: &str) -> bool {
+    if text.ends_with('\n') {
+        return true;
+    }
+    let trimmed = text.trim_end();
+    trimmed.len() < text.len() && trimmed.ends_with(['.', '!', '?', ':'])
+}
+
+pub struct LogBuffer {
+    pending: String,
+    interval: Duration,
+    // When the oldest pending text arrived
+    since: Option<Instant>,
+}
+
+impl LogBuffer {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            pending: String::new(),
+            interval,
+            since: None,
+        }
+    }
+
+    // Adds streamed text and returns what is due to be written, if anything.
+    pub fn push(&mut self, text: &str) -> Option<String> {
+        self.push_at(text, Instant::now())
+    }
+
+    fn push_at(&mut self, text: &str, now: Instant) -> Option<String> {
+        if self.pending.is_empty() {
+            self.since = Some(now);
+        }
+        self.pending.push_str(text);
+        let due = self
+            .since
+            .is_some_and(|since| now.duration_since(since) >= self.interval);
+        if due || ends_sentence(&self.pending) {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    // When the pending text is due even if nothing more arrives.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.since.map(|since| since + self.interval)
+    }
+
+    // The pending text, to write now.
+    pub fn take(&mut self) -> Option<String> {
+        self.since = None;
+        (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        let now = Instant::now();
+        let mut buffer = LogBuffer::new(Duration::from_millis(75));
+        assert_eq!(buffer.push_at("The answer", now), None);
+        assert_eq!(buffer.deadline(), Some(now + Duration::from_millis(75)));
+        assert_eq!(buffer.push_at(" is 4", now), None);
+        // Not a sentence end until whitespace follows the full stop
+        assert_eq!(buffer.push_at("2. It", now), None);
+        assert_eq!(
+            buffer.push_at(" follows", now + Duration::from_millis(80)),
+            Some("The answer is 42. It follows".to_string())
+        );
+        assert_eq!(buffer.deadline(), None);
+        assert_eq!(buffer.push_at("Done. ", now).as_deref(), Some("Done. "));
+        assert_eq!(buffer.push_at("```\n", now).as_deref(), Some("```\n"));
+        assert_eq!(buffer.take(), None);
+
+        let mut unbuffered = LogBuffer::new(Duration::ZERO);
+        assert_eq!(unbuffered.push_at("a", now).as_deref(), Some("a"));
+    }
+}
@@ -1,15 +1,254 @@
+// Only the keys typed through aerogel-ctl are used here
+#[allow(dead_code)]
+mod ctl;
+mod integrate;
+mod ipc;
+// Keys are only stored and deleted here; the AI processes look them up
+#[allow(dead_code)]
+mod keyring;
+mod legacy_files;
+mod models;
+// Only the state file's path and the margin.log import are used here
+#[allow(dead_code)]
+mod state;
+
+use clap::{Parser, Subcommand};
 use colored::*;
+use integrate::{Compositor, Keybindings};
+use models::{ModelSource, OllamaSettings};
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, Write};
+use std::path::PathBuf;
 use std::process::{Command, Stdio, exit};
 
-fn main() {
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<CliCommand>,
+
+    /// Use only local backends; cloud providers are disabled
+    #[clap(long)]
+    offline: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Print key bindings that drive aerogel from the compositor, without the key listener
+    Integrate {
+        #[clap(value_enum)]
+        compositor: Compositor,
+    },
+    /// Manage local models for offline use
+    Models {
+        #[clap(subcommand)]
+        command: ModelsCommand,
+    },
+    /// Manage API keys in the system keyring
+    Key {
+        #[clap(subcommand)]
+        command: KeyCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeyCommand {
+    /// Store the API key of a provider, e.g. openai, claude or a [[providers]] name
+    Set { provider: String },
+    /// Remove the stored API key of a provider
+    Delete { provider: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum ModelsCommand {
+    /// Pull an Ollama model, or a data file: whisper:<size>, tesseract:<lang>
+    Pull { name: String },
+    /// Show whether the local backends are ready for offline mode
+    Status,
+}
+
+#[derive(Deserialize, Default)]
+struct ModelsSettings {
+    #[serde(default)]
+    offline: bool,
+}
+
+#[derive(Deserialize, Default)]
+struct SessionSettings {
+    idle_stop_hours: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+struct InputSettings {
+    // "keylogger" (default) or "compositor"
+    backend: Option<String>,
+    // How long the listener batches key presses before writing them; the
+    // listener's --flush-ms. Called write_interval_ms before.
+    #[serde(alias = "write_interval_ms")]
+    flush_ms: Option<u64>,
+    flush_on_modifier: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Settings {
+    #[serde(default)]
+    session: SessionSettings,
+    #[serde(default)]
+    input: InputSettings,
+    keybindings: Option<Keybindings>,
+    #[serde(default)]
+    answer_formats: HashMap<String, String>,
+    #[serde(default)]
+    models: ModelsSettings,
+    #[serde(rename = "Ollama")]
+    ollama: Option<OllamaSettings>,
+}
+
+// Reads aerogel.toml, searched in the same order as the event handler does.
+fn read_settings() -> Option<Settings> {
+    let mut paths: Vec<PathBuf> = vec!["../../aerogel.toml".into(), "aerogel.toml".into()];
+    if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
+        let base = PathBuf::from(xdg_config);
+        paths.push(base.join("aerogel/aerogel.toml"));
+        paths.push(base.join("aerogel.toml"));
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        let base = PathBuf::from(home);
+        paths.push(base.join(".config/aerogel/aerogel.toml"));
+        paths.push(base.join(".aerogel.toml"));
+    }
+    paths.push("/etc/aerogel/aerogel.toml".into());
+
+    let content = paths
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())?;
+    toml::from_str::<Settings>(&content).ok()
+}
+
+fn print_integration(compositor: Compositor, settings: Option<Settings>) {
+    let Some(settings) = settings else {
+        eprintln!("{}", "Error: Could not read aerogel.toml.".red());
+        exit(1);
+    };
+    let Some(keybindings) = settings.keybindings else {
+        eprintln!(
+            "{}",
+            "Error: aerogel.toml has no [keybindings] section.".red()
+        );
+        exit(1);
+    };
+    let ctl_path = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("aerogel-ctl")))
+        .unwrap_or_else(|| PathBuf::from("aerogel-ctl"));
+
+    print!(
+        "{}",
+        integrate::generate(
+            compositor,
+            &ctl_path.to_string_lossy(),
+            &keybindings,
+            &settings.answer_formats,
+        )
+    );
+}
+
+fn run_models_command(command: ModelsCommand, settings: Option<Settings>) {
+    let ollama = settings.as_ref().and_then(|s| s.ollama.as_ref());
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the async runtime");
+    match command {
+        ModelsCommand::Pull { name } => {
+            let source = ModelSource::parse(&name);
+            if let Err(e) = runtime.block_on(models::pull(&source, ollama)) {
+                eprintln!("{}", format!("Error: {}", e).red());
+                exit(1);
+            }
+            println!("{}", format!("Pulled '{}'.", name).green());
+        }
+        ModelsCommand::Status => {
+            let problems = runtime.block_on(models::check_local_backends(ollama));
+            if problems.is_empty() {
+                println!("{}", "Ollama is ready for offline mode.".green());
+            }
+            for problem in &problems {
+                println!("{}", problem.red());
+            }
+            let files = models::local_data_files();
+            if !files.is_empty() {
+                println!("Data files in {}:", models::data_dir().display());
+                for file in files {
+                    println!("  - {}", file);
+                }
+            }
+        }
+    }
+}
+
+fn run_key_command(command: KeyCommand) {
+    let (result, done) = match &command {
+        KeyCommand::Set { provider } => {
+            println!("Enter the {} API key.", provider);
+            (
+                keyring::store(provider),
+                format!(
+                    "Stored the {} key; set `keyring = true` under [keys] in aerogel.toml to use it.",
+                    provider
+                ),
+            )
+        }
+        KeyCommand::Delete { provider } => (
+            keyring::delete(provider),
+            format!("Removed the {} key.", provider),
+        ),
+    };
+    match result {
+        Ok(()) => println!("{}", done.green()),
+        Err(e) => {
+            eprintln!("{}", format!("Error: {}", e).red());
+            exit(1);
+        }
+    }
+}
+
+// Exits unless the local backends can answer without the cloud providers.
+fn verify_offline_backends(settings: Option<&Settings>) {
+    let ollama = settings.and_then(|s| s.ollama.as_ref());
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the async runtime");
+    let problems = runtime.block_on(models::check_local_backends(ollama));
+    if problems.is_empty() {
+        println!("{}", "Offline mode: cloud providers are disabled.".green());
+        return;
+    }
+    eprintln!(
+        "\n{}",
+        "Error: Offline mode needs a ready local backend.".red()
+    );
+    for problem in problems {
+        eprintln!("- {}", problem);
+    }
+    exit(1);
+}
+
+fn start_event_listener(idle_stop_hours: Option<f64>, input: &InputSettings) {
     println!("Starting the event listener which requires root privileges.");
     io::stdout().flush().expect("Failed to flush stdout.");
 
-    let listener_status = Command::new("sudo")
-        .arg("-b")
-        .arg("./event_listener")
+    let mut listener = Command::new("sudo");
+    listener.arg("-b").arg("./event_listener");
+    if let Some(hours) = idle_stop_hours {
+        println!("The listener stops after {} idle hour(s).", hours);
+        listener.arg("--idle-stop-hours").arg(hours.to_string());
+    }
+    if let Some(flush_ms) = input.flush_ms {
+        listener.arg("--flush-ms").arg(flush_ms.to_string());
+    }
+    if let Some(flush) = input.flush_on_modifier {
+        listener.arg("--flush-on-modifier").arg(flush.to_string());
+    }
+
+    let listener_status = listener
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status()
@@ -26,10 +265,58 @@ fn main() {
     }
 
     println!("\n{}", "'./event_listener' launched successfully.".green());
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let settings = read_settings();
+
+    match cli.command {
+        Some(CliCommand::Integrate { compositor }) => {
+            print_integration(compositor, settings);
+            return;
+        }
+        Some(CliCommand::Models { command }) => {
+            run_models_command(command, settings);
+            return;
+        }
+        Some(CliCommand::Key { command }) => {
+            run_key_command(command);
+            return;
+        }
+        None => {}
+    }
+
+    // Before the components start making these files afresh
+    legacy_files::migrate();
+
+    let offline = cli.offline || settings.as_ref().is_some_and(|s| s.models.offline);
+    if offline {
+        verify_offline_backends(settings.as_ref());
+    }
+
+    let backend = settings
+        .as_ref()
+        .and_then(|s| s.input.backend.clone())
+        .unwrap_or_else(|| "keylogger".to_string());
+    if backend == "compositor" {
+        println!("Using compositor key bindings, the event listener is not started.");
+    } else {
+        let default_input = InputSettings::default();
+        start_event_listener(
+            settings.as_ref().and_then(|s| s.session.idle_stop_hours),
+            settings.as_ref().map_or(&default_input, |s| &s.input),
+        );
+    }
 
     let handler_log_file = File::create("aerogel.log").expect("Failed to create aerogel.log");
 
-    match Command::new("./event_handler")
+    let mut handler = Command::new("./event_handler");
+    if offline {
+        // Inherited by the AI processes the handler spawns
+        handler.env("AEROGEL_OFFLINE", "1");
+    }
+    match handler
         .stdout(Stdio::from(
             handler_log_file
                 .try_clone()
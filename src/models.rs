@@ -0,0 +1,282 @@
+// Local model management for offline use.
+//
+// `aerogel models pull <name>` pulls an Ollama model through the Ollama API,
+// or downloads a data file for a local backend:
+//
+//   <model>             an Ollama model, e.g. gemma3:4b
+//   whisper:<size>      a whisper.cpp model, e.g. whisper:base.en
+//   tesseract:<lang>    Tesseract OCR data, e.g. tesseract:eng
+//
+// Data files are stored under `$XDG_DATA_HOME/aerogel`. While a pull runs,
+// its progress is written to PROGRESS_FILE, which the overlay shows next to
+// the workspace number.
+
+use crate::ipc::PROGRESS_FILE;
+use anyhow::{Result, anyhow};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ModelSource {
+    Ollama(String),
+    Whisper(String),
+    Tesseract(String),
+}
+
+impl ModelSource {
+    pub fn parse(name: &str) -> Self {
+        match name.split_once(':') {
+            Some(("whisper", size)) => ModelSource::Whisper(size.to_string()),
+            Some(("tesseract", lang)) => ModelSource::Tesseract(lang.to_string()),
+            _ => ModelSource::Ollama(name.to_string()),
+        }
+    }
+
+    // Download URL and destination of a data file; None for Ollama models.
+    fn data_file(&self) -> Option<(String, PathBuf)> {
+        match self {
+            ModelSource::Ollama(_) => None,
+            ModelSource::Whisper(size) => Some((
+                format!(
+                    "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-{}.bin",
+                    size
+                ),
+                data_dir()
+                    .join("whisper")
+                    .join(format!("ggml-{}.bin", size)),
+            )),
+            ModelSource::Tesseract(lang) => Some((
+                format!(
+                    "https://github.com/tesseract-ocr/tessdata_fast/raw/main/{}.traineddata",
+                    lang
+                ),
+                data_dir()
+                    .join("tessdata")
+                    .join(format!("{}.traineddata", lang)),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OllamaSettings {
+    pub api_base: String,
+    pub model: String,
+}
+
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("aerogel")
+}
+
+// "http://localhost:11434/v1/chat/completions" -> "http://localhost:11434"
+pub fn ollama_host(api_base: &str) -> String {
+    let scheme_end = api_base.find("://").map_or(0, |i| i + 3);
+    match api_base[scheme_end..].find('/') {
+        Some(path_start) => api_base[..scheme_end + path_start].to_string(),
+        None => api_base.trim_end_matches('/').to_string(),
+    }
+}
+
+fn report_progress(label: &str, completed: u64, total: u64) {
+    let line = match (completed * 100).checked_div(total) {
+        Some(percent) => format!("{} {}%", label, percent),
+        None => label.to_string(),
+    };
+    print!("\r{}   ", line);
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+    let _ = std::fs::write(PROGRESS_FILE, &line);
+}
+
+pub async fn pull(source: &ModelSource, ollama: Option<&OllamaSettings>) -> Result<()> {
+    let result = match source {
+        ModelSource::Ollama(name) => {
+            let api_base = ollama
+                .map(|o| o.api_base.as_str())
+                .ok_or_else(|| anyhow!("aerogel.toml has no [Ollama] section"))?;
+            pull_ollama(&ollama_host(api_base), name).await
+        }
+        _ => {
+            let (url, path) = source.data_file().expect("data file source");
+            download(&url, &path).await
+        }
+    };
+    let _ = std::fs::remove_file(PROGRESS_FILE);
+    println!();
+    result
+}
+
+async fn pull_ollama(host: &str, name: &str) -> Result<()> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/pull", host))
+        .json(&json!({ "name": name, "stream": true }))
+        .send()
+        .await
+        .map_err(|e| anyhow!("Could not reach Ollama at {}: {}", host, e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(anyhow!(
+            "Ollama pull failed ({}): {}",
+            status,
+            response.text().await?
+        ));
+    }
+
+    // The response is one JSON object per line.
+    let mut stream = response.bytes_stream();
+    let mut pending = String::new();
+    while let Some(chunk) = stream.next().await {
+        pending.push_str(&String::from_utf8_lossy(&chunk?));
+        while let Some(newline) = pending.find('\n') {
+            let line: String = pending.drain(..=newline).collect();
+            let Ok(update) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if let Some(error) = update["error"].as_str() {
+                return Err(anyhow!("Ollama pull failed: {}", error));
+            }
+            let status = update["status"].as_str().unwrap_or("pulling");
+            report_progress(
+                &format!("{}: {}", name, status),
+                update["completed"].as_u64().unwrap_or(0),
+                update["total"].as_u64().unwrap_or(0),
+            );
+        }
+    }
+    Ok(())
+}
+
+// Downloads to a temporary file next to `path` first, so an interrupted
+// download never leaves a truncated data file behind.
+async fn download(url: &str, path: &PathBuf) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let response = reqwest::get(url).await?.error_for_status()?;
+    let total = response.content_length().unwrap_or(0);
+    let label = format!(
+        "downloading {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+
+    let partial = path.with_extension("part");
+    let mut file = tokio::fs::File::create(&partial).await?;
+    let mut completed = 0;
+    let mut last_percent = None;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        completed += chunk.len() as u64;
+        let percent = (completed * 100).checked_div(total);
+        if percent != last_percent {
+            last_percent = percent;
+            report_progress(&label, completed, total);
+        }
+    }
+    file.flush().await?;
+    tokio::fs::rename(&partial, path).await?;
+    print!("\nSaved to {}", path.display());
+    Ok(())
+}
+
+// Names of the models Ollama has pulled, or an error when it is unreachable.
+async fn ollama_models(host: &str) -> Result<Vec<String>> {
+    let tags: Value = reqwest::get(format!("{}/api/tags", host))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(tags["models"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m["name"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+// A model named without a tag is pulled as "<name>:latest".
+fn has_model(models: &[String], model: &str) -> bool {
+    models
+        .iter()
+        .any(|m| m == model || (!model.contains(':') && *m == format!("{}:latest", model)))
+}
+
+// Checks that the local backends are ready. Returns the problems found; an
+// empty list means offline mode can answer.
+pub async fn check_local_backends(ollama: Option<&OllamaSettings>) -> Vec<String> {
+    let Some(ollama) = ollama else {
+        return vec!["aerogel.toml has no [Ollama] section".to_string()];
+    };
+    let host = ollama_host(&ollama.api_base);
+    match ollama_models(&host).await {
+        Ok(models) if has_model(&models, &ollama.model) => Vec::new(),
+        Ok(_) => vec![format!(
+            "Ollama model '{}' is not pulled yet, run `aerogel models pull {}`",
+            ollama.model, ollama.model
+        )],
+        Err(e) => vec![format!("Ollama is not reachable at {}: {}", host, e)],
+    }
+}
+
+// The data files pulled so far, relative to the data directory.
+pub fn local_data_files() -> Vec<String> {
+    let mut files = Vec::new();
+    for dir in ["whisper", "tessdata"] {
+        if let Ok(entries) = std::fs::read_dir(data_dir().join(dir)) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.ends_with(".part") {
+                    files.push(format!("{}/{}", dir, name));
+                }
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_source() {
+        assert_eq!(
+            ModelSource::parse("gemma3:4b"),
+            ModelSource::Ollama("gemma3:4b".to_string())
+        );
+        assert_eq!(
+            ModelSource::parse("whisper:base.en"),
+            ModelSource::Whisper("base.en".to_string())
+        );
+        assert_eq!(
+            ModelSource::parse("tesseract:eng"),
+            ModelSource::Tesseract("eng".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ollama_host() {
+        assert_eq!(
+            ollama_host("http://localhost:11434/v1/chat/completions"),
+            "http://localhost:11434"
+        );
+        assert_eq!(ollama_host("http://ollama:11434/"), "http://ollama:11434");
+    }
+
+    #[test]
+    fn test_has_model() {
+        let models = vec!["gemma3:4b".to_string(), "llava:latest".to_string()];
+        assert!(has_model(&models, "gemma3:4b"));
+        assert!(has_model(&models, "llava"));
+        assert!(!has_model(&models, "gemma3"));
+    }
+}
@@ -0,0 +1,125 @@
+// Text extraction from screenshots and other attached images.
+//
+// Providers that cannot read images get the text in them instead, and the
+// {ocr} template placeholder is filled the same way. By default the text is
+// read locally with the `tesseract` command, so screen contents never leave
+// the machine; `backend = "gemini"` in `[ocr]` sends the images to Gemini
+// through OpenRouter instead, which reads layouts and handwriting better but
+// costs money. Tesseract uses the language data pulled with
+// `aerogel models pull tesseract:<lang>` when it is there, and the system's
+// otherwise.
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrBackend {
+    Tesseract,
+    Gemini,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OcrConfig {
+    #[serde(default = "default_backend")]
+    pub backend: OcrBackend,
+    // Tesseract languages, e.g. "eng" or "eng+deu"
+    #[serde(default = "default_lang")]
+    pub lang: String,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            backend: default_backend(),
+            lang: default_lang(),
+        }
+    }
+}
+
+fn default_backend() -> OcrBackend {
+    OcrBackend::Tesseract
+}
+
+fn default_lang() -> String {
+    "eng".to_string()
+}
+
+// Where `aerogel models pull tesseract:<lang>` puts language data.
+fn tessdata_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("aerogel")
+        .join("tessdata")
+}
+
+// Tesseract's arguments for reading an image from stdin, with the pulled
+// language data when every language has been pulled.
+fn tesseract_args(lang: &str, tessdata: Option<PathBuf>) -> Vec<String> {
+    let mut args = vec![
+        "stdin".to_string(),
+        "stdout".to_string(),
+        "-l".to_string(),
+        lang.to_string(),
+    ];
+    if let Some(dir) = tessdata {
+        args.extend([
+            "--tessdata-dir".to_string(),
+            dir.to_string_lossy().into_owned(),
+        ]);
+    }
+    args
+}
+
+pub async fn tesseract(config: &OcrConfig, image: &[u8]) -> Result<String> {
+    let dir = tessdata_dir();
+    let pulled = config
+        .lang
+        .split('+')
+        .all(|lang| dir.join(format!("{}.traineddata", lang)).exists());
+    let mut child = Command::new("tesseract")
+        .args(tesseract_args(&config.lang, pulled.then_some(dir)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow!(
+                "tesseract is not installed; install it or set `backend = \"gemini\"` in [ocr]"
+            ),
+            _ => anyhow!("Could not run tesseract: {}", e),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(image).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tesseract_args() {
+        assert_eq!(
+            tesseract_args("eng", None),
+            ["stdin", "stdout", "-l", "eng"].map(String::from)
+        );
+        assert_eq!(
+            tesseract_args("eng+deu", Some(PathBuf::from("/data/tessdata")))[3..],
+            ["eng+deu", "--tessdata-dir", "/data/tessdata"].map(String::from)
+        );
+    }
+}
@@ -0,0 +1,193 @@
+// `ai_manager --ask "<prompt>" [--file <path>]... [--provider <name>]`: one
+// prompt from a shell script, with the providers and settings of
+// aerogel.toml.
+//
+// The prompt goes to a single provider: `--provider`, or else the primary
+// one (see `primary_provider`), or else the first configured. Only the
+// answer is written to stdout, as it streams; notes and errors go to stderr.
+// The exit code is 0 once the answer is complete, 1 when the request fails
+// and 2 for bad arguments, an unknown provider or a missing file.
+
+use crate::ai_client::{AiClient, ChatReply, PromptData};
+use crate::config::ApiConfig;
+use crate::overrides::Overrides;
+use crate::providers::{Chunk, Provider};
+use crate::status::StatusFile;
+use crate::tokens::count_tokens;
+use crate::usage::{self, UsageLog, UsageRecord};
+use crate::video::VideoConfig;
+use anyhow::{Result, anyhow};
+use futures_util::StreamExt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const USAGE: &str = "Usage: ai_manager --ask <prompt> [--file <path>]... [--provider <name>]";
+
+#[derive(Debug, PartialEq)]
+pub struct OneShot {
+    pub prompt: String,
+    pub files: Vec<String>,
+    pub provider: Option<String>,
+}
+
+// The one-shot request in the command line arguments, None without `--ask`.
+pub fn parse(args: &[String]) -> Result<Option<OneShot>> {
+    if !args.iter().any(|arg| arg == "--ask") {
+        return Ok(None);
+    }
+    let mut prompt = None;
+    let mut files = Vec::new();
+    let mut provider = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| anyhow!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--ask" => prompt = Some(value()?),
+            "--file" => files.push(value()?),
+            "--provider" => provider = Some(value()?),
+            other => return Err(anyhow!("Unknown argument '{}'", other)),
+        }
+    }
+    Ok(Some(OneShot {
+        prompt: prompt.unwrap_or_default(),
+        files,
+        provider,
+    }))
+}
+
+// Asks the prompt and returns the exit code.
+pub async fn run(config: ApiConfig, one_shot: OneShot) -> i32 {
+    if one_shot.prompt.trim().is_empty() && one_shot.files.is_empty() {
+        eprintln!("[ERROR] Cannot send an empty prompt.\n{}", USAGE);
+        return 2;
+    }
+    if let Some(missing) = one_shot.files.iter().find(|file| !Path::new(file).exists()) {
+        eprintln!("[ERROR] File not found at '{}'", missing);
+        return 2;
+    }
+    let name = one_shot
+        .provider
+        .clone()
+        .or_else(|| config.primary_provider(None).map(str::to_string));
+    let video = config.video.clone();
+    let pricing = config.pricing.clone();
+    let client = AiClient::new(config, None);
+    let provider = match &name {
+        Some(name) => client
+            .providers()
+            .iter()
+            .find(|provider| provider.name().eq_ignore_ascii_case(name)),
+        None => client.providers().first(),
+    };
+    let Some(provider) = provider.cloned() else {
+        match name {
+            Some(name) => eprintln!("[ERROR] No provider named '{}' with an API key", name),
+            None => eprintln!("[ERROR] No provider has an API key"),
+        }
+        return 2;
+    };
+    let mut status = StatusFile::new(
+        None,
+        usage::prices(client.providers(), &pricing),
+        client.context_limits(),
+    );
+    match ask(&client, &provider, one_shot, &video, &mut status).await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("[ERROR] {}", e);
+            1
+        }
+    }
+}
+
+async fn ask(
+    client: &AiClient,
+    provider: &Arc<dyn Provider>,
+    one_shot: OneShot,
+    video: &VideoConfig,
+    status: &mut StatusFile,
+) -> Result<()> {
+    let prompt_data = PromptData::new(one_shot.prompt, &one_shot.files, video).await?;
+    let ChatReply {
+        mut stream,
+        prompt_tokens,
+        cached,
+        ..
+    } = client
+        .chat(provider, &prompt_data, &Overrides::default(), true)
+        .await?;
+    let mut stdout = io::stdout().lock();
+    let mut response = String::new();
+    let mut reasoning = String::new();
+    while let Some(chunk) = stream.next().await {
+        match chunk? {
+            Chunk::Reasoning(text) => reasoning.push_str(&text),
+            Chunk::Answer(text) => {
+                write!(stdout, "{}", text)?;
+                stdout.flush()?;
+                response.push_str(&text);
+            }
+        }
+    }
+    if !response.ends_with('\n') {
+        writeln!(stdout)?;
+    }
+    if cached {
+        return Ok(());
+    }
+    let model = &provider.model().model;
+    let tokens_out = count_tokens(model, &response) + count_tokens(model, &reasoning);
+    let cost = status.add_answer(provider.name(), prompt_tokens, tokens_out);
+    let record = UsageRecord {
+        day: usage::today(),
+        workspace: None,
+        provider: provider.name().to_string(),
+        model: model.clone(),
+        tokens_in: prompt_tokens,
+        tokens_out,
+        cost,
+    };
+    if let Err(e) = UsageLog::open().add(&record) {
+        eprintln!("[ERROR] Failed to record usage: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(&args(&["log.md", "2"])).unwrap(), None);
+        assert_eq!(
+            parse(&args(&[
+                "--ask",
+                "What is this?",
+                "--file",
+                "a.png",
+                "--file",
+                "b.png",
+                "--provider",
+                "openai"
+            ]))
+            .unwrap(),
+            Some(OneShot {
+                prompt: "What is this?".to_string(),
+                files: args(&["a.png", "b.png"]),
+                provider: Some("openai".to_string()),
+            })
+        );
+        assert!(parse(&args(&["--ask", "hi", "--provider"])).is_err());
+        assert!(parse(&args(&["--ask", "hi", "--model", "x"])).is_err());
+    }
+}
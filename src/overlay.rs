@@ -1,22 +1,74 @@
+mod answer_diff;
+// Only the reading side is used here; the AI manager saves the answers
+#[allow(dead_code)]
+mod answer_history;
+mod blur;
+mod fonts;
+mod heartbeat;
+mod inline_images;
+mod ipc;
+mod kinetic;
+// Only the reading side is used here; the AI manager writes the panes
+#[allow(dead_code)]
+mod panes;
+mod power;
+mod renderer;
+// Only PAUSE_FILE is used here; the limits are the event handler's
+#[allow(dead_code)]
+mod session;
+mod state;
+// Only the reading side is used here; the AI manager writes the status file
+#[allow(dead_code)]
+mod status;
+mod theme;
+
+use answer_history::AnswerHistory;
+use blur::client::{
+    org_kde_kwin_blur::OrgKdeKwinBlur, org_kde_kwin_blur_manager::OrgKdeKwinBlurManager,
+};
+use fonts::FontSet;
+use ipc::{COLLAPSE_FILE, KEYBOARD_GRAB_FILE, PROGRESS_FILE, REASONING_LANG, WORKSPACE_FILE};
+use kinetic::{Kinetic, VelocityTracker};
 use once_cell::sync::Lazy;
+use panes::Panes;
+use power::{PowerMode, RenderProfile};
 use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
-use rusttype::{Font, Scale, point};
+use renderer::{Frame, RenderJob, RenderStyle, Renderer};
+use rusttype::{Scale, point};
 use serde::Deserialize;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use session::PAUSE_FILE;
+use state::OverlayState;
+use status::{Status, short_count};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::time::{Duration, Instant};
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Style, ThemeSet};
+use syntect::highlighting::Style;
 use syntect::parsing::SyntaxSet;
+use theme::{LegacyColors, Rgba, Spacing, Theme, ThemeColors, ThemeConfig};
 use toml;
+use unicode_bidi::{Level, ParagraphBidiInfo};
 use wayland_client::{
     Connection, Dispatch, QueueHandle, WEnum,
     protocol::{
         wl_buffer::WlBuffer, wl_compositor::WlCompositor, wl_keyboard::WlKeyboard,
-        wl_output::WlOutput, wl_pointer::WlPointer, wl_seat::WlSeat, wl_shm::WlShm,
-        wl_surface::WlSurface,
+        wl_output::WlOutput, wl_pointer::WlPointer, wl_seat::Capability, wl_seat::WlSeat,
+        wl_shm::WlShm, wl_surface::WlSurface, wl_touch::WlTouch,
     },
 };
+use wayland_protocols::wp::cursor_shape::v1::client::{
+    wp_cursor_shape_device_v1::{self, Shape, WpCursorShapeDeviceV1},
+    wp_cursor_shape_manager_v1::{self, WpCursorShapeManagerV1},
+};
+use wayland_protocols::xdg::shell::client::{
+    xdg_surface::{self, XdgSurface},
+    xdg_toplevel::{self, XdgToplevel},
+    xdg_wm_base::{self, XdgWmBase},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
 use wayland_protocols_wlr::layer_shell::v1::client::{
     zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
     zwlr_layer_surface_v1::{Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
@@ -29,20 +81,151 @@ struct AppConfig {
     scroll_speed: f32,
     border_radius: f32,
     auto_scroll: bool,
+    // Directory of extra .sublime-syntax files for code highlighting
+    syntax_dir: Option<String>,
+    // Line numbers in code blocks, and the width of their gutter in pixels
+    #[serde(default = "default_line_numbers")]
+    line_numbers: bool,
+    #[serde(default = "default_line_number_width")]
+    line_number_width: f32,
+    // Show the models' reasoning in full instead of folded to one line
+    #[serde(default)]
+    show_reasoning: bool,
+    // Fit the width to the widest line of the text, up to `width`
+    #[serde(default)]
+    auto_width: bool,
+    // Space left and right of the text, above it, between blocks, and the
+    // line height multiplier; see theme::Spacing for the defaults
+    padding: Option<f32>,
+    top_padding: Option<f32>,
+    block_spacing: Option<f32>,
+    line_spacing: Option<f32>,
+    // Distance in pixels at which a dragged overlay snaps to a screen edge;
+    // 0 disables snapping
+    #[serde(default = "default_snap_distance")]
+    snap_distance: i32,
+    // Output to show the overlay on, by connector name ("DP-2") or a part of
+    // its description or make and model; the first output when unset
+    output: Option<String>,
+    // Show a copy of the overlay on every other output too
+    #[serde(default)]
+    mirror: bool,
+    // Lines scrolled per mouse wheel notch
+    #[serde(default = "default_wheel_lines")]
+    wheel_lines: f32,
+    // Keep scrolling after a fast touchpad flick
+    #[serde(default = "default_kinetic_scroll")]
+    kinetic_scroll: bool,
+    // Seconds without new text or pointer activity before the overlay hides
+    // until the text changes; 0 never hides it
+    #[serde(default)]
+    auto_hide_after: u64,
+    // Hide while a fullscreen window is on the overlay's output
+    #[serde(default)]
+    hide_on_fullscreen: bool,
+    // Line at the bottom with the responding providers, elapsed time and
+    // token and cost counters of the current workspace
+    #[serde(default)]
+    status_line: bool,
+    // Give workspaces without a `[workspaces.<n>] color` one derived from
+    // their number
+    #[serde(default)]
+    workspace_colors: bool,
+    // Send a desktop notification when an answer finishes while the overlay
+    // is hidden or collapsed
+    #[serde(default = "default_notify_when_hidden")]
+    notify_when_hidden: bool,
+    // "none", "on_demand" (default) or "exclusive": whether the overlay takes
+    // keyboard focus when clicked, never, or grabs the keyboard while shown
+    keyboard_interactivity: Option<String>,
+    // Ask the compositor to blur what is behind the overlay
+    #[serde(default)]
+    blur: bool,
+}
+
+impl AppConfig {
+    fn spacing(&self) -> Spacing {
+        let default = Spacing::default();
+        Spacing {
+            padding: self.padding.unwrap_or(default.padding).max(0.0),
+            top: self.top_padding.unwrap_or(default.top).max(0.0),
+            block: self.block_spacing.unwrap_or(default.block).max(0.0),
+            line_height: self.line_spacing.unwrap_or(default.line_height).max(0.5),
+        }
+    }
+}
+
+fn default_line_numbers() -> bool {
+    true
+}
+
+fn default_line_number_width() -> f32 {
+    40.0
+}
+
+fn default_snap_distance() -> i32 {
+    20
+}
+
+fn default_wheel_lines() -> f32 {
+    3.0
+}
+
+fn default_kinetic_scroll() -> bool {
+    true
+}
+
+fn default_notify_when_hidden() -> bool {
+    true
 }
 
 #[derive(Deserialize)]
 struct FontConfig {
-    path: String,
+    // Font family resolved through fontconfig, e.g. "JetBrains Mono"
+    family: Option<String>,
+    path: Option<String>,
     size: f32,
 }
 
+#[derive(Deserialize, Default)]
+struct InputConfig {
+    // "keylogger" (default) or "compositor", which runs without the listener
+    backend: Option<String>,
+}
+
 #[derive(Deserialize)]
-struct ColorsConfig {
-    blue: u8,
-    green: u8,
-    red: u8,
-    alpha_multiplier: f32,
+struct PowerConfig {
+    // "auto" (default) switches to the power saving profile on battery;
+    // "performance" or "powersave" pin one
+    profile: Option<String>,
+    // Text polling interval and redraw cap of the power saving profile
+    #[serde(default = "default_battery_poll_ms")]
+    battery_poll_ms: u64,
+    #[serde(default = "default_battery_max_fps")]
+    battery_max_fps: u32,
+    // Redraw cap of the performance profile, independent of the text
+    // polling; 0 redraws on every change
+    #[serde(default)]
+    max_fps: u32,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            battery_poll_ms: default_battery_poll_ms(),
+            battery_max_fps: default_battery_max_fps(),
+            max_fps: 0,
+        }
+    }
+}
+
+fn default_battery_poll_ms() -> u64 {
+    50
+}
+
+fn default_battery_max_fps() -> u32 {
+    20
 }
 
 #[derive(Deserialize)]
@@ -56,15 +239,35 @@ struct KeybindingsConfig {
     switch_to_workspace: String,
 }
 
+// The overlay's part of a `[workspaces.<number>]` section.
+#[derive(Deserialize, Default)]
+struct WorkspaceStyle {
+    // Color of the workspace's tab and of the overlay's border while it is
+    // shown
+    color: Option<Rgba>,
+}
+
 #[derive(Deserialize)]
 struct Config {
     app: AppConfig,
     font: FontConfig,
-    colors: ColorsConfig,
+    #[serde(default)]
+    theme: ThemeConfig,
+    #[serde(default)]
+    themes: BTreeMap<String, ThemeColors>,
+    // Read in place of `theme` from configs that predate it
+    colors: Option<LegacyColors>,
     keybindings: KeybindingsConfig,
+    #[serde(default)]
+    input: InputConfig,
+    #[serde(default)]
+    power: PowerConfig,
+    #[serde(default)]
+    workspaces: HashMap<String, WorkspaceStyle>,
 }
 
-static CONFIG: Lazy<Config> = Lazy::new(|| {
+// Candidate locations of aerogel.toml, in order of priority.
+fn config_paths() -> Vec<std::path::PathBuf> {
     use std::env;
     use std::path::PathBuf;
 
@@ -93,21 +296,115 @@ static CONFIG: Lazy<Config> = Lazy::new(|| {
         PathBuf::from("aerogel.toml"),
     ]);
 
-    let config_str = config_paths
+    config_paths
+}
+
+fn find_config_path() -> Option<std::path::PathBuf> {
+    config_paths().into_iter().find(|path| path.is_file())
+}
+
+static CONFIG: Lazy<Config> = Lazy::new(|| {
+    let config_str = config_paths()
         .iter()
         .find_map(|path| std::fs::read_to_string(path).ok())
         .expect("Failed to read aerogel.toml from any of the expected locations");
 
-    toml::from_str(&config_str).expect("Failed to parse aerogel.toml")
+    let mut config: Config = toml::from_str(&config_str).expect("Failed to parse aerogel.toml");
+    config.theme = theme::with_legacy_colors(
+        std::mem::take(&mut config.theme),
+        config.colors,
+        has_theme_section(&config_str),
+    );
+    config
 });
 
+// Whether aerogel.toml has a `[theme]` section, which a legacy `[colors]`
+// one gives way to.
+fn has_theme_section(config_str: &str) -> bool {
+    toml::from_str::<toml::Table>(config_str).is_ok_and(|table| table.contains_key("theme"))
+}
+
+// The part of the config the overlay picks up again when aerogel.toml changes.
+#[derive(Deserialize)]
+struct ReloadableConfig {
+    #[serde(default)]
+    theme: ThemeConfig,
+}
+
+const ZOOM_STATE_FILE: &str = ".aerogel_zoom";
+// Thinking files older than this are left over from a crashed AI manager
+const THINKING_TIMEOUT: Duration = Duration::from_secs(300);
+// Longest conversation title shown on a tab, in characters
+const TAB_TITLE_CHARS: usize = 24;
+// Time per step of the spinner, and its number of dots
+const SPINNER_FRAME: Duration = Duration::from_millis(100);
+const SPINNER_DOTS: usize = 8;
+// Vertical padding around the status line's text
+const STATUS_LINE_PADDING: f32 = 8.0;
+// Width of the border drawn in the workspace's color
+const WORKSPACE_BORDER_WIDTH: f32 = 2.0;
+// Space around the workspace chip, also the size of the collapsed overlay's
+// transparent border
+const CHIP_PADDING: f32 = 10.0;
+// How often the power source is checked with the "auto" power profile
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const ZOOM_STEP: f32 = 1.1;
+// Touchpad scrolling, in pixels, that zooms by one ZOOM_STEP with Ctrl held
+const TOUCHPAD_ZOOM_DISTANCE: f32 = 40.0;
+const MIN_FONT_SIZE: f32 = 8.0;
+const MAX_FONT_SIZE: f32 = 72.0;
+
+// Width of the border in which a left click resizes instead of moving the
+// overlay, and the smallest size it can be resized to
+const RESIZE_BORDER: f64 = 8.0;
+const MIN_WIDTH: u32 = 200;
+const MIN_HEIGHT: u32 = 80;
+// Preset positions cycled with Ctrl+P, as (name, horizontal, vertical)
+// fractions of the free space on the output
+const POSITION_PRESETS: [(&str, f32, f32); 3] = [
+    ("top-right", 1.0, 0.0),
+    ("bottom-left", 0.0, 1.0),
+    ("centered", 0.5, 0.5),
+];
+
+// Code block panels: horizontal inset from the overlay edge, inner vertical
+// padding, corner radius and the language label's size relative to the font
+const CODE_PANEL_INSET: f32 = 10.0;
+const CODE_PANEL_PADDING: f32 = 8.0;
+const CODE_PANEL_RADIUS: f32 = 6.0;
+const CODE_LABEL_SCALE: f32 = 0.7;
+
+// What the overlay shows around the answer: the workspace chip and the
+// component status strip.
+struct StatusBar {
+    chip_label: String,
+    // The current workspace and every workspace with a conversation file,
+    // shown as a row of tabs
+    workspace: u32,
+    workspaces: Vec<u32>,
+    // Conversation titles by workspace, for the tabs of the other workspaces
+    titles: HashMap<u32, String>,
+    components_alive: Vec<bool>,
+    // Step of the waiting spinner, while a request is in flight
+    spinner: Option<usize>,
+    // Text of the bottom status line, with `[app] status_line`
+    status_line: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 struct DragState {
     is_dragging: bool,
     start_x: f64,
     start_y: f64,
-    _start_margin_x: i32,
-    _start_margin_y: i32,
+    // Where the overlay would be without snapping, so it leaves an edge
+    // again once the pointer moves far enough away
+    unsnapped_x: i32,
+    unsnapped_y: i32,
+    // Edges being dragged when resizing instead of moving the overlay
+    resize: Option<ResizeEdges>,
+    // Margins when the button was pressed; a release at the same position
+    // is a click
+    pressed_at: (i32, i32),
 }
 
 impl Default for DragState {
@@ -116,8 +413,62 @@ impl Default for DragState {
             is_dragging: false,
             start_x: 0.0,
             start_y: 0.0,
-            _start_margin_x: 20,
-            _start_margin_y: 20,
+            unsnapped_x: 20,
+            unsnapped_y: 20,
+            resize: None,
+            pressed_at: (20, 20),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ResizeEdges {
+    left: bool,
+    right: bool,
+    top: bool,
+    bottom: bool,
+}
+
+impl ResizeEdges {
+    // The edges (or corner) under a pointer at (x, y) on a surface of the
+    // given size, None away from the border.
+    fn at(x: f64, y: f64, width: u32, height: u32) -> Option<Self> {
+        let edges = Self {
+            left: x < RESIZE_BORDER,
+            right: x >= width as f64 - RESIZE_BORDER,
+            top: y < RESIZE_BORDER,
+            bottom: y >= height as f64 - RESIZE_BORDER,
+        };
+        (edges.left || edges.right || edges.top || edges.bottom).then_some(edges)
+    }
+
+    // The cursor shown over these edges.
+    fn cursor_shape(self) -> Shape {
+        match (self.left, self.right, self.top, self.bottom) {
+            (true, _, true, _) => Shape::NwResize,
+            (true, _, _, true) => Shape::SwResize,
+            (_, true, true, _) => Shape::NeResize,
+            (_, true, _, true) => Shape::SeResize,
+            (true, ..) => Shape::WResize,
+            (_, true, ..) => Shape::EResize,
+            (_, _, true, _) => Shape::NResize,
+            _ => Shape::SResize,
+        }
+    }
+
+    // The same edges for an interactive resize of an xdg-shell window.
+    fn xdg_edge(self) -> xdg_toplevel::ResizeEdge {
+        use xdg_toplevel::ResizeEdge;
+
+        match (self.left, self.right, self.top, self.bottom) {
+            (true, _, true, _) => ResizeEdge::TopLeft,
+            (true, _, _, true) => ResizeEdge::BottomLeft,
+            (_, true, true, _) => ResizeEdge::TopRight,
+            (_, true, _, true) => ResizeEdge::BottomRight,
+            (true, ..) => ResizeEdge::Left,
+            (_, true, ..) => ResizeEdge::Right,
+            (_, _, true, _) => ResizeEdge::Top,
+            _ => ResizeEdge::Bottom,
         }
     }
 }
@@ -144,27 +495,179 @@ Switch Workspace     {}+n
     )
 }
 
+// An output announced by the compositor, with what it reported about itself.
+struct OutputInfo {
+    output: WlOutput,
+    global_name: u32,
+    name: Option<String>,
+    description: Option<String>,
+    make_model: Option<String>,
+    width: i32,
+    height: i32,
+}
+
+// Matches the connector name, or a part of the description or make and
+// model, ignoring case.
+fn output_matches(pattern: &str, name: Option<&str>, descriptions: &[Option<&str>]) -> bool {
+    let pattern_lower = pattern.to_lowercase();
+    name.is_some_and(|name| name.eq_ignore_ascii_case(pattern))
+        || descriptions
+            .iter()
+            .flatten()
+            .any(|text| text.to_lowercase().contains(&pattern_lower))
+}
+
+impl OutputInfo {
+    fn matches(&self, pattern: &str) -> bool {
+        output_matches(
+            pattern,
+            self.name.as_deref(),
+            &[self.description.as_deref(), self.make_model.as_deref()],
+        )
+    }
+
+    fn label(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| self.description.clone())
+            .unwrap_or_else(|| format!("output {}", self.global_name))
+    }
+}
+
+// The overlay as a regular borderless window, on compositors without
+// wlr-layer-shell such as GNOME. The compositor decides where it goes and
+// whether it stays on top; dragging and resizing are handed over to it.
+struct XdgWindow {
+    xdg_surface: XdgSurface,
+    toplevel: XdgToplevel,
+    // Buffers may only be attached after the first configure event, again
+    // after every unmap
+    configured: bool,
+}
+
+impl XdgWindow {
+    fn destroy(&self) {
+        self.toplevel.destroy();
+        self.xdg_surface.destroy();
+    }
+}
+
+// Pointer movement after a press beyond which the press becomes a drag of
+// the xdg-shell window instead of a click
+const XDG_DRAG_THRESHOLD: f64 = 3.0;
+
+// A copy of the overlay on another output, showing the same buffer.
+struct Mirror {
+    output: WlOutput,
+    surface: WlSurface,
+    layer_surface: ZwlrLayerSurfaceV1,
+}
+
+impl Mirror {
+    fn destroy(&self) {
+        self.layer_surface.destroy();
+        self.surface.destroy();
+    }
+}
+
+// The finger on the overlay. A touch on the strip above the text or on the
+// border drags or resizes the overlay like the pointer; elsewhere it scrolls.
+struct TouchState {
+    id: i32,
+    scrolling: bool,
+    last_y: f64,
+    velocity: VelocityTracker,
+}
+
+// Vertical scroll events of one wl_pointer frame. Wheels report notches as
+// discrete steps (value120 from version 8, in 1/120 of a notch, for
+// high-resolution wheels); touchpads report finger movement and an axis stop
+// when the fingers lift.
+#[derive(Debug, Default)]
+struct AxisFrame {
+    source: Option<wayland_client::protocol::wl_pointer::AxisSource>,
+    value: f64,
+    discrete: i32,
+    value120: i32,
+    stop: bool,
+}
+
+// Height of the strip above the text, with the workspace chip
+const TOUCH_DRAG_AREA: f64 = 40.0;
+
+// A window reported by the foreign toplevel manager, for hide_on_fullscreen.
+// States and outputs are double-buffered until the handle's done event.
+struct Toplevel {
+    handle: ZwlrForeignToplevelHandleV1,
+    outputs: Vec<WlOutput>,
+    fullscreen: bool,
+    pending_fullscreen: bool,
+}
+
 struct AppState {
     compositor: Option<WlCompositor>,
     shm: Option<WlShm>,
     layer_shell: Option<ZwlrLayerShellV1>,
+    // Fallback when the compositor has no layer-shell
+    xdg_wm_base: Option<XdgWmBase>,
     output: Option<WlOutput>,
-    seat: Option<WlSeat>,
+    outputs: Vec<OutputInfo>,
+    // Set while the overlay has no surface because every output is gone
+    awaiting_output: bool,
+    // The seat's registry name, and its devices as its capabilities come
+    // and go
+    seat: Option<(WlSeat, u32)>,
     pointer: Option<WlPointer>,
+    keyboard: Option<WlKeyboard>,
+    touch_device: Option<WlTouch>,
+    // Cursor shapes for dragging, resizing and the text, with cursor-shape-v1;
+    // other compositors keep showing their own cursor
+    cursor_shape_manager: Option<WpCursorShapeManagerV1>,
+    cursor_device: Option<WpCursorShapeDeviceV1>,
+    // Serial of the pointer's enter event, and the shape set since then
+    pointer_enter_serial: u32,
+    cursor_shape: Option<Shape>,
+    // KWin's blur, bound only with `[app] blur`
+    blur_manager: Option<OrgKdeKwinBlurManager>,
 
     // Surface and buffer management
     surface: Option<WlSurface>,
     layer_surface: Option<ZwlrLayerSurfaceV1>,
+    xdg_window: Option<XdgWindow>,
+    // Serial of the last button press or touch, to start moves and resizes
+    // of the xdg-shell window with
+    press_serial: u32,
     current_buffer: Option<WlBuffer>,
-
-    // Overlay dimensions and position
+    renderer: Renderer,
+    // The theme or font changed since the last frame was requested
+    restyle: bool,
+    // Copies on the other outputs with `[app] mirror`
+    mirrors: Vec<Mirror>,
+
+    // Overlay dimensions and position. The height follows the text up to
+    // max_height, and with `[app] auto_width` the width up to max_width;
+    // both maximums can be changed by resizing.
     width: u32,
     height: u32,
+    max_width: u32,
+    max_height: u32,
     margin_x: i32,
     margin_y: i32,
 
-    // Overlay state
+    // Overlay state. Auto-hiding hides the overlay without changing
+    // `visible`, which is only toggled by the user.
     visible: bool,
+    idle_hidden: bool,
+    fullscreen_hidden: bool,
+    // Last new text or pointer activity, for auto_hide_after
+    last_activity: Instant,
+    toplevel_manager: Option<ZwlrForeignToplevelManagerV1>,
+    toplevels: Vec<Toplevel>,
+
+    // Position, size, visibility and scroll offsets saved across restarts;
+    // state_dirty marks unsaved changes
+    saved: OverlayState,
+    state_dirty: bool,
 
     // Border radius and styling
     border_radius: f32,
@@ -180,8 +683,8 @@ struct AppState {
     output_width: i32,
     output_height: i32,
 
-    // Font for text rendering
-    font: Font<'static>,
+    // Fonts for text rendering
+    font: FontSet,
 
     // Text to display
     text: String,
@@ -190,78 +693,171 @@ struct AppState {
     last_text_update: Instant,
     text_update_interval: Duration,
     current_workspace: u32,
+    // Workspaces with a conversation file, for the tab row
+    workspaces: Vec<u32>,
+    // Their conversations' titles, once they have one
+    titles: HashMap<u32, String>,
 
     // Track if text has changed (for redrawing)
     text_changed: bool,
 
+    // Keyboard modifier state, for Ctrl shortcuts
+    ctrl_pressed: bool,
+
+    // Key capture is paused by the session limits
+    capture_paused: bool,
+
+    // Past answer shown instead of the transcript, counted back from the
+    // newest (0 shows the transcript), and how many there are
+    answer_page: usize,
+    answer_pages: usize,
+    // Provider whose answer of the current round is shown on its own
+    // instead of the transcript
+    pane: Option<String>,
+    // Provider the open pane is diffed against
+    diff_with: Option<String>,
+
+    // Shrunk to the workspace chip, see COLLAPSE_FILE
+    collapsed: bool,
+
+    // Keyboard interactivity of the overlay's layer surface, see
+    // KEYBOARD_GRAB_FILE
+    keyboard_interactivity: KeyboardInteractivity,
+
+    // Progress of a model pull, shown in the workspace chip
+    progress: Option<String>,
+
+    // Liveness of the listener (unless the compositor backend is used), the
+    // event handler and the current workspace's AI process
+    components_alive: Vec<bool>,
+
+    // When the current workspace started waiting for an answer, and the
+    // spinner's step
+    thinking_since: Option<Instant>,
+    spinner_step: usize,
+
+    // Text of the bottom status line, with `[app] status_line`
+    status_line: Option<String>,
+
+    // When each workspace's last answer finished, from its status file, to
+    // notify about new ones
+    answers_finished: HashMap<u32, u64>,
+
+    // Modification time of aerogel.toml, to reload it when it changes
+    config_modified: Option<std::time::SystemTime>,
+
     // Scrolling
     scroll_offset_y: f32,
     max_scroll_offset_y: f32,
+    touch: Option<TouchState>,
+    kinetic: Kinetic,
+    axis_frame: AxisFrame,
+    // Recent touchpad scrolling, for the flick velocity at the axis stop
+    axis_velocity: VelocityTracker,
 
     // Syntect for syntax highlighting
     syntax_set: SyntaxSet,
-    theme: syntect::highlighting::Theme,
+    theme: Theme,
 }
 
 impl AppState {
     fn new() -> Self {
-        // Load margins from log file
-        let (loaded_margin_x, loaded_margin_y) = Self::load_margins_from_log().unwrap_or((20, 20));
-
-        // Load font from config path, with fallbacks
-        let font = {
-            // Try to load from the path specified in the config file first.
-            let config_font_result = std::fs::read(&CONFIG.font.path)
-                .ok()
-                .and_then(|data| Font::try_from_vec(data));
-
-            config_font_result.unwrap_or_else(|| {
-                // If the above fails, print a warning and try system fallbacks.
-                eprintln!(
-                    "Warning: Could not load font from config path: '{}'. Trying system fonts.",
-                    &CONFIG.font.path
-                );
-                let system_font_data =
-                    std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf")
-                        .or_else(|_| std::fs::read("/System/Library/Fonts/Arial.ttf"))
-                        .or_else(|_| std::fs::read("/usr/share/fonts/TTF/DejaVuSans.ttf"))
-                        .expect("No system fallback font found. Please check font paths.");
-
-                Font::try_from_vec(system_font_data).expect("Failed to parse system fallback font.")
-            })
-        };
+        // Restore position, size and visibility from the last run
+        let saved = OverlayState::load();
+        let loaded_margin_x = saved.margin_x.unwrap_or(20);
+        let loaded_margin_y = saved.margin_y.unwrap_or(20);
 
         // Load initial workspace
         let initial_workspace = Self::read_current_workspace().unwrap_or(1);
+
+        // Resolve the font from config, with fontconfig fallbacks, at the
+        // zoom level last used on this workspace
+        let font_size = Self::load_zoom(initial_workspace).unwrap_or(CONFIG.font.size);
+        let font = FontSet::load(
+            CONFIG.font.family.as_deref(),
+            CONFIG.font.path.as_deref(),
+            font_size,
+        );
         let temp_file = format!(".tmp{}", initial_workspace);
 
         // Load initial text from log file for the correct workspace
-        let initial_text = Self::load_text_from_log(&temp_file).unwrap_or_else(get_default_text);
+        let initial_text =
+            Self::load_text_from_log(temp_file.as_ref()).unwrap_or_else(get_default_text);
 
         // Initialize syntect
-        let ss = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
-        let theme = ts.themes["base16-ocean.dark"].clone();
+        let ss = load_syntax_set(CONFIG.app.syntax_dir.as_deref());
+        let theme_name = CONFIG
+            .theme
+            .name
+            .clone()
+            .unwrap_or_else(|| theme::DEFAULT_THEME.to_string());
+        let colors = theme::theme_colors(&CONFIG.theme, &CONFIG.themes, &theme_name)
+            .unwrap_or_else(|| {
+                eprintln!(
+                    "Warning: Theme '{}' not found, using the default theme.",
+                    theme_name
+                );
+                CONFIG.theme.colors.clone()
+            });
+        let theme = Theme {
+            name: theme_name,
+            colors,
+            syntax: theme::load_code_theme(CONFIG.theme.code_theme.as_deref()),
+            line_number_gutter: CONFIG
+                .app
+                .line_numbers
+                .then_some(CONFIG.app.line_number_width),
+            show_reasoning: CONFIG.app.show_reasoning,
+            spacing: CONFIG.app.spacing(),
+        };
 
-        let width = CONFIG.app.width;
+        // A size chosen by resizing the overlay takes priority over the config
+        let max_width = saved.width.unwrap_or(CONFIG.app.width).max(MIN_WIDTH);
+        let width = fitted_width(&font, &initial_text, &theme, max_width);
+        let max_height = saved
+            .max_height
+            .unwrap_or(CONFIG.app.max_height)
+            .max(MIN_HEIGHT);
         let calculated_height = calculate_text_height(&font, &initial_text, &ss, &theme, width);
-        let height = calculated_height.min(CONFIG.app.max_height);
+        let height = calculated_height.min(max_height);
 
         let mut new_state = Self {
             compositor: None,
             shm: None,
             layer_shell: None,
+            xdg_wm_base: None,
             output: None,
+            outputs: Vec::new(),
+            awaiting_output: false,
             seat: None,
             pointer: None,
+            keyboard: None,
+            touch_device: None,
+            cursor_shape_manager: None,
+            blur_manager: None,
+            cursor_device: None,
+            pointer_enter_serial: 0,
+            cursor_shape: None,
             surface: None,
             layer_surface: None,
+            xdg_window: None,
+            press_serial: 0,
             current_buffer: None,
+            renderer: Renderer::spawn(ss.clone()),
+            restyle: true,
+            mirrors: Vec::new(),
             width,
             height,
+            max_width,
+            max_height,
             margin_x: loaded_margin_x,
             margin_y: loaded_margin_y,
-            visible: true,
+            visible: saved.visible,
+            idle_hidden: false,
+            fullscreen_hidden: false,
+            last_activity: Instant::now(),
+            toplevel_manager: None,
+            toplevels: Vec::new(),
             border_radius: CONFIG.app.border_radius,
             drag_state: DragState::default(),
             pointer_x: 0.0,
@@ -273,11 +869,40 @@ impl AppState {
             last_text_update: Instant::now(),
             text_update_interval: Duration::from_millis(10),
             current_workspace: initial_workspace,
+            workspaces: existing_workspaces(),
+            titles: HashMap::new(),
             text_changed: false,
-            scroll_offset_y: 0.0,
+            ctrl_pressed: false,
+            capture_paused: std::path::Path::new(PAUSE_FILE).exists(),
+            answer_page: 0,
+            answer_pages: 0,
+            pane: None,
+            diff_with: None,
+            collapsed: std::path::Path::new(COLLAPSE_FILE).exists(),
+            keyboard_interactivity: keyboard_interactivity(
+                std::path::Path::new(KEYBOARD_GRAB_FILE).exists(),
+            ),
+            progress: None,
+            components_alive: Vec::new(),
+            thinking_since: None,
+            spinner_step: 0,
+            status_line: None,
+            // Answers that finished before the overlay started are not news
+            answers_finished: existing_workspaces()
+                .into_iter()
+                .filter_map(|workspace| Some((workspace, Status::read(workspace)?.finished?)))
+                .collect(),
+            config_modified: Self::config_modified_time(),
+            scroll_offset_y: saved.scroll_for(initial_workspace),
             max_scroll_offset_y: 0.0,
+            touch: None,
+            kinetic: Kinetic::default(),
+            axis_frame: AxisFrame::default(),
+            axis_velocity: VelocityTracker::default(),
             syntax_set: ss,
             theme,
+            saved,
+            state_dirty: false,
         };
 
         // Calculate initial max_scroll_offset_y
@@ -290,290 +915,1299 @@ impl AppState {
         );
         new_state.max_scroll_offset_y =
             (total_text_height as f32 - new_state.height as f32).max(0.0);
+        new_state.scroll_offset_y = new_state.scroll_offset_y.min(new_state.max_scroll_offset_y);
 
         new_state
     }
 
     fn read_current_workspace() -> Option<u32> {
-        std::fs::read_to_string(".aerogel_workspace")
+        std::fs::read_to_string(WORKSPACE_FILE)
             .ok()
             .and_then(|s| s.trim().parse::<u32>().ok())
     }
 
-    fn check_for_workspace_switch(&mut self, qh: &QueueHandle<Self>) {
+    fn check_for_workspace_switch(&mut self) {
         if let Some(new_workspace) = Self::read_current_workspace() {
             if new_workspace != self.current_workspace && new_workspace > 0 {
                 println!("[overlay] Switching to workspace {}", new_workspace);
+                self.remember_scroll();
                 self.current_workspace = new_workspace;
-                self.scroll_offset_y = 0.0;
-                self.last_text_update = Instant::now() - (self.text_update_interval * 2);
-                self.force_redraw(qh);
+                self.answer_page = 0;
+                self.pane = None;
+                self.diff_with = None;
+                self.font
+                    .set_size(Self::load_zoom(new_workspace).unwrap_or(CONFIG.font.size));
+                self.show_saved_scroll();
+                self.force_redraw();
             }
         }
     }
 
-    fn load_margins_from_log() -> Option<(i32, i32)> {
-        let file = File::open("margin.log").ok()?;
-        let reader = BufReader::new(file);
-
-        let mut last_margin_x = None;
-        let mut last_margin_y = None;
-        let mut last_line = String::new();
-
-        // Read all lines and extract the most recent margin values
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Parse lines in format: "Dragging: x=value, y=value"
-                if line.contains("Dragging:") {
-                    // Find x= and y= values
-                    if let Some(x_start) = line.find("x=") {
-                        if let Some(x_end) = line[x_start + 2..].find(',') {
-                            let x_str = &line[x_start + 2..x_start + 2 + x_end];
-                            if let Ok(x_val) = x_str.trim().parse::<i32>() {
-                                last_margin_x = Some(x_val);
-                            }
-                        }
-                    }
-
-                    if let Some(y_start) = line.find("y=") {
-                        let y_str = &line[y_start + 2..];
-                        // Remove any trailing whitespace or characters
-                        let y_clean = y_str
-                            .trim()
-                            .trim_end_matches(|c: char| !c.is_ascii_digit() && c != '-');
-                        if let Ok(y_val) = y_clean.parse::<i32>() {
-                            last_margin_y = Some(y_val);
-                        }
-                    }
-
-                    // Keep track of the last valid line
-                    last_line = line;
-                }
-            }
+    // Pages `step` answers back (positive) or forward through the workspace's
+    // past answers; paging forward past the newest shows the transcript again.
+    fn page_answers(&mut self, step: isize) {
+        self.answer_pages = AnswerHistory::paths_newest_first(self.current_workspace).len();
+        let page = self
+            .answer_page
+            .saturating_add_signed(step)
+            .min(self.answer_pages);
+        if page != self.answer_page || self.pane.is_some() {
+            self.show(page, None);
         }
+    }
 
-        // Clear the log file and write back only the last line
-        if let Ok(mut file) = File::create("margin.log") {
-            if !last_line.is_empty() {
-                let _ = writeln!(file, "{}", last_line);
-            }
+    // Steps through the providers' panes of the current round, with the
+    // transcript before the first and after the last.
+    fn cycle_pane(&mut self, step: isize) {
+        let panes = Panes::new(Some(self.current_workspace)).providers();
+        let current = self
+            .pane
+            .as_ref()
+            .and_then(|pane| panes.iter().position(|p| p == pane))
+            .map_or(0, |index| index + 1);
+        let next = (current as isize + step).rem_euclid(panes.len() as isize + 1) as usize;
+        let pane = next.checked_sub(1).map(|index| panes[index].clone());
+        if pane != self.pane || self.answer_page > 0 {
+            self.show(0, pane);
         }
+    }
 
-        // Return the last found values, or None if not found
-        match (last_margin_x, last_margin_y) {
-            (Some(x), Some(y)) => {
-                println!("Loaded margins from log: x={}, y={}", x, y);
-                Some((x, y))
-            }
-            _ => {
-                println!("No valid margins found in log file, using defaults");
-                None
-            }
+    // Shows a past answer, a provider's pane, or with neither the transcript
+    // where it was left.
+    fn show(&mut self, answer_page: usize, pane: Option<String>) {
+        self.remember_scroll();
+        self.answer_page = answer_page;
+        self.pane = pane;
+        self.diff_with = None;
+        if self.showing_transcript() {
+            self.show_saved_scroll();
+        } else {
+            self.scroll_offset_y = 0.0;
+            self.last_text_update = Instant::now() - (self.text_update_interval * 2);
+            self.update_text_from_log();
         }
+        self.force_redraw();
     }
 
-    fn load_text_from_log(filename: &str) -> Option<String> {
-        match std::fs::read_to_string(filename) {
-            Ok(content) => {
-                if content.trim().is_empty() {
-                    None
-                } else {
-                    Some(content)
-                }
-            }
-            Err(_) => None,
+    // Diffs the open pane against the next other provider's answer, and
+    // after the last one shows the pane alone again. Without an open pane
+    // the first two providers are compared.
+    fn cycle_diff(&mut self) {
+        let panes = Panes::new(Some(self.current_workspace)).providers();
+        if self.pane.is_none() && panes.len() >= 2 {
+            self.show(0, panes.first().cloned());
         }
+        let Some(pane) = self.pane.clone() else {
+            return;
+        };
+        let others: Vec<&String> = panes.iter().filter(|other| **other != pane).collect();
+        let current = self
+            .diff_with
+            .as_ref()
+            .and_then(|diff_with| others.iter().position(|other| *other == diff_with))
+            .map_or(0, |index| index + 1);
+        let next = (current + 1) % (others.len() + 1);
+        self.diff_with = next.checked_sub(1).map(|index| others[index].clone());
+        self.scroll_offset_y = 0.0;
+        self.last_text_update = Instant::now() - (self.text_update_interval * 2);
+        self.update_text_from_log();
+        self.force_redraw();
     }
 
-    fn update_text_from_log(&mut self) -> bool {
-        let now = Instant::now();
-
-        if now.duration_since(self.last_text_update) >= self.text_update_interval {
-            self.last_text_update = now;
+    fn showing_transcript(&self) -> bool {
+        self.answer_page == 0 && self.pane.is_none()
+    }
 
-            let temp_file = format!(".tmp{}", self.current_workspace);
-            let new_text = Self::load_text_from_log(&temp_file).unwrap_or_else(get_default_text);
+    // Loads the current workspace's text right away and scrolls to where it
+    // was left, so the saved offset is not drawn over, or clamped to, the
+    // text that was shown before.
+    fn show_saved_scroll(&mut self) {
+        self.last_text_update = Instant::now() - (self.text_update_interval * 2);
+        self.update_text_from_log();
+        self.relayout();
+        self.scroll_offset_y = self
+            .saved
+            .scroll_for(self.current_workspace)
+            .min(self.max_scroll_offset_y);
+    }
 
-            if new_text != self.text {
-                self.text = new_text;
-                self.text_changed = true;
+    // Switches the whole pipeline to a workspace whose tab was clicked. The
+    // event handler picks the new workspace up from the state file.
+    fn select_workspace(&mut self, workspace: u32) {
+        if let Err(e) = std::fs::write(WORKSPACE_FILE, workspace.to_string()) {
+            eprintln!(
+                "[overlay] Failed to switch to workspace {}: {}",
+                workspace, e
+            );
+            return;
+        }
+        self.check_for_workspace_switch();
+    }
 
-                // Recalculate height
-                let calculated_height = calculate_text_height(
-                    &self.font,
-                    &self.text,
-                    &self.syntax_set,
-                    &self.theme,
-                    self.width,
-                );
-                self.height = calculated_height.min(CONFIG.app.max_height);
-
-                // Calculate max_scroll_offset_y
-                let total_text_height = calculate_text_height(
-                    &self.font,
-                    &self.text,
-                    &self.syntax_set,
-                    &self.theme,
-                    self.width,
-                );
-                self.max_scroll_offset_y = (total_text_height as f32 - self.height as f32).max(0.0);
+    fn check_for_workspaces(&mut self) {
+        let workspaces = existing_workspaces();
+        let titles = conversation_titles(&workspaces, self.current_workspace);
+        if workspaces != self.workspaces || titles != self.titles {
+            self.workspaces = workspaces;
+            self.titles = titles;
+            self.force_redraw();
+        }
+    }
 
-                if CONFIG.app.auto_scroll {
-                    self.scroll_offset_y = self.max_scroll_offset_y;
-                } else {
-                    self.scroll_offset_y = self.scroll_offset_y.min(self.max_scroll_offset_y);
-                }
+    // The workspace whose tab is at (x, y) on the expanded overlay.
+    fn tab_at(&self, x: f64, y: f64) -> Option<u32> {
+        if self.collapsed {
+            return None;
+        }
+        let (_, tab_height) = chip_size(&self.font, "");
+        if !(CHIP_PADDING as f64..(CHIP_PADDING + tab_height) as f64).contains(&y) {
+            return None;
+        }
+        workspace_tabs(&self.font, &self.status_bar())
+            .into_iter()
+            .find(|(_, label, left)| {
+                let (tab_width, _) = chip_size(&self.font, label);
+                (*left as f64..(left + tab_width) as f64).contains(&x)
+            })
+            .map(|(workspace, _, _)| workspace)
+    }
 
-                if let Some(layer_surface) = &self.layer_surface {
-                    layer_surface.set_size(self.width, self.height);
-                    if let Some(surface) = &self.surface {
-                        surface.commit();
-                    }
-                }
+    fn check_for_capture_pause(&mut self) {
+        let paused = std::path::Path::new(PAUSE_FILE).exists();
+        if paused != self.capture_paused {
+            self.capture_paused = paused;
+            self.force_redraw();
+        }
+    }
 
-                return true;
-            }
+    fn check_for_collapse(&mut self) {
+        let collapsed = std::path::Path::new(COLLAPSE_FILE).exists();
+        if collapsed != self.collapsed {
+            self.apply_collapsed(collapsed);
         }
+    }
 
-        false
+    fn check_for_keyboard_grab(&mut self) {
+        let mode = keyboard_interactivity(std::path::Path::new(KEYBOARD_GRAB_FILE).exists());
+        if mode == self.keyboard_interactivity {
+            return;
+        }
+        println!("[overlay] Keyboard interactivity: {:?}", mode);
+        self.keyboard_interactivity = mode;
+        if let (Some(layer_surface), Some(surface)) = (&self.layer_surface, &self.surface) {
+            layer_surface.set_keyboard_interactivity(mode);
+            surface.commit();
+        }
     }
 
-    fn create_overlay(&mut self, qh: &QueueHandle<AppState>) {
-        if let (Some(compositor), Some(layer_shell)) = (&self.compositor, &self.layer_shell) {
-            let surface = compositor.create_surface(qh, ());
-
-            let layer_surface = layer_shell.get_layer_surface(
-                &surface,
-                self.output.as_ref(),
-                Layer::Overlay,
-                "draggable-overlay".to_string(),
-                qh,
-                (),
-            );
+    // Collapses the overlay to its chip or expands it again, remembering the
+    // choice in COLLAPSE_FILE.
+    fn toggle_collapsed(&mut self) {
+        let collapsed = !self.collapsed;
+        let result = if collapsed {
+            std::fs::write(COLLAPSE_FILE, "")
+        } else {
+            std::fs::remove_file(COLLAPSE_FILE)
+        };
+        if let Err(e) = result {
+            eprintln!("[overlay] Failed to update {}: {}", COLLAPSE_FILE, e);
+        }
+        self.apply_collapsed(collapsed);
+    }
 
-            layer_surface.set_size(self.width, self.height);
-            layer_surface.set_anchor(Anchor::Top | Anchor::Left);
-            layer_surface.set_margin(self.margin_y, 0, 0, self.margin_x);
-            layer_surface.set_keyboard_interactivity(KeyboardInteractivity::OnDemand);
+    fn apply_collapsed(&mut self, collapsed: bool) {
+        self.collapsed = collapsed;
+        self.relayout();
+        // The expanded overlay may not fit where the chip was moved to
+        let (max_x, max_y) = self.max_margins();
+        self.move_to(self.margin_x.min(max_x), self.margin_y.min(max_y));
+        self.force_redraw();
+    }
 
-            surface.commit();
+    // Size of the layer surfaces: the chip alone while collapsed.
+    fn surface_size(&self) -> (u32, u32) {
+        if self.collapsed {
+            let (chip_width, chip_height) = chip_size(&self.font, &self.chip_label());
+            (
+                (chip_width + 2.0 * CHIP_PADDING).ceil() as u32,
+                (chip_height + 2.0 * CHIP_PADDING).ceil() as u32,
+            )
+        } else {
+            (self.width, self.height)
+        }
+    }
 
-            self.surface = Some(surface);
-            self.layer_surface = Some(layer_surface);
+    fn check_for_progress(&mut self) {
+        let progress = std::fs::read_to_string(PROGRESS_FILE)
+            .ok()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty());
+        if progress != self.progress {
+            self.progress = progress;
+            self.force_redraw();
         }
     }
 
-    fn draw_overlay(&mut self, qh: &QueueHandle<AppState>) {
-        if let (Some(surface), Some(shm)) = (&self.surface, &self.shm) {
-            if !self.visible {
-                return;
-            }
+    fn config_modified_time() -> Option<std::time::SystemTime> {
+        std::fs::metadata(find_config_path()?).ok()?.modified().ok()
+    }
 
-            // Destroy the old buffer if it exists
-            if let Some(old_buffer) = self.current_buffer.take() {
-                old_buffer.destroy();
-            }
+    // Reloads the code theme when aerogel.toml has been modified.
+    fn check_for_config_reload(&mut self) {
+        let modified = Self::config_modified_time();
+        if modified == self.config_modified {
+            return;
+        }
+        self.config_modified = modified;
 
-            if let Ok(buffer) = create_shm_buffer(
-                shm,
-                self.width,
-                self.height,
-                self.border_radius,
-                &self.font,
-                &self.text,
-                &self.syntax_set,
-                &self.theme,
-                self.scroll_offset_y,
-                self.current_workspace,
-                qh,
-            ) {
-                self.current_buffer = Some(buffer.clone());
-                surface.attach(Some(&buffer), 0, 0);
-                surface.damage(0, 0, self.width as i32, self.height as i32);
-                surface.commit();
+        let Some(path) = find_config_path() else {
+            return;
+        };
+        let reloaded = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                toml::from_str::<ReloadableConfig>(&content).map_err(|e| e.to_string())
+            });
+        match reloaded {
+            Ok(config) => {
+                println!("[overlay] Reloaded {}", path.display());
+                self.theme.syntax = theme::load_code_theme(config.theme.code_theme.as_deref());
+                self.force_redraw();
             }
+            Err(e) => eprintln!("[overlay] Failed to reload {}: {}", path.display(), e),
         }
+    }
 
-        // Reset text changed flag after drawing
-        self.text_changed = false;
+    fn heartbeat_files(&self) -> Vec<String> {
+        let mut files = Vec::new();
+        if CONFIG.input.backend.as_deref() != Some("compositor") {
+            files.push(heartbeat::LISTENER_FILE.to_string());
+        }
+        files.push(heartbeat::HANDLER_FILE.to_string());
+        files.push(format!(
+            "{}{}",
+            heartbeat::AI_FILE_PREFIX,
+            self.current_workspace
+        ));
+        files
     }
 
-    fn toggle_visibility(&mut self, qh: &QueueHandle<AppState>) {
-        self.visible = !self.visible;
-        if self.visible {
-            self.draw_overlay(qh);
-        } else if let Some(surface) = &self.surface {
-            surface.attach(None, 0, 0);
-            surface.commit();
+    fn check_for_heartbeats(&mut self) {
+        let alive: Vec<bool> = self
+            .heartbeat_files()
+            .iter()
+            .map(|file| {
+                std::fs::metadata(file)
+                    .and_then(|meta| meta.modified())
+                    // A timestamp in the future counts as fresh
+                    .map(|modified| {
+                        modified
+                            .elapsed()
+                            .map_or(true, |age| age < heartbeat::TIMEOUT)
+                    })
+                    .unwrap_or(false)
+            })
+            .collect();
+        if alive != self.components_alive {
+            self.components_alive = alive;
+            self.force_redraw();
         }
     }
 
-    fn start_drag(&mut self, surface_x: f64, surface_y: f64) {
-        self.drag_state.is_dragging = true;
-        // Store the initial pointer position to calculate deltas from.
-        self.drag_state.start_x = surface_x;
-        self.drag_state.start_y = surface_y;
+    fn check_for_thinking(&mut self) {
+        let file = ipc::thinking_file(self.current_workspace);
+        let thinking = std::fs::metadata(file)
+            .and_then(|meta| meta.modified())
+            .map(|modified| {
+                modified
+                    .elapsed()
+                    .map_or(true, |age| age < THINKING_TIMEOUT)
+            })
+            .unwrap_or(false);
+        if thinking != self.thinking_since.is_some() {
+            self.thinking_since = thinking.then(Instant::now);
+            self.spinner_step = 0;
+            self.draw_overlay();
+        }
     }
 
-    fn update_drag(&mut self, surface_x: f64, surface_y: f64) {
-        if !self.drag_state.is_dragging {
+    // Advances the spinner while waiting for an answer.
+    fn animate_spinner(&mut self) {
+        let Some(since) = self.thinking_since else {
             return;
+        };
+        let step = (since.elapsed().as_millis() / SPINNER_FRAME.as_millis()) as usize;
+        if step != self.spinner_step {
+            self.spinner_step = step;
+            self.draw_overlay();
         }
+    }
 
-        // Calculate pointer movement since the last motion event.
-        let dx = surface_x - self.drag_state.start_x;
-        let dy = surface_y - self.drag_state.start_y;
-
-        // Apply the delta to our current margins.
-        let new_margin_x = self.margin_x + dx as i32;
-        let new_margin_y = self.margin_y + dy as i32;
-
-        // Clamp to screen boundaries.
-        let max_x = self.output_width.saturating_sub(self.width as i32).max(0);
-        let max_y = self.output_height.saturating_sub(self.height as i32).max(0);
-        self.margin_x = new_margin_x.max(0).min(max_x);
-        self.margin_y = new_margin_y.max(0).min(max_y);
-
-        // Update the reference point for the next motion event.
-        self.drag_state.start_x = surface_x;
-        self.drag_state.start_y = surface_y;
-
-        // Log position
-        if let Ok(mut file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("margin.log")
-        {
-            writeln!(file, "Dragging: x={}, y={}", self.margin_x, self.margin_y).ok();
+    // Refreshes the status line from the AI manager's status file; the
+    // elapsed time makes it change every second during a request.
+    fn check_for_status_line(&mut self) {
+        if !CONFIG.app.status_line {
+            return;
         }
+        let status = Status::read(self.current_workspace).unwrap_or_default();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let line = Some(format_status_line(self.current_workspace, &status, now));
+        if line != self.status_line {
+            self.status_line = line;
+            self.draw_overlay();
+        }
+    }
 
-        // Update the layer surface with the new position.
-        if let Some(layer_surface) = &self.layer_surface {
-            layer_surface.set_margin(self.margin_y, 0, 0, self.margin_x);
-            if let Some(surface) = &self.surface {
-                surface.commit();
+    // Notifies about answers that finished since the last check while the
+    // overlay is hidden or collapsed, so it doesn't have to stay visible.
+    fn check_for_finished_answers(&mut self) {
+        if !CONFIG.app.notify_when_hidden {
+            return;
+        }
+        let mut workspaces = self.workspaces.clone();
+        if !workspaces.contains(&self.current_workspace) {
+            workspaces.push(self.current_workspace);
+        }
+        let unseen = !self.shown() || self.collapsed;
+        for workspace in workspaces {
+            let Some(status) = Status::read(workspace) else {
+                continue;
+            };
+            let Some(finished) = status.finished else {
+                continue;
+            };
+            let previous = self.answers_finished.insert(workspace, finished);
+            if unseen && previous != Some(finished) {
+                notify_answer_ready(workspace, &status);
             }
         }
     }
 
-    fn stop_drag(&mut self) {
-        if self.drag_state.is_dragging {
-            self.drag_state.is_dragging = false;
+    fn status_bar(&self) -> StatusBar {
+        StatusBar {
+            chip_label: self.chip_label(),
+            workspace: self.current_workspace,
+            workspaces: self.workspaces.clone(),
+            titles: self.titles.clone(),
+            components_alive: self.components_alive.clone(),
+            spinner: self.thinking_since.map(|_| self.spinner_step),
+            status_line: self.status_line.clone(),
         }
     }
 
-    fn force_redraw(&mut self, qh: &QueueHandle<AppState>) {
-        self.text_changed = true;
-        self.draw_overlay(qh);
+    // Text of the workspace chip, with the conversation's title, a discreet
+    // note while capture is paused and the progress of a model pull.
+    fn chip_label(&self) -> String {
+        let mut label = tab_label(
+            self.current_workspace,
+            self.titles.get(&self.current_workspace),
+        );
+        if self.capture_paused {
+            label.push_str(" · paused");
+        }
+        if self.answer_page > 0 {
+            label.push_str(&format!(
+                " · answer -{}/{}",
+                self.answer_page, self.answer_pages
+            ));
+        }
+        if let Some(pane) = &self.pane {
+            label.push_str(" · ");
+            label.push_str(pane);
+        }
+        if let Some(diff_with) = &self.diff_with {
+            label.push_str(" vs ");
+            label.push_str(diff_with);
+        }
+        if let Some(progress) = &self.progress {
+            label.push_str(" · ");
+            label.push_str(progress);
+        }
+        label
     }
-}
 
-// Global registry handling
-impl Dispatch<wayland_client::protocol::wl_registry::WlRegistry, ()> for AppState {
-    fn event(
-        state: &mut Self,
-        registry: &wayland_client::protocol::wl_registry::WlRegistry,
-        event: wayland_client::protocol::wl_registry::Event,
+    // Keeps the workspace's scroll offset; that of a past answer or a pane
+    // is not it.
+    fn remember_scroll(&mut self) {
+        if !self.showing_transcript() {
+            return;
+        }
+        self.saved
+            .scroll
+            .insert(self.current_workspace.to_string(), self.scroll_offset_y);
+        self.state_dirty = true;
+    }
+
+    // Writes the overlay state when something changed since the last save.
+    fn save_state_if_dirty(&mut self) {
+        if !self.state_dirty {
+            return;
+        }
+        self.saved.margin_x = Some(self.margin_x);
+        self.saved.margin_y = Some(self.margin_y);
+        self.saved.width = Some(self.max_width);
+        self.saved.max_height = Some(self.max_height);
+        self.saved.visible = self.visible;
+        self.remember_scroll();
+        self.state_dirty = false;
+        if let Err(e) = self.saved.save() {
+            eprintln!("[overlay] Failed to save state: {}", e);
+        }
+    }
+
+    // Reads the zoomed font size saved for a workspace. The zoom file holds
+    // one "workspace=size" line per workspace.
+    fn load_zoom(workspace: u32) -> Option<f32> {
+        let content = std::fs::read_to_string(ZOOM_STATE_FILE).ok()?;
+        content.lines().find_map(|line| {
+            let (ws, size) = line.split_once('=')?;
+            if ws.trim().parse::<u32>().ok()? == workspace {
+                size.trim().parse::<f32>().ok()
+            } else {
+                None
+            }
+        })
+    }
+
+    fn save_zoom(workspace: u32, size: f32) {
+        let mut entries: Vec<String> = std::fs::read_to_string(ZOOM_STATE_FILE)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| {
+                line.split_once('=')
+                    .and_then(|(ws, _)| ws.trim().parse::<u32>().ok())
+                    .is_some_and(|ws| ws != workspace)
+            })
+            .map(str::to_string)
+            .collect();
+        entries.push(format!("{}={}", workspace, size));
+        if let Err(e) = std::fs::write(ZOOM_STATE_FILE, entries.join("\n") + "\n") {
+            eprintln!("[overlay] Failed to save zoom level: {}", e);
+        }
+    }
+
+    // Scales the font size by `factor` (or resets it to the configured size
+    // when None), re-wraps the text and resizes the overlay.
+    fn zoom(&mut self, factor: Option<f32>) {
+        let size = match factor {
+            Some(factor) => (self.font.size() * factor).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE),
+            None => CONFIG.font.size,
+        };
+        if size == self.font.size() {
+            return;
+        }
+
+        self.font.set_size(size);
+        Self::save_zoom(self.current_workspace, size);
+        self.relayout();
+        self.force_redraw();
+    }
+
+    // Switches to the next configured theme.
+    fn cycle_theme(&mut self) {
+        let name = theme::next_theme_name(&CONFIG.themes, &self.theme.name);
+        if let Some(colors) = theme::theme_colors(&CONFIG.theme, &CONFIG.themes, &name) {
+            println!("[overlay] Switching to theme '{}'", name);
+            self.theme.name = name;
+            self.theme.colors = colors;
+            self.force_redraw();
+        }
+    }
+
+    // Shows or hides line numbers in code blocks.
+    fn toggle_line_numbers(&mut self) {
+        self.theme.line_number_gutter = match self.theme.line_number_gutter {
+            Some(_) => None,
+            None => Some(CONFIG.app.line_number_width),
+        };
+        self.relayout();
+        self.force_redraw();
+    }
+
+    // Shows the models' reasoning in full or folds it to one line.
+    fn toggle_reasoning(&mut self) {
+        self.theme.show_reasoning = !self.theme.show_reasoning;
+        self.relayout();
+        self.force_redraw();
+    }
+
+    // Recomputes the overlay height and scroll bounds for the current text,
+    // font size and width, and resizes the layer surface to match.
+    fn relayout(&mut self) {
+        self.width = fitted_width(&self.font, &self.text, &self.theme, self.max_width);
+        let total_text_height = calculate_text_height(
+            &self.font,
+            &self.text,
+            &self.syntax_set,
+            &self.theme,
+            self.width,
+        ) + status_line_height(&self.font);
+        self.height = total_text_height.min(self.max_height);
+        self.max_scroll_offset_y = (total_text_height as f32 - self.height as f32).max(0.0);
+        self.scroll_offset_y = self.scroll_offset_y.min(self.max_scroll_offset_y);
+
+        let (width, height) = self.surface_size();
+        for (layer_surface, surface) in self.layer_surfaces() {
+            layer_surface.set_size(width, height);
+            surface.commit();
+        }
+    }
+
+    // The overlay's layer surface followed by those of its mirrors.
+    fn layer_surfaces(&self) -> impl Iterator<Item = (&ZwlrLayerSurfaceV1, &WlSurface)> {
+        self.layer_surface
+            .iter()
+            .zip(self.surface.iter())
+            .chain(self.mirrors.iter().map(|m| (&m.layer_surface, &m.surface)))
+    }
+
+    fn load_text_from_log(filename: &std::path::Path) -> Option<String> {
+        match std::fs::read_to_string(filename) {
+            Ok(content) => {
+                if content.trim().is_empty() {
+                    None
+                } else {
+                    Some(content)
+                }
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn update_text_from_log(&mut self) -> bool {
+        let now = Instant::now();
+
+        if now.duration_since(self.last_text_update) >= self.text_update_interval {
+            self.last_text_update = now;
+
+            let new_text = match &self.pane {
+                // The heading says whose answer this is while it is empty too
+                Some(pane) => {
+                    let answer = Panes::new(Some(self.current_workspace)).read(pane);
+                    match &self.diff_with {
+                        Some(other) => answer_diff::diff_markdown(
+                            (pane, &answer),
+                            (other, &Panes::new(Some(self.current_workspace)).read(other)),
+                        ),
+                        None => format!("## {}\n\n{}", pane, answer),
+                    }
+                }
+                None => {
+                    let page = self.answer_page.checked_sub(1).and_then(|back| {
+                        AnswerHistory::paths_newest_first(self.current_workspace)
+                            .into_iter()
+                            .nth(back)
+                    });
+                    if page.is_none() {
+                        self.answer_page = 0;
+                    }
+                    let log_file: std::path::PathBuf =
+                        page.unwrap_or_else(|| format!(".tmp{}", self.current_workspace).into());
+                    Self::load_text_from_log(&log_file).unwrap_or_else(get_default_text)
+                }
+            };
+
+            if new_text != self.text {
+                self.text = new_text;
+                self.text_changed = true;
+                // New content brings an idle overlay back on the next draw
+                self.last_activity = now;
+                self.idle_hidden = false;
+
+                self.relayout();
+
+                if CONFIG.app.auto_scroll && self.answer_page == 0 {
+                    self.scroll_offset_y = self.max_scroll_offset_y;
+                }
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Picks the output configured with `[app] output`, or the first one.
+    fn select_output(&mut self) {
+        self.output = None;
+        let configured = CONFIG.app.output.as_deref().and_then(|pattern| {
+            let found = self.outputs.iter().find(|info| info.matches(pattern));
+            if found.is_none() {
+                let available: Vec<String> = self.outputs.iter().map(OutputInfo::label).collect();
+                eprintln!(
+                    "Warning: No output matches '{}', available: {}. Using the first one.",
+                    pattern,
+                    available.join(", ")
+                );
+            }
+            found
+        });
+        if let Some(info) = configured.or(self.outputs.first()) {
+            println!("[overlay] Showing on {}", info.label());
+            self.output = Some(info.output.clone());
+            self.output_width = info.width;
+            self.output_height = info.height;
+        }
+    }
+
+    // Wayland globals the overlay needs but the compositor did not announce.
+    fn missing_globals(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.compositor.is_none() {
+            missing.push("wl_compositor");
+        }
+        if self.shm.is_none() {
+            missing.push("wl_shm");
+        }
+        // A regular window stands in for the layer surface
+        if self.layer_shell.is_none() && self.xdg_wm_base.is_none() {
+            missing.push("zwlr_layer_shell_v1");
+        }
+        missing
+    }
+
+    // Whether the output picked by `[app] output` is not the one in use, so
+    // the overlay should move to it once it shows up.
+    fn prefers_other_output(&self) -> bool {
+        let Some(pattern) = CONFIG.app.output.as_deref() else {
+            return false;
+        };
+        !self
+            .outputs
+            .iter()
+            .any(|info| Some(&info.output) == self.output.as_ref() && info.matches(pattern))
+    }
+
+    // Recreates the overlay on the preferred surviving output, e.g. after
+    // its monitor was unplugged. With no output left, it waits for one.
+    fn move_to_output(&mut self, qh: &QueueHandle<AppState>) {
+        self.destroy_overlay();
+        self.select_output();
+        if self.output.is_none() {
+            println!("[overlay] No output left, waiting for one to appear");
+            self.awaiting_output = true;
+            return;
+        }
+        self.awaiting_output = false;
+        let (max_x, max_y) = self.max_margins();
+        self.margin_x = self.margin_x.min(max_x);
+        self.margin_y = self.margin_y.min(max_y);
+        self.create_overlay(qh);
+    }
+
+    fn destroy_overlay(&mut self) {
+        for mirror in self.mirrors.drain(..) {
+            mirror.destroy();
+        }
+        if let Some(window) = self.xdg_window.take() {
+            window.destroy();
+        }
+        if let Some(layer_surface) = self.layer_surface.take() {
+            layer_surface.destroy();
+        }
+        if let Some(surface) = self.surface.take() {
+            surface.destroy();
+        }
+        if let Some(buffer) = self.current_buffer.take() {
+            buffer.destroy();
+        }
+    }
+
+    fn create_overlay(&mut self, qh: &QueueHandle<AppState>) {
+        if self.layer_shell.is_none() {
+            self.create_xdg_window(qh);
+            return;
+        }
+        if let Some((surface, layer_surface)) =
+            self.new_layer_surface(self.output.as_ref(), self.keyboard_interactivity, qh)
+        {
+            self.surface = Some(surface);
+            self.layer_surface = Some(layer_surface);
+        }
+        self.sync_mirrors(qh);
+    }
+
+    fn new_layer_surface(
+        &self,
+        output: Option<&WlOutput>,
+        keyboard_interactivity: KeyboardInteractivity,
+        qh: &QueueHandle<AppState>,
+    ) -> Option<(WlSurface, ZwlrLayerSurfaceV1)> {
+        let (compositor, layer_shell) = (self.compositor.as_ref()?, self.layer_shell.as_ref()?);
+        let surface = compositor.create_surface(qh, ());
+
+        let layer_surface = layer_shell.get_layer_surface(
+            &surface,
+            output,
+            Layer::Overlay,
+            "draggable-overlay".to_string(),
+            qh,
+            (),
+        );
+
+        let (width, height) = self.surface_size();
+        layer_surface.set_size(width, height);
+        layer_surface.set_anchor(Anchor::Top | Anchor::Left);
+        layer_surface.set_margin(self.margin_y, 0, 0, self.margin_x);
+        layer_surface.set_keyboard_interactivity(keyboard_interactivity);
+        self.request_blur(&surface, qh);
+
+        surface.commit();
+        Some((surface, layer_surface))
+    }
+
+    fn create_xdg_window(&mut self, qh: &QueueHandle<AppState>) {
+        let (Some(compositor), Some(wm_base)) = (&self.compositor, &self.xdg_wm_base) else {
+            return;
+        };
+        let surface = compositor.create_surface(qh, ());
+        let xdg_surface = wm_base.get_xdg_surface(&surface, qh, ());
+        let toplevel = xdg_surface.get_toplevel(qh, ());
+        toplevel.set_title("Aerogel".to_string());
+        toplevel.set_app_id("aerogel-overlay".to_string());
+        toplevel.set_min_size(MIN_WIDTH as i32, MIN_HEIGHT as i32);
+        self.request_blur(&surface, qh);
+        // The first commit has no buffer; the configure event it brings
+        // lets the overlay draw
+        surface.commit();
+        self.surface = Some(surface);
+        self.xdg_window = Some(XdgWindow {
+            xdg_surface,
+            toplevel,
+            configured: false,
+        });
+    }
+
+    // Blurs the whole area behind `surface` from its next commit on. The
+    // blur stays with the surface after the request object is released.
+    fn request_blur(&self, surface: &WlSurface, qh: &QueueHandle<AppState>) {
+        if let Some(manager) = &self.blur_manager {
+            let blur = manager.create(surface, qh, ());
+            blur.set_region(None);
+            blur.commit();
+            blur.release();
+        }
+    }
+
+    // Hands a drag of the xdg-shell window over to the compositor once the
+    // pointer moved far enough for it not to be a click.
+    fn start_xdg_move(&mut self, surface_x: f64, surface_y: f64) {
+        let (Some(window), Some((seat, _))) = (&self.xdg_window, &self.seat) else {
+            return;
+        };
+        let moved =
+            (surface_x - self.drag_state.start_x).hypot(surface_y - self.drag_state.start_y);
+        if moved < XDG_DRAG_THRESHOLD {
+            return;
+        }
+        match self.drag_state.resize {
+            Some(edges) => window
+                .toplevel
+                .resize(seat, self.press_serial, edges.xdg_edge()),
+            None => window.toplevel._move(seat, self.press_serial),
+        }
+        // The compositor owns the pointer until the button is released
+        self.drag_state.is_dragging = false;
+        self.drag_state.resize = None;
+    }
+
+    // With `[app] mirror`, gives every output other than the overlay's own
+    // a mirror, and drops mirrors of outputs that are gone.
+    fn sync_mirrors(&mut self, qh: &QueueHandle<AppState>) {
+        if !CONFIG.app.mirror || self.surface.is_none() {
+            return;
+        }
+        let primary = self.output.clone();
+        let outputs = &self.outputs;
+        self.mirrors.retain(|mirror| {
+            let keep = primary.as_ref() != Some(&mirror.output)
+                && outputs.iter().any(|info| info.output == mirror.output);
+            if !keep {
+                mirror.destroy();
+            }
+            keep
+        });
+
+        let unmirrored: Vec<WlOutput> = self
+            .outputs
+            .iter()
+            .map(|info| info.output.clone())
+            .filter(|output| {
+                primary.as_ref() != Some(output)
+                    && !self.mirrors.iter().any(|mirror| &mirror.output == output)
+            })
+            .collect();
+        for output in unmirrored {
+            // Only the overlay itself takes keyboard input
+            if let Some((surface, layer_surface)) =
+                self.new_layer_surface(Some(&output), KeyboardInteractivity::None, qh)
+            {
+                self.mirrors.push(Mirror {
+                    output,
+                    surface,
+                    layer_surface,
+                });
+            }
+        }
+    }
+
+    // Asks the render thread for a new frame; present_frame attaches it.
+    fn draw_overlay(&mut self) {
+        if self.surface.is_some() && self.shown() {
+            let style = std::mem::take(&mut self.restyle).then(|| RenderStyle {
+                theme: self.theme.clone(),
+                font_size: self.font.size(),
+            });
+            self.renderer.request(RenderJob {
+                size: self.surface_size(),
+                text: self.text.clone(),
+                scroll_offset_y: self.scroll_offset_y,
+                border_radius: self.border_radius,
+                status: self.status_bar(),
+                collapsed: self.collapsed,
+                style,
+            });
+        }
+
+        // Reset text changed flag after drawing
+        self.text_changed = false;
+    }
+
+    // Waits up to `timeout` for the render thread and shows the frame it drew.
+    fn present_frame(&mut self, timeout: Duration, qh: &QueueHandle<AppState>) {
+        let Some(Frame {
+            width,
+            height,
+            pixels,
+        }) = self.renderer.next_frame(timeout)
+        else {
+            return;
+        };
+        // Frames drawn before a resize or hiding are stale; a newer one for
+        // the current size is on its way
+        if !self.shown() || (width, height) != self.surface_size() {
+            return;
+        }
+        if self
+            .xdg_window
+            .as_ref()
+            .is_some_and(|window| !window.configured)
+        {
+            return;
+        }
+        let (Some(surface), Some(shm)) = (&self.surface, &self.shm) else {
+            return;
+        };
+
+        // Destroy the old buffer if it exists
+        if let Some(old_buffer) = self.current_buffer.take() {
+            old_buffer.destroy();
+        }
+        let drawn = create_shm_buffer(shm, width, height, qh, |buffer| {
+            buffer.copy_from_slice(&pixels)
+        });
+        if let Ok(buffer) = drawn {
+            // Mirrors show the same buffer
+            let surfaces = std::iter::once(surface).chain(self.mirrors.iter().map(|m| &m.surface));
+            for surface in surfaces {
+                surface.attach(Some(&buffer), 0, 0);
+                surface.damage(0, 0, width as i32, height as i32);
+                surface.commit();
+            }
+            self.current_buffer = Some(buffer);
+        }
+    }
+
+    // Whether the overlay is on screen: shown by the user and not auto-hidden.
+    fn shown(&self) -> bool {
+        self.visible && !self.idle_hidden && !self.fullscreen_hidden
+    }
+
+    // Draws or unmaps the surfaces after `shown()` changed from `was_shown`.
+    fn apply_visibility(&mut self, was_shown: bool) {
+        match (was_shown, self.shown()) {
+            (false, true) => {
+                // An unmapped xdg-shell window is mapped again like a new
+                // one: an empty commit, then drawing after the configure
+                if let (Some(window), Some(surface)) = (&self.xdg_window, &self.surface)
+                    && !window.configured
+                {
+                    surface.commit();
+                }
+                self.force_redraw();
+            }
+            (true, false) => {
+                let xdg_surface = self.surface.iter().filter(|_| self.xdg_window.is_some());
+                for surface in self
+                    .layer_surfaces()
+                    .map(|(_, surface)| surface)
+                    .chain(xdg_surface)
+                {
+                    surface.attach(None, 0, 0);
+                    surface.commit();
+                }
+                if let Some(window) = &mut self.xdg_window {
+                    window.configured = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn toggle_visibility(&mut self) {
+        let was_shown = self.shown();
+        self.visible = !self.visible;
+        self.state_dirty = true;
+        // Showing it by hand overrides auto-hiding until the next change
+        self.idle_hidden = false;
+        self.fullscreen_hidden = false;
+        self.last_activity = Instant::now();
+        self.apply_visibility(was_shown);
+    }
+
+    // Hides the overlay after `[app] auto_hide_after` seconds of inactivity.
+    fn check_for_idle(&mut self) {
+        let after = CONFIG.app.auto_hide_after;
+        if after == 0
+            || !self.shown()
+            || self.drag_state.is_dragging
+            || self.last_activity.elapsed() < Duration::from_secs(after)
+        {
+            return;
+        }
+        self.idle_hidden = true;
+        self.apply_visibility(true);
+    }
+
+    // With `[app] hide_on_fullscreen`, hides the overlay while a window on
+    // its output is fullscreen and shows it again afterwards.
+    fn update_fullscreen(&mut self) {
+        let Some(output) = &self.output else {
+            return;
+        };
+        let fullscreen = self
+            .toplevels
+            .iter()
+            .any(|toplevel| toplevel.fullscreen && toplevel.outputs.contains(output));
+        if fullscreen != self.fullscreen_hidden {
+            let was_shown = self.shown();
+            self.fullscreen_hidden = fullscreen;
+            self.apply_visibility(was_shown);
+        }
+    }
+
+    fn start_drag(&mut self, surface_x: f64, surface_y: f64) {
+        self.drag_state.is_dragging = true;
+        self.drag_state.pressed_at = (self.margin_x, self.margin_y);
+        // The collapsed chip can only be moved
+        self.drag_state.resize = if self.collapsed {
+            None
+        } else {
+            ResizeEdges::at(surface_x, surface_y, self.width, self.height)
+        };
+        // Store the initial pointer position to calculate deltas from.
+        self.drag_state.start_x = surface_x;
+        self.drag_state.start_y = surface_y;
+        self.drag_state.unsnapped_x = self.margin_x;
+        self.drag_state.unsnapped_y = self.margin_y;
+    }
+
+    fn update_drag(&mut self, surface_x: f64, surface_y: f64) {
+        if !self.drag_state.is_dragging {
+            return;
+        }
+        if self.xdg_window.is_some() {
+            self.start_xdg_move(surface_x, surface_y);
+            return;
+        }
+
+        // Calculate pointer movement since the last motion event.
+        let dx = surface_x - self.drag_state.start_x;
+        let dy = surface_y - self.drag_state.start_y;
+
+        if let Some(edges) = self.drag_state.resize {
+            self.update_resize(edges, dx, dy);
+            // Left and top edges move the surface, so the pointer stays put
+            // in surface coordinates along those axes.
+            if !edges.left {
+                self.drag_state.start_x = surface_x;
+            }
+            if !edges.top {
+                self.drag_state.start_y = surface_y;
+            }
+            return;
+        }
+
+        // Apply the delta to the unsnapped position and clamp it to the
+        // screen boundaries.
+        let (max_x, max_y) = self.max_margins();
+        let drag = &mut self.drag_state;
+        drag.unsnapped_x = (drag.unsnapped_x + dx as i32).max(0).min(max_x);
+        drag.unsnapped_y = (drag.unsnapped_y + dy as i32).max(0).min(max_y);
+
+        // Update the reference point for the next motion event.
+        drag.start_x = surface_x;
+        drag.start_y = surface_y;
+
+        let snap = CONFIG.app.snap_distance;
+        let margin_x = snap_to_edge(drag.unsnapped_x, max_x, snap);
+        let margin_y = snap_to_edge(drag.unsnapped_y, max_y, snap);
+        self.move_to(margin_x, margin_y);
+    }
+
+    // The cursor for the pointer's position: grabbing while moving the
+    // overlay, a resize arrow on the border, a hand on the tabs and the chip,
+    // an open hand on the strip above the text, and a text cursor over it.
+    fn wanted_cursor_shape(&self) -> Shape {
+        let (x, y) = (self.pointer_x, self.pointer_y);
+        if self.drag_state.is_dragging {
+            return self
+                .drag_state
+                .resize
+                .map_or(Shape::Grabbing, ResizeEdges::cursor_shape);
+        }
+        if self.collapsed {
+            return Shape::Pointer;
+        }
+        if let Some(edges) = ResizeEdges::at(x, y, self.width, self.height) {
+            edges.cursor_shape()
+        } else if self.tab_at(x, y).is_some() {
+            Shape::Pointer
+        } else if y < TOUCH_DRAG_AREA {
+            Shape::Grab
+        } else {
+            Shape::Text
+        }
+    }
+
+    fn update_cursor(&mut self) {
+        let shape = self.wanted_cursor_shape();
+        if let Some(device) = &self.cursor_device
+            && self.cursor_shape != Some(shape)
+        {
+            device.set_shape(self.pointer_enter_serial, shape);
+            self.cursor_shape = Some(shape);
+        }
+    }
+
+    fn max_margins(&self) -> (i32, i32) {
+        let (width, height) = self.surface_size();
+        (
+            self.output_width.saturating_sub(width as i32).max(0),
+            self.output_height.saturating_sub(height as i32).max(0),
+        )
+    }
+
+    // Moves the overlay to the given margins from the top left corner.
+    fn move_to(&mut self, margin_x: i32, margin_y: i32) {
+        if (margin_x, margin_y) == (self.margin_x, self.margin_y) {
+            return;
+        }
+        self.margin_x = margin_x;
+        self.margin_y = margin_y;
+        self.state_dirty = true;
+
+        // Update the layer surfaces with the new position.
+        for (layer_surface, surface) in self.layer_surfaces() {
+            layer_surface.set_margin(self.margin_y, 0, 0, self.margin_x);
+            surface.commit();
+        }
+    }
+
+    // Moves the overlay to the preset position after the one it is at.
+    fn cycle_position_preset(&mut self) {
+        let (max_x, max_y) = self.max_margins();
+        let positions: Vec<(i32, i32)> = POSITION_PRESETS
+            .iter()
+            .map(|&(_, fx, fy)| preset_margins(fx, fy, max_x, max_y))
+            .collect();
+        let current = positions
+            .iter()
+            .position(|&p| p == (self.margin_x, self.margin_y));
+        let next = current.map_or(0, |i| (i + 1) % positions.len());
+        println!("[overlay] Moving to {}", POSITION_PRESETS[next].0);
+        self.move_to(positions[next].0, positions[next].1);
+    }
+
+    // Lets go of the devices of the capabilities in `gone`, ending whatever
+    // they were in the middle of, so none of them is left stale.
+    fn release_seat_devices(&mut self, gone: Capability) {
+        use wayland_client::Proxy;
+
+        if gone.contains(Capability::Pointer)
+            && let Some(pointer) = self.pointer.take()
+        {
+            if let Some(device) = self.cursor_device.take() {
+                device.destroy();
+            }
+            if pointer.version() >= 3 {
+                pointer.release();
+            }
+            self.cursor_shape = None;
+            self.stop_drag();
+        }
+        if gone.contains(Capability::Keyboard)
+            && let Some(keyboard) = self.keyboard.take()
+        {
+            if keyboard.version() >= 3 {
+                keyboard.release();
+            }
+            self.ctrl_pressed = false;
+        }
+        if gone.contains(Capability::Touch)
+            && let Some(touch) = self.touch_device.take()
+        {
+            if touch.version() >= 3 {
+                touch.release();
+            }
+            if self.touch.take().is_some() {
+                self.stop_drag();
+            }
+        }
+    }
+
+    fn stop_drag(&mut self) {
+        if self.drag_state.is_dragging {
+            self.drag_state.is_dragging = false;
+            self.drag_state.resize = None;
+        }
+    }
+
+    // Moves the dragged edges by (dx, dy), re-wrapping the text for the new
+    // width. Dragging the left or top edge also moves the overlay so the
+    // opposite edge stays in place.
+    fn update_resize(&mut self, edges: ResizeEdges, dx: f64, dy: f64) {
+        let (dx, dy) = (dx as i32, dy as i32);
+        let max_width = (self.output_width - self.margin_x).max(MIN_WIDTH as i32);
+        let max_height = (self.output_height - self.margin_y).max(MIN_HEIGHT as i32);
+        let width = self.width as i32;
+        let height = self.height as i32;
+        let mut new_width = width;
+        // Dragging a vertical edge sets max_height from the visible height;
+        // otherwise it stays as is.
+        let mut new_max_height = self.max_height as i32;
+
+        if edges.right {
+            new_width = (width + dx).clamp(MIN_WIDTH as i32, max_width);
+        } else if edges.left {
+            new_width = (width - dx).clamp(MIN_WIDTH as i32, width + self.margin_x);
+        }
+        if edges.bottom {
+            new_max_height = (height + dy).clamp(MIN_HEIGHT as i32, max_height);
+        } else if edges.top {
+            new_max_height = (height - dy).clamp(MIN_HEIGHT as i32, height + self.margin_y);
+            self.margin_y -= new_max_height - height;
+        }
+
+        if new_width as u32 == self.width && new_max_height as u32 == self.max_height {
+            return;
+        }
+        self.max_width = new_width as u32;
+        self.max_height = new_max_height as u32;
+
+        self.state_dirty = true;
+
+        // The text may not need all of the new width with `[app] auto_width`
+        self.relayout();
+        if edges.left {
+            self.margin_x -= self.width as i32 - width;
+        }
+        for (layer_surface, surface) in self.layer_surfaces() {
+            layer_surface.set_margin(self.margin_y, 0, 0, self.margin_x);
+            surface.commit();
+        }
+        self.force_redraw();
+    }
+
+    // Scrolls the text by `amount` pixels, returning false at either end.
+    fn scroll_by(&mut self, amount: f32) -> bool {
+        let offset = (self.scroll_offset_y + amount).clamp(0.0, self.max_scroll_offset_y);
+        if offset == self.scroll_offset_y {
+            return false;
+        }
+        self.scroll_offset_y = offset;
+        self.state_dirty = true;
+        self.draw_overlay();
+        true
+    }
+
+    fn line_height(&self) -> f32 {
+        line_height(&self.font, Scale::uniform(self.font.size()), &self.theme)
+    }
+
+    // Scrolls by the wheel notches or touchpad movement of a pointer frame.
+    fn apply_axis_frame(&mut self) {
+        use wayland_client::protocol::wl_pointer::AxisSource;
+
+        let frame = std::mem::take(&mut self.axis_frame);
+        let notches = if frame.value120 != 0 {
+            frame.value120 as f32 / 120.0
+        } else {
+            frame.discrete as f32
+        };
+        // Ctrl+scroll zooms: up or away makes the text larger
+        if self.ctrl_pressed {
+            let steps = if notches != 0.0 {
+                notches
+            } else {
+                frame.value as f32 / TOUCHPAD_ZOOM_DISTANCE
+            };
+            if steps != 0.0 {
+                self.zoom(Some(ZOOM_STEP.powf(-steps)));
+            }
+            self.axis_velocity = VelocityTracker::default();
+            return;
+        }
+        let amount = if notches != 0.0 {
+            notches * CONFIG.app.wheel_lines * self.line_height()
+        } else {
+            frame.value as f32 / CONFIG.app.scroll_speed // Adjust scroll speed
+        };
+
+        let now = Instant::now();
+        if amount != 0.0 {
+            self.kinetic.stop();
+            if frame.source == Some(AxisSource::Finger) {
+                self.axis_velocity.push(now, amount);
+            }
+            self.scroll_by(amount);
+        }
+        if frame.stop {
+            if CONFIG.app.kinetic_scroll {
+                self.kinetic.start(self.axis_velocity.velocity(now), now);
+            }
+            self.axis_velocity = VelocityTracker::default();
+        }
+    }
+
+    // Continues a flick until it slows down or reaches the end of the text.
+    fn step_kinetic(&mut self) {
+        if let Some(amount) = self.kinetic.step(Instant::now())
+            && !self.scroll_by(amount)
+        {
+            self.kinetic.stop();
+        }
+    }
+
+    fn force_redraw(&mut self) {
+        // The collapsed surface follows the chip label's width
+        if self.collapsed {
+            self.relayout();
+        }
+        self.text_changed = true;
+        self.restyle = true;
+        self.draw_overlay();
+    }
+}
+
+// Global registry handling
+impl Dispatch<wayland_client::protocol::wl_registry::WlRegistry, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        registry: &wayland_client::protocol::wl_registry::WlRegistry,
+        event: wayland_client::protocol::wl_registry::Event,
         _: &(),
         _: &Connection,
         qh: &QueueHandle<Self>,
@@ -595,17 +2229,65 @@ impl Dispatch<wayland_client::protocol::wl_registry::WlRegistry, ()> for AppStat
                 "zwlr_layer_shell_v1" => {
                     state.layer_shell = Some(registry.bind(name, version, qh, ()));
                 }
+                "wp_cursor_shape_manager_v1" => {
+                    state.cursor_shape_manager = Some(registry.bind(name, version.min(1), qh, ()));
+                }
+                "org_kde_kwin_blur_manager" if CONFIG.app.blur => {
+                    state.blur_manager = Some(registry.bind(name, version.min(1), qh, ()));
+                }
+                "xdg_wm_base" => {
+                    state.xdg_wm_base = Some(registry.bind(name, version.min(5), qh, ()));
+                }
                 "wl_output" => {
-                    if state.output.is_none() {
-                        state.output = Some(registry.bind(name, version, qh, ()));
-                    }
+                    // Version 4 adds the name and description events
+                    let output = registry.bind(name, version.min(4), qh, name);
+                    state.outputs.push(OutputInfo {
+                        output,
+                        global_name: name,
+                        name: None,
+                        description: None,
+                        make_model: None,
+                        width: 1920,
+                        height: 1080,
+                    });
                 }
-                "wl_seat" => {
-                    state.seat = Some(registry.bind(name, version, qh, ()));
+                // A seat that comes back after being removed is bound again
+                "wl_seat" if state.seat.is_none() => {
+                    state.seat = Some((registry.bind(name, version, qh, ()), name));
+                }
+                // Version 2 adds the fullscreen state
+                "zwlr_foreign_toplevel_manager_v1" if CONFIG.app.hide_on_fullscreen => {
+                    state.toplevel_manager = Some(registry.bind(name, version.min(3), qh, ()));
                 }
                 _ => {}
             },
-            Event::GlobalRemove { .. } => {}
+            Event::GlobalRemove { name } => {
+                if let Some((seat, _)) = state.seat.take_if(|(_, seat_name)| *seat_name == name) {
+                    println!("[overlay] The seat was removed");
+                    state.release_seat_devices(Capability::all());
+                    if wayland_client::Proxy::version(&seat) >= 5 {
+                        seat.release();
+                    }
+                    return;
+                }
+                let Some(index) = state
+                    .outputs
+                    .iter()
+                    .position(|info| info.global_name == name)
+                else {
+                    return;
+                };
+                let removed = state.outputs.remove(index);
+                if state.output.as_ref() == Some(&removed.output) {
+                    println!("[overlay] {} was removed", removed.label());
+                    state.move_to_output(qh);
+                } else {
+                    state.sync_mirrors(qh);
+                }
+                if wayland_client::Proxy::version(&removed.output) >= 3 {
+                    removed.output.release();
+                }
+            }
             _ => {}
         }
     }
@@ -629,16 +2311,27 @@ impl Dispatch<ZwlrLayerSurfaceV1, ()> for AppState {
                 width,
                 height,
             } => {
-                if width > 0 && height > 0 {
+                // While collapsed the surface has the chip's size, not the
+                // overlay's
+                if width > 0 && height > 0 && !state.collapsed {
                     state.width = width;
                     state.height = height;
                 }
 
                 layer_surface.ack_configure(serial);
-                state.draw_overlay(qh);
+                state.draw_overlay();
             }
             Event::Closed => {
-                std::process::exit(0);
+                // The compositor closes the surface when its output goes away
+                if let Some(index) = state
+                    .mirrors
+                    .iter()
+                    .position(|mirror| &mirror.layer_surface == layer_surface)
+                {
+                    state.mirrors.remove(index).destroy();
+                } else {
+                    state.move_to_output(qh);
+                }
             }
             _ => {}
         }
@@ -649,7 +2342,7 @@ impl Dispatch<ZwlrLayerSurfaceV1, ()> for AppState {
 impl Dispatch<WlPointer, ()> for AppState {
     fn event(
         state: &mut Self,
-        _: &WlPointer,
+        pointer: &WlPointer,
         event: wayland_client::protocol::wl_pointer::Event,
         _: &(),
         _: &Connection,
@@ -658,14 +2351,20 @@ impl Dispatch<WlPointer, ()> for AppState {
         use wayland_client::protocol::wl_pointer::Axis;
         use wayland_client::protocol::wl_pointer::Event;
 
+        state.last_activity = Instant::now();
         match event {
             Event::Enter {
+                serial,
                 surface_x,
                 surface_y,
                 ..
             } => {
                 state.pointer_x = surface_x;
                 state.pointer_y = surface_y;
+                // The compositor shows its own cursor until it is set again
+                state.pointer_enter_serial = serial;
+                state.cursor_shape = None;
+                state.update_cursor();
             }
             Event::Leave { .. } => {
 
@@ -682,8 +2381,10 @@ impl Dispatch<WlPointer, ()> for AppState {
                 if state.drag_state.is_dragging {
                     state.update_drag(surface_x, surface_y);
                 }
+                state.update_cursor();
             }
             Event::Button {
+                serial,
                 button,
                 state: button_state,
                 ..
@@ -692,32 +2393,328 @@ impl Dispatch<WlPointer, ()> for AppState {
 
                 match button_state {
                     WEnum::Value(ButtonState::Pressed) => {
+                        state.kinetic.stop();
+                        state.press_serial = serial;
                         if button == 0x110 {
                             // Left mouse button
 
                             state.start_drag(state.pointer_x, state.pointer_y);
+                            state.update_cursor();
                         }
                     }
                     WEnum::Value(ButtonState::Released) => {
                         if button == 0x110 {
                             // Left mouse button
-
+                            let clicked = state.drag_state.is_dragging
+                                && state.drag_state.pressed_at == (state.margin_x, state.margin_y);
                             state.stop_drag();
+                            // Clicking the collapsed chip expands the overlay,
+                            // clicking a workspace tab switches to it
+                            if clicked && state.collapsed {
+                                state.toggle_collapsed();
+                            } else if let Some(workspace) = clicked
+                                .then(|| state.tab_at(state.pointer_x, state.pointer_y))
+                                .flatten()
+                            {
+                                state.select_workspace(workspace);
+                            }
+                            state.update_cursor();
                         }
                     }
                     _ => {}
                 }
             }
-            Event::Axis { axis, value, .. } => {
-                match axis {
-                    WEnum::Value(Axis::VerticalScroll) => {
-                        let scroll_amount = value as f32 / CONFIG.app.scroll_speed; // Adjust scroll speed
-                        state.scroll_offset_y = (state.scroll_offset_y + scroll_amount)
-                            .max(0.0)
-                            .min(state.max_scroll_offset_y);
-                        state.draw_overlay(_qh);
+            Event::AxisSource {
+                axis_source: WEnum::Value(source),
+            } => state.axis_frame.source = Some(source),
+            Event::Axis {
+                axis: WEnum::Value(Axis::VerticalScroll),
+                value,
+                ..
+            } => {
+                state.axis_frame.value += value;
+                // Before version 5 there are no frame events to wait for
+                if wayland_client::Proxy::version(pointer) < 5 {
+                    state.apply_axis_frame();
+                }
+            }
+            Event::AxisDiscrete {
+                axis: WEnum::Value(Axis::VerticalScroll),
+                discrete,
+            } => state.axis_frame.discrete += discrete,
+            Event::AxisValue120 {
+                axis: WEnum::Value(Axis::VerticalScroll),
+                value120,
+            } => state.axis_frame.value120 += value120,
+            Event::AxisStop {
+                axis: WEnum::Value(Axis::VerticalScroll),
+                ..
+            } => state.axis_frame.stop = true,
+            Event::Frame => state.apply_axis_frame(),
+            _ => {}
+        }
+    }
+}
+
+// Keyboard handling
+impl Dispatch<WlKeyboard, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        _: &WlKeyboard,
+        event: wayland_client::protocol::wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use wayland_client::protocol::wl_keyboard::Event;
+
+        match event {
+            Event::Key {
+                key,
+                state: key_state,
+                ..
+            } => {
+                if matches!(
+                    key_state,
+                    WEnum::Value(wayland_client::protocol::wl_keyboard::KeyState::Pressed)
+                ) {
+                    match key {
+                        13 if state.ctrl_pressed => {
+                            // Ctrl+= - zoom in
+                            state.zoom(Some(ZOOM_STEP));
+                        }
+                        12 if state.ctrl_pressed => {
+                            // Ctrl+- - zoom out
+                            state.zoom(Some(1.0 / ZOOM_STEP));
+                        }
+                        11 if state.ctrl_pressed => {
+                            // Ctrl+0 - reset zoom
+                            state.zoom(None);
+                        }
+                        20 if state.ctrl_pressed => {
+                            // Ctrl+T - next theme
+                            state.cycle_theme();
+                        }
+                        38 if state.ctrl_pressed => {
+                            // Ctrl+L - toggle code line numbers
+                            state.toggle_line_numbers();
+                        }
+                        25 if state.ctrl_pressed => {
+                            // Ctrl+P - next preset position
+                            state.cycle_position_preset();
+                        }
+                        35 if state.ctrl_pressed => {
+                            // Ctrl+H - collapse to the workspace chip or expand
+                            state.toggle_collapsed();
+                        }
+                        105 if state.ctrl_pressed => {
+                            // Ctrl+Left - previous answer
+                            state.page_answers(1);
+                        }
+                        106 if state.ctrl_pressed => {
+                            // Ctrl+Right - next answer, then the transcript
+                            state.page_answers(-1);
+                        }
+                        27 if state.ctrl_pressed => {
+                            // Ctrl+] - next provider's pane
+                            state.cycle_pane(1);
+                        }
+                        26 if state.ctrl_pressed => {
+                            // Ctrl+[ - previous provider's pane
+                            state.cycle_pane(-1);
+                        }
+                        43 if state.ctrl_pressed => {
+                            // Ctrl+\ - diff the pane against another provider's
+                            state.cycle_diff();
+                        }
+                        1 => {
+                            // ESC key
+                            state.toggle_visibility();
+                        }
+                        19 if state.ctrl_pressed => {
+                            // Ctrl+R - show or hide the models' reasoning
+                            state.toggle_reasoning();
+                        }
+                        19 => {
+                            // R key - force redraw
+                            state.force_redraw();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Modifiers { mods_depressed, .. } => {
+                // Control is bit 2 of the modifier mask in standard xkb keymaps.
+                state.ctrl_pressed = mods_depressed & 0x4 != 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Touch handling for dragging and flick scrolling, one finger at a time
+impl Dispatch<WlTouch, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        _: &WlTouch,
+        event: wayland_client::protocol::wl_touch::Event,
+        _: &(),
+        _: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use wayland_client::protocol::wl_touch::Event;
+
+        state.last_activity = Instant::now();
+        match event {
+            Event::Down {
+                serial, id, x, y, ..
+            } if state.touch.is_none() => {
+                state.kinetic.stop();
+                state.press_serial = serial;
+                let (width, height) = state.surface_size();
+                let scrolling = !state.collapsed
+                    && y >= TOUCH_DRAG_AREA
+                    && ResizeEdges::at(x, y, width, height).is_none();
+                if !scrolling {
+                    state.start_drag(x, y);
+                }
+                state.touch = Some(TouchState {
+                    id,
+                    scrolling,
+                    last_y: y,
+                    velocity: VelocityTracker::default(),
+                });
+            }
+            Event::Motion { id, x, y, .. } => {
+                let Some(touch) = state.touch.as_mut().filter(|touch| touch.id == id) else {
+                    return;
+                };
+                if touch.scrolling {
+                    // Content follows the finger
+                    let amount = (touch.last_y - y) as f32;
+                    touch.last_y = y;
+                    touch.velocity.push(Instant::now(), amount);
+                    state.scroll_by(amount);
+                } else {
+                    state.update_drag(x, y);
+                }
+            }
+            Event::Up { id, .. } => {
+                let Some(touch) = state.touch.take_if(|touch| touch.id == id) else {
+                    return;
+                };
+                if touch.scrolling {
+                    let now = Instant::now();
+                    state.kinetic.start(touch.velocity.velocity(now), now);
+                } else {
+                    // A tap on the collapsed chip expands the overlay, a tap
+                    // on a workspace tab switches to it
+                    let tapped = state.drag_state.pressed_at == (state.margin_x, state.margin_y);
+                    let (x, y) = (state.drag_state.start_x, state.drag_state.start_y);
+                    state.stop_drag();
+                    if tapped && state.collapsed {
+                        state.toggle_collapsed();
+                    } else if let Some(workspace) = tapped.then(|| state.tab_at(x, y)).flatten() {
+                        state.select_workspace(workspace);
                     }
-                    _ => {}
+                }
+            }
+            Event::Cancel => {
+                state.touch = None;
+                state.stop_drag();
+            }
+            _ => {}
+        }
+    }
+}
+
+// Seat handling
+impl Dispatch<WlSeat, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        seat: &WlSeat,
+        event: wayland_client::protocol::wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use wayland_client::protocol::wl_seat::Event;
+
+        match event {
+            // Capabilities are sent again whenever a keyboard, mouse or
+            // touchscreen is plugged in or out
+            Event::Capabilities {
+                capabilities: WEnum::Value(caps),
+            } => {
+                state.release_seat_devices(caps.complement());
+                if caps.contains(Capability::Keyboard) && state.keyboard.is_none() {
+                    state.keyboard = Some(seat.get_keyboard(qh, ()));
+                }
+                if caps.contains(Capability::Pointer) && state.pointer.is_none() {
+                    let pointer = seat.get_pointer(qh, ());
+                    state.cursor_device = state
+                        .cursor_shape_manager
+                        .as_ref()
+                        .map(|manager| manager.get_pointer(&pointer, qh, ()));
+                    state.pointer = Some(pointer);
+                }
+                if caps.contains(Capability::Touch) && state.touch_device.is_none() {
+                    state.touch_device = Some(seat.get_touch(qh, ()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Output handling
+impl Dispatch<WlOutput, u32> for AppState {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wayland_client::protocol::wl_output::Event,
+        global_name: &u32,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use wayland_client::protocol::wl_output::Event;
+
+        let is_selected = state.output.as_ref() == Some(output);
+        let Some(info) = state
+            .outputs
+            .iter_mut()
+            .find(|info| info.global_name == *global_name)
+        else {
+            return;
+        };
+        match event {
+            Event::Geometry { make, model, .. } => {
+                info.make_model = Some(format!("{} {}", make, model));
+            }
+            Event::Mode { width, height, .. } => {
+                info.width = width;
+                info.height = height;
+                if is_selected {
+                    state.output_width = width;
+                    state.output_height = height;
+                }
+            }
+            Event::Name { name } => info.name = Some(name),
+            Event::Description { description } => info.description = Some(description),
+            Event::Done => {
+                // A newly plugged in output is complete; show the overlay on
+                // it when there was none, or when it is the configured one.
+                let wanted = CONFIG
+                    .app
+                    .output
+                    .as_deref()
+                    .is_some_and(|pattern| info.matches(pattern));
+                if state.awaiting_output
+                    || (state.surface.is_some() && wanted && state.prefers_other_output())
+                {
+                    state.move_to_output(qh);
+                } else {
+                    state.sync_mirrors(qh);
                 }
             }
             _ => {}
@@ -725,97 +2722,187 @@ impl Dispatch<WlPointer, ()> for AppState {
     }
 }
 
-// Keyboard handling
-impl Dispatch<WlKeyboard, ()> for AppState {
+// The cursor-shape objects have no events
+impl Dispatch<WpCursorShapeManagerV1, ()> for AppState {
+    fn event(
+        _: &mut Self,
+        _: &WpCursorShapeManagerV1,
+        _: wp_cursor_shape_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WpCursorShapeDeviceV1, ()> for AppState {
+    fn event(
+        _: &mut Self,
+        _: &WpCursorShapeDeviceV1,
+        _: wp_cursor_shape_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+// The blur objects have no events
+impl Dispatch<OrgKdeKwinBlurManager, ()> for AppState {
+    fn event(
+        _: &mut Self,
+        _: &OrgKdeKwinBlurManager,
+        _: blur::client::org_kde_kwin_blur_manager::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<OrgKdeKwinBlur, ()> for AppState {
+    fn event(
+        _: &mut Self,
+        _: &OrgKdeKwinBlur,
+        _: blur::client::org_kde_kwin_blur::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+// xdg-shell handling, for the fallback window
+impl Dispatch<XdgWmBase, ()> for AppState {
+    fn event(
+        _: &mut Self,
+        wm_base: &XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, ()> for AppState {
     fn event(
         state: &mut Self,
-        _: &WlKeyboard,
-        event: wayland_client::protocol::wl_keyboard::Event,
+        xdg_surface: &XdgSurface,
+        event: xdg_surface::Event,
         _: &(),
         _: &Connection,
-        qh: &QueueHandle<Self>,
+        _: &QueueHandle<Self>,
     ) {
-        use wayland_client::protocol::wl_keyboard::Event;
+        if let xdg_surface::Event::Configure { serial } = event {
+            xdg_surface.ack_configure(serial);
+            if let Some(window) = &mut state.xdg_window {
+                window.configured = true;
+            }
+            state.force_redraw();
+        }
+    }
+}
 
+impl Dispatch<XdgToplevel, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        _: &XdgToplevel,
+        event: xdg_toplevel::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
         match event {
-            Event::Key {
-                key,
-                state: key_state,
-                ..
-            } => {
-                if matches!(
-                    key_state,
-                    WEnum::Value(wayland_client::protocol::wl_keyboard::KeyState::Pressed)
-                ) {
-                    match key {
-                        1 => {
-                            // ESC key
-                            state.toggle_visibility(qh);
-                        }
-                        19 => {
-                            // R key - force redraw
-                            state.force_redraw(qh);
-                        }
-                        _ => {}
-                    }
+            // A size of 0 leaves it to the overlay; the compositor suggests
+            // one after an interactive resize or when tiling the window
+            xdg_toplevel::Event::Configure { width, height, .. }
+                if width > 0 && height > 0 && !state.collapsed =>
+            {
+                let (width, height) = (width as u32, height as u32);
+                if (width, height) != (state.width, state.height) {
+                    state.max_width = width.max(MIN_WIDTH);
+                    state.max_height = height.max(MIN_HEIGHT);
+                    state.state_dirty = true;
+                    state.relayout();
                 }
             }
+            // Closing the window hides the overlay, like the show/hide key
+            xdg_toplevel::Event::Close if state.visible => state.toggle_visibility(),
             _ => {}
         }
     }
 }
 
-// Seat handling
-impl Dispatch<WlSeat, ()> for AppState {
+// Foreign toplevel handling, for hiding the overlay over fullscreen windows
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for AppState {
     fn event(
         state: &mut Self,
-        seat: &WlSeat,
-        event: wayland_client::protocol::wl_seat::Event,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
         _: &(),
         _: &Connection,
-        qh: &QueueHandle<Self>,
+        _: &QueueHandle<Self>,
     ) {
-        use wayland_client::protocol::wl_seat::Event;
+        use zwlr_foreign_toplevel_manager_v1::Event;
 
         match event {
-            Event::Capabilities { capabilities } => {
-                use wayland_client::protocol::wl_seat::Capability;
-
-                if let WEnum::Value(caps) = capabilities {
-                    if caps.contains(Capability::Keyboard) {
-                        seat.get_keyboard(qh, ());
-                    }
-                    if caps.contains(Capability::Pointer) {
-                        let pointer = seat.get_pointer(qh, ());
-                        state.pointer = Some(pointer);
-                    }
-                }
-            }
+            Event::Toplevel { toplevel } => state.toplevels.push(Toplevel {
+                handle: toplevel,
+                outputs: Vec::new(),
+                fullscreen: false,
+                pending_fullscreen: false,
+            }),
+            Event::Finished => state.toplevels.clear(),
             _ => {}
         }
     }
+
+    wayland_client::event_created_child!(AppState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
 }
 
-// Output handling
-impl Dispatch<WlOutput, ()> for AppState {
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for AppState {
     fn event(
         state: &mut Self,
-        _: &WlOutput,
-        event: wayland_client::protocol::wl_output::Event,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        _qh: &QueueHandle<Self>,
     ) {
-        use wayland_client::protocol::wl_output::Event;
+        use zwlr_foreign_toplevel_handle_v1::{Event, State};
 
+        let Some(index) = state
+            .toplevels
+            .iter()
+            .position(|toplevel| &toplevel.handle == handle)
+        else {
+            return;
+        };
+        let toplevel = &mut state.toplevels[index];
         match event {
-            Event::Geometry { .. } => {}
-            Event::Mode { width, height, .. } => {
-                state.output_width = width;
-                state.output_height = height;
+            Event::State { state: states } => {
+                // An array of native-endian u32 state values
+                toplevel.pending_fullscreen = states
+                    .chunks_exact(4)
+                    .map(|chunk| u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                    .any(|value| value == State::Fullscreen as u32);
+            }
+            Event::OutputEnter { output } => toplevel.outputs.push(output),
+            Event::OutputLeave { output } => toplevel.outputs.retain(|o| o != &output),
+            Event::Done => {
+                toplevel.fullscreen = toplevel.pending_fullscreen;
+                state.update_fullscreen();
+            }
+            Event::Closed => {
+                state.toplevels.remove(index).handle.destroy();
+                state.update_fullscreen();
             }
-            Event::Scale { .. } => {}
-            Event::Done => {}
             _ => {}
         }
     }
@@ -824,11 +2911,33 @@ impl Dispatch<WlOutput, ()> for AppState {
 // --- MARKDOWN RENDERING LOGIC ---
 
 // Represents a block of content parsed from Markdown.
-// We only distinguish between Code and general Text for now.
+// We distinguish between Code, general Text and images drawn inline.
 #[derive(Debug)]
 enum ContentBlock {
     Code(String, String), // lang, content
     Text(String),         // content
+    Image(String),        // path
+}
+
+// The blocks `text` is drawn as. The models' reasoning, which ai_manager
+// writes as a code block in REASONING_LANG, is folded to a one-line summary
+// unless the theme shows it.
+fn visible_blocks(text: &str, theme: &Theme) -> Vec<ContentBlock> {
+    let mut blocks = parse_markdown(text);
+    if !theme.show_reasoning {
+        for block in &mut blocks {
+            if let ContentBlock::Code(lang, content) = block
+                && lang == REASONING_LANG
+            {
+                let words = content.split_whitespace().count();
+                *block = ContentBlock::Text(format!(
+                    "[Reasoning: {} words hidden, Ctrl+R shows it]",
+                    words
+                ));
+            }
+        }
+    }
+    blocks
 }
 
 // Parses a markdown string into a vector of `ContentBlock`s.
@@ -840,9 +2949,21 @@ fn parse_markdown(text: &str) -> Vec<ContentBlock> {
     let mut current_code = String::new();
     let mut current_lang = String::new();
     let mut in_code_block = false;
+    // The alt text of an image is not shown
+    let mut in_image = false;
 
     for event in parser {
         match event {
+            Event::Start(Tag::Image(_, path, _)) if !in_code_block => {
+                if !current_text.is_empty() {
+                    blocks.push(ContentBlock::Text(current_text.trim_end().to_string()));
+                    current_text.clear();
+                }
+                blocks.push(ContentBlock::Image(path.to_string()));
+                in_image = true;
+            }
+            Event::End(Tag::Image(..)) => in_image = false,
+            Event::Text(_) if in_image => {}
             Event::Start(Tag::CodeBlock(kind)) => {
                 if !current_text.trim().is_empty() {
                     blocks.push(ContentBlock::Text(current_text.trim_end().to_string()));
@@ -885,10 +3006,13 @@ fn parse_markdown(text: &str) -> Vec<ContentBlock> {
                     current_text.push('\n');
                 }
             }
-            Event::End(Tag::Paragraph) => {
-                if !in_code_block && !current_text.ends_with("\n\n") {
-                    current_text.push_str("\n\n");
-                }
+            // A paragraph of only an image leaves no text behind
+            Event::End(Tag::Paragraph)
+                if !in_code_block
+                    && !current_text.is_empty()
+                    && !current_text.ends_with("\n\n") =>
+            {
+                current_text.push_str("\n\n");
             }
             _ => {}
         }
@@ -902,19 +3026,43 @@ fn parse_markdown(text: &str) -> Vec<ContentBlock> {
     blocks
 }
 
+// Loads syntect's built-in syntaxes plus any .sublime-syntax files found in
+// `extra_dir`, so languages syntect doesn't ship still get highlighted.
+fn load_syntax_set(extra_dir: Option<&str>) -> SyntaxSet {
+    let Some(dir) = extra_dir else {
+        return SyntaxSet::load_defaults_newlines();
+    };
+
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    match builder.add_from_folder(dir, true) {
+        Ok(()) => {
+            let syntax_set = builder.build();
+            println!(
+                "[overlay] Loaded extra syntaxes from '{}' ({} total)",
+                dir,
+                syntax_set.syntaxes().len()
+            );
+            syntax_set
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: Could not load syntax definitions from '{}': {}",
+                dir, e
+            );
+            SyntaxSet::load_defaults_newlines()
+        }
+    }
+}
+
 // Buffer creation and drawing helpers
+// Creates a shared memory buffer of the given size, with its pixels filled
+// in by `draw`.
 fn create_shm_buffer(
     shm: &WlShm,
     width: u32,
     height: u32,
-    border_radius: f32,
-    font: &Font,
-    text: &str,
-    syntax_set: &SyntaxSet,
-    theme: &syntect::highlighting::Theme,
-    scroll_offset_y: f32,
-    current_workspace: u32,
     qh: &QueueHandle<AppState>,
+    draw: impl FnOnce(&mut [u8]),
 ) -> Result<wayland_client::protocol::wl_buffer::WlBuffer, Box<dyn std::error::Error>> {
     use std::os::unix::io::AsFd;
 
@@ -926,18 +3074,7 @@ fn create_shm_buffer(
 
     let mut mmap = unsafe { memmap2::MmapMut::map_mut(&file)? };
 
-    draw_content_to_buffer(
-        &mut mmap,
-        width,
-        height,
-        border_radius,
-        font,
-        text,
-        syntax_set,
-        theme,
-        scroll_offset_y,
-        current_workspace,
-    );
+    draw(&mut mmap);
 
     let pool = shm.create_pool(file.as_fd(), size as i32, qh, ());
     let buffer = pool.create_buffer(
@@ -955,36 +3092,216 @@ fn create_shm_buffer(
     Ok(buffer)
 }
 
+const CHIP_HORIZONTAL_PADDING: f32 = 8.0;
+const CHIP_FONT_SCALE: f32 = 0.8;
+
+// Width and height of the workspace chip for a label.
+fn chip_size(font: &FontSet, chip_label: &str) -> (f32, f32) {
+    let scale = Scale::uniform(font.size() * CHIP_FONT_SCALE);
+    let v_metrics = font.v_metrics(scale);
+    let text_width = measure_text_width(font, chip_label, scale);
+    (
+        text_width + (2.0 * CHIP_HORIZONTAL_PADDING),
+        v_metrics.ascent - v_metrics.descent + 10.0, // Add some vertical padding
+    )
+}
+
+// The collapsed overlay: only the workspace chip on a transparent surface.
+fn draw_chip_to_buffer(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    font: &FontSet,
+    theme: &Theme,
+    status: &StatusBar,
+) {
+    buffer.fill(0);
+    draw_workspace_indicator(
+        buffer,
+        width,
+        height,
+        font,
+        &tab_colors(&theme.colors, status.workspace),
+        &status.chip_label,
+        (CHIP_PADDING, 1.0),
+    );
+}
+
+// The color of a workspace: the one set in its `[workspaces.<n>]` section,
+// or with `[app] workspace_colors` one derived from its number.
+fn workspace_color(workspace: u32) -> Option<Rgba> {
+    CONFIG
+        .workspaces
+        .get(&workspace.to_string())
+        .and_then(|style| style.color)
+        .or_else(|| {
+            CONFIG
+                .app
+                .workspace_colors
+                .then(|| derived_workspace_color(workspace))
+        })
+}
+
+// A color per workspace number, with hues a golden angle apart so that
+// neighbouring workspaces differ clearly. Dark enough for the chip text.
+fn derived_workspace_color(workspace: u32) -> Rgba {
+    let hue = (workspace as f32 * 137.508) % 360.0;
+    let (saturation, lightness) = (0.55, 0.4);
+    let chroma = (1.0 - (2.0 * lightness - 1.0f32).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |value: f32| ((value + m) * 255.0).round() as u8;
+    Rgba {
+        r: channel(r),
+        g: channel(g),
+        b: channel(b),
+        a: 255,
+    }
+}
+
+// The theme's colors with the chip in the workspace's color, if it has one.
+fn tab_colors(colors: &ThemeColors, workspace: u32) -> ThemeColors {
+    ThemeColors {
+        chip: workspace_color(workspace).unwrap_or(colors.chip),
+        ..colors.clone()
+    }
+}
+
+// Blends a rounded border of the given color along the edge of the overlay.
+fn draw_border(buffer: &mut [u8], width: u32, height: u32, border_radius: f32, color: Rgba) {
+    let (w, h) = (width as f32, height as f32);
+    let band = (border_radius.max(WORKSPACE_BORDER_WIDTH) + 1.0).ceil() as u32;
+    let inner_radius = (border_radius - WORKSPACE_BORDER_WIDTH).max(0.0);
+    let color_alpha = color.a as f32 / 255.0;
+    for y in 0..height {
+        let near_edge_row = y < band || y + band >= height;
+        for x in 0..width {
+            if !near_edge_row && x >= band && x + band < width {
+                continue;
+            }
+            let (fx, fy) = (x as f32, y as f32);
+            let outer = calculate_rounded_rect_alpha(fx, fy, w, h, border_radius);
+            let inside = fx >= WORKSPACE_BORDER_WIDTH
+                && fy >= WORKSPACE_BORDER_WIDTH
+                && fx < w - WORKSPACE_BORDER_WIDTH
+                && fy < h - WORKSPACE_BORDER_WIDTH;
+            let inner = if inside {
+                calculate_rounded_rect_alpha(
+                    fx - WORKSPACE_BORDER_WIDTH,
+                    fy - WORKSPACE_BORDER_WIDTH,
+                    w - 2.0 * WORKSPACE_BORDER_WIDTH,
+                    h - 2.0 * WORKSPACE_BORDER_WIDTH,
+                    inner_radius,
+                )
+            } else {
+                0.0
+            };
+            let alpha = (outer - inner).max(0.0) * color_alpha;
+            if alpha > 0.0 {
+                let pixel_idx = ((y * width + x) * 4) as usize;
+                let pixel = &mut buffer[pixel_idx..pixel_idx + 4];
+                pixel[0] = (pixel[0] as f32 * (1.0 - alpha) + color.b as f32 * alpha) as u8;
+                pixel[1] = (pixel[1] as f32 * (1.0 - alpha) + color.g as f32 * alpha) as u8;
+                pixel[2] = (pixel[2] as f32 * (1.0 - alpha) + color.r as f32 * alpha) as u8;
+                pixel[3] = pixel[3].max((alpha * 255.0) as u8);
+            }
+        }
+    }
+}
+
+// Opacity of the tabs of the workspaces not shown
+const INACTIVE_TAB_OPACITY: f32 = 0.45;
+// Space between two workspace tabs
+const TAB_SPACING: f32 = 4.0;
+
+// The tabs of the workspace row as (workspace, label, left edge). The current
+// workspace's tab has the full chip label, the others their number and
+// conversation title.
+fn workspace_tabs(font: &FontSet, status: &StatusBar) -> Vec<(u32, String, f32)> {
+    let mut workspaces = status.workspaces.clone();
+    if !workspaces.contains(&status.workspace) {
+        workspaces.push(status.workspace);
+        workspaces.sort_unstable();
+    }
+    let mut left = CHIP_PADDING;
+    workspaces
+        .into_iter()
+        .map(|workspace| {
+            let label = if workspace == status.workspace {
+                status.chip_label.clone()
+            } else {
+                tab_label(workspace, status.titles.get(&workspace))
+            };
+            let tab = (workspace, label, left);
+            left += chip_size(font, &tab.1).0 + TAB_SPACING;
+            tab
+        })
+        .collect()
+}
+
+// Draws the workspace tabs and returns where the row ends.
+fn draw_workspace_tabs(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    font: &FontSet,
+    colors: &ThemeColors,
+    status: &StatusBar,
+) -> f32 {
+    let mut row_end = CHIP_PADDING;
+    for (workspace, label, left) in workspace_tabs(font, status) {
+        let opacity = if workspace == status.workspace {
+            1.0
+        } else {
+            INACTIVE_TAB_OPACITY
+        };
+        let colors = tab_colors(colors, workspace);
+        draw_workspace_indicator(
+            buffer,
+            width,
+            height,
+            font,
+            &colors,
+            &label,
+            (left, opacity),
+        );
+        row_end = left + chip_size(font, &label).0;
+    }
+    row_end
+}
+
+// Draws a chip with the given label at `placement`, (left edge, opacity).
 fn draw_workspace_indicator(
     buffer: &mut [u8],
     width: u32,
     height: u32,
-    font: &Font,
-    current_workspace: u32,
+    font: &FontSet,
+    colors: &ThemeColors,
+    chip_label: &str,
+    placement: (f32, f32),
 ) {
-    let chip_padding = 10.0;
-    let chip_horizontal_padding = 8.0;
-    let font_scale_factor = 0.8;
+    let (chip_x, opacity) = placement;
+    let chip_horizontal_padding = CHIP_HORIZONTAL_PADDING;
     let chip_corner_radius = 6.0;
 
-    let chip_bg_color = (
-        CONFIG.colors.blue.saturating_sub(15),
-        CONFIG.colors.green.saturating_sub(15),
-        CONFIG.colors.red.saturating_sub(15),
-    );
+    let chip_bg_color = (colors.chip.b, colors.chip.g, colors.chip.r);
+    let chip_alpha = colors.chip.a as f32 / 255.0 * opacity;
 
-    let text_color = (220u8, 220u8, 230u8);
+    let text_color = (colors.chip_text.b, colors.chip_text.g, colors.chip_text.r);
 
-    let ws_text = format!("{}", current_workspace);
-    let scale = Scale::uniform(CONFIG.font.size * font_scale_factor);
+    let scale = Scale::uniform(font.size() * CHIP_FONT_SCALE);
     let v_metrics = font.v_metrics(scale);
-    let text_width = measure_text_width(font, &ws_text, scale);
-
-    let chip_width = text_width + (2.0 * chip_horizontal_padding);
-    let chip_height = v_metrics.ascent - v_metrics.descent + 10.0; // Add some vertical padding
+    let (chip_width, chip_height) = chip_size(font, chip_label);
 
-    let chip_x = chip_padding;
-    let chip_y = chip_padding;
+    let chip_y = CHIP_PADDING;
 
     for y_local in 0..chip_height as u32 {
         for x_local in 0..chip_width as u32 {
@@ -998,7 +3315,7 @@ fn draw_workspace_indicator(
                     chip_width,
                     chip_height,
                     chip_corner_radius,
-                );
+                ) * chip_alpha;
 
                 if alpha > 0.0 {
                     let pixel_idx = ((y_abs * width + x_abs) * 4) as usize;
@@ -1022,180 +3339,835 @@ fn draw_workspace_indicator(
         chip_y + (chip_height / 2.0) + (v_metrics.ascent + v_metrics.descent) / 2.0;
     let text_x = chip_x + chip_horizontal_padding;
 
-    let glyphs = font.layout(&ws_text, scale, point(text_x, text_baseline_y));
-
-    for glyph in glyphs {
-        if let Some(bounding_box) = glyph.pixel_bounding_box() {
-            glyph.draw(|x, y, v| {
-                let pixel_x = x as i32 + bounding_box.min.x;
-                let pixel_y = y as i32 + bounding_box.min.y;
+    font.draw(
+        chip_label,
+        scale,
+        point(text_x, text_baseline_y),
+        |pixel_x, pixel_y, v| {
+            if pixel_x >= 0
+                && pixel_x < width as i32
+                && pixel_y >= 0
+                && pixel_y < height as i32
+                && v > 0.1
+            {
+                let pixel_idx = ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
+                let text_alpha = (v * 255.0) as u8;
+                if text_alpha > 50 {
+                    let alpha_f = text_alpha as f32 / 255.0 * opacity;
+                    let inv_alpha = 1.0 - alpha_f;
+                    let bg_b = buffer[pixel_idx] as f32;
+                    let bg_g = buffer[pixel_idx + 1] as f32;
+                    let bg_r = buffer[pixel_idx + 2] as f32;
 
-                if pixel_x >= 0
-                    && pixel_x < width as i32
-                    && pixel_y >= 0
-                    && pixel_y < height as i32
-                    && v > 0.1
-                {
-                    let pixel_idx = ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
-                    let text_alpha = (v * 255.0) as u8;
-                    if text_alpha > 50 {
-                        let alpha_f = text_alpha as f32 / 255.0;
-                        let inv_alpha = 1.0 - alpha_f;
-                        let bg_b = buffer[pixel_idx] as f32;
-                        let bg_g = buffer[pixel_idx + 1] as f32;
-                        let bg_r = buffer[pixel_idx + 2] as f32;
-
-                        buffer[pixel_idx] =
-                            (bg_b * inv_alpha + text_color.0 as f32 * alpha_f) as u8;
-                        buffer[pixel_idx + 1] =
-                            (bg_g * inv_alpha + text_color.1 as f32 * alpha_f) as u8;
-                        buffer[pixel_idx + 2] =
-                            (bg_r * inv_alpha + text_color.2 as f32 * alpha_f) as u8;
-                    }
+                    buffer[pixel_idx] = (bg_b * inv_alpha + text_color.0 as f32 * alpha_f) as u8;
+                    buffer[pixel_idx + 1] =
+                        (bg_g * inv_alpha + text_color.1 as f32 * alpha_f) as u8;
+                    buffer[pixel_idx + 2] =
+                        (bg_r * inv_alpha + text_color.2 as f32 * alpha_f) as u8;
                 }
-            });
+            }
+        },
+    );
+}
+
+// One dot per component in the top right corner: green while its heartbeat
+// is fresh, red once it stopped.
+fn draw_status_strip(buffer: &mut [u8], width: u32, height: u32, font: &FontSet, alive: &[bool]) {
+    let dot_radius = 4.0;
+    let dot_spacing = 12.0;
+    let right_padding = 14.0;
+
+    // Vertically centered on the workspace chip
+    let v_metrics = font.v_metrics(Scale::uniform(font.size() * 0.8));
+    let center_y = 10.0 + (v_metrics.ascent - v_metrics.descent + 10.0) / 2.0;
+
+    for (i, is_alive) in alive.iter().enumerate() {
+        let center_x = width as f32 - right_padding - (alive.len() - 1 - i) as f32 * dot_spacing;
+        let color: (u8, u8, u8) = if *is_alive {
+            (120, 200, 80) // BGR
+        } else {
+            (70, 70, 220)
+        };
+
+        draw_dot(
+            buffer,
+            width,
+            height,
+            (center_x, center_y),
+            dot_radius,
+            color,
+            1.0,
+        );
+    }
+}
+
+// The answer's background and text as last drawn. While an answer streams,
+// only the lines from the first changed one on are rasterized again; the rows
+// above it are copied from the previous frame, shifted up by however far the
+// text scrolled since.
+#[derive(Default)]
+struct TextLayer {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    scroll_offset_y: f32,
+    // Hash of every unit of the text, and where each drawn one ends relative
+    // to the top of the text
+    units: Vec<u64>,
+    ends: Vec<f32>,
+    valid: bool,
+}
+
+impl TextLayer {
+    // Makes the next update draw everything again, e.g. after the theme or
+    // font changed.
+    fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    fn update(
+        &mut self,
+        size: (u32, u32),
+        font: &FontSet,
+        text: &str,
+        syntax_set: &SyntaxSet,
+        theme: &Theme,
+        scroll_offset_y: f32,
+    ) {
+        let (width, height) = size;
+        let blocks = visible_blocks(text, theme);
+        let units = text_units(&blocks);
+        let hashes: Vec<u64> = units.iter().map(hash_unit).collect();
+        // Whole pixels, so that the previous frame can be shifted by rows
+        let scroll = scroll_offset_y.round();
+        let top = theme.spacing.top - scroll; // Top margin to not overlap with the pill
+
+        let (first, first_top, kept_rows) = self
+            .reusable(font, theme, &units, &hashes, size, scroll)
+            .unwrap_or((0, 0.0, 0));
+        let row_len = width as usize * 4;
+        let mut pixels = vec![0u8; row_len * height as usize];
+        if kept_rows > 0 {
+            let shift = (scroll - self.scroll_offset_y) as usize * row_len;
+            let kept = kept_rows as usize * row_len;
+            pixels[..kept].copy_from_slice(&self.pixels[shift..shift + kept]);
+        }
+
+        let background = theme.colors.background;
+        let (_, redrawn) = pixels.split_at_mut(kept_rows as usize * row_len);
+        for chunk in redrawn.chunks_mut(4) {
+            chunk.copy_from_slice(&[background.b, background.g, background.r, background.a]);
+        }
+        let ends = render_text(
+            redrawn,
+            width,
+            height - kept_rows,
+            font,
+            &units,
+            syntax_set,
+            theme,
+            (first, top + first_top - kept_rows as f32),
+        );
+
+        self.ends.truncate(first);
+        self.ends
+            .extend(ends.iter().map(|end| end + kept_rows as f32 - top));
+        self.units = hashes;
+        self.pixels = pixels;
+        self.width = width;
+        self.height = height;
+        self.scroll_offset_y = scroll;
+        self.valid = true;
+    }
+
+    // Where to start drawing again: the unit to draw from, its top relative to
+    // the top of the text and the number of rows kept from the previous frame.
+    // Drawing restarts one unit before the first changed one, as that unit's
+    // descenders may reach into the first row drawn again.
+    fn reusable(
+        &self,
+        font: &FontSet,
+        theme: &Theme,
+        units: &[TextUnit],
+        hashes: &[u64],
+        size: (u32, u32),
+        scroll: f32,
+    ) -> Option<(usize, f32, u32)> {
+        if !self.valid || self.width != size.0 || scroll < self.scroll_offset_y {
+            return None;
         }
+        let shift = (scroll - self.scroll_offset_y) as u32;
+        // Rows of the new frame the previous one has pixels for
+        let available = size.1.min(self.height.saturating_sub(shift));
+        let unchanged = self
+            .units
+            .iter()
+            .zip(hashes)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let identical = unchanged == hashes.len() && unchanged == self.units.len();
+        let unit_top = |i: usize| match i {
+            0 => 0.0,
+            _ => next_unit_top(font, theme, &units[i - 1], self.ends[i - 1], &units[i]),
+        };
+        let top = theme.spacing.top - scroll;
+
+        (1..=unchanged.min(self.ends.len())).rev().find_map(|next| {
+            let kept_rows = if next < units.len() {
+                (top + unit_top(next)).floor()
+            } else if identical {
+                available as f32
+            } else {
+                return None;
+            };
+            (kept_rows <= available as f32)
+                .then(|| (next - 1, unit_top(next - 1), kept_rows.max(0.0) as u32))
+        })
+    }
+}
+
+// A piece of the text laid out on its own: a whole code block, as its
+// highlighting and panel span all of its lines, or a single line of text.
+#[derive(Hash)]
+struct TextUnit<'a> {
+    block: usize,
+    line: usize,
+    lang: &'a str,
+    is_code_block: bool,
+    // An inline image, with its path as the text
+    is_image: bool,
+    text: &'a str,
+}
+
+fn text_units(blocks: &[ContentBlock]) -> Vec<TextUnit<'_>> {
+    let mut units = Vec::new();
+    for (block, content) in blocks.iter().enumerate() {
+        match content {
+            ContentBlock::Code(lang, code) => units.push(TextUnit {
+                block,
+                line: 0,
+                lang,
+                is_code_block: true,
+                is_image: false,
+                text: code,
+            }),
+            ContentBlock::Image(path) => units.push(TextUnit {
+                block,
+                line: 0,
+                lang: "txt",
+                is_code_block: false,
+                is_image: true,
+                text: path,
+            }),
+            ContentBlock::Text(text) => units.extend(text.split_inclusive('\n').enumerate().map(
+                |(line, text)| TextUnit {
+                    block,
+                    line,
+                    lang: "txt",
+                    is_code_block: false,
+                    is_image: false,
+                    text,
+                },
+            )),
+        }
+    }
+    units
+}
+
+fn hash_unit(unit: &TextUnit) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    unit.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Where `unit` starts, given where the unit before it ended.
+fn next_unit_top(
+    font: &FontSet,
+    theme: &Theme,
+    prev: &TextUnit,
+    prev_end: f32,
+    unit: &TextUnit,
+) -> f32 {
+    if unit.line > 0 {
+        // _render_block returns the baseline of the line after its last one
+        prev_end - font.v_metrics(Scale::uniform(font.size())).ascent
+    } else {
+        let (_, padding_bottom) = code_panel_padding(font, prev.lang, prev.is_code_block);
+        prev_end + padding_bottom + theme.spacing.block // Add padding between blocks
+    }
+}
+
+fn draw_content_to_buffer(
+    buffer: &mut [u8],
+    layer: &TextLayer,
+    border_radius: f32,
+    font: &FontSet,
+    theme: &Theme,
+    status: &StatusBar,
+) {
+    let (width, height) = (layer.width, layer.height);
+    buffer.copy_from_slice(&layer.pixels);
+
+    // Round the corners of the background
+    let radius = border_radius.ceil() as u32;
+    let background = theme.colors.background;
+    for y in (0..radius.min(height)).chain(height.saturating_sub(radius)..height) {
+        for x in (0..radius.min(width)).chain(width.saturating_sub(radius)..width) {
+            let alpha = calculate_rounded_rect_alpha(
+                x as f32,
+                y as f32,
+                width as f32,
+                height as f32,
+                border_radius,
+            );
+            if alpha < 1.0 {
+                let pixel_idx = ((y * width + x) * 4) as usize;
+                let pixel = if alpha > 0.0 {
+                    let final_alpha = (alpha * background.a as f32) as u8;
+                    [background.b, background.g, background.r, final_alpha]
+                } else {
+                    [0; 4]
+                };
+                buffer[pixel_idx..pixel_idx + 4].copy_from_slice(&pixel);
+            }
+        }
+    }
+
+    // Draw the workspace tabs on top of the text
+    let row_end = draw_workspace_tabs(buffer, width, height, font, &theme.colors, status);
+    if let Some(step) = status.spinner {
+        draw_spinner(buffer, width, height, font, &theme.colors, row_end, step);
+    }
+    draw_status_strip(buffer, width, height, font, &status.components_alive);
+    if let Some(line) = &status.status_line {
+        draw_status_line(buffer, width, height, font, &theme.colors, line);
+    }
+    if let Some(color) = workspace_color(status.workspace) {
+        draw_border(buffer, width, height, border_radius, color);
+    }
+}
+
+// Height the status line adds below the text, 0 when it is off.
+fn status_line_height(font: &FontSet) -> u32 {
+    if !CONFIG.app.status_line {
+        return 0;
+    }
+    let v_metrics = font.v_metrics(Scale::uniform(font.size() * CHIP_FONT_SCALE));
+    (v_metrics.ascent - v_metrics.descent + 2.0 * STATUS_LINE_PADDING).ceil() as u32
+}
+
+// E.g. "ws 2 · Claude, Gemini · 12s · 1.5k tokens · $0.0042 · 1.5k/8.0k ctx"
+// from a workspace's status file.
+fn format_status_line(workspace: u32, status: &Status, now: u64) -> String {
+    let mut parts = vec![format!("ws {}", workspace)];
+    if !status.providers.is_empty() {
+        parts.push(status.providers.join(", "));
+    }
+    if let Some(started) = status.started {
+        let elapsed = status.finished.unwrap_or(now).saturating_sub(started);
+        parts.push(match elapsed {
+            0..60 => format!("{}s", elapsed),
+            _ => format!("{}m {:02}s", elapsed / 60, elapsed % 60),
+        });
+    }
+    let tokens = status.tokens_in + status.tokens_out;
+    if tokens > 0 {
+        parts.push(format!("{} tokens", short_count(tokens)));
+    }
+    if status.cost > 0.0 {
+        parts.push(format!("${:.4}", status.cost));
+    }
+    if let Some((used, limit)) = status.context.filter(|(used, _)| *used > 0) {
+        parts.push(match limit {
+            0 => format!("{} ctx", short_count(used)),
+            _ => format!("{}/{} ctx", short_count(used), short_count(limit)),
+        });
+    }
+    parts.join(" · ")
+}
+
+// E.g. "Workspace 2: answer ready" with "Claude, Gemini" in the body.
+fn notify_answer_ready(workspace: u32, status: &Status) {
+    let summary = format!("Workspace {}: answer ready", workspace);
+    let providers = status.providers.join(", ");
+    println!("[overlay] {}", summary);
+    // notify-send may block until the notification daemon answers
+    std::thread::spawn(move || {
+        let result = std::process::Command::new("notify-send")
+            .args(["--app-name=aerogel", &summary, &providers])
+            .status();
+        if let Err(e) = result {
+            eprintln!("[overlay] Failed to run notify-send: {}", e);
+        }
+    });
+}
+
+// A band across the bottom of the overlay in the chip's colors.
+fn draw_status_line(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    font: &FontSet,
+    colors: &ThemeColors,
+    line: &str,
+) {
+    let band_height = status_line_height(font);
+    let top = height.saturating_sub(band_height);
+    let band_alpha = colors.chip.a as f32 / 255.0;
+    for y in top..height {
+        for x in 0..width {
+            let pixel_idx = ((y * width + x) * 4) as usize;
+            // Keep the overlay's rounded corners
+            let alpha = band_alpha * (buffer[pixel_idx + 3] as f32 / 255.0).min(1.0);
+            if alpha > 0.0 {
+                buffer[pixel_idx] =
+                    (buffer[pixel_idx] as f32 * (1.0 - alpha) + colors.chip.b as f32 * alpha) as u8;
+                buffer[pixel_idx + 1] = (buffer[pixel_idx + 1] as f32 * (1.0 - alpha)
+                    + colors.chip.g as f32 * alpha) as u8;
+                buffer[pixel_idx + 2] = (buffer[pixel_idx + 2] as f32 * (1.0 - alpha)
+                    + colors.chip.r as f32 * alpha) as u8;
+            }
+        }
+    }
+
+    let scale = Scale::uniform(font.size() * CHIP_FONT_SCALE);
+    let baseline = top as f32 + STATUS_LINE_PADDING + font.v_metrics(scale).ascent;
+    let text_color = colors.chip_text;
+    font.draw(
+        line,
+        scale,
+        point(CHIP_PADDING + CHIP_HORIZONTAL_PADDING, baseline),
+        |pixel_x, pixel_y, v| {
+            if pixel_x >= 0
+                && pixel_x < width as i32
+                && pixel_y >= 0
+                && pixel_y < height as i32
+                && v > 0.1
+            {
+                let pixel_idx = ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
+                let inv_alpha = 1.0 - v;
+                buffer[pixel_idx] =
+                    (buffer[pixel_idx] as f32 * inv_alpha + text_color.b as f32 * v) as u8;
+                buffer[pixel_idx + 1] =
+                    (buffer[pixel_idx + 1] as f32 * inv_alpha + text_color.g as f32 * v) as u8;
+                buffer[pixel_idx + 2] =
+                    (buffer[pixel_idx + 2] as f32 * inv_alpha + text_color.r as f32 * v) as u8;
+            }
+        },
+    );
+}
+
+// A ring of dots right of the workspace tabs, which end at `row_end`, the
+// brightest one going round once per SPINNER_DOTS steps.
+fn draw_spinner(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    font: &FontSet,
+    colors: &ThemeColors,
+    row_end: f32,
+    step: usize,
+) {
+    let (_, chip_height) = chip_size(font, "");
+    let ring_radius = chip_height * 0.3;
+    let center = (
+        row_end + 8.0 + ring_radius,
+        CHIP_PADDING + chip_height / 2.0,
+    );
+    let color = (colors.accent.b, colors.accent.g, colors.accent.r);
+
+    for i in 0..SPINNER_DOTS {
+        let angle = i as f32 / SPINNER_DOTS as f32 * std::f32::consts::TAU;
+        // Dots fade out behind the brightest one
+        let age = (step + SPINNER_DOTS - i) % SPINNER_DOTS;
+        let opacity = 1.0 - age as f32 / SPINNER_DOTS as f32;
+        let dot = (
+            center.0 + ring_radius * angle.sin(),
+            center.1 - ring_radius * angle.cos(),
+        );
+        draw_dot(
+            buffer,
+            width,
+            height,
+            dot,
+            ring_radius * 0.3,
+            color,
+            opacity,
+        );
     }
 }
 
-fn draw_content_to_buffer(
+// Blends an anti-aliased dot of a BGR color over the buffer.
+fn draw_dot(
     buffer: &mut [u8],
     width: u32,
     height: u32,
-    border_radius: f32,
-    font: &Font,
-    text: &str,
-    syntax_set: &SyntaxSet,
-    theme: &syntect::highlighting::Theme,
-    scroll_offset_y: f32,
-    current_workspace: u32,
+    center: (f32, f32),
+    radius: f32,
+    color: (u8, u8, u8),
+    opacity: f32,
 ) {
-    let data = unsafe { std::slice::from_raw_parts_mut(buffer.as_ptr() as *mut u8, buffer.len()) };
-
-    // Clear with transparent background
-    for chunk in data.chunks_mut(4) {
-        chunk[0] = 0; // Blue
-        chunk[1] = 0; // Green
-        chunk[2] = 0; // Red
-        chunk[3] = 0; // Alpha
-    }
-
-    // Draw rounded rectangle background
-    for y in 0..height {
-        for x in 0..width {
-            let pixel_idx = ((y * width + x) * 4) as usize;
-
-            if pixel_idx + 3 < data.len() {
-                let alpha = calculate_rounded_rect_alpha(
-                    x as f32,
-                    y as f32,
-                    width as f32,
-                    height as f32,
-                    border_radius,
-                );
-
-                if alpha > 0.0 {
-                    let final_alpha = (alpha * CONFIG.colors.alpha_multiplier) as u8;
-                    data[pixel_idx] = CONFIG.colors.blue; // Blue
-                    data[pixel_idx + 1] = CONFIG.colors.green; // Green
-                    data[pixel_idx + 2] = CONFIG.colors.red; // Red
-                    data[pixel_idx + 3] = final_alpha;
-                }
+    let (center_x, center_y) = center;
+    let y_start = (center_y - radius - 1.0).max(0.0) as u32;
+    let x_start = (center_x - radius - 1.0).max(0.0) as u32;
+    for y in y_start..(center_y + radius + 1.0) as u32 {
+        for x in x_start..(center_x + radius + 1.0) as u32 {
+            if x >= width || y >= height {
+                continue;
+            }
+            let dx = x as f32 + 0.5 - center_x;
+            let dy = y as f32 + 0.5 - center_y;
+            // Anti-aliased edge
+            let alpha = (radius + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0) * opacity;
+            if alpha > 0.0 {
+                let pixel_idx = ((y * width + x) * 4) as usize;
+                buffer[pixel_idx] =
+                    (buffer[pixel_idx] as f32 * (1.0 - alpha) + color.0 as f32 * alpha) as u8;
+                buffer[pixel_idx + 1] =
+                    (buffer[pixel_idx + 1] as f32 * (1.0 - alpha) + color.1 as f32 * alpha) as u8;
+                buffer[pixel_idx + 2] =
+                    (buffer[pixel_idx + 2] as f32 * (1.0 - alpha) + color.2 as f32 * alpha) as u8;
             }
         }
     }
-
-    // Draw workspace indicator on top of the background
-    draw_workspace_indicator(data, width, height, font, current_workspace);
-
-    // Render markdown content
-    render_text(
-        data,
-        width,
-        height,
-        font,
-        text,
-        syntax_set,
-        theme,
-        scroll_offset_y,
-    );
 }
 
-// Orchestrator for rendering markdown content
+// Lays out and draws `units` from `start`, whose top is at the given y, until
+// the bottom of the buffer. Returns where each drawn unit ends.
 fn render_text(
     buffer: &mut [u8],
     width: u32,
     height: u32,
-    font: &Font,
-    text: &str,
+    font: &FontSet,
+    units: &[TextUnit],
     syntax_set: &SyntaxSet,
-    theme: &syntect::highlighting::Theme,
-    scroll_offset_y: f32,
-) {
-    let blocks = parse_markdown(text);
-    let mut y_cursor = 40.0 - scroll_offset_y; // Increased top margin to not overlap with the pill
-
-    for block in blocks.iter() {
+    theme: &Theme,
+    start: (usize, f32),
+) -> Vec<f32> {
+    let (first, mut y_cursor) = start;
+    let mut ends: Vec<f32> = Vec::new();
+
+    for (i, unit) in units.iter().enumerate().skip(first) {
+        if let Some(&prev_end) = ends.last() {
+            y_cursor = next_unit_top(font, theme, &units[i - 1], prev_end, unit);
+        }
         if y_cursor > height as f32 {
-            break; // Don't render blocks that are completely off-screen
+            break; // Don't render units that are completely off-screen
         }
 
-        let (content, lang, is_code_block) = match block {
-            ContentBlock::Code(lang, content) => (content.as_str(), lang.as_str(), true),
-            ContentBlock::Text(content) => (content.as_str(), "txt", false), // Treat non-code as plain text
-        };
+        // Images that cannot be read are shown as their path
+        if unit.is_image
+            && let Some(image) = inline_image(theme, unit.text, width)
+        {
+            let x = theme.spacing.padding as i32;
+            inline_images::draw(buffer, width, height, &image, x, y_cursor.round() as i32);
+            ends.push(y_cursor + image.height() as f32);
+            continue;
+        }
+
+        let (padding_top, padding_bottom) = code_panel_padding(font, unit.lang, unit.is_code_block);
+        if unit.is_code_block {
+            let block_height =
+                _calculate_block_height(font, unit.text, syntax_set, theme, width, unit.lang, true);
+            let panel_bottom = y_cursor + padding_top + block_height + padding_bottom;
+            draw_code_panel(
+                buffer,
+                width,
+                height,
+                font,
+                theme,
+                (y_cursor, panel_bottom),
+                code_label(unit.lang),
+            );
+        }
 
-        y_cursor = _render_block(
+        ends.push(_render_block(
             buffer,
             width,
             height,
             font,
-            content,
+            unit.text,
             syntax_set,
             theme,
-            y_cursor,
-            lang,
-            is_code_block,
-        );
-        y_cursor += 15.0; // Add padding between blocks
+            y_cursor + padding_top,
+            unit.lang,
+            unit.is_code_block,
+        ));
     }
+    ends
 }
 
-// Helper to split a string into words and preserving surrounding whitespace.
-// Example: "  fn main() " -> ["  ", "fn", " ", "main", "()", " "]
-fn split_segment_into_tokens(s: &str) -> Vec<String> {
-    let mut tokens = Vec::new();
-    let mut current_token = String::new();
-    let mut in_whitespace = false;
+fn unsupported_compositor_message(missing: &[&str], desktop: &str) -> String {
+    let mut message = format!(
+        "The compositor does not support {}, which the overlay needs to stay on top \
+         of other windows.",
+        missing.join(", ")
+    );
+    if missing.contains(&"zwlr_layer_shell_v1") {
+        if desktop.to_lowercase().contains("gnome") {
+            message.push_str(
+                " GNOME's Mutter does not implement wlr-layer-shell. Run aerogel in a \
+                 session of a compositor that does (Sway, Hyprland, KDE Plasma, river, \
+                 Wayfire), or start that compositor nested in a window, e.g. `sway` \
+                 from a GNOME terminal.",
+            );
+        } else {
+            message.push_str(
+                " Use a compositor implementing wlr-layer-shell, such as Sway, Hyprland, \
+                 KDE Plasma, river or Wayfire.",
+            );
+        }
+    }
+    message
+}
 
-    let mut chars = s.chars().peekable();
-    if let Some(&c) = chars.peek() {
-        in_whitespace = c.is_whitespace();
+// Parses `[app] keyboard_interactivity`, falling back to on-demand focus.
+fn parse_keyboard_interactivity(mode: Option<&str>) -> KeyboardInteractivity {
+    match mode.map(str::to_lowercase).as_deref() {
+        None | Some("on_demand") => KeyboardInteractivity::OnDemand,
+        Some("none") => KeyboardInteractivity::None,
+        Some("exclusive") => KeyboardInteractivity::Exclusive,
+        Some(other) => {
+            eprintln!(
+                "Warning: Unknown keyboard_interactivity '{}', expected none, on_demand or \
+                 exclusive. Using on_demand.",
+                other
+            );
+            KeyboardInteractivity::OnDemand
+        }
     }
+}
 
-    while let Some(c) = chars.next() {
-        let char_is_whitespace = c.is_whitespace();
+// The configured keyboard interactivity, or with the grab toggled, an
+// exclusive grab (or none at all when the configured mode already grabs).
+fn keyboard_interactivity(toggled: bool) -> KeyboardInteractivity {
+    let configured = parse_keyboard_interactivity(CONFIG.app.keyboard_interactivity.as_deref());
+    toggle_keyboard_grab(configured, toggled)
+}
 
-        if current_token.is_empty() {
-            // Always start a new token (and set its type)
-            current_token.push(c);
-            in_whitespace = char_is_whitespace;
-        } else if char_is_whitespace == in_whitespace {
-            // Continue the current token of the same type
-            current_token.push(c);
-        } else {
-            // Type changed (whitespace to non-whitespace or vice versa), push current token
-            tokens.push(current_token);
-            current_token = String::new();
-            current_token.push(c); // Start new token with current character
-            in_whitespace = char_is_whitespace;
+fn toggle_keyboard_grab(configured: KeyboardInteractivity, toggled: bool) -> KeyboardInteractivity {
+    match (toggled, configured) {
+        (false, mode) => mode,
+        (true, KeyboardInteractivity::Exclusive) => KeyboardInteractivity::None,
+        (true, _) => KeyboardInteractivity::Exclusive,
+    }
+}
+
+// The titles of the conversations of `workspaces` and the current one.
+fn conversation_titles(workspaces: &[u32], current: u32) -> HashMap<u32, String> {
+    workspaces
+        .iter()
+        .chain(std::iter::once(&current))
+        .filter_map(|&workspace| {
+            let title = std::fs::read_to_string(ipc::title_file(workspace)).ok()?;
+            let title = title.trim();
+            (!title.is_empty()).then(|| (workspace, title.to_string()))
+        })
+        .collect()
+}
+
+// A workspace's number, followed by its conversation's title cut to
+// TAB_TITLE_CHARS.
+fn tab_label(workspace: u32, title: Option<&String>) -> String {
+    let Some(title) = title else {
+        return workspace.to_string();
+    };
+    if title.chars().count() <= TAB_TITLE_CHARS {
+        return format!("{} · {}", workspace, title);
+    }
+    let cut: String = title.chars().take(TAB_TITLE_CHARS - 1).collect();
+    format!("{} · {}…", workspace, cut.trim_end())
+}
+
+// Workspaces with a conversation file (`.tmp<n>`) in the working directory,
+// in ascending order.
+fn existing_workspaces() -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir(".") else {
+        return Vec::new();
+    };
+    let mut workspaces: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| workspace_of_file(&entry.file_name().to_string_lossy()))
+        .collect();
+    workspaces.sort_unstable();
+    workspaces
+}
+
+fn workspace_of_file(name: &str) -> Option<u32> {
+    name.strip_prefix(".tmp")?
+        .parse()
+        .ok()
+        .filter(|&workspace| workspace > 0)
+}
+
+// Snaps a margin to 0 or `max` when it is within `distance` of either.
+fn snap_to_edge(margin: i32, max: i32, distance: i32) -> i32 {
+    if margin <= distance {
+        0
+    } else if max - margin <= distance {
+        max
+    } else {
+        margin
+    }
+}
+
+// Margins that put the overlay at fractions (fx, fy) of the free space.
+fn preset_margins(fx: f32, fy: f32, max_x: i32, max_y: i32) -> (i32, i32) {
+    (
+        (max_x as f32 * fx).round() as i32,
+        (max_y as f32 * fy).round() as i32,
+    )
+}
+
+// Language name shown in the corner of a code block's panel. Indented code
+// blocks ("txt") and fences without a language get no label.
+fn code_label(lang: &str) -> Option<&str> {
+    lang.split([',', ' '])
+        .next()
+        .filter(|label| !label.is_empty() && *label != "txt")
+}
+
+// Space above and below the code inside its panel; the top leaves room for
+// the language label. Plain text blocks have no panel.
+fn code_panel_padding(font: &FontSet, lang: &str, is_code_block: bool) -> (f32, f32) {
+    if !is_code_block {
+        return (0.0, 0.0);
+    }
+    match code_label(lang) {
+        Some(_) => {
+            let v_metrics = font.v_metrics(Scale::uniform(font.size() * CODE_LABEL_SCALE));
+            (
+                v_metrics.ascent - v_metrics.descent + CODE_PANEL_PADDING,
+                CODE_PANEL_PADDING,
+            )
+        }
+        None => (CODE_PANEL_PADDING, CODE_PANEL_PADDING),
+    }
+}
+
+// Draws the rounded panel behind a code block spanning `span` (top, bottom),
+// with the language label in its top right corner.
+fn draw_code_panel(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    font: &FontSet,
+    theme: &Theme,
+    span: (f32, f32),
+    label: Option<&str>,
+) {
+    let (top, bottom) = span;
+    let left = CODE_PANEL_INSET;
+    let panel_width = width as f32 - 2.0 * CODE_PANEL_INSET;
+    let panel_height = bottom - top;
+    let color = theme.colors.code_background;
+    let color_alpha = color.a as f32 / 255.0;
+
+    let y_start = top.max(0.0) as u32;
+    let y_end = (bottom.max(0.0) as u32).min(height);
+    for y in y_start..y_end {
+        for x in left as u32..(left + panel_width) as u32 {
+            let alpha = calculate_rounded_rect_alpha(
+                x as f32 - left,
+                y as f32 - top,
+                panel_width,
+                panel_height,
+                CODE_PANEL_RADIUS,
+            ) * color_alpha;
+
+            let pixel_idx = ((y * width + x) * 4) as usize;
+            if alpha > 0.0 && pixel_idx + 3 < buffer.len() {
+                let bg_b = buffer[pixel_idx] as f32;
+                let bg_g = buffer[pixel_idx + 1] as f32;
+                let bg_r = buffer[pixel_idx + 2] as f32;
+                buffer[pixel_idx] = (bg_b * (1.0 - alpha) + color.b as f32 * alpha) as u8;
+                buffer[pixel_idx + 1] = (bg_g * (1.0 - alpha) + color.g as f32 * alpha) as u8;
+                buffer[pixel_idx + 2] = (bg_r * (1.0 - alpha) + color.r as f32 * alpha) as u8;
+            }
         }
     }
 
-    if !current_token.is_empty() {
-        tokens.push(current_token);
+    let Some(label) = label else {
+        return;
+    };
+    let scale = Scale::uniform(font.size() * CODE_LABEL_SCALE);
+    let v_metrics = font.v_metrics(scale);
+    let label_x = left + panel_width - CODE_PANEL_PADDING - measure_text_width(font, label, scale);
+    let label_y = top + CODE_PANEL_PADDING / 2.0 + v_metrics.ascent;
+    let label_color = theme.colors.accent;
+
+    font.draw(
+        label,
+        scale,
+        point(label_x, label_y),
+        |pixel_x, pixel_y, v| {
+            if pixel_x >= 0
+                && pixel_x < width as i32
+                && pixel_y >= 0
+                && pixel_y < height as i32
+                && v > 0.1
+            {
+                let pixel_idx = ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
+                let inv_alpha = 1.0 - v;
+                let bg_b = buffer[pixel_idx] as f32;
+                let bg_g = buffer[pixel_idx + 1] as f32;
+                let bg_r = buffer[pixel_idx + 2] as f32;
+                buffer[pixel_idx] = (bg_b * inv_alpha + label_color.b as f32 * v) as u8;
+                buffer[pixel_idx + 1] = (bg_g * inv_alpha + label_color.g as f32 * v) as u8;
+                buffer[pixel_idx + 2] = (bg_r * inv_alpha + label_color.r as f32 * v) as u8;
+            }
+        },
+    );
+}
+
+// Splits a line of highlighted ranges into words at the line break
+// opportunities of UAX #14: after spaces and hyphens, and between most CJK
+// characters. Break opportunities are found on the whole line, so a word
+// highlighted as several ranges stays together. Trailing whitespace stays
+// with its word.
+// Example: "fn main() 你好" -> ["fn ", "main() ", "你", "好"]
+fn line_break_words(ranges: &[(Style, &str)]) -> Vec<Vec<(Style, String)>> {
+    // syntect ranges might include newlines or carriage returns, strip them for word wrapping.
+    let ranges: Vec<(Style, &str)> = ranges
+        .iter()
+        .map(|(style, text)| (*style, text.trim_end_matches(['\n', '\r'])))
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+    let line: String = ranges.iter().map(|(_, text)| *text).collect();
+    let mut breaks = unicode_linebreak::linebreaks(&line).map(|(index, _)| index);
+    let mut next_break = breaks.next();
+
+    let mut words = Vec::new();
+    let mut word: Vec<(Style, String)> = Vec::new();
+    let mut offset = 0;
+    for (style, text) in ranges {
+        let mut rest = text;
+        while !rest.is_empty() {
+            let end = offset + rest.len();
+            match next_break.filter(|&at| at <= end) {
+                Some(at) => {
+                    let (head, tail) = rest.split_at(at - offset);
+                    if !head.is_empty() {
+                        word.push((style, head.to_string()));
+                    }
+                    words.push(std::mem::take(&mut word));
+                    offset = at;
+                    rest = tail;
+                    next_break = breaks.next();
+                }
+                None => {
+                    word.push((style, rest.to_string()));
+                    offset = end;
+                    rest = "";
+                }
+            }
+        }
     }
-    tokens
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words.retain(|word| !word.is_empty());
+    words
 }
 
 // Renders a single block of text (either code or plain)
@@ -1203,30 +4175,30 @@ fn _render_block(
     buffer: &mut [u8],
     width: u32,
     height: u32,
-    font: &Font,
+    font: &FontSet,
     text: &str,
     syntax_set: &SyntaxSet,
-    theme: &syntect::highlighting::Theme,
+    theme: &Theme,
     y_cursor: f32,
     lang: &str,
     is_code_block: bool,
 ) -> f32 {
-    let scale = Scale::uniform(CONFIG.font.size);
+    let scale = Scale::uniform(font.size());
     let v_metrics = font.v_metrics(scale);
-    let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+    let line_height = line_height(font, scale, theme);
     let mut y_offset = y_cursor + v_metrics.ascent;
 
     let syntax = syntax_set
         .find_syntax_by_token(lang)
         .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
-    let mut h = HighlightLines::new(syntax, theme);
+    let mut h = HighlightLines::new(syntax, &theme.syntax);
 
     let lines: Vec<&str> = text.lines().collect();
 
     // Conditionally define layout based on is_code_block
-    let text_margin_left = 18.0;
-    let text_margin_right = 18.0;
-    let line_number_width = 40.0;
+    let text_margin_left = theme.spacing.padding;
+    let text_margin_right = theme.spacing.padding;
+    let line_number_width = theme.line_number_gutter.unwrap_or(0.0);
 
     let text_start_x = if is_code_block {
         text_margin_left + line_number_width
@@ -1235,12 +4207,7 @@ fn _render_block(
     };
     let max_line_width = width as f32 - text_start_x - text_margin_right;
 
-    let line_number_color = syntect::highlighting::Color {
-        r: 128,
-        g: 128,
-        b: 128,
-        a: 255,
-    };
+    let line_number_color = theme.colors.accent;
 
     for (line_idx, line) in lines.iter().enumerate() {
         if y_offset > height as f32 + line_height && line_idx > 0 {
@@ -1253,59 +4220,53 @@ fn _render_block(
 
         let wrapped_lines = wrap_line_with_syntax(&ranges, font, scale, max_line_width);
         let original_line_y = y_offset;
+        let para_level = ParagraphBidiInfo::new(line, None).paragraph_level;
 
         // --- CONDITIONALLY RENDER LINE NUMBER ---
-        if is_code_block {
-            if !wrapped_lines.is_empty() && original_line_y + line_height > 0.0 {
-                let line_number_text = format!("{:>3} ", line_idx + 1);
-                let (text_r, text_g, text_b) = (
-                    line_number_color.r,
-                    line_number_color.g,
-                    line_number_color.b,
-                );
-                let glyphs = font.layout(
-                    &line_number_text,
-                    scale,
-                    point(text_margin_left, original_line_y),
-                );
-
-                for glyph in glyphs {
-                    if let Some(bounding_box) = glyph.pixel_bounding_box() {
-                        glyph.draw(|x, y, v| {
-                            let pixel_x = x as i32 + bounding_box.min.x;
-                            let pixel_y = y as i32 + bounding_box.min.y;
-
-                            if pixel_x >= 0
-                                && pixel_x < width as i32
-                                && pixel_y >= 0
-                                && pixel_y < height as i32
-                            {
-                                let pixel_idx =
-                                    ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
-                                if pixel_idx + 3 < buffer.len() && v > 0.1 {
-                                    let text_alpha = (v * 255.0) as u8;
-                                    if text_alpha > 50 {
-                                        let alpha_f = text_alpha as f32 / 255.0;
-                                        let inv_alpha = 1.0 - alpha_f;
-                                        let bg_b = buffer[pixel_idx] as f32;
-                                        let bg_g = buffer[pixel_idx + 1] as f32;
-                                        let bg_r = buffer[pixel_idx + 2] as f32;
-                                        let bg_a = buffer[pixel_idx + 3] as f32;
-                                        buffer[pixel_idx] =
-                                            (bg_b * inv_alpha + text_b as f32 * alpha_f) as u8;
-                                        buffer[pixel_idx + 1] =
-                                            (bg_g * inv_alpha + text_g as f32 * alpha_f) as u8;
-                                        buffer[pixel_idx + 2] =
-                                            (bg_r * inv_alpha + text_r as f32 * alpha_f) as u8;
-                                        buffer[pixel_idx + 3] =
-                                            ((bg_a + text_alpha as f32).min(255.0)) as u8;
-                                    }
-                                }
+        if is_code_block
+            && theme.line_number_gutter.is_some()
+            && !wrapped_lines.is_empty()
+            && original_line_y + line_height > 0.0
+        {
+            let line_number_text = format!("{:>3} ", line_idx + 1);
+            let (text_r, text_g, text_b) = (
+                line_number_color.r,
+                line_number_color.g,
+                line_number_color.b,
+            );
+            font.draw(
+                &line_number_text,
+                scale,
+                point(text_margin_left, original_line_y),
+                |pixel_x, pixel_y, v| {
+                    if pixel_x >= 0
+                        && pixel_x < width as i32
+                        && pixel_y >= 0
+                        && pixel_y < height as i32
+                    {
+                        let pixel_idx = ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
+                        if pixel_idx + 3 < buffer.len() && v > 0.1 {
+                            let text_alpha = (v * 255.0) as u8;
+                            if text_alpha > 50 {
+                                let alpha_f = text_alpha as f32 / 255.0;
+                                let inv_alpha = 1.0 - alpha_f;
+                                let bg_b = buffer[pixel_idx] as f32;
+                                let bg_g = buffer[pixel_idx + 1] as f32;
+                                let bg_r = buffer[pixel_idx + 2] as f32;
+                                let bg_a = buffer[pixel_idx + 3] as f32;
+                                buffer[pixel_idx] =
+                                    (bg_b * inv_alpha + text_b as f32 * alpha_f) as u8;
+                                buffer[pixel_idx + 1] =
+                                    (bg_g * inv_alpha + text_g as f32 * alpha_f) as u8;
+                                buffer[pixel_idx + 2] =
+                                    (bg_r * inv_alpha + text_r as f32 * alpha_f) as u8;
+                                buffer[pixel_idx + 3] =
+                                    ((bg_a + text_alpha as f32).min(255.0)) as u8;
                             }
-                        });
+                        }
                     }
-                }
-            }
+                },
+            );
         }
 
         for (_wrapped_idx, wrapped_line) in wrapped_lines.into_iter().enumerate() {
@@ -1313,54 +4274,69 @@ fn _render_block(
                 break;
             }
 
+            let wrapped_line = reorder_bidi_line(wrapped_line, para_level);
             let mut x_offset = text_start_x;
 
+            // Right-align right-to-left paragraphs; code stays left-aligned.
+            if para_level.is_rtl() && !is_code_block {
+                let line_width: f32 = wrapped_line
+                    .iter()
+                    .map(|(_, segment)| measure_text_width(font, segment, scale))
+                    .sum();
+                x_offset += (max_line_width - line_width).max(0.0);
+            }
+
             for (style, text_segment) in wrapped_line {
                 if text_segment.is_empty() {
                     continue;
                 }
 
-                let (text_r, text_g, text_b) =
-                    (style.foreground.r, style.foreground.g, style.foreground.b);
-                let glyphs = font.layout(&text_segment, scale, point(x_offset, y_offset));
-
-                for glyph in glyphs {
-                    if let Some(bounding_box) = glyph.pixel_bounding_box() {
-                        glyph.draw(|x, y, v| {
-                            let pixel_x = x as i32 + bounding_box.min.x;
-                            let pixel_y = y as i32 + bounding_box.min.y;
-
-                            if pixel_x >= 0
-                                && pixel_x < width as i32
-                                && pixel_y >= 0
-                                && pixel_y < height as i32
-                            {
-                                let pixel_idx =
-                                    ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
-                                if pixel_idx + 3 < buffer.len() && v > 0.1 {
-                                    let text_alpha = (v * 255.0) as u8;
-                                    if text_alpha > 50 {
-                                        let alpha_f = text_alpha as f32 / 255.0;
-                                        let inv_alpha = 1.0 - alpha_f;
-                                        let bg_b = buffer[pixel_idx] as f32;
-                                        let bg_g = buffer[pixel_idx + 1] as f32;
-                                        let bg_r = buffer[pixel_idx + 2] as f32;
-                                        let bg_a = buffer[pixel_idx + 3] as f32;
-                                        buffer[pixel_idx] =
-                                            (bg_b * inv_alpha + text_b as f32 * alpha_f) as u8;
-                                        buffer[pixel_idx + 1] =
-                                            (bg_g * inv_alpha + text_g as f32 * alpha_f) as u8;
-                                        buffer[pixel_idx + 2] =
-                                            (bg_r * inv_alpha + text_r as f32 * alpha_f) as u8;
-                                        buffer[pixel_idx + 3] =
-                                            ((bg_a + text_alpha as f32).min(255.0)) as u8;
-                                    }
+                // Plain text uses the theme's text color, code keeps its
+                // syntax highlighting.
+                let (text_r, text_g, text_b) = if is_code_block {
+                    (style.foreground.r, style.foreground.g, style.foreground.b)
+                } else {
+                    (
+                        theme.colors.text.r,
+                        theme.colors.text.g,
+                        theme.colors.text.b,
+                    )
+                };
+                font.draw(
+                    &text_segment,
+                    scale,
+                    point(x_offset, y_offset),
+                    |pixel_x, pixel_y, v| {
+                        if pixel_x >= 0
+                            && pixel_x < width as i32
+                            && pixel_y >= 0
+                            && pixel_y < height as i32
+                        {
+                            let pixel_idx =
+                                ((pixel_y as u32 * width + pixel_x as u32) * 4) as usize;
+                            if pixel_idx + 3 < buffer.len() && v > 0.1 {
+                                let text_alpha = (v * 255.0) as u8;
+                                if text_alpha > 50 {
+                                    let alpha_f = text_alpha as f32 / 255.0;
+                                    let inv_alpha = 1.0 - alpha_f;
+                                    let bg_b = buffer[pixel_idx] as f32;
+                                    let bg_g = buffer[pixel_idx + 1] as f32;
+                                    let bg_r = buffer[pixel_idx + 2] as f32;
+                                    let bg_a = buffer[pixel_idx + 3] as f32;
+                                    buffer[pixel_idx] =
+                                        (bg_b * inv_alpha + text_b as f32 * alpha_f) as u8;
+                                    buffer[pixel_idx + 1] =
+                                        (bg_g * inv_alpha + text_g as f32 * alpha_f) as u8;
+                                    buffer[pixel_idx + 2] =
+                                        (bg_r * inv_alpha + text_r as f32 * alpha_f) as u8;
+                                    buffer[pixel_idx + 3] =
+                                        ((bg_a + text_alpha as f32).min(255.0)) as u8;
                                 }
                             }
-                        });
-                    }
-                    x_offset += glyph.unpositioned().h_metrics().advance_width;
-                }
+                        }
+                    },
+                );
+                x_offset += measure_text_width(font, &text_segment, scale);
             }
             y_offset += line_height;
         }
@@ -1369,7 +4345,7 @@ fn _render_block(
 }
 fn wrap_line_with_syntax(
     ranges: &[(Style, &str)],
-    font: &Font,
+    font: &FontSet,
     scale: Scale,
     max_width: f32,
 ) -> Vec<Vec<(Style, String)>> {
@@ -1377,29 +4353,40 @@ fn wrap_line_with_syntax(
     let mut current_line_segments = Vec::new(); // Stores (Style, String) tuples
     let mut current_line_pixel_width = 0.0;
 
-    for (style, text_segment) in ranges {
-        // syntect ranges might include newlines or carriage returns, strip them for word wrapping.
-        let clean_segment = text_segment.trim_end_matches('\n').trim_end_matches('\r');
-        if clean_segment.is_empty() {
-            // An empty segment, or one that was just newlines. Skip.
-            continue;
-        }
+    for word in line_break_words(ranges) {
+        let text: String = word.iter().map(|(_, text)| text.as_str()).collect();
+        let word_pixel_width = measure_text_width(font, &text, scale);
+        // Trailing whitespace may hang past the right edge
+        let visible_width = measure_text_width(font, text.trim_end(), scale);
 
-        let tokens = split_segment_into_tokens(clean_segment);
+        if current_line_pixel_width + visible_width > max_width && current_line_pixel_width > 0.0 {
+            wrapped_lines.push(std::mem::take(&mut current_line_segments));
+            current_line_pixel_width = 0.0;
+        }
 
-        for token in tokens {
-            let token_pixel_width = measure_text_width(font, &token, scale);
+        if visible_width <= max_width {
+            current_line_segments.extend(word);
+            current_line_pixel_width += word_pixel_width;
+            continue;
+        }
 
-            if current_line_pixel_width + token_pixel_width > max_width
-                && current_line_pixel_width > 0.0
-            {
-                wrapped_lines.push(current_line_segments.clone());
-                current_line_segments.clear();
-                current_line_pixel_width = 0.0;
+        // A word wider than the whole line, e.g. a long URL, is broken
+        // between any two characters.
+        for (style, piece) in word {
+            for c in piece.chars() {
+                let char_width = measure_text_width(font, c.encode_utf8(&mut [0; 4]), scale);
+                if current_line_pixel_width + char_width > max_width
+                    && current_line_pixel_width > 0.0
+                {
+                    wrapped_lines.push(std::mem::take(&mut current_line_segments));
+                    current_line_pixel_width = 0.0;
+                }
+                match current_line_segments.last_mut() {
+                    Some((last_style, text)) if *last_style == style => text.push(c),
+                    _ => current_line_segments.push((style, c.to_string())),
+                }
+                current_line_pixel_width += char_width;
             }
-
-            current_line_segments.push((*style, token));
-            current_line_pixel_width += token_pixel_width;
         }
     }
 
@@ -1416,15 +4403,89 @@ fn wrap_line_with_syntax(
     wrapped_lines
 }
 
-fn measure_text_width(font: &Font, text: &str, scale: Scale) -> f32 {
-    let glyphs = font.layout(text, scale, point(0.0, 0.0));
-    let mut width = 0.0;
+// Reorders the styled pieces of one wrapped line into visual order (UAX #9).
+// Pieces inside right-to-left runs are emitted right to left; the shaper
+// takes care of the glyph order inside each piece.
+fn reorder_bidi_line(segments: Vec<(Style, String)>, para_level: Level) -> Vec<(Style, String)> {
+    let line: String = segments.iter().map(|(_, text)| text.as_str()).collect();
+    let info = ParagraphBidiInfo::new(&line, Some(para_level));
+    if !info.has_rtl() {
+        return segments;
+    }
+
+    let (levels, runs) = info.visual_runs(0..line.len());
 
-    for glyph in glyphs {
-        width += glyph.unpositioned().h_metrics().advance_width;
+    let mut bounds = Vec::with_capacity(segments.len());
+    let mut pos = 0;
+    for (_, text) in &segments {
+        bounds.push((pos, pos + text.len()));
+        pos += text.len();
     }
 
-    width
+    let mut ordered = Vec::new();
+    for run in runs {
+        let mut pieces: Vec<(Style, String)> = segments
+            .iter()
+            .zip(&bounds)
+            .filter_map(|((style, _), &(start, end))| {
+                let start = start.max(run.start);
+                let end = end.min(run.end);
+                (start < end).then(|| (*style, line[start..end].to_string()))
+            })
+            .collect();
+        if levels[run.start].is_rtl() {
+            pieces.reverse();
+        }
+        ordered.extend(pieces);
+    }
+    ordered
+}
+
+// The overlay's width: `max_width`, or with `[app] auto_width` the width the
+// widest line of the text needs, at least MIN_WIDTH.
+fn fitted_width(font: &FontSet, text: &str, theme: &Theme, max_width: u32) -> u32 {
+    if !CONFIG.app.auto_width {
+        return max_width;
+    }
+    // A pixel to spare, so the widest line does not wrap on rounding
+    (content_width(font, text, theme).ceil() as u32 + 1).clamp(MIN_WIDTH, max_width)
+}
+
+// The width the text takes without wrapping: its widest line, code lines
+// with the line number gutter, and the padding on both sides.
+fn content_width(font: &FontSet, text: &str, theme: &Theme) -> f32 {
+    let scale = Scale::uniform(font.size());
+    let blocks = visible_blocks(text, theme);
+    let widest = blocks
+        .iter()
+        .flat_map(|block| {
+            let (content, gutter) = match block {
+                ContentBlock::Code(_, content) => {
+                    (content.as_str(), theme.line_number_gutter.unwrap_or(0.0))
+                }
+                ContentBlock::Text(content) => (content.as_str(), 0.0),
+                ContentBlock::Image(path) => match inline_images::natural_width(path) {
+                    Some(width) => return vec![width as f32],
+                    None => (path.as_str(), 0.0),
+                },
+            };
+            content
+                .lines()
+                .map(|line| gutter + measure_text_width(font, line.trim_end(), scale))
+                .collect()
+        })
+        .fold(0.0, f32::max);
+    widest + 2.0 * theme.spacing.padding
+}
+
+// Distance between the baselines of two lines of text at `scale`.
+fn line_height(font: &FontSet, scale: Scale, theme: &Theme) -> f32 {
+    let v_metrics = font.v_metrics(scale);
+    (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap) * theme.spacing.line_height
+}
+
+fn measure_text_width(font: &FontSet, text: &str, scale: Scale) -> f32 {
+    font.text_width(text, scale)
 }
 fn calculate_rounded_rect_alpha(x: f32, y: f32, width: f32, height: f32, radius: f32) -> f32 {
     let corner_x = if x < radius {
@@ -1458,26 +4519,40 @@ fn calculate_rounded_rect_alpha(x: f32, y: f32, width: f32, height: f32, radius:
     }
 }
 
+// An image block as drawn at `width`, None when it cannot be read.
+fn inline_image(theme: &Theme, path: &str, width: u32) -> Option<std::sync::Arc<image::RgbaImage>> {
+    let max_width = (width as f32 - 2.0 * theme.spacing.padding).max(1.0) as u32;
+    inline_images::fitted(path, max_width)
+}
+
 // Orchestrator for calculating total height of markdown content
 fn calculate_text_height(
-    font: &Font,
+    font: &FontSet,
     text: &str,
     syntax_set: &SyntaxSet,
-    theme: &syntect::highlighting::Theme,
+    theme: &Theme,
     width: u32,
 ) -> u32 {
-    let blocks = parse_markdown(text);
-    let mut total_height = 40.0; // 
+    let blocks = visible_blocks(text, theme);
+    let mut total_height = theme.spacing.top;
 
     for block in blocks.iter() {
+        if let ContentBlock::Image(path) = block
+            && let Some(image) = inline_image(theme, path, width)
+        {
+            total_height += image.height() as f32 + theme.spacing.block;
+            continue;
+        }
         let (content, lang, is_code_block) = match block {
             ContentBlock::Code(lang, content) => (content.as_str(), lang.as_str(), true),
             ContentBlock::Text(content) => (content.as_str(), "txt", false),
+            ContentBlock::Image(path) => (path.as_str(), "txt", false),
         };
 
+        let (padding_top, padding_bottom) = code_panel_padding(font, lang, is_code_block);
         total_height +=
             _calculate_block_height(font, content, syntax_set, theme, width, lang, is_code_block);
-        total_height += 15.0; // Padding between blocks
+        total_height += padding_top + padding_bottom + theme.spacing.block; // Padding between blocks
     }
 
     (total_height + 20.0).ceil() as u32 // final bottom margin
@@ -1485,30 +4560,30 @@ fn calculate_text_height(
 
 // Calculates the height of a single block of text
 fn _calculate_block_height(
-    font: &Font,
+    font: &FontSet,
     text: &str,
     syntax_set: &SyntaxSet,
-    theme: &syntect::highlighting::Theme,
+    theme: &Theme,
     width: u32,
     lang: &str,
     is_code_block: bool,
 ) -> f32 {
-    let scale = Scale::uniform(CONFIG.font.size);
+    let scale = Scale::uniform(font.size());
     let v_metrics = font.v_metrics(scale);
-    let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+    let line_height = line_height(font, scale, theme);
     let mut y_offset = v_metrics.ascent;
 
     let syntax = syntax_set
         .find_syntax_by_token(lang)
         .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
-    let mut h = HighlightLines::new(syntax, theme);
+    let mut h = HighlightLines::new(syntax, &theme.syntax);
 
     let lines: Vec<&str> = text.lines().collect();
 
     // Conditionally define layout based on is_code_block
-    let text_margin_left = 18.0;
-    let text_margin_right = 18.0;
-    let line_number_width = 40.0;
+    let text_margin_left = theme.spacing.padding;
+    let text_margin_right = theme.spacing.padding;
+    let line_number_width = theme.line_number_gutter.unwrap_or(0.0);
 
     let text_start_x = if is_code_block {
         text_margin_left + line_number_width
@@ -1619,26 +4694,103 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Initial roundtrip to get globals
     event_queue.roundtrip(&mut state)?;
+    // Without layer-shell the overlay cannot be shown at all, so explain why
+    // instead of running without a surface.
+    let missing = state.missing_globals();
+    if !missing.is_empty() {
+        let message = unsupported_compositor_message(
+            &missing,
+            &std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default(),
+        );
+        eprintln!("[overlay] {}", message);
+        let _ = std::process::Command::new("notify-send")
+            .args(["Aerogel overlay cannot start", &message])
+            .status();
+        std::process::exit(1);
+    }
+
+    if state.layer_shell.is_none() {
+        eprintln!(
+            "[overlay] The compositor does not support zwlr_layer_shell_v1; showing the \
+             overlay as a regular window. The compositor decides where it goes and \
+             whether it stays above other windows."
+        );
+    }
+
+    if CONFIG.app.blur && state.blur_manager.is_none() {
+        eprintln!(
+            "[overlay] The compositor does not support org_kde_kwin_blur_manager; set up \
+             blur for the \"draggable-overlay\" layer in the compositor instead (see README)."
+        );
+    }
+
+    // A second roundtrip so the bound outputs report their names and modes
+    event_queue.roundtrip(&mut state)?;
+    state.select_output();
 
     // Create the overlay
     state.create_overlay(&qh);
 
     // Use a separate thread approach or polling with proper connection management
     let mut last_text_check = std::time::Instant::now();
-    let text_check_interval = std::time::Duration::from_millis(10);
     let mut last_workspace_check = std::time::Instant::now();
     let workspace_check_interval = std::time::Duration::from_millis(250);
 
+    // Poll and redraw less often on battery
+    let power_mode = PowerMode::parse(CONFIG.power.profile.as_deref());
+    let power_save =
+        RenderProfile::power_save(CONFIG.power.battery_poll_ms, CONFIG.power.battery_max_fps);
+    let performance = RenderProfile::performance(CONFIG.power.max_fps);
+    let render_profile = |on_battery: bool| match power_mode {
+        PowerMode::Performance => performance,
+        PowerMode::PowerSave => power_save,
+        PowerMode::Auto if on_battery => power_save,
+        PowerMode::Auto => performance,
+    };
+    let mut on_battery = power_mode == PowerMode::Auto && power::on_battery();
+    let mut profile = render_profile(on_battery);
+    let mut last_power_check = std::time::Instant::now();
+    let mut last_redraw = std::time::Instant::now();
+    let mut redraw_pending = false;
+
     loop {
         // Always check for text updates first, independent of Wayland events
         let now = std::time::Instant::now();
 
         if now.duration_since(last_workspace_check) >= workspace_check_interval {
             last_workspace_check = now;
-            state.check_for_workspace_switch(&qh);
+            state.check_for_workspace_switch();
+            state.check_for_workspaces();
+            state.check_for_capture_pause();
+            state.check_for_heartbeats();
+            state.check_for_progress();
+            state.check_for_collapse();
+            state.check_for_keyboard_grab();
+            state.check_for_thinking();
+            state.check_for_status_line();
+            state.check_for_finished_answers();
+            state.check_for_config_reload();
+            state.check_for_idle();
+            state.save_state_if_dirty();
         }
 
-        if now.duration_since(last_text_check) >= text_check_interval {
+        if power_mode == PowerMode::Auto
+            && now.duration_since(last_power_check) >= POWER_CHECK_INTERVAL
+        {
+            last_power_check = now;
+            let battery = power::on_battery();
+            if battery != on_battery {
+                on_battery = battery;
+                profile = render_profile(on_battery);
+                println!(
+                    "[overlay] {} power, polling every {:?}",
+                    if on_battery { "Battery" } else { "AC" },
+                    profile.poll_interval
+                );
+            }
+        }
+
+        if now.duration_since(last_text_check) >= profile.poll_interval {
             last_text_check = now;
 
             // Force the text update check to run
@@ -1646,10 +4798,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             state.last_text_update = now - state.text_update_interval; // Force update check
 
             if state.update_text_from_log() {
-                state.draw_overlay(&qh);
+                redraw_pending = true;
             }
         }
 
+        state.step_kinetic();
+        state.animate_spinner();
+
+        // Streamed text can change on every poll; redraw at most once per
+        // min_redraw_interval and catch up on the next loop otherwise
+        if redraw_pending && now.duration_since(last_redraw) >= profile.min_redraw_interval {
+            redraw_pending = false;
+            last_redraw = now;
+            state.draw_overlay();
+        }
+
         // Process any pending Wayland events without blocking
         match event_queue.dispatch_pending(&mut state) {
             Ok(dispatched) => {
@@ -1680,7 +4843,356 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        // Small delay to prevent excessive CPU usage
-        std::thread::sleep(std::time::Duration::from_millis(10));
+        // Show what the render thread drew, waiting for it instead of idling
+        state.present_frame(profile.poll_interval, &qh);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markdown_image() {
+        let blocks = parse_markdown("A fox:\n\n![a red fox](<screenshots/fox.png>)\n\nDone.");
+        assert!(matches!(&blocks[0], ContentBlock::Text(text) if text == "A fox:"));
+        assert!(matches!(&blocks[1], ContentBlock::Image(path) if path == "screenshots/fox.png"));
+        assert!(matches!(&blocks[2], ContentBlock::Text(text) if text.trim() == "Done."));
+        assert_eq!(blocks.len(), 3);
+    }
+
+    #[test]
+    fn test_visible_blocks_fold_reasoning() {
+        let mut theme = Theme {
+            name: theme::DEFAULT_THEME.to_string(),
+            colors: ThemeColors::default(),
+            syntax: theme::load_code_theme(None),
+            line_number_gutter: None,
+            show_reasoning: false,
+            spacing: Spacing::default(),
+        };
+        let answer = "````reasoning\nThe user wants a sum.\n````\n\n42";
+        let blocks = visible_blocks(answer, &theme);
+        assert!(matches!(&blocks[0], ContentBlock::Text(text) if text.contains("5 words hidden")));
+        assert!(matches!(&blocks[1], ContentBlock::Text(text) if text.trim() == "42"));
+
+        theme.show_reasoning = true;
+        let blocks = visible_blocks(answer, &theme);
+        assert!(
+            matches!(&blocks[0], ContentBlock::Code(lang, text) if lang == REASONING_LANG && text.starts_with("The user"))
+        );
+    }
+
+    #[test]
+    fn test_format_status_line() {
+        let line =
+            |workspace, contents, now| format_status_line(workspace, &Status::parse(contents), now);
+        assert_eq!(line(2, "", 100), "ws 2");
+        let running =
+            "started=100\nproviders=Claude,Gemini\ntokens_in=300\ntokens_out=1200\ncost=0.0042\n";
+        assert_eq!(
+            line(2, running, 175),
+            "ws 2 · Claude, Gemini · 1m 15s · 1.5k tokens · $0.0042"
+        );
+        let finished = "started=100\nfinished=112\nproviders=\ntokens_in=0\ntokens_out=0\ncost=0\n";
+        assert_eq!(line(1, finished, 500), "ws 1 · 12s");
+        let context = "tokens_in=1200\ntokens_out=300\ncontext_used=1500\ncontext_limit=8000\n";
+        assert_eq!(line(1, context, 500), "ws 1 · 1.5k tokens · 1.5k/8.0k ctx");
+        let unlimited = "tokens_in=120\ntokens_out=30\ncontext_used=150\ncontext_limit=0\n";
+        assert_eq!(line(1, unlimited, 500), "ws 1 · 150 tokens · 150 ctx");
+    }
+
+    #[test]
+    fn test_text_layer_matches_full_redraw() {
+        let font = FontSet::load(None, Some("fonts/JetBrainsMono-Regular.ttf"), 20.0);
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let theme = Theme {
+            name: theme::DEFAULT_THEME.to_string(),
+            colors: ThemeColors::default(),
+            syntax: theme::load_code_theme(None),
+            line_number_gutter: None,
+            show_reasoning: false,
+            spacing: Spacing::default(),
+        };
+        let answer = "Some text that is long enough to wrap around the edge of the overlay.\n\n\
+                      ```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\n\
+                      More text after the code, and a last line that keeps going.";
+
+        let mut streamed = TextLayer::default();
+        for end in (10..answer.len()).step_by(17).chain([answer.len()]) {
+            if answer.is_char_boundary(end) {
+                let scroll = (end / 40) as f32;
+                streamed.update(
+                    (300, 400),
+                    &font,
+                    &answer[..end],
+                    &syntax_set,
+                    &theme,
+                    scroll,
+                );
+            }
+        }
+        let mut full = TextLayer::default();
+        let scroll = (answer.len() / 40) as f32;
+        full.update((300, 400), &font, answer, &syntax_set, &theme, scroll);
+
+        assert_eq!(streamed.ends, full.ends);
+        let differing = streamed
+            .pixels
+            .iter()
+            .zip(&full.pixels)
+            .filter(|(a, b)| a.abs_diff(**b) > 2)
+            .count();
+        assert_eq!(differing, 0);
+    }
+
+    #[test]
+    fn test_text_layer_reuses_unchanged_units() {
+        let font = FontSet::load(None, Some("fonts/JetBrainsMono-Regular.ttf"), 20.0);
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let theme = Theme {
+            name: theme::DEFAULT_THEME.to_string(),
+            colors: ThemeColors::default(),
+            syntax: theme::load_code_theme(None),
+            line_number_gutter: None,
+            show_reasoning: false,
+            spacing: Spacing::default(),
+        };
+        let drawn = "First paragraph.\n\nSecond paragraph.";
+        let mut layer = TextLayer::default();
+        layer.update((300, 400), &font, drawn, &syntax_set, &theme, 0.0);
+        let drawn_units = text_units(&visible_blocks(drawn, &theme)).len();
+        assert_eq!(layer.ends.len(), drawn_units);
+
+        // Where the next update would start drawing for `text`
+        let reusable = |layer: &TextLayer, text: &str| {
+            let blocks = visible_blocks(text, &theme);
+            let units = text_units(&blocks);
+            let hashes: Vec<u64> = units.iter().map(hash_unit).collect();
+            layer.reusable(&font, &theme, &units, &hashes, (300, 400), 0.0)
+        };
+
+        // Appending redraws from the last unchanged unit on, below the rows
+        // kept from the previous frame
+        let appended = format!("{}\n\nThird paragraph.", drawn);
+        let (first, first_top, kept_rows) = reusable(&layer, &appended).unwrap();
+        assert_eq!(first, drawn_units - 1);
+        assert!(first_top > 0.0 && first_top < layer.ends[first]);
+        assert!(kept_rows > 0);
+        let ends = layer.ends.clone();
+        layer.update((300, 400), &font, &appended, &syntax_set, &theme, 0.0);
+        assert_eq!(layer.ends[..first], ends[..first]);
+        assert!(layer.ends.len() > drawn_units);
+
+        // Changing earlier text draws everything again
+        let changed = appended.replace("First", "Changed");
+        assert!(reusable(&layer, &changed).is_none());
+        assert!(reusable(&layer, &appended).is_some());
+        layer.invalidate();
+        assert!(reusable(&layer, &appended).is_none());
+    }
+
+    #[test]
+    fn test_dense_spacing_is_shorter() {
+        let font = FontSet::load(None, Some("fonts/JetBrainsMono-Regular.ttf"), 20.0);
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut theme = Theme {
+            name: theme::DEFAULT_THEME.to_string(),
+            colors: ThemeColors::default(),
+            syntax: theme::load_code_theme(None),
+            line_number_gutter: None,
+            show_reasoning: false,
+            spacing: Spacing::default(),
+        };
+        let answer = "First paragraph.\n\nSecond paragraph.\n\n```rust\nlet a = 1;\n```";
+        let height = |theme: &Theme| calculate_text_height(&font, answer, &syntax_set, theme, 400);
+
+        let default = height(&theme);
+        theme.spacing = Spacing {
+            padding: 8.0,
+            top: 30.0,
+            block: 5.0,
+            line_height: 1.0,
+        };
+        // Two blocks, the text and the code; nothing wraps either way
+        assert_eq!(height(&theme), default - 10 - 2 * 10);
+        theme.spacing.line_height = 0.8;
+        assert!(height(&theme) < default - 40);
+    }
+
+    fn texts(segments: &[(Style, String)]) -> Vec<&str> {
+        segments.iter().map(|(_, text)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_reorder_bidi_line_mixed_direction() {
+        let style = Style::default();
+        let segments = vec![
+            (style, "// ".to_string()),
+            (style, "שלום ".to_string()),
+            (style, "עולם".to_string()),
+        ];
+
+        let ordered = reorder_bidi_line(segments, Level::ltr());
+        assert_eq!(texts(&ordered), vec!["// ", "עולם", "שלום "]);
+    }
+
+    #[test]
+    fn test_resize_edges_at() {
+        assert_eq!(ResizeEdges::at(400.0, 300.0, 800, 600), None);
+        assert_eq!(
+            ResizeEdges::at(797.0, 300.0, 800, 600),
+            Some(ResizeEdges {
+                left: false,
+                right: true,
+                top: false,
+                bottom: false
+            })
+        );
+        assert_eq!(
+            ResizeEdges::at(2.0, 598.0, 800, 600),
+            Some(ResizeEdges {
+                left: true,
+                right: false,
+                top: false,
+                bottom: true
+            })
+        );
+    }
+
+    #[test]
+    fn test_line_break_words() {
+        let style = Style::default();
+        let words = |ranges: &[&str]| -> Vec<String> {
+            let ranges: Vec<(Style, &str)> = ranges.iter().map(|text| (style, *text)).collect();
+            line_break_words(&ranges)
+                .iter()
+                .map(|word| word.iter().map(|(_, text)| text.as_str()).collect())
+                .collect()
+        };
+        assert_eq!(
+            words(&["fn ", "main", "() ", "你好\n"]),
+            vec!["fn ", "main() ", "你", "好"]
+        );
+        assert_eq!(words(&["これは、テスト。"]).len(), 6);
+        assert_eq!(words(&["\n"]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_code_label() {
+        assert_eq!(code_label("rust"), Some("rust"));
+        assert_eq!(code_label("rust,ignore"), Some("rust"));
+        assert_eq!(code_label(""), None);
+        assert_eq!(code_label("txt"), None);
+    }
+
+    #[test]
+    fn test_output_matches() {
+        let descriptions = [
+            Some("Dell Inc. DELL U2720Q (DP-2)"),
+            Some("Dell Inc. DELL U2720Q"),
+        ];
+        assert!(output_matches("DP-2", Some("DP-2"), &descriptions));
+        assert!(output_matches("u2720q", Some("DP-2"), &descriptions));
+        assert!(!output_matches("HDMI-A-1", Some("DP-2"), &descriptions));
+        assert!(output_matches("dp-1", Some("DP-1"), &[None, None]));
+    }
+
+    #[test]
+    fn test_unsupported_compositor_message() {
+        let message = unsupported_compositor_message(&["zwlr_layer_shell_v1"], "ubuntu:GNOME");
+        assert!(message.contains("zwlr_layer_shell_v1"));
+        assert!(message.contains("Mutter"));
+        assert!(!unsupported_compositor_message(&["wl_shm"], "sway").contains("Sway"));
+    }
+
+    #[test]
+    fn test_workspace_tabs() {
+        assert_eq!(workspace_of_file(".tmp3"), Some(3));
+        assert_eq!(workspace_of_file(".tmp0"), None);
+        assert_eq!(workspace_of_file(".tmpfile"), None);
+
+        let font = FontSet::load(None, Some("fonts/JetBrainsMono-Regular.ttf"), 20.0);
+        let status = StatusBar {
+            chip_label: "2 · paused".to_string(),
+            workspace: 2,
+            workspaces: vec![1, 3],
+            titles: HashMap::from([(3, "Fixing the borrow checker errors in main".to_string())]),
+            components_alive: Vec::new(),
+            spinner: None,
+            status_line: None,
+        };
+        let tabs = workspace_tabs(&font, &status);
+        let labels: Vec<&str> = tabs.iter().map(|(_, label, _)| label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec!["1", "2 · paused", "3 · Fixing the borrow check…"]
+        );
+        assert_eq!(tabs[0].2, CHIP_PADDING);
+        assert!(tabs[1].2 > tabs[0].2 && tabs[2].2 > tabs[1].2);
+    }
+
+    #[test]
+    fn test_derived_workspace_color() {
+        let colors: Vec<Rgba> = (1..=9).map(derived_workspace_color).collect();
+        for (i, color) in colors.iter().enumerate() {
+            assert_eq!(color.a, 255);
+            // Dark enough for the light chip text
+            assert!(color.r.max(color.g).max(color.b) < 180);
+            assert!(!colors[..i].contains(color));
+        }
+        assert_eq!(derived_workspace_color(2), derived_workspace_color(2));
+    }
+
+    #[test]
+    fn test_resize_edges_xdg_edge() {
+        use xdg_toplevel::ResizeEdge;
+
+        let edges = |x, y| ResizeEdges::at(x, y, 800, 600).unwrap().xdg_edge();
+        assert_eq!(edges(2.0, 2.0), ResizeEdge::TopLeft);
+        assert_eq!(edges(797.0, 598.0), ResizeEdge::BottomRight);
+        assert_eq!(edges(400.0, 2.0), ResizeEdge::Top);
+        assert_eq!(edges(2.0, 300.0), ResizeEdge::Left);
+    }
+
+    #[test]
+    fn test_resize_edges_cursor_shape() {
+        let shape = |x, y| ResizeEdges::at(x, y, 800, 600).unwrap().cursor_shape();
+        assert_eq!(shape(797.0, 2.0), Shape::NeResize);
+        assert_eq!(shape(2.0, 598.0), Shape::SwResize);
+        assert_eq!(shape(797.0, 300.0), Shape::EResize);
+        assert_eq!(shape(400.0, 598.0), Shape::SResize);
+    }
+
+    #[test]
+    fn test_keyboard_interactivity() {
+        use KeyboardInteractivity::{Exclusive, None, OnDemand};
+
+        assert_eq!(parse_keyboard_interactivity(Option::None), OnDemand);
+        assert_eq!(parse_keyboard_interactivity(Some("Exclusive")), Exclusive);
+        assert_eq!(parse_keyboard_interactivity(Some("none")), None);
+        assert_eq!(parse_keyboard_interactivity(Some("always")), OnDemand);
+        assert_eq!(toggle_keyboard_grab(OnDemand, false), OnDemand);
+        assert_eq!(toggle_keyboard_grab(OnDemand, true), Exclusive);
+        assert_eq!(toggle_keyboard_grab(None, true), Exclusive);
+        assert_eq!(toggle_keyboard_grab(Exclusive, true), None);
+    }
+
+    #[test]
+    fn test_snap_to_edge() {
+        assert_eq!(snap_to_edge(15, 1000, 20), 0);
+        assert_eq!(snap_to_edge(500, 1000, 20), 500);
+        assert_eq!(snap_to_edge(985, 1000, 20), 1000);
+        assert_eq!(snap_to_edge(5, 1000, 0), 5);
+        assert_eq!(preset_margins(0.5, 1.0, 1001, 400), (501, 400));
+    }
+
+    #[test]
+    fn test_reorder_bidi_line_ltr_untouched() {
+        let style = Style::default();
+        let segments = vec![(style, "fn ".to_string()), (style, "main()".to_string())];
+
+        let ordered = reorder_bidi_line(segments, Level::ltr());
+        assert_eq!(texts(&ordered), vec!["fn ", "main()"]);
     }
 }
@@ -0,0 +1,109 @@
+// Model parameters for a single prompt, e.g. `/ask --temp 0 --max-tokens 300`.
+//
+// Overrides are layered on top of each provider's ModelConfig for the one
+// request and forgotten afterwards. They can follow `@<provider>`:
+//
+//   --temp, --temperature <x>   sampling temperature
+//   --top-p <x>                 nucleus sampling
+//   --max-tokens <n>            answer length limit
+//   --reasoning-tokens <n>      reasoning budget (Claude and Gemini)
+
+use crate::config::ModelConfig;
+use anyhow::{Result, anyhow};
+use std::fmt;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Overrides {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub reasoning_tokens: Option<u32>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, model: &ModelConfig) -> ModelConfig {
+        ModelConfig {
+            temperature: self.temperature.unwrap_or(model.temperature),
+            top_p: self.top_p.unwrap_or(model.top_p),
+            max_tokens: self.max_tokens.unwrap_or(model.max_tokens),
+            reasoning_tokens: self.reasoning_tokens.unwrap_or(model.reasoning_tokens),
+            ..model.clone()
+        }
+    }
+}
+
+impl fmt::Display for Overrides {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(temperature) = self.temperature {
+            parts.push(format!("temperature {}", temperature));
+        }
+        if let Some(top_p) = self.top_p {
+            parts.push(format!("top_p {}", top_p));
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            parts.push(format!("max_tokens {}", max_tokens));
+        }
+        if let Some(reasoning_tokens) = self.reasoning_tokens {
+            parts.push(format!("reasoning_tokens {}", reasoning_tokens));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+fn value<T: std::str::FromStr>(flag: &str, value: Option<&str>) -> Result<T> {
+    let value = value.ok_or_else(|| anyhow!("{} needs a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| anyhow!("'{}' is not a valid value for {}", value, flag))
+}
+
+// The provider named with `@<name>` and the overrides in the arguments of
+// `/ask`.
+pub fn parse_ask_args(args: &str) -> Result<(Option<&str>, Overrides)> {
+    let mut target = None;
+    let mut overrides = Overrides::default();
+    let mut words = args.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "--temp" | "--temperature" => overrides.temperature = Some(value(word, words.next())?),
+            "--top-p" => overrides.top_p = Some(value(word, words.next())?),
+            "--max-tokens" => overrides.max_tokens = Some(value(word, words.next())?),
+            "--reasoning-tokens" => overrides.reasoning_tokens = Some(value(word, words.next())?),
+            _ => match word.strip_prefix('@') {
+                Some(name) if target.is_none() => target = Some(name),
+                _ => return Err(anyhow!("Unknown /ask argument '{}'", word)),
+            },
+        }
+    }
+    Ok((target, overrides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ask_args() {
+        let (target, overrides) = parse_ask_args("@Claude --temp 0 --max-tokens 300").unwrap();
+        assert_eq!(target, Some("Claude"));
+        assert_eq!(
+            overrides,
+            Overrides {
+                temperature: Some(0.0),
+                max_tokens: Some(300),
+                ..Overrides::default()
+            }
+        );
+        assert_eq!(overrides.to_string(), "temperature 0, max_tokens 300");
+
+        assert_eq!(parse_ask_args("").unwrap(), (None, Overrides::default()));
+        assert!(parse_ask_args("--temp").is_err());
+        assert!(parse_ask_args("--max-tokens lots").is_err());
+        assert!(parse_ask_args("--fast").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+// Each provider's answer of the current round on its own, for the overlay's
+// provider panes.
+//
+// The transcript gets the providers' chunks in the order they arrive, so the
+// answers of several providers interleave there. Every chunk is also appended
+// to `.aerogel_panes/ws<workspace>/<provider>.md`, which the overlay shows
+// under a heading when the user switches to that provider's pane with Ctrl+[
+// and Ctrl+]. The directory is emptied when a round starts.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub struct Panes {
+    dir: PathBuf,
+}
+
+impl Panes {
+    pub fn new(workspace: Option<u32>) -> Self {
+        Self {
+            dir: PathBuf::from(".aerogel_panes").join(format!(
+                "ws{}",
+                workspace.map(|w| w.to_string()).unwrap_or_default()
+            )),
+        }
+    }
+
+    fn path(&self, provider: &str) -> PathBuf {
+        self.dir.join(format!("{}.md", provider))
+    }
+
+    // Removes the previous round's panes.
+    pub fn clear(&self) -> io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => std::fs::create_dir_all(&self.dir),
+        }
+    }
+
+    pub fn append(&self, provider: &str, text: &str) -> io::Result<()> {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(provider))?
+            .write_all(text.as_bytes())
+    }
+
+    // Replaces a pane, e.g. with the reshaped answer of an answer format.
+    pub fn set(&self, provider: &str, text: &str) -> io::Result<()> {
+        std::fs::write(self.path(provider), text)
+    }
+
+    // Providers with an answer in the current round, by name. Only the
+    // overlay reads the panes.
+    #[allow(dead_code)]
+    pub fn providers(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut providers: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| Some(entry.file_name().to_str()?.strip_suffix(".md")?.to_string()))
+            .collect();
+        providers.sort_unstable();
+        providers
+    }
+
+    #[allow(dead_code)]
+    pub fn read(&self, provider: &str) -> String {
+        std::fs::read_to_string(self.path(provider)).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panes() {
+        let dir = tempfile::tempdir().unwrap();
+        let panes = Panes {
+            dir: dir.path().join("ws1"),
+        };
+        panes.clear().unwrap();
+        panes.append("Claude", "Hello").unwrap();
+        panes.append("Gemini", "Hi").unwrap();
+        panes.append("Claude", ", world").unwrap();
+        assert_eq!(panes.read("Claude"), "Hello, world");
+        assert_eq!(panes.providers(), vec!["Claude", "Gemini"]);
+
+        panes.clear().unwrap();
+        assert!(panes.providers().is_empty());
+        assert_eq!(panes.read("Claude"), "");
+    }
+}
@@ -0,0 +1,143 @@
+// Power-aware render profile.
+//
+// On battery the overlay polls for text less often and caps how often it
+// redraws, since the default 10ms loop keeps a laptop from idling. Whether
+// the machine runs on battery comes from UPower's `OnBattery` property over
+// D-Bus (through `busctl`), falling back to the mains supplies in
+// /sys/class/power_supply when UPower is not available.
+
+use std::process::Command;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerMode {
+    // Follow the power source
+    Auto,
+    Performance,
+    PowerSave,
+}
+
+impl PowerMode {
+    pub fn parse(name: Option<&str>) -> Self {
+        match name.map(str::to_lowercase).as_deref() {
+            Some("performance") => PowerMode::Performance,
+            Some("powersave") | Some("battery") => PowerMode::PowerSave,
+            Some("auto") | None => PowerMode::Auto,
+            Some(other) => {
+                eprintln!("Warning: Unknown power profile '{}', using 'auto'.", other);
+                PowerMode::Auto
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderProfile {
+    // How often the text files are polled and the main loop wakes up
+    pub poll_interval: Duration,
+    // Minimum time between two redraws for text updates
+    pub min_redraw_interval: Duration,
+}
+
+impl RenderProfile {
+    // Polls every 10ms; redraws as often as the text changes unless capped
+    // with `max_fps` (0 leaves it uncapped)
+    pub fn performance(max_fps: u32) -> Self {
+        Self {
+            poll_interval: Duration::from_millis(10),
+            min_redraw_interval: match max_fps {
+                0 => Duration::ZERO,
+                fps => Duration::from_secs(1) / fps,
+            },
+        }
+    }
+
+    pub fn power_save(poll_ms: u64, max_fps: u32) -> Self {
+        Self {
+            poll_interval: Duration::from_millis(poll_ms.max(10)),
+            min_redraw_interval: Duration::from_secs(1) / max_fps.max(1),
+        }
+    }
+}
+
+// "b true" -> Some(true)
+fn parse_busctl_bool(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("b ")? {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn upower_on_battery() -> Option<bool> {
+    let output = Command::new("busctl")
+        .args([
+            "--system",
+            "get-property",
+            "org.freedesktop.UPower",
+            "/org/freedesktop/UPower",
+            "org.freedesktop.UPower",
+            "OnBattery",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_busctl_bool(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn sysfs_on_battery() -> Option<bool> {
+    let mut found_mains = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+        if kind.trim() != "Mains" {
+            continue;
+        }
+        found_mains = true;
+        if std::fs::read_to_string(path.join("online")).is_ok_and(|s| s.trim() == "1") {
+            return Some(false);
+        }
+    }
+    found_mains.then_some(true)
+}
+
+// Whether the machine runs on battery; false when it cannot be told.
+pub fn on_battery() -> bool {
+    upower_on_battery()
+        .or_else(sysfs_on_battery)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_busctl_bool() {
+        assert_eq!(parse_busctl_bool("b true\n"), Some(true));
+        assert_eq!(parse_busctl_bool("b false"), Some(false));
+        assert_eq!(parse_busctl_bool(""), None);
+    }
+
+    #[test]
+    fn test_power_save_profile() {
+        let profile = RenderProfile::power_save(50, 20);
+        assert_eq!(profile.poll_interval, Duration::from_millis(50));
+        assert_eq!(profile.min_redraw_interval, Duration::from_millis(50));
+        assert_eq!(
+            RenderProfile::performance(0).min_redraw_interval,
+            Duration::ZERO
+        );
+        assert_eq!(
+            RenderProfile::performance(25).min_redraw_interval,
+            Duration::from_millis(40)
+        );
+        assert_eq!(
+            PowerMode::parse(Some("Performance")),
+            PowerMode::Performance
+        );
+        assert_eq!(PowerMode::parse(None), PowerMode::Auto);
+    }
+}
@@ -0,0 +1,925 @@
+// The chat APIs aerogel asks, and what sets them apart.
+//
+// A provider knows how to turn the prompt into its API's user message, how
+// to build the streaming request around it and the conversation history, and
+// how to read the text out of one `data: ` line of the response stream.
+// Everything else (media preprocessing, sending, error handling, streaming)
+// is shared in `AiClient::chat`. Most APIs speak OpenAI's chat completions
+// format, so they are `ChatCompletions` values that only differ in name,
+// settings and media support; `registry` lists the providers every prompt
+// is sent to.
+
+use crate::ai_client::{Media, Message};
+use crate::config::{ApiConfig, ModelConfig};
+use crate::video;
+use anyhow::{Result, anyhow};
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+// What `AiClient::chat` does with the attached media before the user
+// message is built.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaPrep {
+    None,
+    // Audio is transcribed with OpenAI's or Gemini's API and the transcript
+    // put before the prompt
+    TranscribeOpenAi,
+    TranscribeGemini,
+    // Text in images is extracted with the `[ocr]` backend and put after
+    // the prompt; images it fails on are attached as they are
+    OcrImages,
+}
+
+// How the API key is sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyStyle {
+    // `Authorization: Bearer <key>`
+    Bearer,
+    // `api-key: <key>`, as Azure OpenAI expects
+    ApiKeyHeader,
+}
+
+// A piece of a streaming answer: answer text, or the model's reasoning
+// ("thinking"), which the overlay shows folded and the history leaves out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Chunk {
+    Answer(String),
+    Reasoning(String),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum ThinkState {
+    #[default]
+    Start,
+    Inside,
+    Answer,
+}
+
+// Separates the reasoning that models like DeepSeek-R1 wrap in `<think>`
+// tags at the start of their answer, when the server sends it inline rather
+// than in a field of its own. Tags later in the answer are left alone.
+#[derive(Default)]
+pub struct ThinkTags {
+    state: ThinkState,
+}
+
+impl ThinkTags {
+    pub fn split(&mut self, text: &str) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let mut rest = text;
+        if self.state == ThinkState::Start {
+            let trimmed = rest.trim_start();
+            if trimmed.is_empty() {
+                return chunks;
+            }
+            match trimmed.strip_prefix("<think>") {
+                Some(inside) => {
+                    self.state = ThinkState::Inside;
+                    rest = inside;
+                }
+                None => self.state = ThinkState::Answer,
+            }
+        }
+        if self.state == ThinkState::Inside {
+            match rest.split_once("</think>") {
+                Some((reasoning, answer)) => {
+                    if !reasoning.is_empty() {
+                        chunks.push(Chunk::Reasoning(reasoning.to_string()));
+                    }
+                    self.state = ThinkState::Answer;
+                    rest = answer.trim_start();
+                }
+                None => {
+                    chunks.push(Chunk::Reasoning(rest.to_string()));
+                    return chunks;
+                }
+            }
+        }
+        if !rest.is_empty() {
+            chunks.push(Chunk::Answer(rest.to_string()));
+        }
+        chunks
+    }
+}
+
+pub trait Provider: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn model(&self) -> &ModelConfig;
+
+    // Whether the API key it needs is set.
+    fn has_key(&self) -> bool;
+
+    // Its API keys, used in turn as they get rate limited (see key_pool.rs).
+    fn api_keys(&self) -> &[String];
+
+    fn media_prep(&self) -> MediaPrep {
+        MediaPrep::None
+    }
+
+    // The user message's content for the prompt text and its remaining
+    // media; it is also what the history keeps.
+    fn user_content(&self, text: &str, media: &[Media]) -> Value;
+
+    // `model` is its model settings with the prompt's overrides applied.
+    fn build_request(
+        &self,
+        http: &Client,
+        key: Option<&str>,
+        model: &ModelConfig,
+        system_prompt: Option<&str>,
+        history: &[Message],
+        user_content: &Value,
+    ) -> Result<RequestBuilder>;
+
+    // The answer text in one `data: ` line of the response stream, if any.
+    fn parse_stream_chunk(&self, data: &str) -> Option<String>;
+
+    // The reasoning ("thinking") text in one `data: ` line, for models that
+    // stream it apart from the answer.
+    fn parse_reasoning_chunk(&self, _data: &str) -> Option<String> {
+        None
+    }
+}
+
+fn skip_media(provider: &str, media: &Media) {
+    if video::is_video(&media.mime_type) {
+        eprintln!(
+            "\n[Warning ({})]: Skipping a video; only Gemini reads video.",
+            provider
+        );
+        return;
+    }
+    eprintln!(
+        "\n[Warning ({})]: Skipping media with unsupported MIME type '{}'.",
+        provider, media.mime_type
+    );
+}
+
+fn api_key<'a>(provider: &str, key: Option<&'a str>) -> Result<&'a str> {
+    key.ok_or_else(|| anyhow!("{} API key not found", provider))
+}
+
+// An OpenAI-style chat completions API.
+pub struct ChatCompletions {
+    pub name: String,
+    pub model: ModelConfig,
+    // Sent as a bearer token when set
+    pub api_keys: Vec<String>,
+    // Whether requests fail without a key, unlike with a local server
+    pub needs_key: bool,
+    pub key_style: KeyStyle,
+    // Images are sent as `image_url` parts; without them the content is
+    // the plain prompt text and all media is dropped
+    pub images: bool,
+    pub media_prep: MediaPrep,
+}
+
+impl Provider for ChatCompletions {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &ModelConfig {
+        &self.model
+    }
+
+    fn has_key(&self) -> bool {
+        !self.needs_key || !self.api_keys.is_empty()
+    }
+
+    fn api_keys(&self) -> &[String] {
+        &self.api_keys
+    }
+
+    fn media_prep(&self) -> MediaPrep {
+        self.media_prep
+    }
+
+    fn user_content(&self, text: &str, media: &[Media]) -> Value {
+        if !self.images {
+            if !media.is_empty() {
+                eprintln!(
+                    "\n[Warning ({})]: This model does not support media inputs. Ignoring all attached files.",
+                    self.name
+                );
+            }
+            return json!(text);
+        }
+        let transcribed = matches!(
+            self.media_prep,
+            MediaPrep::TranscribeOpenAi | MediaPrep::TranscribeGemini
+        );
+        let mut parts = vec![json!({ "type": "text", "text": text })];
+        for media in media {
+            if media.mime_type.starts_with("image/") {
+                let image_url = format!("data:{};base64,{}", media.mime_type, media.data);
+                parts.push(json!({
+                    "type": "image_url",
+                    "image_url": { "url": image_url }
+                }));
+            } else if !(transcribed && media.mime_type.starts_with("audio/")) {
+                skip_media(&self.name, media);
+            }
+        }
+        json!(parts)
+    }
+
+    fn build_request(
+        &self,
+        http: &Client,
+        key: Option<&str>,
+        model: &ModelConfig,
+        system_prompt: Option<&str>,
+        history: &[Message],
+        user_content: &Value,
+    ) -> Result<RequestBuilder> {
+        let messages: Vec<Value> = system_prompt
+            .map(|prompt| json!({ "role": "system", "content": prompt }))
+            .into_iter()
+            .chain(history.iter().map(|m| json!(m)))
+            .chain([json!({ "role": "user", "content": user_content })])
+            .collect();
+        let payload = json!({
+            "model": &model.model,
+            "messages": messages,
+            "max_tokens": model.max_tokens,
+            "temperature": model.temperature,
+            "top_p": model.top_p,
+            "stream": true
+        });
+
+        let mut request = http
+            .post(model.api_base.trim_end_matches('/'))
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        if self.needs_key || key.is_some() {
+            let key = api_key(&self.name, key)?;
+            request = match self.key_style {
+                KeyStyle::Bearer => request.header("Authorization", format!("Bearer {}", key)),
+                KeyStyle::ApiKeyHeader => request.header("api-key", key),
+            };
+        }
+        Ok(request)
+    }
+
+    fn parse_stream_chunk(&self, data: &str) -> Option<String> {
+        let json: Value = serde_json::from_str(data).ok()?;
+        json["choices"][0]["delta"]["content"]
+            .as_str()
+            .map(str::to_string)
+    }
+
+    // DeepSeek sends `reasoning_content`, OpenRouter and Ollama `reasoning`.
+    fn parse_reasoning_chunk(&self, data: &str) -> Option<String> {
+        let json: Value = serde_json::from_str(data).ok()?;
+        let delta = &json["choices"][0]["delta"];
+        delta["reasoning_content"]
+            .as_str()
+            .or_else(|| delta["reasoning"].as_str())
+            .filter(|text| !text.is_empty())
+            .map(str::to_string)
+    }
+}
+
+// Anthropic's messages API.
+pub struct Claude {
+    pub model: ModelConfig,
+    pub api_keys: Vec<String>,
+}
+
+impl Provider for Claude {
+    fn name(&self) -> &str {
+        "Claude"
+    }
+
+    fn model(&self) -> &ModelConfig {
+        &self.model
+    }
+
+    fn has_key(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn api_keys(&self) -> &[String] {
+        &self.api_keys
+    }
+
+    fn user_content(&self, text: &str, media: &[Media]) -> Value {
+        let mut parts = vec![json!({ "type": "text", "text": text })];
+        for media in media {
+            if media.mime_type.starts_with("image/") {
+                parts.push(json!({
+                    "type": "image",
+                    "source": {
+                        "type": "base64",
+                        "media_type": &media.mime_type,
+                        "data": &media.data,
+                    }
+                }));
+            } else {
+                skip_media(self.name(), media);
+            }
+        }
+        json!(parts)
+    }
+
+    fn build_request(
+        &self,
+        http: &Client,
+        key: Option<&str>,
+        model: &ModelConfig,
+        system_prompt: Option<&str>,
+        history: &[Message],
+        user_content: &Value,
+    ) -> Result<RequestBuilder> {
+        let key = api_key(self.name(), key)?;
+        let messages: Vec<Value> = history
+            .iter()
+            .map(|m| json!(m))
+            .chain([json!({ "role": "user", "content": user_content })])
+            .collect();
+        let mut payload = json!({
+            "model": &model.model,
+            "max_tokens": model.max_tokens,
+            "messages": messages,
+            "temperature": model.temperature,
+            "top_p": model.top_p,
+            "stream": true
+        });
+        if let Some(system_prompt) = system_prompt {
+            payload["system"] = json!(system_prompt);
+        }
+        // Extended thinking takes neither a temperature nor top_p
+        if model.reasoning_tokens > 0 {
+            payload["thinking"] = json!({
+                "type": "enabled",
+                "budget_tokens": model.reasoning_tokens,
+            });
+            if let Some(payload) = payload.as_object_mut() {
+                payload.remove("temperature");
+                payload.remove("top_p");
+            }
+        }
+
+        Ok(http
+            .post(&model.api_base)
+            .header("x-api-key", key)
+            .header("Content-Type", "application/json")
+            .header("anthropic-version", "2023-06-01")
+            .json(&payload))
+    }
+
+    fn parse_stream_chunk(&self, data: &str) -> Option<String> {
+        let json: Value = serde_json::from_str(data).ok()?;
+        if json["type"] != "content_block_delta" {
+            return None;
+        }
+        json["delta"]["text"].as_str().map(str::to_string)
+    }
+
+    fn parse_reasoning_chunk(&self, data: &str) -> Option<String> {
+        let json: Value = serde_json::from_str(data).ok()?;
+        if json["type"] != "content_block_delta" {
+            return None;
+        }
+        json["delta"]["thinking"].as_str().map(str::to_string)
+    }
+}
+
+// Google's Gemini API, which takes the API key in the URL and calls the
+// assistant "model".
+pub struct Gemini {
+    pub model: ModelConfig,
+    pub api_keys: Vec<String>,
+}
+
+impl Provider for Gemini {
+    fn name(&self) -> &str {
+        "Gemini"
+    }
+
+    fn model(&self) -> &ModelConfig {
+        &self.model
+    }
+
+    fn has_key(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn api_keys(&self) -> &[String] {
+        &self.api_keys
+    }
+
+    fn media_prep(&self) -> MediaPrep {
+        MediaPrep::TranscribeGemini
+    }
+
+    // Gemini reads images, video and documents inline; audio has been
+    // transcribed.
+    fn user_content(&self, text: &str, media: &[Media]) -> Value {
+        let mut parts = vec![json!({ "text": text })];
+        for media in media {
+            if !media.mime_type.starts_with("audio/") {
+                parts.push(json!({
+                    "inline_data": {
+                        "mime_type": &media.mime_type,
+                        "data": &media.data
+                    }
+                }));
+            }
+        }
+        json!(parts)
+    }
+
+    fn build_request(
+        &self,
+        http: &Client,
+        key: Option<&str>,
+        model: &ModelConfig,
+        system_prompt: Option<&str>,
+        history: &[Message],
+        user_content: &Value,
+    ) -> Result<RequestBuilder> {
+        let key = api_key(self.name(), key)?;
+        let contents: Vec<Value> = history
+            .iter()
+            .map(|msg| {
+                let role = if msg.role == "assistant" {
+                    "model"
+                } else {
+                    "user"
+                };
+                let parts = if msg.content.is_array() {
+                    msg.content.clone()
+                } else {
+                    json!([{"text": msg.content}])
+                };
+                json!({ "role": role, "parts": parts })
+            })
+            .chain([json!({ "role": "user", "parts": user_content })])
+            .collect();
+
+        let mut payload = json!({
+            "contents": contents,
+            "generationConfig": {
+                "maxOutputTokens": model.max_tokens,
+                "temperature": model.temperature,
+                "topP": model.top_p,
+            }
+        });
+        if let Some(system_prompt) = system_prompt {
+            payload["systemInstruction"] = json!({ "parts": [{ "text": system_prompt }] });
+        }
+        if model.reasoning_tokens > 0 {
+            payload["generationConfig"]["thinkingConfig"] = json!({
+                "thinkingBudget": model.reasoning_tokens,
+                "includeThoughts": true,
+            });
+        }
+
+        let base_url = model.api_base.replace("{model}", &model.model);
+        let url = format!("{}:streamGenerateContent?key={}&alt=sse", base_url, key);
+        Ok(http.post(&url).json(&payload))
+    }
+
+    fn parse_stream_chunk(&self, data: &str) -> Option<String> {
+        gemini_parts(data, false)
+    }
+
+    fn parse_reasoning_chunk(&self, data: &str) -> Option<String> {
+        gemini_parts(data, true)
+    }
+}
+
+// The text of the parts in a Gemini stream event that are thought summaries,
+// or of those that are not.
+fn gemini_parts(data: &str, thought: bool) -> Option<String> {
+    let json: Value = serde_json::from_str(data).ok()?;
+    let parts = json.pointer("/candidates/0/content/parts")?.as_array()?;
+    let text: String = parts
+        .iter()
+        .filter(|part| part["thought"].as_bool().unwrap_or(false) == thought)
+        .filter_map(|part| part["text"].as_str())
+        .collect();
+    (!text.is_empty()).then_some(text)
+}
+
+// Cohere's v2 chat API. It takes plain text messages, so media is dropped
+// and array contents in the history are reduced to their text parts.
+pub struct Cohere {
+    pub model: ModelConfig,
+    pub api_keys: Vec<String>,
+}
+
+// The text of a message's content: the string itself, or its text parts.
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+impl Provider for Cohere {
+    fn name(&self) -> &str {
+        "Cohere"
+    }
+
+    fn model(&self) -> &ModelConfig {
+        &self.model
+    }
+
+    fn has_key(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn api_keys(&self) -> &[String] {
+        &self.api_keys
+    }
+
+    fn user_content(&self, text: &str, media: &[Media]) -> Value {
+        for media in media {
+            skip_media(self.name(), media);
+        }
+        json!(text)
+    }
+
+    fn build_request(
+        &self,
+        http: &Client,
+        key: Option<&str>,
+        model: &ModelConfig,
+        system_prompt: Option<&str>,
+        history: &[Message],
+        user_content: &Value,
+    ) -> Result<RequestBuilder> {
+        let key = api_key(self.name(), key)?;
+        let messages: Vec<Value> = system_prompt
+            .map(|prompt| json!({ "role": "system", "content": prompt }))
+            .into_iter()
+            .chain(
+                history
+                    .iter()
+                    .map(|m| json!({ "role": &m.role, "content": content_text(&m.content) })),
+            )
+            .chain([json!({ "role": "user", "content": content_text(user_content) })])
+            .collect();
+        let payload = json!({
+            "model": &model.model,
+            "messages": messages,
+            "max_tokens": model.max_tokens,
+            "temperature": model.temperature,
+            "p": model.top_p,
+            "stream": true
+        });
+
+        Ok(http
+            .post(&model.api_base)
+            .header("Authorization", format!("Bearer {}", key))
+            .header("Content-Type", "application/json")
+            .json(&payload))
+    }
+
+    // Text arrives in `content-delta` events; the others mark where messages
+    // and content blocks start and end.
+    fn parse_stream_chunk(&self, data: &str) -> Option<String> {
+        let json: Value = serde_json::from_str(data).ok()?;
+        if json["type"] != "content-delta" {
+            return None;
+        }
+        json.pointer("/delta/message/content/text")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    }
+}
+
+// OpenAI's public API, or the Azure OpenAI deployment configured in its
+// place. Audio is transcribed with the public API either way.
+fn openai(config: &ApiConfig) -> Arc<dyn Provider> {
+    match &config.azure {
+        Some(azure) => Arc::new(ChatCompletions {
+            name: "Azure".to_string(),
+            model: ModelConfig {
+                api_base: azure.chat_url(),
+                ..config.openai.clone()
+            },
+            api_keys: config.get_keys("azure").to_vec(),
+            needs_key: true,
+            key_style: KeyStyle::ApiKeyHeader,
+            images: true,
+            media_prep: MediaPrep::TranscribeOpenAi,
+        }),
+        None => Arc::new(ChatCompletions {
+            name: "OpenAI".to_string(),
+            model: config.openai.clone(),
+            api_keys: config.get_keys("openai").to_vec(),
+            needs_key: true,
+            key_style: KeyStyle::Bearer,
+            images: true,
+            media_prep: MediaPrep::TranscribeOpenAi,
+        }),
+    }
+}
+
+// The providers every prompt is sent to, in the order their answers are
+// written. Providers with an optional config section are left out without
+// it, and so are those with `enabled = false` or without their API key.
+pub fn registry(config: &ApiConfig) -> Vec<Arc<dyn Provider>> {
+    let keys = |provider: &str| config.get_keys(provider).to_vec();
+    let mut providers: Vec<Arc<dyn Provider>> = vec![
+        Arc::new(ChatCompletions {
+            name: "Ollama".to_string(),
+            model: config.ollama.clone(),
+            api_keys: Vec::new(),
+            needs_key: false,
+            key_style: KeyStyle::Bearer,
+            images: true,
+            media_prep: MediaPrep::None,
+        }),
+        Arc::new(ChatCompletions {
+            name: "OpenRouter".to_string(),
+            model: config.openrouter.clone(),
+            api_keys: keys("openrouter"),
+            needs_key: true,
+            key_style: KeyStyle::Bearer,
+            images: true,
+            media_prep: MediaPrep::OcrImages,
+        }),
+        openai(config),
+        Arc::new(Claude {
+            model: config.claude.clone(),
+            api_keys: keys("claude"),
+        }),
+        Arc::new(Gemini {
+            model: config.gemini.clone(),
+            api_keys: keys("gemini"),
+        }),
+        Arc::new(ChatCompletions {
+            name: "XAI".to_string(),
+            model: config.xai.clone(),
+            api_keys: keys("xai"),
+            needs_key: true,
+            key_style: KeyStyle::Bearer,
+            images: false,
+            media_prep: MediaPrep::None,
+        }),
+    ];
+    if let Some(model) = &config.mistral {
+        // Media is dropped, since the large models do not read images
+        providers.push(Arc::new(ChatCompletions {
+            name: "Mistral".to_string(),
+            model: model.clone(),
+            api_keys: keys("mistral"),
+            needs_key: true,
+            key_style: KeyStyle::Bearer,
+            images: false,
+            media_prep: MediaPrep::None,
+        }));
+    }
+    for custom in &config.providers {
+        providers.push(Arc::new(ChatCompletions {
+            name: custom.name.clone(),
+            model: custom.model.clone(),
+            api_keys: custom.api_keys.clone(),
+            needs_key: custom.key_env.is_some(),
+            key_style: KeyStyle::Bearer,
+            images: custom.images,
+            media_prep: MediaPrep::None,
+        }));
+    }
+    if let Some(model) = &config.cohere {
+        providers.push(Arc::new(Cohere {
+            model: model.clone(),
+            api_keys: keys("cohere"),
+        }));
+    }
+    providers.retain(|provider| {
+        if !provider.model().enabled {
+            return false;
+        }
+        if !provider.has_key() {
+            eprintln!("[INFO] Skipping {}: no API key set", provider.name());
+            return false;
+        }
+        true
+    });
+    providers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> ModelConfig {
+        ModelConfig {
+            api_base: "https://example.com/v1/chat/completions/".to_string(),
+            model: "m".to_string(),
+            max_tokens: 100,
+            temperature: 0.5,
+            top_p: 1.0,
+            input_price: 0.0,
+            output_price: 0.0,
+            enabled: true,
+            requests_per_minute: 0,
+            max_in_flight: 0,
+            context_tokens: 0,
+            reasoning_tokens: 0,
+        }
+    }
+
+    fn body(request: RequestBuilder) -> Value {
+        let request = request.build().unwrap();
+        serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_stream_chunk() {
+        let openai = ChatCompletions {
+            name: "OpenAI".to_string(),
+            model: model(),
+            api_keys: Vec::new(),
+            needs_key: true,
+            key_style: KeyStyle::Bearer,
+            images: true,
+            media_prep: MediaPrep::None,
+        };
+        assert_eq!(
+            openai.parse_stream_chunk(r#"{"choices":[{"delta":{"content":"Hi"}}]}"#),
+            Some("Hi".to_string())
+        );
+        assert_eq!(openai.parse_stream_chunk("[DONE]"), None);
+
+        let claude = Claude {
+            model: model(),
+            api_keys: Vec::new(),
+        };
+        assert_eq!(
+            claude.parse_stream_chunk(r#"{"type":"content_block_delta","delta":{"text":"Hi"}}"#),
+            Some("Hi".to_string())
+        );
+        assert_eq!(
+            claude.parse_stream_chunk(r#"{"type":"message_start","delta":{"text":"no"}}"#),
+            None
+        );
+
+        let cohere = Cohere {
+            model: model(),
+            api_keys: Vec::new(),
+        };
+        assert_eq!(
+            cohere.parse_stream_chunk(
+                r#"{"type":"content-delta","index":0,"delta":{"message":{"content":{"text":"Hi"}}}}"#
+            ),
+            Some("Hi".to_string())
+        );
+        assert_eq!(
+            cohere.parse_stream_chunk(
+                r#"{"type":"message-end","delta":{"finish_reason":"COMPLETE"}}"#
+            ),
+            None
+        );
+        assert_eq!(
+            content_text(&json!([{ "type": "text", "text": "a" }, { "type": "image_url" }])),
+            "a"
+        );
+    }
+
+    #[test]
+    fn test_parse_reasoning_chunk() {
+        let deepseek = ChatCompletions {
+            name: "DeepSeek".to_string(),
+            model: model(),
+            api_keys: Vec::new(),
+            needs_key: true,
+            key_style: KeyStyle::Bearer,
+            images: false,
+            media_prep: MediaPrep::None,
+        };
+        let data = r#"{"choices":[{"delta":{"content":null,"reasoning_content":"Hm"}}]}"#;
+        assert_eq!(deepseek.parse_reasoning_chunk(data), Some("Hm".to_string()));
+        assert_eq!(deepseek.parse_stream_chunk(data), None);
+
+        let claude = Claude {
+            model: model(),
+            api_keys: Vec::new(),
+        };
+        let data =
+            r#"{"type":"content_block_delta","delta":{"type":"thinking_delta","thinking":"Hm"}}"#;
+        assert_eq!(claude.parse_reasoning_chunk(data), Some("Hm".to_string()));
+        assert_eq!(claude.parse_stream_chunk(data), None);
+
+        let gemini = Gemini {
+            model: model(),
+            api_keys: Vec::new(),
+        };
+        let data = r#"{"candidates":[{"content":{"parts":[{"text":"Hm","thought":true},{"text":"Hi"}]}}]}"#;
+        assert_eq!(gemini.parse_reasoning_chunk(data), Some("Hm".to_string()));
+        assert_eq!(gemini.parse_stream_chunk(data), Some("Hi".to_string()));
+    }
+
+    #[test]
+    fn test_think_tags() {
+        let mut tags = ThinkTags::default();
+        let chunks: Vec<Chunk> = ["\n", "<think>Hm", ", a sum.</think>\n\n4", "2 <think>"]
+            .iter()
+            .flat_map(|text| tags.split(text))
+            .collect();
+        assert_eq!(
+            chunks,
+            [
+                Chunk::Reasoning("Hm".to_string()),
+                Chunk::Reasoning(", a sum.".to_string()),
+                Chunk::Answer("4".to_string()),
+                Chunk::Answer("2 <think>".to_string()),
+            ]
+        );
+        assert_eq!(
+            ThinkTags::default().split("Hi"),
+            [Chunk::Answer("Hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_build_request() {
+        let http = Client::new();
+        let history = [Message {
+            role: "user".to_string(),
+            content: json!("Earlier"),
+        }];
+        let mut local = ChatCompletions {
+            name: "Ollama".to_string(),
+            model: model(),
+            api_keys: Vec::new(),
+            needs_key: false,
+            key_style: KeyStyle::Bearer,
+            images: false,
+            media_prep: MediaPrep::None,
+        };
+        let content = local.user_content("Now", &[]);
+        let request = local
+            .build_request(
+                &http,
+                None,
+                &local.model,
+                Some("Be brief"),
+                &history,
+                &content,
+            )
+            .unwrap();
+        let built = request.try_clone().unwrap().build().unwrap();
+        assert_eq!(
+            built.url().as_str(),
+            "https://example.com/v1/chat/completions"
+        );
+        assert!(built.headers().get("Authorization").is_none());
+        assert_eq!(
+            body(request)["messages"],
+            json!([
+                { "role": "system", "content": "Be brief" },
+                { "role": "user", "content": "Earlier" },
+                { "role": "user", "content": "Now" },
+            ])
+        );
+
+        local.needs_key = true;
+        assert!(
+            local
+                .build_request(&http, None, &local.model, None, &[], &content)
+                .is_err()
+        );
+
+        local.key_style = KeyStyle::ApiKeyHeader;
+        let built = local
+            .build_request(&http, Some("key"), &local.model, None, &[], &content)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(built.headers()["api-key"], "key");
+        assert!(built.headers().get("Authorization").is_none());
+
+        let claude = Claude {
+            model: model(),
+            api_keys: vec!["key".to_string()],
+        };
+        let payload = body(
+            claude
+                .build_request(
+                    &http,
+                    Some("key"),
+                    &model(),
+                    Some("Be brief"),
+                    &history,
+                    &content,
+                )
+                .unwrap(),
+        );
+        assert_eq!(payload["system"], "Be brief");
+        assert_eq!(payload["messages"].as_array().unwrap().len(), 2);
+    }
+}
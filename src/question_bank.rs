@@ -0,0 +1,158 @@
+// Question bank for reviewing past sessions as flashcards.
+//
+// With `[question_bank] enabled = true`, every typed question and the best
+// answer to it (the longest one, when several providers answered) are
+// appended to `$XDG_DATA_HOME/aerogel/question_bank.jsonl`. The quiz
+// keybinding shows the card that was quizzed least recently; the next solved
+// prompt is taken as the attempt and graded by the models against the stored
+// answer.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Card {
+    pub question: String,
+    pub answer: String,
+    pub workspace: Option<u32>,
+    // Seconds since the Unix epoch
+    pub added: u64,
+    #[serde(default)]
+    pub last_quizzed: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl Card {
+    pub fn new(question: String, answer: String, workspace: Option<u32>) -> Self {
+        Self {
+            question,
+            answer,
+            workspace,
+            added: now(),
+            last_quizzed: 0,
+        }
+    }
+
+    // The prompt asking the models to grade an attempt at this card.
+    pub fn grading_prompt(&self, attempt: &str) -> String {
+        format!(
+            "You are grading a flashcard answer.\n\n\
+             Question:\n{}\n\n\
+             Reference answer:\n{}\n\n\
+             The user's answer:\n{}\n\n\
+             Grade the user's answer from 0 to 10 against the reference answer. \
+             Start with the grade as \"Grade: <n>/10\", then briefly list what \
+             was correct and what was missing or wrong.",
+            self.question, self.answer, attempt
+        )
+    }
+}
+
+// The longest answer, as the one most likely to be complete.
+pub fn best_answer(answers: &[String]) -> Option<&String> {
+    answers
+        .iter()
+        .filter(|answer| !answer.trim().is_empty())
+        .max_by_key(|answer| answer.len())
+}
+
+pub struct QuestionBank {
+    path: PathBuf,
+}
+
+impl QuestionBank {
+    pub fn open() -> Self {
+        let dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("aerogel");
+        Self {
+            path: dir.join("question_bank.jsonl"),
+        }
+    }
+
+    pub fn add(&self, card: &Card) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(card)?)
+    }
+
+    pub fn load(&self) -> io::Result<Vec<Card>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    // Picks the card quizzed least recently and marks it as quizzed now.
+    pub fn next_card(&self) -> io::Result<Option<Card>> {
+        let mut cards = self.load()?;
+        let Some(card) = cards.iter_mut().min_by_key(|card| card.last_quizzed) else {
+            return Ok(None);
+        };
+        card.last_quizzed = now().max(card.last_quizzed + 1);
+        let picked = card.clone();
+        self.save_all(&cards)?;
+        Ok(Some(picked))
+    }
+
+    fn save_all(&self, cards: &[Card]) -> io::Result<()> {
+        let mut content = String::new();
+        for card in cards {
+            content.push_str(&serde_json::to_string(card)?);
+            content.push('\n');
+        }
+        let tmp = self.path.with_extension("jsonl.tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_answer() {
+        let answers = vec![
+            "short".to_string(),
+            "a longer answer".to_string(),
+            String::new(),
+        ];
+        assert_eq!(best_answer(&answers).unwrap(), "a longer answer");
+        assert!(best_answer(&[]).is_none());
+    }
+
+    #[test]
+    fn test_next_card_rotates() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let bank = QuestionBank {
+            path: dir.path().join("bank.jsonl"),
+        };
+        assert!(bank.next_card()?.is_none());
+
+        bank.add(&Card::new("q1".into(), "a1".into(), Some(1)))?;
+        bank.add(&Card::new("q2".into(), "a2".into(), None))?;
+        let first = bank.next_card()?.unwrap();
+        let second = bank.next_card()?.unwrap();
+        assert_ne!(first.question, second.question);
+        assert_eq!(bank.load()?.len(), 2);
+        Ok(())
+    }
+}
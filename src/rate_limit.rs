@@ -0,0 +1,94 @@
+// Per-provider limits on outbound requests, so pressing Solve in quick
+// succession does not burn through a quota or run into 429 responses.
+//
+// `requests_per_minute` caps how many requests start in any 60 second
+// window; a request over the cap waits until the oldest one leaves the
+// window. `max_in_flight` caps how many answers stream at once; the permit
+// is held until the answer stream ends. 0 leaves either unlimited.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+pub struct RateLimiter {
+    per_minute: usize,
+    // Start times of the requests in the current window, oldest first
+    recent: Mutex<VecDeque<Instant>>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+// How long a request starting at `now` has to wait for a place in the window.
+fn wait_time(recent: &mut VecDeque<Instant>, now: Instant, per_minute: usize) -> Duration {
+    while recent
+        .front()
+        .is_some_and(|start| now.duration_since(*start) >= WINDOW)
+    {
+        recent.pop_front();
+    }
+    if per_minute == 0 || recent.len() < per_minute {
+        return Duration::ZERO;
+    }
+    (recent[recent.len() - per_minute] + WINDOW).saturating_duration_since(now)
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, max_in_flight: u32) -> Self {
+        Self {
+            per_minute: per_minute as usize,
+            recent: Mutex::new(VecDeque::new()),
+            in_flight: (max_in_flight > 0)
+                .then(|| Arc::new(Semaphore::new(max_in_flight as usize))),
+        }
+    }
+
+    // Waits until a request may start. The returned permit, if any, keeps
+    // its in-flight slot until it is dropped.
+    pub async fn acquire(&self, provider: &str) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.in_flight {
+            Some(semaphore) => Arc::clone(semaphore).acquire_owned().await.ok(),
+            None => None,
+        };
+        if self.per_minute > 0 {
+            let mut recent = self.recent.lock().await;
+            let wait = wait_time(&mut recent, Instant::now(), self.per_minute);
+            if !wait.is_zero() {
+                eprintln!(
+                    "[INFO] {} rate limit reached, waiting {}s",
+                    provider,
+                    wait.as_secs_f32().ceil()
+                );
+                tokio::time::sleep(wait).await;
+            }
+            recent.push_back(Instant::now());
+        }
+        permit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_time() {
+        let start = Instant::now();
+        let mut recent: VecDeque<Instant> = [0, 10, 20]
+            .into_iter()
+            .map(|secs| start + Duration::from_secs(secs))
+            .collect();
+
+        let now = start + Duration::from_secs(30);
+        assert_eq!(wait_time(&mut recent, now, 0), Duration::ZERO);
+        assert_eq!(wait_time(&mut recent, now, 4), Duration::ZERO);
+        assert_eq!(wait_time(&mut recent, now, 3), Duration::from_secs(30));
+        assert_eq!(wait_time(&mut recent, now, 2), Duration::from_secs(40));
+
+        // The first request has left the window
+        let now = start + Duration::from_secs(65);
+        assert_eq!(wait_time(&mut recent, now, 3), Duration::ZERO);
+        assert_eq!(recent.len(), 2);
+    }
+}
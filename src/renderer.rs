@@ -0,0 +1,131 @@
+// Rasterization on a worker thread.
+//
+// Laying out and drawing a long answer can take longer than a frame, and the
+// Wayland thread has to keep dispatching pointer and keyboard events
+// meanwhile. It only sends the worker what to draw; the worker draws the
+// newest request into a pixel buffer of its own and sends it back, and the
+// Wayland thread copies that into a shm buffer and attaches it. Requests that
+// pile up while the worker is busy are skipped.
+
+use super::fonts::FontSet;
+use super::theme::Theme;
+use super::{CONFIG, StatusBar, TextLayer, draw_chip_to_buffer, draw_content_to_buffer};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use syntect::parsing::SyntaxSet;
+
+// The theme and font size, sent only when they changed.
+pub struct RenderStyle {
+    pub theme: Theme,
+    pub font_size: f32,
+}
+
+pub struct RenderJob {
+    pub size: (u32, u32),
+    pub text: String,
+    pub scroll_offset_y: f32,
+    pub border_radius: f32,
+    pub status: StatusBar,
+    pub collapsed: bool,
+    pub style: Option<RenderStyle>,
+}
+
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+pub struct Renderer {
+    jobs: Sender<RenderJob>,
+    frames: Receiver<Frame>,
+}
+
+impl Renderer {
+    pub fn spawn(syntax_set: SyntaxSet) -> Self {
+        let (jobs, job_rx) = mpsc::channel();
+        let (frame_tx, frames) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("overlay-render".to_string())
+            .spawn(move || run(job_rx, frame_tx, syntax_set))
+            .expect("Failed to start the render thread");
+        Self { jobs, frames }
+    }
+
+    pub fn request(&self, job: RenderJob) {
+        // Only fails once the worker died, which it reports itself
+        let _ = self.jobs.send(job);
+    }
+
+    // Waits up to `timeout` for a frame and returns the newest one drawn.
+    pub fn next_frame(&self, timeout: Duration) -> Option<Frame> {
+        let frame = match self.frames.recv_timeout(timeout) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => return None,
+            Err(RecvTimeoutError::Disconnected) => {
+                std::thread::sleep(timeout);
+                return None;
+            }
+        };
+        Some(self.frames.try_iter().last().unwrap_or(frame))
+    }
+}
+
+fn run(jobs: Receiver<RenderJob>, frames: Sender<Frame>, syntax_set: SyntaxSet) {
+    // The worker resolves its own copy of the font; FontSet caches aren't
+    // shared between threads
+    let mut font = FontSet::load(
+        CONFIG.font.family.as_deref(),
+        CONFIG.font.path.as_deref(),
+        CONFIG.font.size,
+    );
+    let mut theme = None;
+    let mut layer = TextLayer::default();
+
+    while let Ok(mut job) = jobs.recv() {
+        let mut style = job.style.take();
+        for mut newer in jobs.try_iter() {
+            style = newer.style.take().or(style);
+            job = newer;
+        }
+        if let Some(style) = style {
+            font.set_size(style.font_size);
+            theme = Some(style.theme);
+            layer.invalidate();
+        }
+        let Some(theme) = &theme else {
+            continue;
+        };
+
+        let (width, height) = job.size;
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        if job.collapsed {
+            draw_chip_to_buffer(&mut pixels, width, height, &font, theme, &job.status);
+        } else {
+            layer.update(
+                job.size,
+                &font,
+                &job.text,
+                &syntax_set,
+                theme,
+                job.scroll_offset_y,
+            );
+            draw_content_to_buffer(
+                &mut pixels,
+                &layer,
+                job.border_radius,
+                &font,
+                theme,
+                &job.status,
+            );
+        }
+        let frame = Frame {
+            width,
+            height,
+            pixels,
+        };
+        if frames.send(frame).is_err() {
+            break;
+        }
+    }
+}
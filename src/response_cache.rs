@@ -0,0 +1,208 @@
+// Cached answers, so asking the same thing again does not bill again.
+//
+// With `[cache] enabled = true`, an answer is cached under a SHA-256 digest
+// of its provider, model, system prompt, the provider's conversation so far,
+// the prompt text with its whitespace collapsed and the attachments, and is
+// reused for `ttl` seconds. Since the conversation is part of the key, a
+// follow-up such as "continue" is only answered from the cache in the same
+// conversation. `/nocache` sends the next prompt to the providers
+// regardless, and its answers replace the cached ones. Entries are files
+// under `$XDG_CACHE_HOME/aerogel/responses`, removed once expired.
+
+use crate::ai_client::{Message, PromptData};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Seconds an answer is reused for
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl: default_ttl(),
+        }
+    }
+}
+
+fn default_ttl() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    // The user message as it was sent, for the conversation history
+    pub user_content: Value,
+    pub response: String,
+}
+
+// Adds a field, prefixed with its length so neighbouring fields cannot run
+// into each other.
+fn add_field(hasher: &mut Sha256, field: &[u8]) {
+    hasher.update((field.len() as u64).to_le_bytes());
+    hasher.update(field);
+}
+
+pub fn cache_key(
+    provider: &str,
+    model: &str,
+    system_prompt: Option<&str>,
+    history: &[Message],
+    prompt_data: &PromptData,
+) -> String {
+    let mut hasher = Sha256::new();
+    add_field(&mut hasher, provider.as_bytes());
+    add_field(&mut hasher, model.as_bytes());
+    add_field(&mut hasher, system_prompt.unwrap_or_default().as_bytes());
+    hasher.update([u8::from(system_prompt.is_some())]);
+    hasher.update((history.len() as u64).to_le_bytes());
+    for message in history {
+        add_field(&mut hasher, message.role.as_bytes());
+        add_field(&mut hasher, message.content.to_string().as_bytes());
+    }
+    let words: Vec<&str> = prompt_data.text.split_whitespace().collect();
+    add_field(&mut hasher, words.join(" ").as_bytes());
+    for media in &prompt_data.media {
+        add_field(&mut hasher, media.mime_type.as_bytes());
+        add_field(&mut hasher, media.data.as_bytes());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    pub fn open(config: &CacheConfig) -> Option<Self> {
+        let dir = dirs::cache_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("aerogel")
+            .join("responses");
+        config
+            .enabled
+            .then(|| Self::new(dir, Duration::from_secs(config.ttl)))
+    }
+
+    fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn expired(&self, path: &std::path::Path) -> bool {
+        let age = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        age.is_none_or(|age| age >= self.ttl)
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedResponse> {
+        let path = self.path(key);
+        if self.expired(&path) {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()
+    }
+
+    // Stores an answer and removes the expired ones.
+    pub fn put(&self, key: &str, entry: &CachedResponse) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        for dir_entry in std::fs::read_dir(&self.dir)?.flatten() {
+            if self.expired(&dir_entry.path()) {
+                let _ = std::fs::remove_file(dir_entry.path());
+            }
+        }
+        std::fs::write(self.path(key), serde_json::to_string(entry)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_client::Media;
+    use serde_json::json;
+
+    fn prompt(text: &str, media: &[&str]) -> PromptData {
+        PromptData {
+            text: text.to_string(),
+            media: media
+                .iter()
+                .map(|data| Media {
+                    mime_type: "image/png".to_string(),
+                    data: data.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let key = |text, media: &[&str]| cache_key("Claude", "m", None, &[], &prompt(text, media));
+        assert_eq!(key("Solve  this\n", &["a"]), key("Solve this", &["a"]));
+        assert_ne!(key("Solve this", &["a"]), key("Solve this", &["b"]));
+        assert_ne!(key("Solve this", &["a"]), key("Solve that", &["a"]));
+        assert_ne!(
+            key("Solve this", &[]),
+            cache_key("Gemini", "m", None, &[], &prompt("Solve this", &[]))
+        );
+        // The same follow-up in another conversation is another question
+        let history = |answer: &str| {
+            vec![
+                Message {
+                    role: "user".to_string(),
+                    content: json!("What is 6 * 7?"),
+                },
+                Message {
+                    role: "assistant".to_string(),
+                    content: json!(answer),
+                },
+            ]
+        };
+        let follow_up =
+            |history: &[Message]| cache_key("Claude", "m", None, history, &prompt("continue", &[]));
+        assert_ne!(follow_up(&[]), follow_up(&history("42")));
+        assert_ne!(follow_up(&history("42")), follow_up(&history("41")));
+        assert_eq!(follow_up(&history("42")), follow_up(&history("42")));
+        // Stable across builds, since it names files on disk
+        assert_eq!(
+            key("Solve this", &[]),
+            "cc63f32e0cfe32ac1c13642653cf7317aecc63656993520e3a20044132166861"
+        );
+    }
+
+    #[test]
+    fn test_get_and_put() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = CachedResponse {
+            user_content: json!("Solve this"),
+            response: "42".to_string(),
+        };
+        let cache = ResponseCache::new(dir.path().to_path_buf(), Duration::from_secs(60));
+        assert_eq!(cache.get("key"), None);
+        cache.put("key", &entry).unwrap();
+        assert_eq!(cache.get("key"), Some(entry.clone()));
+
+        let expired = ResponseCache::new(dir.path().to_path_buf(), Duration::ZERO);
+        assert_eq!(expired.get("key"), None);
+        assert!(!dir.path().join("key.json").exists());
+    }
+}
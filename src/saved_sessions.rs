@@ -0,0 +1,150 @@
+// Named sessions, kept with `/save <name>` and continued with `/load <name>`.
+//
+// A workspace's conversation lasts until the workspace is cleared; a named
+// session is kept under `$XDG_DATA_HOME/aerogel/sessions/<name>.json` until
+// the file is deleted. It holds the history sent to each provider,
+// attachments included, the exchanges `/export` writes and the transcript
+// the overlay shows. Loading one replaces the current workspace's
+// conversation, in whichever workspace it was saved from. `/sessions` lists
+// them, most recently saved first.
+
+use crate::ai_client::Message;
+use crate::conversation::Exchange;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub workspace: Option<u32>,
+    // Local time it was saved, RFC 3339
+    pub saved: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    pub history: HashMap<String, Vec<Message>>,
+    pub exchanges: Vec<Exchange>,
+    pub transcript: String,
+}
+
+impl SavedSession {
+    pub fn new(
+        workspace: Option<u32>,
+        title: Option<String>,
+        history: HashMap<String, Vec<Message>>,
+        exchanges: Vec<Exchange>,
+        transcript: String,
+    ) -> Self {
+        Self {
+            workspace,
+            saved: chrono::Local::now().to_rfc3339(),
+            title,
+            history,
+            exchanges,
+            transcript,
+        }
+    }
+}
+
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn open() -> Self {
+        let dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("aerogel")
+            .join("sessions");
+        Self { dir }
+    }
+
+    // Names become file names, so they cannot reach outside the directory.
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        let name = name.trim();
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(anyhow!("'{}' is not a valid session name", name));
+        }
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+
+    // Saves the session under `name`, replacing one saved under it before.
+    pub fn save(&self, name: &str, session: &SavedSession) -> Result<()> {
+        let path = self.path(name)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(session)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    pub fn load(&self, name: &str) -> Result<SavedSession> {
+        let contents = match std::fs::read(self.path(name)?) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(anyhow!("No session named '{}'; /sessions lists them", name));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    // The saved sessions' names and when each was saved, newest first.
+    pub fn list(&self) -> Result<Vec<(String, SystemTime)>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut sessions: Vec<(String, SystemTime)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                let name = path.file_stem()?.to_string_lossy().into_owned();
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((name, modified))
+            })
+            .collect();
+        sessions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore {
+            dir: dir.path().join("sessions"),
+        };
+        assert!(store.list().unwrap().is_empty());
+        assert!(store.load("interview").is_err());
+
+        let session =
+            SavedSession::new(Some(2), None, HashMap::new(), Vec::new(), "hi".to_string());
+        store.save("interview", &session).unwrap();
+        store.save("notes", &session).unwrap();
+        let loaded = store.load("interview").unwrap();
+        assert_eq!(loaded.workspace, Some(2));
+        assert_eq!(loaded.transcript, "hi");
+
+        let mut names: Vec<String> = store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["interview", "notes"]);
+
+        assert!(store.save("../escape", &session).is_err());
+        assert!(store.save(".hidden", &session).is_err());
+    }
+}
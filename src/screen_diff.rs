@@ -0,0 +1,215 @@
+// Visual diff between two consecutive screenshots.
+//
+// The screen is split into a grid of cells; a cell counts as changed when
+// enough of its pixels differ noticeably (JPEG noise alone stays below the
+// thresholds). Adjacent changed cells are merged into regions. The composite
+// keeps changed regions at full brightness with a red outline and dims the
+// rest, so the model is pointed at what changed.
+
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+const CELL_SIZE: u32 = 16;
+// Per-pixel difference (sum over RGB) that counts as a changed pixel
+const PIXEL_THRESHOLD: u32 = 60;
+// Fraction of changed pixels that marks a cell as changed
+const CELL_THRESHOLD: f32 = 0.05;
+const DIM_FACTOR: f32 = 0.35;
+const OUTLINE: Rgb<u8> = Rgb([255, 40, 40]);
+const OUTLINE_WIDTH: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+fn cell_changed(before: &RgbImage, after: &RgbImage, cx: u32, cy: u32) -> bool {
+    let x_end = ((cx + 1) * CELL_SIZE).min(after.width());
+    let y_end = ((cy + 1) * CELL_SIZE).min(after.height());
+    let mut changed = 0;
+    let mut total = 0;
+
+    for y in cy * CELL_SIZE..y_end {
+        for x in cx * CELL_SIZE..x_end {
+            let a = before.get_pixel(x, y);
+            let b = after.get_pixel(x, y);
+            let diff: u32 = (0..3).map(|i| a[i].abs_diff(b[i]) as u32).sum();
+            if diff > PIXEL_THRESHOLD {
+                changed += 1;
+            }
+            total += 1;
+        }
+    }
+    total > 0 && changed as f32 / total as f32 >= CELL_THRESHOLD
+}
+
+// Returns the bounding boxes of connected groups of changed cells, largest
+// first. Images of different sizes cannot be compared and give None.
+pub fn changed_regions(before: &RgbImage, after: &RgbImage) -> Option<Vec<Region>> {
+    if before.dimensions() != after.dimensions() {
+        return None;
+    }
+
+    let cols = after.width().div_ceil(CELL_SIZE);
+    let rows = after.height().div_ceil(CELL_SIZE);
+    let mut changed = vec![false; (cols * rows) as usize];
+    for cy in 0..rows {
+        for cx in 0..cols {
+            changed[(cy * cols + cx) as usize] = cell_changed(before, after, cx, cy);
+        }
+    }
+
+    // Flood-fill groups of changed cells, tracking each group's bounds.
+    let mut visited = vec![false; changed.len()];
+    let mut regions = Vec::new();
+    for start in 0..changed.len() {
+        if !changed[start] || visited[start] {
+            continue;
+        }
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (cols, rows, 0, 0);
+        let mut stack = vec![start];
+        visited[start] = true;
+
+        while let Some(index) = stack.pop() {
+            let (cx, cy) = (index as u32 % cols, index as u32 / cols);
+            min_x = min_x.min(cx);
+            min_y = min_y.min(cy);
+            max_x = max_x.max(cx);
+            max_y = max_y.max(cy);
+
+            let neighbours = [
+                (cx > 0).then(|| index - 1),
+                (cx + 1 < cols).then(|| index + 1),
+                (cy > 0).then(|| index - cols as usize),
+                (cy + 1 < rows).then(|| index + cols as usize),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if changed[next] && !visited[next] {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+
+        let x = min_x * CELL_SIZE;
+        let y = min_y * CELL_SIZE;
+        regions.push(Region {
+            x,
+            y,
+            width: ((max_x + 1) * CELL_SIZE).min(after.width()) - x,
+            height: ((max_y + 1) * CELL_SIZE).min(after.height()) - y,
+        });
+    }
+
+    regions.sort_by_key(|r| std::cmp::Reverse(r.width * r.height));
+    Some(regions)
+}
+
+// Dims everything outside the regions and outlines each region.
+pub fn composite(after: &RgbImage, regions: &[Region]) -> RgbImage {
+    let mut out = after.clone();
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let region = regions.iter().find(|r| r.contains(x, y));
+        match region {
+            Some(r) => {
+                let on_edge = x < r.x + OUTLINE_WIDTH
+                    || y < r.y + OUTLINE_WIDTH
+                    || x >= r.x + r.width - OUTLINE_WIDTH.min(r.width)
+                    || y >= r.y + r.height - OUTLINE_WIDTH.min(r.height);
+                if on_edge {
+                    *pixel = OUTLINE;
+                }
+            }
+            None => {
+                for channel in pixel.0.iter_mut() {
+                    *channel = (*channel as f32 * DIM_FACTOR) as u8;
+                }
+            }
+        }
+    }
+    out
+}
+
+// A short description of the regions for the prompt.
+pub fn describe_regions(regions: &[Region], width: u32, height: u32) -> String {
+    if regions.is_empty() {
+        return "Nothing visibly changed between the last two screenshots.".to_string();
+    }
+    let area: u32 = regions.iter().map(|r| r.width * r.height).sum();
+    let percent = area as f32 * 100.0 / (width * height) as f32;
+    let list = regions
+        .iter()
+        .map(|r| format!("{}x{} at ({}, {})", r.width, r.height, r.x, r.y))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "Between the last two screenshots, {} region(s) changed ({:.0}% of the screen): {}. \
+         The attached diff image highlights them and dims everything else.",
+        regions.len(),
+        percent,
+        list
+    )
+}
+
+// Diffs two screenshot files and writes the composite to `out_path`.
+// Returns the prompt note, or None when the screenshots cannot be compared.
+pub fn diff_screenshots(
+    before_path: &Path,
+    after_path: &Path,
+    out_path: &Path,
+) -> Result<Option<String>, image::ImageError> {
+    let before = image::open(before_path)?.to_rgb8();
+    let after = image::open(after_path)?.to_rgb8();
+    let Some(regions) = changed_regions(&before, &after) else {
+        return Ok(None);
+    };
+    if !regions.is_empty() {
+        composite(&after, &regions).save(out_path)?;
+    }
+    Ok(Some(describe_regions(
+        &regions,
+        after.width(),
+        after.height(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_regions() {
+        let before = RgbImage::from_pixel(128, 64, Rgb([30, 30, 30]));
+        let mut after = before.clone();
+        // A 20x20 change spanning cells (2..=3, 1..=2)
+        for y in 20..40 {
+            for x in 40..60 {
+                after.put_pixel(x, y, Rgb([240, 240, 240]));
+            }
+        }
+        // Slight noise everywhere should not count
+        after.put_pixel(100, 10, Rgb([40, 40, 40]));
+
+        let regions = changed_regions(&before, &after).unwrap();
+        assert_eq!(
+            regions,
+            vec![Region {
+                x: 32,
+                y: 16,
+                width: 32,
+                height: 32
+            }]
+        );
+        assert!(changed_regions(&before, &RgbImage::new(10, 10)).is_none());
+        assert!(changed_regions(&before, &before).unwrap().is_empty());
+    }
+}
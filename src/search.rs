@@ -0,0 +1,234 @@
+// Web search results as prompt context.
+//
+// With a `backend` set in `[search]`, `/search <query>` fetches the top
+// results and attaches their titles, links and snippets to the next prompt;
+// with `auto = true` every prompt is searched for before it is sent, so
+// questions about current events get answered from fresh sources.
+//
+//   searxng     a SearxNG instance at `url`, with its JSON format enabled
+//   brave       the Brave Search API, with the key in BRAVE_API_KEY
+//   duckduckgo  DuckDuckGo's Instant Answer API: no key, but only abstracts
+//               and related topics rather than full web results
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+
+const BRAVE_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+const DUCKDUCKGO_URL: &str = "https://api.duckduckgo.com/";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Searxng,
+    Brave,
+    Duckduckgo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchConfig {
+    // Unset turns web search off
+    pub backend: Option<Backend>,
+    // Base URL of the SearxNG instance
+    pub url: Option<String>,
+    // How many results go along with a prompt
+    #[serde(default = "default_results")]
+    pub results: usize,
+    // Search for every prompt, not only after /search
+    #[serde(default)]
+    pub auto: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            url: None,
+            results: default_results(),
+            auto: false,
+        }
+    }
+}
+
+fn default_results() -> usize {
+    5
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+fn text(value: &Value) -> String {
+    value.as_str().unwrap_or_default().trim().to_string()
+}
+
+// DuckDuckGo nests some related topics in groups.
+fn duckduckgo_topics(topics: &Value, results: &mut Vec<SearchResult>) {
+    for topic in topics.as_array().into_iter().flatten() {
+        if topic.get("Topics").is_some() {
+            duckduckgo_topics(&topic["Topics"], results);
+        } else if let Some(url) = topic["FirstURL"].as_str() {
+            let snippet = text(&topic["Text"]);
+            results.push(SearchResult {
+                title: snippet.split(" - ").next().unwrap_or_default().to_string(),
+                url: url.to_string(),
+                snippet,
+            });
+        }
+    }
+}
+
+fn parse_results(backend: Backend, body: &Value, limit: usize) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    match backend {
+        Backend::Searxng => {
+            for result in body["results"].as_array().into_iter().flatten() {
+                results.push(SearchResult {
+                    title: text(&result["title"]),
+                    url: text(&result["url"]),
+                    snippet: text(&result["content"]),
+                });
+            }
+        }
+        Backend::Brave => {
+            for result in body["web"]["results"].as_array().into_iter().flatten() {
+                results.push(SearchResult {
+                    title: text(&result["title"]),
+                    url: text(&result["url"]),
+                    snippet: text(&result["description"]),
+                });
+            }
+        }
+        Backend::Duckduckgo => {
+            let abstract_text = text(&body["AbstractText"]);
+            if !abstract_text.is_empty() {
+                results.push(SearchResult {
+                    title: text(&body["Heading"]),
+                    url: text(&body["AbstractURL"]),
+                    snippet: abstract_text,
+                });
+            }
+            duckduckgo_topics(&body["RelatedTopics"], &mut results);
+        }
+    }
+    results.truncate(limit);
+    results
+}
+
+// Fetches the top results for `query` from the configured backend.
+pub async fn search(
+    client: &reqwest::Client,
+    config: &SearchConfig,
+    query: &str,
+) -> Result<Vec<SearchResult>> {
+    let backend = config
+        .backend
+        .ok_or_else(|| anyhow!("Web search is off; set `backend` in [search]"))?;
+    let request = match backend {
+        Backend::Searxng => {
+            let url = config
+                .url
+                .as_deref()
+                .ok_or_else(|| anyhow!("Set `url` in [search] to your SearxNG instance"))?;
+            client
+                .get(format!("{}/search", url.trim_end_matches('/')))
+                .query(&[("q", query), ("format", "json")])
+        }
+        Backend::Brave => {
+            let key =
+                std::env::var("BRAVE_API_KEY").map_err(|_| anyhow!("BRAVE_API_KEY is not set"))?;
+            client
+                .get(BRAVE_URL)
+                .header("X-Subscription-Token", key)
+                .query(&[("q", query), ("count", &config.results.to_string())])
+        }
+        Backend::Duckduckgo => client.get(DUCKDUCKGO_URL).query(&[
+            ("q", query),
+            ("format", "json"),
+            ("no_html", "1"),
+            ("skip_disambig", "1"),
+        ]),
+    };
+    let response = request.timeout(TIMEOUT).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Web search failed ({})", response.status()));
+    }
+    let body: Value = response.json().await?;
+    Ok(parse_results(backend, &body, config.results))
+}
+
+// The results as context above the prompt.
+pub fn prompt_context(query: &str, results: &[SearchResult]) -> String {
+    let mut context = format!(
+        "Web search results for \"{}\". Use them where they help and cite the links you rely on.\n",
+        query
+    );
+    for (i, result) in results.iter().enumerate() {
+        context.push_str(&format!(
+            "\n{}. {} ({})\n{}\n",
+            i + 1,
+            result.title,
+            result.url,
+            result.snippet
+        ));
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_results() {
+        let searxng = json!({ "results": [
+            { "title": "Rust 1.90", "url": "https://blog.rust-lang.org", "content": "Released today" },
+            { "title": "Other", "url": "https://example.com", "content": "" },
+        ]});
+        assert_eq!(
+            parse_results(Backend::Searxng, &searxng, 1),
+            vec![SearchResult {
+                title: "Rust 1.90".to_string(),
+                url: "https://blog.rust-lang.org".to_string(),
+                snippet: "Released today".to_string(),
+            }]
+        );
+
+        let brave = json!({ "web": { "results": [
+            { "title": "A", "url": "https://a.example", "description": "about a" },
+        ]}});
+        assert_eq!(
+            parse_results(Backend::Brave, &brave, 5)[0].snippet,
+            "about a"
+        );
+
+        let duckduckgo = json!({
+            "Heading": "Rust",
+            "AbstractText": "A programming language.",
+            "AbstractURL": "https://en.wikipedia.org/wiki/Rust",
+            "RelatedTopics": [
+                { "Text": "Cargo - The Rust package manager", "FirstURL": "https://duckduckgo.com/Cargo" },
+                { "Name": "See also", "Topics": [
+                    { "Text": "Ferris - The mascot", "FirstURL": "https://duckduckgo.com/Ferris" },
+                ]},
+            ],
+        });
+        let results = parse_results(Backend::Duckduckgo, &duckduckgo, 5);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].title, "Rust");
+        assert_eq!(results[1].title, "Cargo");
+        assert_eq!(results[2].url, "https://duckduckgo.com/Ferris");
+
+        assert_eq!(
+            prompt_context("rust", &results[..1]),
+            "Web search results for \"rust\". Use them where they help and cite the links you rely on.\n\
+             \n1. Rust (https://en.wikipedia.org/wiki/Rust)\nA programming language.\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+// Limits on how long key capture runs unattended: a maximum session length and
+// a daily do-not-disturb window. The idle auto-stop is enforced by the listener
+// itself (see `idle_stop_hours`).
+//
+// While capture is paused the handler writes the reason to PAUSE_FILE. The
+// listener drops key presses while that file exists, and the overlay shows a
+// small "paused" note in its workspace chip.
+
+use chrono::NaiveTime;
+use serde::Deserialize;
+use std::io;
+use std::time::{Duration, Instant};
+
+pub const PAUSE_FILE: &str = ".aerogel_paused";
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SessionConfig {
+    // Pause capture this many minutes after aerogel starts
+    pub max_minutes: Option<u64>,
+    // Daily do-not-disturb window in local time, e.g. "22:00-07:00"
+    pub quiet_hours: Option<String>,
+}
+
+pub struct Session {
+    started: Instant,
+    max_length: Option<Duration>,
+    quiet_hours: Option<(NaiveTime, NaiveTime)>,
+}
+
+// Parses an "HH:MM-HH:MM" window.
+fn parse_quiet_hours(window: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let (start, end) = window.split_once('-')?;
+    let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+    let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+    Some((start, end))
+}
+
+// True when `time` falls in [start, end). Windows may wrap past midnight.
+fn in_window(start: NaiveTime, end: NaiveTime, time: NaiveTime) -> bool {
+    if start <= end {
+        time >= start && time < end
+    } else {
+        time >= start || time < end
+    }
+}
+
+impl Session {
+    pub fn new(config: &SessionConfig) -> Self {
+        let quiet_hours = config.quiet_hours.as_deref().and_then(|window| {
+            let parsed = parse_quiet_hours(window);
+            if parsed.is_none() {
+                eprintln!(
+                    "Warning: Invalid quiet_hours '{}', expected e.g. \"22:00-07:00\".",
+                    window
+                );
+            }
+            parsed
+        });
+
+        Self {
+            started: Instant::now(),
+            max_length: config.max_minutes.map(|m| Duration::from_secs(m * 60)),
+            quiet_hours,
+        }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.max_length.is_some() || self.quiet_hours.is_some()
+    }
+
+    // Returns why capture should be paused right now, if it should.
+    pub fn pause_reason(&self, now: Instant, time_of_day: NaiveTime) -> Option<&'static str> {
+        if let Some(max_length) = self.max_length
+            && now.duration_since(self.started) >= max_length
+        {
+            return Some("session limit reached, restart aerogel to resume");
+        }
+        if let Some((start, end)) = self.quiet_hours
+            && in_window(start, end, time_of_day)
+        {
+            return Some("quiet hours");
+        }
+        None
+    }
+}
+
+// Creates the pause file with the given reason, or removes it when None.
+pub async fn update_pause_file(reason: Option<&str>) -> io::Result<()> {
+    match reason {
+        Some(reason) => tokio::fs::write(PAUSE_FILE, reason).await,
+        None => match tokio::fs::remove_file(PAUSE_FILE).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_wrap_past_midnight() {
+        let (start, end) = parse_quiet_hours("22:00 - 07:30").unwrap();
+        assert!(in_window(start, end, time(23, 15)));
+        assert!(in_window(start, end, time(3, 0)));
+        assert!(!in_window(start, end, time(7, 30)));
+        assert!(!in_window(start, end, time(12, 0)));
+        assert!(parse_quiet_hours("late").is_none());
+    }
+
+    #[test]
+    fn test_pause_reason() {
+        let session = Session::new(&SessionConfig {
+            max_minutes: Some(30),
+            quiet_hours: Some("12:00-13:00".to_string()),
+        });
+        let start = session.started;
+
+        assert_eq!(session.pause_reason(start, time(9, 0)), None);
+        assert_eq!(
+            session.pause_reason(start, time(12, 30)),
+            Some("quiet hours")
+        );
+        assert!(
+            session
+                .pause_reason(start + Duration::from_secs(31 * 60), time(9, 0))
+                .is_some()
+        );
+    }
+}
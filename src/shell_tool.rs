@@ -0,0 +1,260 @@
+// Shell commands proposed by the models, run only after confirmation.
+//
+// With `[shell] enabled = true`, the system prompt tells the models they may
+// propose a command in a ```run block. The first proposed command of a round
+// is shown in the overlay and nothing more happens until the Run Command
+// keybinding (or `/run`) confirms it; its output is then sent back as the
+// next prompt. Commands run with `sh -c` in `dir`, with a time limit and,
+// unless `sandbox = false`, under bubblewrap: the filesystem is read-only
+// apart from a scratch /tmp (and `dir` with `writable = true`), the home
+// directory, aerogel's own directory and any `.env` are hidden, the
+// environment is cleared, and there is no network unless `network = true`.
+
+use crate::code_extract;
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+// Output beyond this is cut before it goes to the models
+const MAX_OUTPUT_CHARS: usize = 20_000;
+
+// Environment variables sandboxed commands get; the rest are cleared
+const KEPT_ENV: &[&str] = &["PATH", "HOME", "USER", "LANG", "LC_ALL", "TERM"];
+
+pub const SYSTEM_PROMPT: &str = "You can run a shell command on the user's machine when \
+    its output would help, e.g. to inspect files or versions. Propose at most one command \
+    per answer, in a fenced code block with the language `run`. The user reviews it before \
+    it runs, and its output comes back as the next message.";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShellConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Run commands under bubblewrap
+    #[serde(default = "default_sandbox")]
+    pub sandbox: bool,
+    // Directory commands run in; the current directory when unset
+    pub dir: Option<String>,
+    // Let sandboxed commands write to `dir`
+    #[serde(default)]
+    pub writable: bool,
+    // Let sandboxed commands use the network
+    #[serde(default)]
+    pub network: bool,
+    // Seconds a command may run before it is killed
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sandbox: default_sandbox(),
+            dir: None,
+            writable: false,
+            network: false,
+            timeout: default_timeout(),
+        }
+    }
+}
+
+fn default_sandbox() -> bool {
+    true
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+impl ShellConfig {
+    fn work_dir(&self) -> PathBuf {
+        match self.dir.as_deref() {
+            Some(dir) => match (dir.strip_prefix("~/"), dirs::home_dir()) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(dir),
+            },
+            None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+
+    // The program and arguments that run `command`.
+    fn command_line(&self, command: &str) -> (String, Vec<String>) {
+        let shell = ["sh", "-c", command].map(String::from);
+        if !self.sandbox {
+            return ("sh".to_string(), shell[1..].to_vec());
+        }
+        let work_dir = self.work_dir();
+        let dir = work_dir.to_string_lossy().into_owned();
+        let mut args: Vec<String> = [
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--tmpfs",
+            "/tmp",
+        ]
+        .map(String::from)
+        .to_vec();
+        // The home directory (SSH keys, keyrings), the directory aerogel runs
+        // in (its .env) and the runtime directory (the session bus, and with
+        // it the Secret Service) are hidden; only `dir` is mounted back
+        for hidden in hidden_dirs() {
+            args.extend(["--tmpfs".to_string(), hidden.to_string_lossy().into_owned()]);
+        }
+        let bind = if self.writable { "--bind" } else { "--ro-bind" };
+        args.extend([bind.to_string(), dir.clone(), dir.clone()]);
+        // API keys in a .env of `dir` or above stay out of reach
+        for env_file in work_dir.ancestors().map(|dir| dir.join(".env")) {
+            if env_file.exists() {
+                let env_file = env_file.to_string_lossy().into_owned();
+                args.extend(["--ro-bind".to_string(), "/dev/null".to_string(), env_file]);
+            }
+        }
+        args.extend(["--unshare-all", "--die-with-parent", "--new-session"].map(String::from));
+        if self.network {
+            args.push("--share-net".to_string());
+        }
+        args.extend(["--chdir".to_string(), dir]);
+        args.extend(shell);
+        ("bwrap".to_string(), args)
+    }
+}
+
+// Directories a sandboxed command sees as empty.
+fn hidden_dirs() -> Vec<PathBuf> {
+    let mut hidden: Vec<PathBuf> = [
+        dirs::home_dir(),
+        std::env::current_dir().ok(),
+        std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|dir| dir.parent().is_some())
+    .collect();
+    hidden.dedup();
+    hidden
+}
+
+// The command of the first ```run block in an answer.
+pub fn proposed_command(answer: &str) -> Option<String> {
+    code_extract::extract_code_blocks(answer)
+        .into_iter()
+        .find(|block| block.lang.eq_ignore_ascii_case("run"))
+        .map(|block| block.code.trim().to_string())
+}
+
+// Runs a confirmed command and returns its exit code and combined output.
+pub async fn run(config: &ShellConfig, command: &str) -> Result<(Option<i32>, String)> {
+    let (program, args) = config.command_line(command);
+    let mut child = Command::new(&program);
+    child
+        .args(&args)
+        .current_dir(config.work_dir())
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    if config.sandbox {
+        // The API keys loaded from .env are in aerogel's environment
+        child.env_clear();
+        for name in KEPT_ENV {
+            if let Some(value) = std::env::var_os(name) {
+                child.env(name, value);
+            }
+        }
+    }
+    let child = child.output();
+    let output = tokio::time::timeout(Duration::from_secs(config.timeout), child)
+        .await
+        .map_err(|_| anyhow!("timed out after {}s", config.timeout))?
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound if config.sandbox => anyhow!(
+                "bwrap is not installed; install bubblewrap or set `sandbox = false` in [shell]"
+            ),
+            _ => anyhow!("Could not run {}: {}", program, e),
+        })?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if text.len() > MAX_OUTPUT_CHARS {
+        let mut end = MAX_OUTPUT_CHARS;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[output truncated]");
+    }
+    Ok((output.status.code(), text))
+}
+
+// The prompt that sends a command's output back to the models.
+pub fn output_prompt(command: &str, code: Option<i32>, output: &str) -> String {
+    let status = match code {
+        Some(code) => format!("exit code {}", code),
+        None => "killed by a signal".to_string(),
+    };
+    format!(
+        "I ran `{}` ({}). Its output:\n\n```\n{}\n```",
+        command,
+        status,
+        output.trim_end()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proposed_command() {
+        let answer =
+            "Check the version:\n\n```sh\ncargo build\n```\n\n```run\nrustc --version\n```\n";
+        assert_eq!(proposed_command(answer).as_deref(), Some("rustc --version"));
+        assert_eq!(proposed_command("```sh\nls\n```"), None);
+    }
+
+    #[test]
+    fn test_command_line() {
+        let mut config = ShellConfig {
+            dir: Some("/work".to_string()),
+            ..ShellConfig::default()
+        };
+        let (program, args) = config.command_line("ls");
+        assert_eq!(program, "bwrap");
+        assert!(args.ends_with(&["--chdir", "/work", "sh", "-c", "ls"].map(String::from)));
+        assert!(!args.contains(&"--share-net".to_string()));
+        assert!(!args.contains(&"--bind".to_string()));
+        let home = dirs::home_dir().unwrap().to_string_lossy().into_owned();
+        assert!(
+            args.windows(2)
+                .any(|pair| pair == ["--tmpfs", home.as_str()])
+        );
+        // `dir` is mounted back after the home directory is hidden
+        let hidden_at = args.iter().position(|arg| *arg == home).unwrap();
+        assert!(
+            args[hidden_at..]
+                .windows(3)
+                .any(|w| w == ["--ro-bind", "/work", "/work"])
+        );
+
+        let work = tempfile::tempdir().unwrap();
+        std::fs::write(work.path().join(".env"), "OPENAI_API_KEY=sk-secret").unwrap();
+        config.dir = Some(work.path().to_string_lossy().into_owned());
+        let env_file = work.path().join(".env").to_string_lossy().into_owned();
+        let (_, args) = config.command_line("cat .env");
+        assert!(
+            args.windows(3)
+                .any(|w| w == ["--ro-bind", "/dev/null", env_file.as_str()])
+        );
+
+        config.sandbox = false;
+        assert_eq!(
+            config.command_line("ls"),
+            ("sh".to_string(), vec!["-c".to_string(), "ls".to_string()])
+        );
+    }
+}
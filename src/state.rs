@@ -0,0 +1,196 @@
+// Persistent overlay state: position, size, visibility and the scroll offset
+// of each workspace, restored on the next start.
+//
+// The state lives in `$XDG_STATE_HOME/aerogel/overlay.toml`. It is written to
+// a temporary file first and renamed over the old one, so a crash mid-write
+// never leaves a truncated file behind. A `margin.log` from older versions is
+// imported when no state file exists yet, written to the state file and then
+// removed.
+//
+// The transcripts (.tmpN), .aerogel_workspace and .event of older versions
+// are dealt with by the launcher (see legacy_files.rs).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const STATE_FILE: &str = "overlay.toml";
+pub const LEGACY_LOG: &str = "margin.log";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayState {
+    pub margin_x: Option<i32>,
+    pub margin_y: Option<i32>,
+    pub width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub visible: bool,
+    // Scroll offset keyed by workspace number
+    pub scroll: BTreeMap<String, f32>,
+}
+
+impl Default for OverlayState {
+    fn default() -> Self {
+        Self {
+            margin_x: None,
+            margin_y: None,
+            width: None,
+            max_height: None,
+            visible: true,
+            scroll: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn state_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("aerogel")
+        .join(STATE_FILE)
+}
+
+impl OverlayState {
+    pub fn load() -> Self {
+        Self::load_from(&state_path(), Path::new(LEGACY_LOG))
+    }
+
+    pub fn load_from(path: &Path, legacy_log: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                eprintln!(
+                    "[overlay] Ignoring invalid state file {}: {}",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }),
+            Err(_) => match std::fs::read_to_string(legacy_log) {
+                Ok(log) => Self::migrate_legacy_log(&log, path, legacy_log),
+                Err(_) => Self::default(),
+            },
+        }
+    }
+
+    // Moves the state of an old margin.log to the state file. The log is
+    // only removed once the state file is written.
+    fn migrate_legacy_log(log: &str, path: &Path, legacy_log: &Path) -> Self {
+        let state = Self::from_legacy_log(log);
+        match state
+            .save_to(path)
+            .and_then(|()| std::fs::remove_file(legacy_log))
+        {
+            Ok(()) => println!(
+                "[overlay] Moved {} to {}",
+                legacy_log.display(),
+                path.display()
+            ),
+            Err(e) => eprintln!(
+                "[overlay] Failed to migrate {}: {}",
+                legacy_log.display(),
+                e
+            ),
+        }
+        state
+    }
+
+    // Reads the last "Dragging: x=.., y=.." and "Resizing: width=..,
+    // max_height=.." lines of an old margin.log.
+    fn from_legacy_log(log: &str) -> Self {
+        let mut state = Self::default();
+        for line in log.lines() {
+            let (fields, is_resize) = if let Some(fields) = line.strip_prefix("Dragging:") {
+                (fields, false)
+            } else if let Some(fields) = line.strip_prefix("Resizing:") {
+                (fields, true)
+            } else {
+                continue;
+            };
+            for part in fields.split(',') {
+                let Some((key, value)) = part.trim().split_once('=') else {
+                    continue;
+                };
+                let value = value.trim();
+                match (key, is_resize) {
+                    ("x", false) => state.margin_x = value.parse().ok(),
+                    ("y", false) => state.margin_y = value.parse().ok(),
+                    ("width", true) => state.width = value.parse().ok(),
+                    ("max_height", true) => state.max_height = value.parse().ok(),
+                    _ => {}
+                }
+            }
+        }
+        state
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to(&state_path())
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    pub fn scroll_for(&self, workspace: u32) -> f32 {
+        self.scroll
+            .get(&workspace.to_string())
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_legacy_log() {
+        let log = "Dragging: x=10, y=20\nDragging: x=30, y=40\n\
+                   Resizing: width=500, max_height=300\n";
+        let state = OverlayState::from_legacy_log(log);
+        assert_eq!(state.margin_x, Some(30));
+        assert_eq!(state.margin_y, Some(40));
+        assert_eq!(state.width, Some(500));
+        assert_eq!(state.max_height, Some(300));
+        assert!(state.visible);
+    }
+
+    #[test]
+    fn test_migrate_legacy_log() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("aerogel").join(STATE_FILE);
+        let legacy_log = dir.path().join(LEGACY_LOG);
+        std::fs::write(&legacy_log, "Dragging: x=7, y=8\n")?;
+
+        let state = OverlayState::load_from(&path, &legacy_log);
+        assert_eq!((state.margin_x, state.margin_y), (Some(7), Some(8)));
+        assert!(!legacy_log.exists());
+        assert_eq!(OverlayState::load_from(&path, &legacy_log), state);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_round_trip() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("aerogel").join(STATE_FILE);
+        let mut state = OverlayState {
+            margin_x: Some(5),
+            visible: false,
+            ..Default::default()
+        };
+        state.scroll.insert("2".to_string(), 120.0);
+        state.save_to(&path)?;
+
+        let loaded: OverlayState = toml::from_str(&std::fs::read_to_string(&path)?).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded.scroll_for(2), 120.0);
+        assert_eq!(loaded.scroll_for(3), 0.0);
+        Ok(())
+    }
+}
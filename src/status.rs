@@ -0,0 +1,286 @@
+// What the overlay's status line shows about this workspace's requests.
+//
+// `.aerogel_status<workspace>` is rewritten when a round starts, when a
+// provider starts answering and when the round ends. It holds `key=value`
+// lines: the round's start and end as Unix seconds, the providers that
+// answered, the session's running token and cost counters, and how much of
+// its context the fullest conversation uses. Tokens are counted with
+// `tokens::count_tokens`, since not every provider reports usage while
+// streaming; costs use each provider's `input_price` and `output_price` (USD
+// per million tokens), or the `[pricing]` entry of its model, and context
+// limits its `context_tokens`. The overlay reads the file back with
+// `Status::read`.
+
+use crate::ipc;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Default)]
+pub struct StatusFile {
+    path: String,
+    // (input, output) prices per million tokens, keyed by provider
+    prices: HashMap<String, (f64, f64)>,
+    // context_tokens by provider, 0 when unset
+    context_limits: HashMap<String, u64>,
+    // Tokens of each provider's last request and answer, the size of the
+    // conversation its next request starts from
+    context_used: HashMap<String, u64>,
+    started: Option<u64>,
+    finished: Option<u64>,
+    responding: Vec<String>,
+    tokens_in: u64,
+    tokens_out: u64,
+    cost: f64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+// E.g. "1.5k" for 1500.
+pub fn short_count(count: u64) -> String {
+    match count {
+        0..1000 => count.to_string(),
+        _ => format!("{:.1}k", count as f64 / 1000.0),
+    }
+}
+
+// The contents of a status file, as written by `StatusFile`. Only the
+// overlay reads them.
+#[allow(dead_code)]
+#[derive(Debug, Default, PartialEq)]
+pub struct Status {
+    pub started: Option<u64>,
+    pub finished: Option<u64>,
+    pub providers: Vec<String>,
+    pub tokens_in: u64,
+    pub tokens_out: u64,
+    pub cost: f64,
+    // (used, limit) of the fullest conversation; the limit is 0 when its
+    // provider sets no context_tokens
+    pub context: Option<(u64, u64)>,
+}
+
+#[allow(dead_code)]
+impl Status {
+    pub fn parse(contents: &str) -> Self {
+        let fields: HashMap<&str, &str> = contents
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect();
+        let number = |key: &str| fields.get(key).and_then(|v| v.parse::<u64>().ok());
+        Self {
+            started: number("started"),
+            finished: number("finished"),
+            providers: fields
+                .get("providers")
+                .map(|providers| {
+                    providers
+                        .split(',')
+                        .filter(|p| !p.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            tokens_in: number("tokens_in").unwrap_or(0),
+            tokens_out: number("tokens_out").unwrap_or(0),
+            cost: fields
+                .get("cost")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.0),
+            context: number("context_used")
+                .map(|used| (used, number("context_limit").unwrap_or(0))),
+        }
+    }
+
+    // None while the workspace's AI manager hasn't written one.
+    pub fn read(workspace: u32) -> Option<Self> {
+        let contents = std::fs::read_to_string(ipc::status_file(workspace)).ok()?;
+        Some(Self::parse(&contents))
+    }
+}
+
+impl StatusFile {
+    // `prices` holds each provider's (input, output) price per million
+    // tokens and `context_limits` its context_tokens.
+    pub fn new(
+        workspace: Option<u32>,
+        prices: HashMap<String, (f64, f64)>,
+        context_limits: HashMap<String, u64>,
+    ) -> Self {
+        Self {
+            path: workspace.map_or_else(|| ipc::STATUS_FILE_PREFIX.to_string(), ipc::status_file),
+            prices,
+            context_limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn start_round(&mut self) {
+        self.started = Some(now());
+        self.finished = None;
+        self.responding.clear();
+        self.write();
+    }
+
+    pub fn responding(&mut self, provider: &str) {
+        if !self.responding.iter().any(|p| p == provider) {
+            self.responding.push(provider.to_string());
+            self.write();
+        }
+    }
+
+    // Adds one provider's answer to the counters and returns its cost.
+    // `tokens_in` covers the whole request: system prompt, history and
+    // prompt.
+    pub fn add_answer(&mut self, provider: &str, tokens_in: u64, tokens_out: u64) -> f64 {
+        let cost = match self.prices.get(provider) {
+            Some((input_price, output_price)) => {
+                (tokens_in as f64 * input_price + tokens_out as f64 * output_price) / 1e6
+            }
+            None => 0.0,
+        };
+        self.tokens_in += tokens_in;
+        self.tokens_out += tokens_out;
+        self.cost += cost;
+        self.context_used
+            .insert(provider.to_string(), tokens_in + tokens_out);
+        cost
+    }
+
+    // E.g. "$0.0042, 1.5k tokens" for this session so far.
+    pub fn session_summary(&self) -> String {
+        format!(
+            "${:.4}, {} tokens",
+            self.cost,
+            short_count(self.tokens_in + self.tokens_out)
+        )
+    }
+
+    // E.g. "1.2k tokens in, 300 out, 1.5k of 8.0k context used (6.5k left)"
+    // for the answer just added with `add_answer`.
+    pub fn usage_summary(&self, provider: &str, tokens_in: u64, tokens_out: u64) -> String {
+        let used = self.context_used.get(provider).copied().unwrap_or(0);
+        let mut summary = format!(
+            "{} tokens in, {} out",
+            short_count(tokens_in),
+            short_count(tokens_out)
+        );
+        match self.context_limits.get(provider).copied().unwrap_or(0) {
+            0 => summary.push_str(&format!(", {} in context", short_count(used))),
+            limit => summary.push_str(&format!(
+                ", {} of {} context used ({} left)",
+                short_count(used),
+                short_count(limit),
+                short_count(limit.saturating_sub(used))
+            )),
+        }
+        summary
+    }
+
+    // (used, limit) of the conversation closest to its context limit, or
+    // the largest one when no provider sets context_tokens.
+    fn fullest_context(&self) -> Option<(u64, u64)> {
+        let share = |used: u64, limit: u64| match limit {
+            0 => 0.0,
+            _ => used as f64 / limit as f64,
+        };
+        self.context_used
+            .iter()
+            .map(|(provider, used)| {
+                let limit = self.context_limits.get(provider).copied().unwrap_or(0);
+                (*used, limit)
+            })
+            .max_by(|(used_a, limit_a), (used_b, limit_b)| {
+                share(*used_a, *limit_a)
+                    .total_cmp(&share(*used_b, *limit_b))
+                    .then(used_a.cmp(used_b))
+            })
+    }
+
+    pub fn finish_round(&mut self) {
+        self.finished = Some(now());
+        self.write();
+    }
+
+    fn contents(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(started) = self.started {
+            lines.push(format!("started={}", started));
+        }
+        if let Some(finished) = self.finished {
+            lines.push(format!("finished={}", finished));
+        }
+        lines.push(format!("providers={}", self.responding.join(",")));
+        lines.push(format!("tokens_in={}", self.tokens_in));
+        lines.push(format!("tokens_out={}", self.tokens_out));
+        lines.push(format!("cost={}", self.cost));
+        if let Some((used, limit)) = self.fullest_context() {
+            lines.push(format!("context_used={}", used));
+            lines.push(format!("context_limit={}", limit));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn write(&self) {
+        if let Err(e) = std::fs::write(&self.path, self.contents()) {
+            eprintln!("[ERROR] Failed to write {}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let mut status = StatusFile::default();
+        status.prices.insert("Claude".to_string(), (3.0, 15.0));
+        status.context_limits.insert("Claude".to_string(), 8000);
+        status.started = Some(100);
+        status.responding.push("Claude".to_string());
+        assert_eq!(status.add_answer("Claude", 2, 1), 0.000021);
+        assert_eq!(status.add_answer("Ollama", 2, 1), 0.0);
+
+        assert_eq!(
+            status.contents(),
+            "started=100\nproviders=Claude\ntokens_in=4\ntokens_out=2\ncost=0.000021\n\
+             context_used=3\ncontext_limit=8000\n"
+        );
+
+        status.add_answer("Claude", 1200, 300);
+        assert_eq!(
+            status.usage_summary("Claude", 1200, 300),
+            "1.2k tokens in, 300 out, 1.5k of 8.0k context used (6.5k left)"
+        );
+        status.finished = Some(112);
+        assert_eq!(
+            Status::parse(&status.contents()),
+            Status {
+                started: Some(100),
+                finished: Some(112),
+                providers: vec!["Claude".to_string()],
+                tokens_in: 1204,
+                tokens_out: 302,
+                cost: status.cost,
+                context: Some((1500, 8000)),
+            }
+        );
+        assert_eq!(
+            status.usage_summary("Ollama", 2, 1),
+            "2 tokens in, 1 out, 3 in context"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Status::parse(""), Status::default());
+        let status = Status::parse("started=100\nproviders=\ncontext_used=150\n");
+        assert_eq!(status.started, Some(100));
+        assert!(status.providers.is_empty());
+        assert_eq!(status.context, Some((150, 0)));
+    }
+}
@@ -0,0 +1,263 @@
+// End-to-end encrypted sync of conversations between machines.
+//
+// With `[sync] folder` set and AEROGEL_SYNC_KEY in the environment, each
+// workspace's conversation (the history sent to the models and the transcript
+// shown in the overlay) is written encrypted to `<folder>/ws<n>.session`
+// after every answer. The folder is meant to be shared through Syncthing,
+// Dropbox or a mounted server; starting the same workspace on another machine
+// continues the conversation from there. Only ciphertext is written to it.
+//
+// A session is only overwritten when it is the copy this process loaded or
+// last wrote. If another machine has written a newer one since (its
+// `updated` is later), this machine's copy goes to `ws<n>.conflict.session`
+// instead, with a warning, and the newer one is kept; restarting the
+// workspace continues it.
+//
+// File format: the "AGS1" magic, a 16-byte salt, a 12-byte nonce and then the
+// ChaCha20-Poly1305 ciphertext of the session as JSON. The key is derived
+// from the passphrase and the salt with Argon2id.
+
+use crate::ai_client::Message;
+use crate::config::ApiConfig;
+use crate::conversation::Exchange;
+use anyhow::{Result, anyhow};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8] = b"AGS1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncedSession {
+    // Seconds since the Unix epoch of the last change
+    pub updated: u64,
+    #[serde(default)]
+    pub title: Option<String>,
+    pub history: HashMap<String, Vec<Message>>,
+    // Missing from sessions synced by older versions
+    #[serde(default)]
+    pub exchanges: Vec<Exchange>,
+    pub transcript: String,
+}
+
+impl SyncedSession {
+    pub fn new(
+        title: Option<String>,
+        history: HashMap<String, Vec<Message>>,
+        exchanges: Vec<Exchange>,
+        transcript: String,
+    ) -> Self {
+        let updated = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self {
+            updated,
+            title,
+            history,
+            exchanges,
+            transcript,
+        }
+    }
+}
+
+pub struct SessionSync {
+    dir: PathBuf,
+    passphrase: String,
+    // `updated` of each workspace's session as last loaded or written here;
+    // 0 when there was none
+    seen: Mutex<HashMap<u32, u64>>,
+}
+
+impl SessionSync {
+    // None unless both the sync folder and the key are configured.
+    pub fn from_config(config: &ApiConfig) -> Option<Self> {
+        let folder = config.sync_folder.as_ref()?;
+        let Some(passphrase) = config.sync_key.clone() else {
+            eprintln!("[WARN] [sync] folder is set but AEROGEL_SYNC_KEY is not; sync is off.");
+            return None;
+        };
+        let dir = match folder.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir()?.join(rest),
+            None => PathBuf::from(folder),
+        };
+        Some(Self {
+            dir,
+            passphrase,
+            seen: Mutex::default(),
+        })
+    }
+
+    fn path(&self, workspace: u32) -> PathBuf {
+        self.dir.join(format!("ws{}.session", workspace))
+    }
+
+    fn conflict_path(&self, workspace: u32) -> PathBuf {
+        self.dir.join(format!("ws{}.conflict.session", workspace))
+    }
+
+    // Loads the workspace's session, which `save` may then replace.
+    pub fn load(&self, workspace: u32) -> Result<Option<SyncedSession>> {
+        let session = self.read(workspace)?;
+        let updated = session.as_ref().map_or(0, |session| session.updated);
+        self.seen.lock().unwrap().insert(workspace, updated);
+        Ok(session)
+    }
+
+    fn read(&self, workspace: u32) -> Result<Option<SyncedSession>> {
+        let data = match std::fs::read(self.path(workspace)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let plaintext = decrypt(&self.passphrase, &data)?;
+        Ok(Some(serde_json::from_slice(&plaintext)?))
+    }
+
+    // Writes the session unless another machine has written a newer one
+    // since it was loaded, in which case it goes to the conflict file and
+    // an error says so. A workspace never loaded here, as when it starts a
+    // new conversation, is written over.
+    pub fn save(&self, workspace: u32, session: &SyncedSession) -> Result<()> {
+        let seen = self.seen.lock().unwrap().get(&workspace).copied();
+        if let Some(seen) = seen
+            && let Some(newer) = self.read(workspace)?.filter(|synced| synced.updated > seen)
+        {
+            let conflict = self.conflict_path(workspace);
+            self.write(&conflict, session)?;
+            return Err(anyhow!(
+                "Workspace {}'s conversation was synced from another machine {} second(s) after \
+                 it was loaded here, so it was not overwritten; this machine's copy is in '{}'. \
+                 Restart the workspace to continue the newer one.",
+                workspace,
+                newer.updated - seen,
+                conflict.display()
+            ));
+        }
+        self.write(&self.path(workspace), session)?;
+        self.seen.lock().unwrap().insert(workspace, session.updated);
+        Ok(())
+    }
+
+    // Writes to a temporary file first, so the sync tool never picks up a
+    // half-written session.
+    fn write(&self, path: &Path, session: &SyncedSession) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let data = encrypt(&self.passphrase, &serde_json::to_vec(session)?)?;
+        let tmp = path.with_extension("session.tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+fn encrypt(passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("Encryption failed"))?;
+
+    let mut data = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+fn decrypt(passphrase: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let rest = data
+        .strip_prefix(MAGIC)
+        .filter(|rest| rest.len() > SALT_LEN + NONCE_LEN)
+        .ok_or_else(|| anyhow!("Not an aerogel session file"))?;
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Could not decrypt the session, is AEROGEL_SYNC_KEY the same?"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_round_trip() -> Result<()> {
+        let data = encrypt("correct horse", b"hello")?;
+        assert!(data.starts_with(MAGIC));
+        assert!(!data.windows(5).any(|w| w == b"hello"));
+        assert_eq!(decrypt("correct horse", &data)?, b"hello");
+        assert!(decrypt("wrong key", &data).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sync = SessionSync {
+            dir: dir.path().join("sync"),
+            passphrase: "secret".to_string(),
+            seen: Mutex::default(),
+        };
+        assert!(sync.load(2)?.is_none());
+
+        let session = SyncedSession::new(None, HashMap::new(), Vec::new(), "**Q:** hi".to_string());
+        sync.save(2, &session)?;
+        let loaded = sync.load(2)?.expect("saved session");
+        assert_eq!(loaded.transcript, "**Q:** hi");
+        assert_eq!(loaded.updated, session.updated);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_keeps_newer_remote_session() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let machine = || SessionSync {
+            dir: dir.path().to_path_buf(),
+            passphrase: "secret".to_string(),
+            seen: Mutex::default(),
+        };
+        let session = |updated, transcript: &str| SyncedSession {
+            updated,
+            transcript: transcript.to_string(),
+            ..Default::default()
+        };
+        let (here, other) = (machine(), machine());
+        here.save(1, &session(100, "new here"))?;
+        assert!(here.load(1)?.is_some());
+        other.load(1)?;
+        other.save(1, &session(200, "from the other machine"))?;
+
+        // Loaded at 100, the other machine wrote at 200
+        assert!(here.save(1, &session(150, "older here")).is_err());
+        assert_eq!(here.read(1)?.unwrap().transcript, "from the other machine");
+        let conflict = std::fs::read(here.conflict_path(1))?;
+        let conflict: SyncedSession = serde_json::from_slice(&decrypt("secret", &conflict)?)?;
+        assert_eq!(conflict.transcript, "older here");
+
+        // Once the newer one is loaded, it may be replaced
+        here.load(1)?;
+        here.save(1, &session(300, "continued here"))?;
+        assert_eq!(other.read(1)?.unwrap().transcript, "continued here");
+        Ok(())
+    }
+}
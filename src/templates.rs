@@ -0,0 +1,79 @@
+// Prompt templates from the `[templates]` section of aerogel.toml.
+//
+// A template is a prompt with placeholders, filled in when it is used with
+// `/t <name>` or its keybinding:
+//
+//   {input}      the typed prompt
+//   {clipboard}  the text on the Wayland clipboard (wl-paste)
+//   {ocr}        the text in the attached images
+//
+// A typed prompt is added below a template without {input}, so it is not
+// lost.
+
+use anyhow::{Result, anyhow};
+use tokio::process::Command;
+
+#[derive(Debug, Default)]
+pub struct Placeholders {
+    pub input: String,
+    pub clipboard: Option<String>,
+    pub ocr: Option<String>,
+}
+
+pub fn uses(template: &str, placeholder: &str) -> bool {
+    template.contains(&format!("{{{}}}", placeholder))
+}
+
+pub fn expand(template: &str, values: &Placeholders) -> String {
+    let mut prompt = template
+        .replace(
+            "{clipboard}",
+            values.clipboard.as_deref().unwrap_or_default(),
+        )
+        .replace("{ocr}", values.ocr.as_deref().unwrap_or_default());
+    if uses(template, "input") {
+        prompt = prompt.replace("{input}", &values.input);
+    } else if !values.input.is_empty() {
+        prompt = format!("{}\n\n{}", prompt.trim_end(), values.input);
+    }
+    prompt.trim().to_string()
+}
+
+pub async fn read_clipboard() -> Result<String> {
+    let output = Command::new("wl-paste")
+        .args(["--no-newline", "--type", "text"])
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to run wl-paste: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "wl-paste failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let values = Placeholders {
+            input: "fn main() {}".to_string(),
+            clipboard: Some("copied".to_string()),
+            ocr: None,
+        };
+        assert_eq!(
+            expand("Explain this code step by step:\n{input}", &values),
+            "Explain this code step by step:\nfn main() {}"
+        );
+        assert_eq!(
+            expand("Translate: {clipboard}\n{ocr}", &values),
+            "Translate: copied\n\nfn main() {}"
+        );
+        assert!(uses("Read {ocr}", "ocr"));
+        assert!(!uses("Read {ocr}", "clipboard"));
+    }
+}
@@ -0,0 +1,316 @@
+// Overlay color themes.
+//
+// The `[theme]` section sets the default colors. Extra `[themes.<name>]`
+// sections define named themes; `[theme] name` picks the one to start with and
+// Ctrl+T in the overlay cycles through them. Colors are hex strings in
+// `#rgb`, `#rrggbb` or `#rrggbbaa` form.
+//
+// Code blocks are highlighted with the syntect theme set by `[theme]
+// code_theme`, either a built-in theme name or a path to a `.tmTheme` file.
+//
+// Configs from before themes set the background in a `[colors]` section of
+// `red`, `green`, `blue` and `alpha_multiplier`; it is still read, with a
+// warning, when there is no `[theme]` section.
+
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use syntect::highlighting::ThemeSet;
+
+// Name of the theme made of the `[theme]` section's own colors.
+pub const DEFAULT_THEME: &str = "default";
+
+pub const DEFAULT_CODE_THEME: &str = "base16-ocean.dark";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim().strip_prefix('#')?;
+        if !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize, len: usize| {
+            let value = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok()?;
+            // "#abc" is shorthand for "#aabbcc"
+            Some(if len == 1 { value * 17 } else { value })
+        };
+
+        match hex.len() {
+            3 => Some(Self::rgb(channel(0, 1)?, channel(1, 1)?, channel(2, 1)?)),
+            6 => Some(Self::rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+            8 => Some(Self {
+                r: channel(0, 2)?,
+                g: channel(1, 2)?,
+                b: channel(2, 2)?,
+                a: channel(3, 2)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Rgba {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Rgba::from_hex(&hex).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid color '{}', expected #rgb, #rrggbb or #rrggbbaa",
+                hex
+            ))
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ThemeColors {
+    // Overlay background; its alpha sets the overlay's opacity
+    pub background: Rgba,
+    // Plain (non-code) text
+    pub text: Rgba,
+    // Code block line numbers and language labels
+    pub accent: Rgba,
+    // Panel behind code blocks, blended over the background
+    pub code_background: Rgba,
+    pub chip: Rgba,
+    pub chip_text: Rgba,
+}
+
+impl Default for ThemeColors {
+    fn default() -> Self {
+        Self {
+            background: Rgba {
+                a: 230,
+                ..Rgba::rgb(20, 20, 20)
+            },
+            text: Rgba::rgb(192, 197, 206),
+            accent: Rgba::rgb(128, 128, 128),
+            code_background: Rgba {
+                a: 110,
+                ..Rgba::rgb(0, 0, 0)
+            },
+            chip: Rgba::rgb(5, 5, 5),
+            chip_text: Rgba::rgb(230, 220, 220),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ThemeConfig {
+    // Named theme from [themes.<name>] to start with
+    pub name: Option<String>,
+    // Built-in syntect theme name or path to a .tmTheme file
+    pub code_theme: Option<String>,
+    #[serde(flatten)]
+    pub colors: ThemeColors,
+}
+
+// The `[colors]` section of configs from before themes.
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct LegacyColors {
+    #[serde(default = "default_legacy_channel")]
+    pub red: u8,
+    #[serde(default = "default_legacy_channel")]
+    pub green: u8,
+    #[serde(default = "default_legacy_channel")]
+    pub blue: u8,
+    // The background's opacity, 0 to 255
+    #[serde(default = "default_alpha_multiplier")]
+    pub alpha_multiplier: f32,
+}
+
+fn default_legacy_channel() -> u8 {
+    20
+}
+
+fn default_alpha_multiplier() -> f32 {
+    230.0
+}
+
+impl LegacyColors {
+    // The background it sets, with the workspace chip a shade darker as it
+    // was drawn then.
+    fn apply(self, colors: &mut ThemeColors) {
+        let darker = |channel: u8| channel.saturating_sub(15);
+        colors.background = Rgba {
+            a: self.alpha_multiplier.clamp(0.0, 255.0) as u8,
+            ..Rgba::rgb(self.red, self.green, self.blue)
+        };
+        colors.chip = Rgba::rgb(darker(self.red), darker(self.green), darker(self.blue));
+    }
+}
+
+// The theme config, taking the colors of a legacy `[colors]` section when
+// there is no `[theme]` section.
+pub fn with_legacy_colors(
+    mut theme: ThemeConfig,
+    legacy: Option<LegacyColors>,
+    has_theme: bool,
+) -> ThemeConfig {
+    let Some(legacy) = legacy else {
+        return theme;
+    };
+    if has_theme {
+        eprintln!("Warning: [colors] in aerogel.toml is ignored, since [theme] is set.");
+    } else {
+        eprintln!(
+            "Warning: [colors] in aerogel.toml is deprecated; set background = \"#rrggbbaa\" \
+             in a [theme] section instead."
+        );
+        legacy.apply(&mut theme.colors);
+    }
+    theme
+}
+
+// The colors and syntax highlighting theme the overlay renders with.
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub colors: ThemeColors,
+    pub syntax: syntect::highlighting::Theme,
+    // Width of the code block line number gutter, None when line numbers are
+    // hidden
+    pub line_number_gutter: Option<f32>,
+    // Whether the models' reasoning is shown in full or folded to one line
+    pub show_reasoning: bool,
+    pub spacing: Spacing,
+}
+
+// Space around and between the text, in pixels, from `[app]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spacing {
+    // Left and right of the text
+    pub padding: f32,
+    // Above the text, to keep it clear of the workspace chip
+    pub top: f32,
+    // Between paragraphs and code blocks
+    pub block: f32,
+    // Multiplier of the font's line height
+    pub line_height: f32,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Self {
+            padding: 18.0,
+            top: 40.0,
+            block: 15.0,
+            line_height: 1.0,
+        }
+    }
+}
+
+// Loads a syntect theme by built-in name or from a .tmTheme file, falling back
+// to DEFAULT_CODE_THEME.
+pub fn load_code_theme(name_or_path: Option<&str>) -> syntect::highlighting::Theme {
+    let mut defaults = ThemeSet::load_defaults();
+    let name_or_path = name_or_path.unwrap_or(DEFAULT_CODE_THEME);
+
+    if let Some(theme) = defaults.themes.remove(name_or_path) {
+        return theme;
+    }
+    match ThemeSet::get_theme(name_or_path) {
+        Ok(theme) => theme,
+        Err(e) => {
+            let mut available: Vec<&String> = defaults.themes.keys().collect();
+            available.sort();
+            eprintln!(
+                "Warning: Could not load code theme '{}': {}. Built-in themes: {:?}",
+                name_or_path, e, available
+            );
+            defaults
+                .themes
+                .remove(DEFAULT_CODE_THEME)
+                .expect("syntect ships base16-ocean.dark")
+        }
+    }
+}
+
+// Looks up a theme's colors by name.
+pub fn theme_colors(
+    config: &ThemeConfig,
+    themes: &BTreeMap<String, ThemeColors>,
+    name: &str,
+) -> Option<ThemeColors> {
+    match themes.get(name) {
+        Some(colors) => Some(colors.clone()),
+        None if name == DEFAULT_THEME => Some(config.colors.clone()),
+        None => None,
+    }
+}
+
+// Returns the name of the theme after `current`, wrapping around. The default
+// theme comes first, then the named themes in alphabetical order.
+pub fn next_theme_name(themes: &BTreeMap<String, ThemeColors>, current: &str) -> String {
+    let mut names: Vec<&str> = vec![DEFAULT_THEME];
+    names.extend(
+        themes
+            .keys()
+            .map(String::as_str)
+            .filter(|name| *name != DEFAULT_THEME),
+    );
+    let index = names.iter().position(|name| *name == current);
+    let next = index.map_or(0, |i| (i + 1) % names.len());
+    names[next].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(Rgba::from_hex("#fff"), Some(Rgba::rgb(255, 255, 255)));
+        assert_eq!(Rgba::from_hex("#1e1e2e"), Some(Rgba::rgb(30, 30, 46)));
+        assert_eq!(
+            Rgba::from_hex("#1e1e2e80"),
+            Some(Rgba {
+                a: 128,
+                ..Rgba::rgb(30, 30, 46)
+            })
+        );
+        assert_eq!(Rgba::from_hex("1e1e2e"), None);
+        assert_eq!(Rgba::from_hex("#12345"), None);
+        assert_eq!(Rgba::from_hex("#gggggg"), None);
+    }
+
+    #[test]
+    fn test_with_legacy_colors() {
+        let legacy: LegacyColors =
+            toml::from_str("red = 40\ngreen = 30\nblue = 10\nalpha_multiplier = 200.0").unwrap();
+        let theme = with_legacy_colors(ThemeConfig::default(), Some(legacy), false);
+        assert_eq!(
+            theme.colors.background,
+            Rgba {
+                a: 200,
+                ..Rgba::rgb(40, 30, 10)
+            }
+        );
+        assert_eq!(theme.colors.chip, Rgba::rgb(25, 15, 0));
+
+        let theme = with_legacy_colors(ThemeConfig::default(), Some(legacy), true);
+        assert_eq!(theme.colors.background, ThemeColors::default().background);
+    }
+
+    #[test]
+    fn test_next_theme_name() {
+        let mut themes = BTreeMap::new();
+        themes.insert("nord".to_string(), ThemeColors::default());
+        themes.insert("light".to_string(), ThemeColors::default());
+
+        assert_eq!(next_theme_name(&themes, DEFAULT_THEME), "light");
+        assert_eq!(next_theme_name(&themes, "light"), "nord");
+        assert_eq!(next_theme_name(&themes, "nord"), DEFAULT_THEME);
+        assert_eq!(next_theme_name(&themes, "missing"), DEFAULT_THEME);
+    }
+}
@@ -0,0 +1,116 @@
+// Short titles for conversations, generated after the first exchange.
+//
+// The question and the start of the first answer go to a cheap model,
+// `[titles] provider` and `model` (by default the first provider asked, with
+// its own model), which answers with a few words. The title labels the
+// workspace's tab in the overlay through `.aerogel_title<workspace>`, names
+// the file of a `/save` without a name and goes into export file names.
+
+use crate::config::ModelConfig;
+use crate::ipc;
+use serde::Deserialize;
+
+// Characters of the first answer sent along with the question
+const ANSWER_CHARS: usize = 500;
+const MAX_WORDS: usize = 8;
+// Answer length limit for titles, with room for a preamble
+const TITLE_TOKENS: u32 = 32;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TitleConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // Provider name, as in `/ask @<name>`
+    pub provider: Option<String>,
+    // Model asked in place of the provider's own
+    pub model: Option<String>,
+}
+
+impl Default for TitleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            provider: None,
+            model: None,
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// The provider's model settings as titles are asked with.
+pub fn title_model(config: &TitleConfig, model: &ModelConfig) -> ModelConfig {
+    ModelConfig {
+        model: config.model.clone().unwrap_or_else(|| model.model.clone()),
+        max_tokens: TITLE_TOKENS,
+        reasoning_tokens: 0,
+        ..model.clone()
+    }
+}
+
+pub fn title_file(workspace: Option<u32>) -> String {
+    workspace.map_or_else(|| ipc::TITLE_FILE_PREFIX.to_string(), ipc::title_file)
+}
+
+pub fn title_prompt(question: &str, answer: &str) -> String {
+    let answer: String = answer.chars().take(ANSWER_CHARS).collect();
+    format!(
+        "Write a title of at most six words for a conversation that starts with the question \
+         below. Reply with the title only, without quotes.\n\nQuestion:\n{}\n\nAnswer:\n{}",
+        question.trim(),
+        answer.trim()
+    )
+}
+
+// The title in a model's reply: its first line, without markdown, quotes or
+// a final full stop, and at most MAX_WORDS words.
+pub fn clean_title(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let strip = |text: &str| {
+        text.trim_matches(|c: char| c.is_whitespace() || matches!(c, '#' | '"' | '\'' | '*' | '`'))
+            .to_string()
+    };
+    let line = strip(line);
+    let line = strip(line.strip_prefix("Title:").unwrap_or(&line));
+    let line = line.trim_end_matches('.');
+    let title = line
+        .split_whitespace()
+        .take(MAX_WORDS)
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+// The title as a file name part, e.g. "rust-borrow-checker-errors".
+pub fn slug(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_title() {
+        assert_eq!(
+            clean_title("\n\"Rust Borrow Checker Errors.\"\nMore text"),
+            Some("Rust Borrow Checker Errors".to_string())
+        );
+        assert_eq!(
+            clean_title("**Title: Sorting a Linked List**"),
+            Some("Sorting a Linked List".to_string())
+        );
+        assert_eq!(clean_title("  \n "), None);
+        assert_eq!(
+            slug("Rust: Borrow Checker Errors!"),
+            "rust-borrow-checker-errors"
+        );
+    }
+}
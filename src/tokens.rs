@@ -0,0 +1,115 @@
+// Token counts for the status line, the usage output and the history budget.
+//
+// OpenAI's models are counted exactly, with the byte-pair encoding
+// tiktoken-rs names for the model (o200k_base for the GPT-4o and o-series
+// families, cl100k_base for GPT-4 and GPT-3.5). A `vendor/` prefix, as
+// OpenRouter puts before model names, is ignored. Other providers do not
+// publish their tokenizers, so their text is estimated instead: it is split
+// the way byte-pair tokenizers split it before merging, into runs of
+// letters, digits, whitespace and punctuation, and each piece is counted the
+// way cl100k usually encodes it. A short word is one token and a long one
+// about one per four letters, digits go in groups of three, and a space
+// before a word belongs to the word. Characters outside ASCII count one
+// token each. The estimate is only a guide to the provider's own count.
+
+use tiktoken_rs::CoreBPE;
+use tiktoken_rs::tokenizer::{Tokenizer, get_tokenizer};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Piece {
+    Letters,
+    Digits,
+    Space,
+    Symbols,
+}
+
+fn piece(c: char) -> Piece {
+    if c.is_ascii_alphabetic() {
+        Piece::Letters
+    } else if c.is_ascii_digit() {
+        Piece::Digits
+    } else if c.is_ascii_whitespace() {
+        Piece::Space
+    } else {
+        Piece::Symbols
+    }
+}
+
+// The encoding of an OpenAI model, None for other models.
+fn encoding(model: &str) -> Option<&'static CoreBPE> {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    let encoding = match get_tokenizer(name)? {
+        Tokenizer::O200kHarmony => tiktoken_rs::o200k_harmony_singleton(),
+        Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+        Tokenizer::P50kBase => tiktoken_rs::p50k_base_singleton(),
+        Tokenizer::P50kEdit => tiktoken_rs::p50k_edit_singleton(),
+        Tokenizer::R50kBase | Tokenizer::Gpt2 => tiktoken_rs::r50k_base_singleton(),
+    };
+    Some(encoding)
+}
+
+// The tokens `text` takes with `model`.
+pub fn count_tokens(model: &str, text: &str) -> u64 {
+    match encoding(model) {
+        Some(encoding) => encoding.encode_ordinary(text).len() as u64,
+        None => estimate_tokens(text),
+    }
+}
+
+fn estimate_tokens(text: &str) -> u64 {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii() {
+            tokens += 1;
+            continue;
+        }
+        let kind = piece(c);
+        let mut len: u64 = 1;
+        while chars
+            .next_if(|next| next.is_ascii() && piece(*next) == kind)
+            .is_some()
+        {
+            len += 1;
+        }
+        tokens += match kind {
+            Piece::Letters if len <= 6 => 1,
+            Piece::Letters => len.div_ceil(4),
+            Piece::Digits => len.div_ceil(3),
+            // A single space is merged into the word after it
+            Piece::Space if len == 1 && c == ' ' && chars.peek().is_some() => 0,
+            Piece::Space => 1,
+            Piece::Symbols => len.div_ceil(2),
+        };
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_tokens() {
+        assert_eq!(count_tokens("gpt-4o-mini", "Hello world"), 2);
+        assert_eq!(count_tokens("gpt-4", "tokenization"), 2);
+        assert_eq!(count_tokens("openai/gpt-4o", "fn main() {}\n"), 4);
+        assert_eq!(
+            count_tokens("claude-sonnet-4", "tokenization"),
+            estimate_tokens("tokenization")
+        );
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello world"), 2);
+        assert_eq!(estimate_tokens("tokenization"), 3);
+        assert_eq!(estimate_tokens("12345"), 2);
+        assert_eq!(estimate_tokens("fn main() {}\n"), 5);
+        assert_eq!(estimate_tokens("a  b"), 3);
+        assert_eq!(estimate_tokens("日本語"), 3);
+        assert_eq!(estimate_tokens(&"a".repeat(400)), 100);
+    }
+}
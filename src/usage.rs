@@ -0,0 +1,202 @@
+// Estimated spend across sessions, for the `/usage` command.
+//
+// Every answer appends its provider, model, workspace, token counts and cost
+// to `$XDG_DATA_HOME/aerogel/usage.jsonl`, dated by the local day. `/usage`
+// adds them up per provider and per workspace for today, and per day for
+// the last week. Costs come from each provider's `input_price` and
+// `output_price`, or from the `[pricing]` entry of its model.
+
+use crate::providers::Provider;
+use crate::status::short_count;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Days listed by /usage besides today
+const DAYS_SHOWN: usize = 7;
+
+// USD per million tokens, from a `[pricing]` entry
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Price {
+    #[serde(default)]
+    pub input: f64,
+    #[serde(default)]
+    pub output: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    // Local date, e.g. 2026-10-15
+    pub day: String,
+    pub workspace: Option<u32>,
+    pub provider: String,
+    pub model: String,
+    pub tokens_in: u64,
+    pub tokens_out: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Default)]
+struct Total {
+    tokens: u64,
+    cost: f64,
+}
+
+impl Total {
+    fn add(&mut self, record: &UsageRecord) {
+        self.tokens += record.tokens_in + record.tokens_out;
+        self.cost += record.cost;
+    }
+}
+
+impl std::fmt::Display for Total {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "${:.4}, {} tokens", self.cost, short_count(self.tokens))
+    }
+}
+
+// (input, output) price of each provider, keyed by name. A provider without
+// prices of its own is priced by the `[pricing]` entry of its model.
+pub fn prices(
+    providers: &[Arc<dyn Provider>],
+    pricing: &HashMap<String, Price>,
+) -> HashMap<String, (f64, f64)> {
+    providers
+        .iter()
+        .map(|provider| {
+            let model = provider.model();
+            let prices = match pricing.get(&model.model) {
+                Some(price) if model.input_price == 0.0 && model.output_price == 0.0 => {
+                    (price.input, price.output)
+                }
+                _ => (model.input_price, model.output_price),
+            };
+            (provider.name().to_string(), prices)
+        })
+        .collect()
+}
+
+pub fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+// The /usage report, as a markdown list.
+pub fn summarize(records: &[UsageRecord], today: &str) -> String {
+    let mut today_total = Total::default();
+    let mut providers: BTreeMap<&str, Total> = BTreeMap::new();
+    let mut workspaces: BTreeMap<Option<u32>, Total> = BTreeMap::new();
+    let mut days: BTreeMap<&str, Total> = BTreeMap::new();
+    let mut all_time = Total::default();
+    for record in records {
+        all_time.add(record);
+        days.entry(&record.day).or_default().add(record);
+        if record.day == today {
+            today_total.add(record);
+            providers.entry(&record.provider).or_default().add(record);
+            workspaces.entry(record.workspace).or_default().add(record);
+        }
+    }
+
+    let mut lines = vec![format!("- Today ({}): {}", today, today_total)];
+    for (provider, total) in &providers {
+        lines.push(format!("  - {}: {}", provider, total));
+    }
+    for (workspace, total) in &workspaces {
+        match workspace {
+            Some(workspace) => lines.push(format!("  - Workspace {}: {}", workspace, total)),
+            None => lines.push(format!("  - No workspace: {}", total)),
+        }
+    }
+    let earlier: Vec<_> = days
+        .iter()
+        .rev()
+        .filter(|(day, _)| **day != today)
+        .take(DAYS_SHOWN)
+        .collect();
+    if !earlier.is_empty() {
+        lines.push("- Earlier:".to_string());
+        for (day, total) in earlier {
+            lines.push(format!("  - {}: {}", day, total));
+        }
+    }
+    lines.push(format!("- All time: {}", all_time));
+    lines.join("\n")
+}
+
+pub struct UsageLog {
+    path: PathBuf,
+}
+
+impl UsageLog {
+    pub fn open() -> Self {
+        let dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("aerogel");
+        Self {
+            path: dir.join("usage.jsonl"),
+        }
+    }
+
+    pub fn add(&self, record: &UsageRecord) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+    }
+
+    pub fn load(&self) -> io::Result<Vec<UsageRecord>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(day: &str, workspace: u32, provider: &str, cost: f64) -> UsageRecord {
+        UsageRecord {
+            day: day.to_string(),
+            workspace: Some(workspace),
+            provider: provider.to_string(),
+            model: "model".to_string(),
+            tokens_in: 1000,
+            tokens_out: 500,
+            cost,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let records = [
+            record("2026-10-14", 1, "Claude", 0.5),
+            record("2026-10-15", 1, "Claude", 0.25),
+            record("2026-10-15", 2, "Claude", 0.25),
+            record("2026-10-15", 2, "Ollama", 0.0),
+        ];
+        assert_eq!(
+            summarize(&records, "2026-10-15"),
+            "- Today (2026-10-15): $0.5000, 4.5k tokens\n\
+             \x20 - Claude: $0.5000, 3.0k tokens\n\
+             \x20 - Ollama: $0.0000, 1.5k tokens\n\
+             \x20 - Workspace 1: $0.2500, 1.5k tokens\n\
+             \x20 - Workspace 2: $0.2500, 3.0k tokens\n\
+             - Earlier:\n\
+             \x20 - 2026-10-14: $0.5000, 1.5k tokens\n\
+             - All time: $1.0000, 6.0k tokens"
+        );
+    }
+}
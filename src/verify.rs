@@ -0,0 +1,233 @@
+// Verification of the code blocks in an answer.
+//
+// With `[verify] enabled = true`, every code block of a new answer in a
+// language with a checker is written to a fresh temporary directory and the
+// checker runs on it there, with an empty environment apart from PATH and a
+// time limit. The pass/fail result and the checker's errors are appended to
+// the conversation under the answer. Checkers are command lines where
+// `{file}` is replaced with the block's file; `[verify.commands]` overrides
+// or adds them per language.
+
+use crate::code_extract::{self, CodeBlock};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+// Checker output beyond this is cut from the overlay
+const MAX_ERROR_LINES: usize = 20;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Seconds a checker may run before it is killed
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    // Checker command lines keyed by language, on top of the defaults
+    #[serde(default)]
+    pub commands: HashMap<String, String>,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout: default_timeout(),
+            commands: HashMap::new(),
+        }
+    }
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_command(lang: &str) -> Option<&'static str> {
+    Some(match lang {
+        "rust" => "rustc --edition 2021 --crate-type lib --emit=metadata {file}",
+        "python" => "python3 -m py_compile {file}",
+        "javascript" => "node --check {file}",
+        "bash" => "bash -n {file}",
+        "c" => "cc -fsyntax-only {file}",
+        "cpp" => "c++ -fsyntax-only {file}",
+        "go" => "gofmt -e {file}",
+        _ => return None,
+    })
+}
+
+// The same language names code_extract gives file extensions for.
+fn normalize_lang(lang: &str) -> String {
+    match lang.to_lowercase().as_str() {
+        "rs" => "rust",
+        "py" | "python3" => "python",
+        "js" => "javascript",
+        "sh" | "shell" => "bash",
+        "c++" | "cc" => "cpp",
+        "golang" => "go",
+        other => return other.to_string(),
+    }
+    .to_string()
+}
+
+impl VerifyConfig {
+    fn command_for(&self, lang: &str) -> Option<String> {
+        let lang = normalize_lang(lang);
+        self.commands
+            .get(&lang)
+            .cloned()
+            .or_else(|| default_command(&lang).map(str::to_string))
+            .filter(|command| !command.trim().is_empty())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    // The checker could not be run, e.g. it is not installed
+    Skipped(String),
+}
+
+// Runs the checker for `block` in a temporary directory, or returns None
+// when its language has no checker.
+pub async fn verify_block(config: &VerifyConfig, block: &CodeBlock) -> Option<Outcome> {
+    let command = config.command_for(&block.lang)?;
+    let dir = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(e) => return Some(Outcome::Skipped(e.to_string())),
+    };
+    let path = match code_extract::write_code_blocks(dir.path(), std::slice::from_ref(block)) {
+        Ok(mut paths) => paths.remove(0),
+        Err(e) => return Some(Outcome::Skipped(e.to_string())),
+    };
+    let file = path.file_name()?.to_string_lossy().into_owned();
+    let mut args = command
+        .split_whitespace()
+        .map(|arg| arg.replace("{file}", &file));
+    let program = args.next()?;
+
+    let child = Command::new(&program)
+        .args(args)
+        .current_dir(dir.path())
+        .env_clear()
+        .env("PATH", std::env::var("PATH").unwrap_or_default())
+        .env("HOME", dir.path())
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(Duration::from_secs(config.timeout), child).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Some(Outcome::Skipped(format!("{}: {}", program, e))),
+        Err(_) => {
+            return Some(Outcome::Failed(format!(
+                "timed out after {}s",
+                config.timeout
+            )));
+        }
+    };
+    if output.status.success() {
+        return Some(Outcome::Passed);
+    }
+    let mut errors = String::from_utf8_lossy(&output.stderr).into_owned();
+    errors.push_str(&String::from_utf8_lossy(&output.stdout));
+    Some(Outcome::Failed(trim_errors(&errors)))
+}
+
+fn trim_errors(errors: &str) -> String {
+    let lines: Vec<&str> = errors.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut trimmed = lines
+        .iter()
+        .take(MAX_ERROR_LINES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if lines.len() > MAX_ERROR_LINES {
+        trimmed.push_str(&format!(
+            "\n... {} more line(s)",
+            lines.len() - MAX_ERROR_LINES
+        ));
+    }
+    trimmed
+}
+
+// The verification results of one answer as markdown, or None when none of
+// its blocks could be checked.
+pub async fn verify_answer(config: &VerifyConfig, answer: &str) -> Option<String> {
+    let mut lines = Vec::new();
+    for (i, block) in code_extract::extract_code_blocks(answer).iter().enumerate() {
+        let Some(outcome) = verify_block(config, block).await else {
+            continue;
+        };
+        let name = block
+            .filename
+            .clone()
+            .unwrap_or_else(|| format!("block {}", i + 1));
+        let lang = normalize_lang(&block.lang);
+        lines.push(match outcome {
+            Outcome::Passed => format!("- ✅ `{}` ({}) passed", name, lang),
+            Outcome::Failed(errors) => {
+                format!("- ❌ `{}` ({}) failed:\n\n```\n{}\n```", name, lang, errors)
+            }
+            Outcome::Skipped(reason) => {
+                format!("- ⚠️ `{}` ({}) not checked: {}", name, lang, reason)
+            }
+        });
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(lang: &str, code: &str) -> CodeBlock {
+        CodeBlock {
+            lang: lang.to_string(),
+            filename: None,
+            code: code.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_command_for() {
+        let mut config = VerifyConfig::default();
+        assert!(config.command_for("py").unwrap().starts_with("python3"));
+        assert!(config.command_for("toml").is_none());
+        config.commands.insert(
+            "python".to_string(),
+            "python3 -m pyflakes {file}".to_string(),
+        );
+        config.commands.insert("rust".to_string(), String::new());
+        assert_eq!(
+            config.command_for("Python").unwrap(),
+            "python3 -m pyflakes {file}"
+        );
+        assert!(config.command_for("rust").is_none());
+    }
+
+    #[test]
+    fn test_trim_errors() {
+        let errors: String = (0..MAX_ERROR_LINES + 3)
+            .map(|i| format!("{}\n\n", i))
+            .collect();
+        let trimmed = trim_errors(&errors);
+        assert_eq!(trimmed.lines().count(), MAX_ERROR_LINES + 1);
+        assert!(trimmed.ends_with("... 3 more line(s)"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_block() {
+        let config = VerifyConfig::default();
+        assert_eq!(
+            verify_block(&config, &block("sh", "echo ok\n")).await,
+            Some(Outcome::Passed)
+        );
+        assert!(matches!(
+            verify_block(&config, &block("sh", "if then fi (\n")).await,
+            Some(Outcome::Failed(_))
+        ));
+        assert_eq!(verify_block(&config, &block("text", "hi")).await, None);
+    }
+}
@@ -0,0 +1,164 @@
+// Video attachments, which only Gemini reads.
+//
+// Videos are sent inline like images, so a request stays under Gemini's
+// inline size limit only with short clips. A video larger than `max_mb` is
+// re-encoded with ffmpeg first when `downsample` is on: scaled down to
+// `height` pixels and sampled at `fps` frames per second, which is about
+// what Gemini looks at anyway. Other providers skip videos with a warning.
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::path::Path;
+use tokio::process::Command;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VideoConfig {
+    // Largest video sent as it is, in megabytes
+    #[serde(default = "default_max_mb")]
+    pub max_mb: u64,
+    // Re-encode larger videos with ffmpeg instead of refusing them
+    #[serde(default = "default_downsample")]
+    pub downsample: bool,
+    // Frame height and frame rate of re-encoded videos
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default = "default_fps")]
+    pub fps: f32,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            max_mb: default_max_mb(),
+            downsample: default_downsample(),
+            height: default_height(),
+            fps: default_fps(),
+        }
+    }
+}
+
+fn default_max_mb() -> u64 {
+    // Gemini's limit for a whole request with inline data
+    20
+}
+
+fn default_downsample() -> bool {
+    true
+}
+
+fn default_height() -> u32 {
+    360
+}
+
+fn default_fps() -> f32 {
+    1.0
+}
+
+pub fn is_video(mime_type: &str) -> bool {
+    mime_type.starts_with("video/")
+}
+
+impl VideoConfig {
+    fn max_bytes(&self) -> u64 {
+        self.max_mb * 1024 * 1024
+    }
+
+    fn ffmpeg_args(&self, input: &Path, output: &Path) -> Vec<String> {
+        vec![
+            "-y".to_string(),
+            "-loglevel".to_string(),
+            "error".to_string(),
+            "-i".to_string(),
+            input.to_string_lossy().into_owned(),
+            "-vf".to_string(),
+            format!("fps={},scale=-2:{}", self.fps, self.height),
+            "-c:v".to_string(),
+            "libx264".to_string(),
+            "-preset".to_string(),
+            "veryfast".to_string(),
+            "-crf".to_string(),
+            "32".to_string(),
+            "-c:a".to_string(),
+            "aac".to_string(),
+            "-ac".to_string(),
+            "1".to_string(),
+            "-b:a".to_string(),
+            "32k".to_string(),
+            output.to_string_lossy().into_owned(),
+        ]
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+// The bytes and MIME type to send for a video, re-encoded when it is too
+// large to send as it is.
+pub async fn prepare(
+    config: &VideoConfig,
+    path: &Path,
+    mime_type: &str,
+) -> Result<(Vec<u8>, String)> {
+    let size = tokio::fs::metadata(path).await?.len();
+    if size <= config.max_bytes() {
+        return Ok((tokio::fs::read(path).await?, mime_type.to_string()));
+    }
+    if !config.downsample {
+        return Err(anyhow!(
+            "{} is {}, over the {} MB limit; trim it or set `downsample = true` in [video]",
+            path.display(),
+            megabytes(size),
+            config.max_mb
+        ));
+    }
+    eprintln!(
+        "[INFO] {} is {}; downsampling it with ffmpeg...",
+        path.display(),
+        megabytes(size)
+    );
+    let dir = tempfile::tempdir()?;
+    let output = dir.path().join("video.mp4");
+    let result = Command::new("ffmpeg")
+        .args(config.ffmpeg_args(path, &output))
+        .output()
+        .await
+        .map_err(|e| anyhow!("Could not run ffmpeg to downsample the video: {}", e))?;
+    if !result.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    let data = tokio::fs::read(&output).await?;
+    if data.len() as u64 > config.max_bytes() {
+        return Err(anyhow!(
+            "{} is still {} after downsampling, over the {} MB limit; attach a shorter clip",
+            path.display(),
+            megabytes(data.len() as u64),
+            config.max_mb
+        ));
+    }
+    eprintln!("[INFO] Downsampled to {}", megabytes(data.len() as u64));
+    Ok((data, "video/mp4".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let config = VideoConfig {
+            height: 240,
+            fps: 0.5,
+            ..VideoConfig::default()
+        };
+        assert_eq!(config.max_bytes(), 20 * 1024 * 1024);
+        let args = config.ffmpeg_args(Path::new("in.mov"), Path::new("/tmp/out.mp4"));
+        assert_eq!(&args[3..7], ["-i", "in.mov", "-vf", "fps=0.5,scale=-2:240"]);
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/out.mp4"));
+        assert!(is_video("video/mp4"));
+        assert!(!is_video("image/png"));
+    }
+}
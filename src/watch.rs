@@ -0,0 +1,201 @@
+// Files watched as live context for the conversation.
+//
+// `/watch <path>` tails a file such as a build log or test output. New
+// non-empty lines are collected and sent along with the next prompt. With
+// `[watch] auto_ask = true`, a line matching one of `failure_patterns` asks
+// the models what the new error means right away, at most once per
+// AUTO_ASK_COOLDOWN.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinHandle;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const AUTO_ASK_COOLDOWN: Duration = Duration::from_secs(30);
+// Lines kept per file until the next prompt; older ones are dropped
+const MAX_CONTEXT_LINES: usize = 200;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatchConfig {
+    // Ask about new errors without waiting for a prompt
+    #[serde(default)]
+    pub auto_ask: bool,
+    // Case-insensitive substrings marking a line as a failure
+    #[serde(default = "default_failure_patterns")]
+    pub failure_patterns: Vec<String>,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            auto_ask: false,
+            failure_patterns: default_failure_patterns(),
+        }
+    }
+}
+
+fn default_failure_patterns() -> Vec<String> {
+    ["error", "failed", "panicked", "traceback", "exception"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+// New lines of the watched files, keyed by path.
+#[derive(Debug, Default)]
+pub struct WatchContext {
+    lines: BTreeMap<String, Vec<String>>,
+}
+
+impl WatchContext {
+    fn push(&mut self, path: &str, new_lines: Vec<String>) {
+        let lines = self.lines.entry(path.to_string()).or_default();
+        lines.extend(new_lines);
+        if lines.len() > MAX_CONTEXT_LINES {
+            lines.drain(..lines.len() - MAX_CONTEXT_LINES);
+        }
+    }
+
+    // The collected lines as prompt context, emptying the context.
+    pub fn take_prompt_context(&mut self) -> Option<String> {
+        let sections: Vec<String> = std::mem::take(&mut self.lines)
+            .into_iter()
+            .filter(|(_, lines)| !lines.is_empty())
+            .map(|(path, lines)| format!("New output in {}:\n```\n{}\n```", path, lines.join("\n")))
+            .collect();
+        (!sections.is_empty()).then(|| sections.join("\n\n"))
+    }
+}
+
+// The lines of a chunk worth sending: progress redraws are reduced to their
+// final state, and blank and repeated lines are dropped.
+fn significant_lines(chunk: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in chunk.lines() {
+        let line = line.rsplit('\r').next().unwrap_or(line).trim_end();
+        if !line.trim().is_empty() && lines.last().map(String::as_str) != Some(line) {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+fn is_failure(line: &str, patterns: &[String]) -> bool {
+    let line = line.to_lowercase();
+    patterns
+        .iter()
+        .any(|pattern| line.contains(&pattern.to_lowercase()))
+}
+
+// Reads what was appended to `path` since `pos`, starting over when the file
+// was truncated or replaced.
+fn read_new(path: &PathBuf, pos: &mut u64) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    if len < *pos {
+        *pos = 0;
+    }
+    file.seek(SeekFrom::Start(*pos))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    *pos += bytes.len() as u64;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// Tails `path`, adding new lines to `context`. On a failure with auto_ask,
+// sends "/watch-error <path>" and "/ask" to the command loop on `input_tx`.
+pub fn spawn_watch(
+    path: PathBuf,
+    context: Arc<Mutex<WatchContext>>,
+    input_tx: mpsc::UnboundedSender<String>,
+    config: WatchConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let name = path.display().to_string();
+        // Only content written from now on counts
+        let mut pos = std::fs::metadata(&path).map_or(0, |m| m.len());
+        let mut last_auto_ask: Option<Instant> = None;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let chunk = match read_new(&path, &mut pos) {
+                Ok(chunk) => chunk,
+                // The file may not exist yet, e.g. before a build starts
+                Err(_) => continue,
+            };
+            let lines = significant_lines(&chunk);
+            if lines.is_empty() {
+                continue;
+            }
+            let failed = lines
+                .iter()
+                .any(|line| is_failure(line, &config.failure_patterns));
+            context.lock().await.push(&name, lines);
+
+            if failed
+                && config.auto_ask
+                && last_auto_ask.is_none_or(|at| at.elapsed() >= AUTO_ASK_COOLDOWN)
+            {
+                last_auto_ask = Some(Instant::now());
+                if input_tx.send(format!("/watch-error {}", name)).is_err()
+                    || input_tx.send("/ask".to_string()).is_err()
+                {
+                    break;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_significant_lines() {
+        let chunk = "Compiling foo\n\n 10%\r 50%\r100%\nerror: oops\nerror: oops\n";
+        assert_eq!(
+            significant_lines(chunk),
+            vec!["Compiling foo", "100%", "error: oops"]
+        );
+    }
+
+    #[test]
+    fn test_is_failure() {
+        let patterns = default_failure_patterns();
+        assert!(is_failure(
+            "thread 'main' PANICKED at src/main.rs",
+            &patterns
+        ));
+        assert!(!is_failure("Finished dev profile", &patterns));
+    }
+
+    #[test]
+    fn test_take_prompt_context() {
+        let mut context = WatchContext::default();
+        assert!(context.take_prompt_context().is_none());
+        context.push(
+            "build.log",
+            (0..MAX_CONTEXT_LINES + 5).map(|i| i.to_string()).collect(),
+        );
+        let prompt = context.take_prompt_context().unwrap();
+        assert!(prompt.starts_with("New output in build.log:\n```\n5\n"));
+        assert!(context.take_prompt_context().is_none());
+    }
+
+    #[test]
+    fn test_read_new() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.log");
+        std::fs::write(&path, "one\n")?;
+        let mut pos = 0;
+        assert_eq!(read_new(&path, &mut pos)?, "one\n");
+        std::fs::write(&path, "x\n")?;
+        assert_eq!(read_new(&path, &mut pos)?, "x\n");
+        Ok(())
+    }
+}